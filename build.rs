@@ -0,0 +1,88 @@
+// Picks the link address for linker.ld. The bootloader is linked above the
+// kernel load address so that it can receive a kernel there, see
+// src/bootloader. The kernel runs in the high half, at its load address
+// plus mm::KERNEL_BASE, the bootloader with the MMU off.
+//
+// Also writes the initramfs linked into the kernel, see fs::initramfs: the
+// cpio newc archive in OSDEV_INITRAMFS, or one with the user programs in
+// OSDEV_USER_BIN, see user/, or nothing.
+
+use std::path::PathBuf;
+
+const KERNEL_BASE: u64 = 0x8_0000;
+// mm::KERNEL_BASE
+const KERNEL_OFFSET: u64 = 0xFFFF_0000_0000_0000;
+// Above bootloader::MAX_IMAGE_SIZE and the stack below the bootloader.
+const BOOTLOADER_BASE: u64 = 0x200_0000;
+// Binaries in OSDEV_USER_BIN and their paths in the initramfs
+const PROGRAMS: [(&str, &str); 3] =
+  [("init", "sbin/init"), ("sh", "bin/sh"), ("ls", "bin/ls")];
+const DIRECTORIES: [&str; 2] = ["sbin", "bin"];
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+// Appends a newc entry, see src/fs/cpio.rs.
+fn cpio_entry(
+  archive: &mut Vec<u8>,
+  ino: u32,
+  name: &str,
+  mode: u32,
+  data: &[u8],
+) {
+  let size = data.len() as u32;
+  let namesize = name.len() as u32 + 1;
+  let fields = [ino, mode, 0, 0, 1, 0, size, 0, 0, 0, 0, namesize, 0];
+  archive.extend(b"070701");
+  for field in fields {
+    archive.extend(format!("{:08X}", field).as_bytes());
+  }
+  archive.extend(name.as_bytes());
+  archive.push(0);
+  archive.resize(archive.len().next_multiple_of(4), 0);
+  archive.extend(data);
+  archive.resize(archive.len().next_multiple_of(4), 0);
+}
+
+fn initramfs() -> Vec<u8> {
+  if let Some(file) = std::env::var_os("OSDEV_INITRAMFS") {
+    println!("cargo:rerun-if-changed={}", PathBuf::from(&file).display());
+    return std::fs::read(file).expect("OSDEV_INITRAMFS is not readable");
+  }
+  let Some(dir) = std::env::var_os("OSDEV_USER_BIN") else {
+    return Vec::new();
+  };
+  let dir = PathBuf::from(dir);
+  let mut archive = Vec::new();
+  let mut ino = 1;
+  for name in DIRECTORIES {
+    cpio_entry(&mut archive, ino, name, S_IFDIR | 0o755, &[]);
+    ino += 1;
+  }
+  for (name, path) in PROGRAMS {
+    let file = dir.join(name);
+    println!("cargo:rerun-if-changed={}", file.display());
+    let data = std::fs::read(&file).expect("missing user program");
+    cpio_entry(&mut archive, ino, path, S_IFREG | 0o755, &data);
+    ino += 1;
+  }
+  cpio_entry(&mut archive, 0, "TRAILER!!!", 0, &[]);
+  archive
+}
+
+fn main() {
+  let (base, offset) = match std::env::var_os("CARGO_FEATURE_BOOTLOADER") {
+    Some(_) => (BOOTLOADER_BASE, 0),
+    None => (KERNEL_BASE, KERNEL_OFFSET),
+  };
+  let out = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+  std::fs::write(
+    out.join("link_base.ld"),
+    format!("LINK_BASE = {:#x};\nKERNEL_OFFSET = {:#x};\n", base, offset),
+  )
+  .unwrap();
+  std::fs::write(out.join("initramfs.cpio"), initramfs()).unwrap();
+  println!("cargo:rustc-link-search={}", out.display());
+  println!("cargo:rerun-if-changed=build.rs");
+  println!("cargo:rerun-if-env-changed=OSDEV_USER_BIN");
+  println!("cargo:rerun-if-env-changed=OSDEV_INITRAMFS");
+}
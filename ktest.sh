@@ -0,0 +1,13 @@
+#!/bin/bash
+# Builds the ktest kernel and runs it in QEMU.
+# Exits with 0 if all tests passed, 1 if any failed, 124 on timeout.
+
+set -e
+cargo build_ktest_img
+set +e
+
+timeout "${KTEST_TIMEOUT:-60}" qemu-system-aarch64 \
+  -nographic \
+  -M raspi3b \
+  -semihosting \
+  -device loader,file=ktest.img,addr=0x80000,cpu-num=0
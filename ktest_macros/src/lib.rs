@@ -0,0 +1,76 @@
+// `#[ktest]` registers a kernel test, see src/ktest.
+//
+// The function is kept as is and a `ktest::KTest` descriptor pointing to it
+// is placed in the `.ktest` linker section. Both only exist with the `ktest`
+// feature. Functions without a return type are wrapped to return Ok(()).
+//
+// No syn/quote: the item is only scanned for its name and return type.
+
+use proc_macro::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
+
+#[proc_macro_attribute]
+pub fn ktest(attr: TokenStream, item: TokenStream) -> TokenStream {
+  if !attr.is_empty() {
+    return error("#[ktest] takes no arguments");
+  }
+  let tokens: Vec<TokenTree> = item.clone().into_iter().collect();
+  let Some(name) = function_name(&tokens) else {
+    return error("#[ktest] expects a function");
+  };
+  let returns = tokens.windows(2).any(|pair| {
+    matches!(
+      pair,
+      [TokenTree::Punct(a), TokenTree::Punct(b)]
+        if a.as_char() == '-' && b.as_char() == '>'
+    )
+  });
+
+  let descriptor = Ident::new(&format!("__KTEST_{}", name), Span::call_site());
+  let run = if returns {
+    name.to_string()
+  } else {
+    format!(
+      "{{ fn wrapper() -> crate::ktest::Outcome {{ {}(); Ok(()) }} wrapper }}",
+      name
+    )
+  };
+  let generated = format!(
+    r#"
+    #[cfg(feature = "ktest")]
+    #[used]
+    #[link_section = ".ktest"]
+    #[allow(non_upper_case_globals)]
+    static {descriptor}: crate::ktest::KTest = crate::ktest::KTest {{
+      name: concat!(module_path!(), "::", "{name}"),
+      run: {run},
+    }};
+    "#
+  );
+
+  let mut output: TokenStream = "#[cfg(feature = \"ktest\")]".parse().unwrap();
+  output.extend(item);
+  output.extend(generated.parse::<TokenStream>().unwrap());
+  output
+}
+
+// Identifier following `fn`.
+fn function_name(tokens: &[TokenTree]) -> Option<Ident> {
+  tokens.windows(2).find_map(|pair| match pair {
+    [TokenTree::Ident(keyword), TokenTree::Ident(name)]
+      if keyword.to_string() == "fn" =>
+    {
+      Some(name.clone())
+    }
+    _ => None,
+  })
+}
+
+fn error(message: &str) -> TokenStream {
+  let mut output: TokenStream = "compile_error!".parse().unwrap();
+  output.extend([TokenTree::Group(Group::new(
+    Delimiter::Parenthesis,
+    format!("{:?}", message).parse().unwrap(),
+  ))]);
+  output.extend(";".parse::<TokenStream>());
+  output
+}
@@ -0,0 +1,11 @@
+#!/bin/bash
+
+# Runs the bootloader (cargo build_bootloader_img) with the console UART on a
+# pty. QEMU prints the pty, send a kernel to it with
+#   tools/chainload/chainload.py /dev/pts/<N> osdev.img
+
+qemu-system-aarch64 \
+  -nographic \
+  -M raspi3b \
+  -serial pty \
+  -device loader,file=bootloader.img,addr=0x80000,cpu-num=0
@@ -0,0 +1,13 @@
+#!/bin/bash
+
+# Console UART on localhost:5555 to debug through the in-kernel GDB stub.
+# 1. Connect with `nc localhost 5555` and run `gdb` in the shell
+# 2. Disconnect, then
+#    gdb-multiarch -ex "target remote localhost:5555" \
+#      target/aarch64-unknown-none-softfloat/debug/osdev
+
+qemu-system-aarch64 \
+  -nographic \
+  -M raspi3b \
+  -serial tcp::5555,server=on,wait=off \
+  -device loader,file=osdev.img,addr=0x80000,cpu-num=0
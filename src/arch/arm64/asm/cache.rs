@@ -0,0 +1,60 @@
+// https://developer.arm.com/documentation/den0024/a/Caches/Cache-maintenance
+// https://developer.arm.com/documentation/ddi0601/2024-09/AArch64-Registers/CTR-EL0--Cache-Type-Register
+
+use crate::arch::arm64::asm;
+
+// Smallest data cache line in the system, from CTR_EL0.DminLine
+// (log2 of the number of words).
+pub fn dcache_line_size() -> usize {
+  let ctr: u64;
+  unsafe {
+    core::arch::asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack));
+  }
+  4 << ((ctr >> 16) & 0xF)
+}
+
+macro_rules! for_each_line {
+  ($op:literal, $start:expr, $size:expr) => {{
+    let line = dcache_line_size();
+    let mut addr = $start & !(line - 1);
+    let end = $start + $size;
+    while addr < end {
+      unsafe {
+        core::arch::asm!(
+          core::concat!("dc ", $op, ", {}"),
+          in(reg) addr,
+          options(nostack, preserves_flags)
+        );
+      }
+      addr += line;
+    }
+    asm::barrier::data_synchronization!("sy");
+  }};
+}
+
+// Writes dirty lines covering [start, start + size) back to memory so that
+// other bus masters see the data.
+pub fn clean_range(start: usize, size: usize) {
+  for_each_line!("cvac", start, size);
+}
+
+// Discards lines covering [start, start + size) so the next read fetches
+// from memory. Dirty data in partially covered lines is lost as well.
+pub fn invalidate_range(start: usize, size: usize) {
+  for_each_line!("ivac", start, size);
+}
+
+pub fn clean_invalidate_range(start: usize, size: usize) {
+  for_each_line!("civac", start, size);
+}
+
+// Makes instructions written to [start, start + size) visible to
+// instruction fetches.
+pub fn sync_instructions(start: usize, size: usize) {
+  clean_range(start, size);
+  unsafe {
+    core::arch::asm!("ic iallu", options(nostack, preserves_flags));
+  }
+  asm::barrier::data_synchronization!("ish");
+  asm::barrier::instruction_synchronization!();
+}
@@ -1,2 +1,4 @@
 #[cfg(target_arch = "aarch64")]
 pub mod barrier;
+#[cfg(target_arch = "aarch64")]
+pub mod cache;
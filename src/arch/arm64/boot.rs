@@ -0,0 +1,29 @@
+// Starting another kernel, see bootloader.
+
+use crate::arch::arm64::asm;
+
+// Jumps to `entry` at EL1 with all exceptions masked. x0, the DTB pointer
+// from the firmware, is 0.
+pub fn jump(entry: usize) -> ! {
+  unsafe {
+    core::arch::asm!("msr daifset, #0xf", options(nomem, nostack));
+  }
+  // The image was written with data accesses.
+  asm::barrier::data_synchronization!("sy");
+  unsafe {
+    core::arch::asm!("ic iallu", options(nostack, preserves_flags));
+  }
+  asm::barrier::data_synchronization!("sy");
+  asm::barrier::instruction_synchronization!();
+  unsafe {
+    core::arch::asm!(
+      "mov x1, xzr",
+      "mov x2, xzr",
+      "mov x3, xzr",
+      "br {entry}",
+      entry = in(reg) entry,
+      in("x0") 0u64,
+      options(noreturn)
+    )
+  }
+}
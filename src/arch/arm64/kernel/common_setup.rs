@@ -1,6 +1,16 @@
+use super::debug;
 use super::interrupt_handle;
 use crate::metadata;
 
+// Called by head.S at the load address, with the MMU off and .bss zeroed.
+#[cfg(feature = "device")]
+#[no_mangle]
+extern "C" fn mmu_setup() {
+  // The bootloader's kernel expects the MMU off.
+  #[cfg(not(feature = "bootloader"))]
+  crate::arch::arm64::mm::enable();
+}
+
 #[cfg(feature = "device")]
 #[no_mangle]
 extern "C" fn arch_setup() {
@@ -8,6 +18,9 @@ extern "C" fn arch_setup() {
     get_memory_model: crate::arch::arm64::metadata::cpu::get_memory_model,
     get_ring_level: crate::arch::arm64::metadata::cpu::get_ring_level,
   });
+  #[cfg(not(feature = "bootloader"))]
+  crate::arch::arm64::mm::initialize();
   interrupt_handle::initialize();
+  debug::initialize();
   interrupt_handle::enable_irq();
 }
@@ -0,0 +1,118 @@
+// Debug exceptions, handled by the GDB stub.
+// https://developer.arm.com/documentation/102120/0101/Debug-exceptions
+
+use crate::arch::arm64::asm;
+use crate::common::error::ErrorKind;
+use crate::gdb;
+use crate::mm;
+
+// ESR_EL1.EC
+const EC_SOFTWARE_STEP_CURRENT_EL: u64 = 0x33;
+const EC_BRK: u64 = 0x3C;
+
+const MDSCR_SS: u64 = 1 << 0;
+// Debug exceptions at the current EL
+const MDSCR_KDE: u64 = 1 << 13;
+
+const SPSR_SS: u64 = 1 << 21;
+const SPSR_D: u64 = 1 << 9;
+const SPSR_I: u64 = 1 << 7;
+
+// D and I of the stepped context. Stepping unmasks debug exceptions and
+// masks IRQs, so the step does not end in the IRQ handler.
+static mut STEP_MASKS: u64 = 0;
+
+extern "C" {
+  // End of the kernel image, see linker.ld
+  static __end: [u8; 0];
+}
+
+// Stack and kernel image, which the linear map places between KERNEL_OFFSET
+// and __end. Lower addresses go through the current TTBR0 and may fault,
+// higher ones could be peripherals with read side effects.
+fn check_range(addr: u64, len: usize) -> Result<(), ErrorKind> {
+  let end = unsafe { __end.as_ptr() } as u64;
+  match addr.checked_add(len as u64) {
+    Some(last) if addr >= mm::KERNEL_OFFSET && last <= end => Ok(()),
+    _ => Err(ErrorKind::InvalidInput),
+  }
+}
+
+fn read_memory(addr: u64, buffer: &mut [u8]) -> Result<(), ErrorKind> {
+  check_range(addr, buffer.len())?;
+  // Byte accesses, so unaligned requests also work in the bootloader, which
+  // runs with the MMU off and sees all memory as Device memory.
+  for (i, byte) in buffer.iter_mut().enumerate() {
+    *byte = unsafe { core::ptr::read_volatile((addr as *const u8).add(i)) };
+  }
+  Ok(())
+}
+
+fn write_memory(addr: u64, data: &[u8]) -> Result<(), ErrorKind> {
+  check_range(addr, data.len())?;
+  for (i, byte) in data.iter().enumerate() {
+    unsafe { core::ptr::write_volatile((addr as *mut u8).add(i), *byte) };
+  }
+  Ok(())
+}
+
+fn sync_icache(addr: u64, len: usize) {
+  asm::cache::sync_instructions(addr as usize, len);
+}
+
+fn breakpoint() {
+  unsafe { core::arch::asm!("brk #0") };
+}
+
+fn read_mdscr() -> u64 {
+  let mdscr: u64;
+  unsafe { core::arch::asm!("mrs {}, mdscr_el1", out(reg) mdscr) };
+  mdscr
+}
+
+fn write_mdscr(mdscr: u64) {
+  unsafe { core::arch::asm!("msr mdscr_el1, {}", in(reg) mdscr) };
+  asm::barrier::instruction_synchronization!();
+}
+
+fn begin_step(frame: &mut gdb::Registers) {
+  unsafe { STEP_MASKS = frame.pstate & (SPSR_D | SPSR_I) };
+  frame.pstate = (frame.pstate | SPSR_SS | SPSR_I) & !SPSR_D;
+  write_mdscr(read_mdscr() | MDSCR_SS | MDSCR_KDE);
+}
+
+fn end_step(frame: &mut gdb::Registers) {
+  write_mdscr(read_mdscr() & !MDSCR_SS);
+  frame.pstate =
+    (frame.pstate & !(SPSR_SS | SPSR_D | SPSR_I)) | unsafe { STEP_MASKS };
+}
+
+// False if the exception is not a debug exception for the stub.
+pub fn handle_exception(frame: &mut gdb::Registers, esr_el1: u64) -> bool {
+  let reason = match esr_el1 >> 26 {
+    EC_BRK => gdb::StopReason::Breakpoint,
+    EC_SOFTWARE_STEP_CURRENT_EL => {
+      end_step(frame);
+      gdb::StopReason::Step
+    }
+    _ => return false,
+  };
+  match gdb::handle_stop(frame, reason) {
+    Some(gdb::Resume::Step) => begin_step(frame),
+    Some(gdb::Resume::Continue) => {}
+    None => return false,
+  }
+  true
+}
+
+pub fn initialize() {
+  // The OS lock blocks debug exceptions other than brk, it is set on reset.
+  unsafe { core::arch::asm!("msr oslar_el1, xzr") };
+  asm::barrier::instruction_synchronization!();
+  gdb::register(gdb::Ops {
+    read_memory,
+    write_memory,
+    sync_icache,
+    breakpoint,
+  });
+}
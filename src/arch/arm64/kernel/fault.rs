@@ -0,0 +1,44 @@
+// Instruction and data aborts, resolved by the active address space.
+// https://developer.arm.com/documentation/ddi0601/2024-09/AArch64-Registers/ESR-EL1--Exception-Syndrome-Register--EL1-
+
+use crate::mm;
+
+// ESR_EL1.EC
+const EC_INSTRUCTION_ABORT_LOWER_EL: u64 = 0x20;
+const EC_INSTRUCTION_ABORT_CURRENT_EL: u64 = 0x21;
+const EC_DATA_ABORT_LOWER_EL: u64 = 0x24;
+const EC_DATA_ABORT_CURRENT_EL: u64 = 0x25;
+
+// ISS.DFSC/IFSC without the level
+const FSC_TYPE_MASK: u64 = 0x3C;
+const FSC_TRANSLATION: u64 = 0x04;
+const FSC_PERMISSION: u64 = 0x0C;
+// ISS.WnR of data aborts
+const ISS_WRITE: u64 = 1 << 6;
+
+// True if the abort was resolved and the access can be retried. Only user
+// instruction fetches and data accesses are resolved.
+pub fn handle_exception(esr_el1: u64) -> bool {
+  let access = match esr_el1 >> 26 {
+    EC_INSTRUCTION_ABORT_LOWER_EL => mm::Access::Execute,
+    // The kernel never runs from user pages, retrying a PXN fault would
+    // loop forever. Left to the caller to panic.
+    EC_INSTRUCTION_ABORT_CURRENT_EL => return false,
+    EC_DATA_ABORT_LOWER_EL | EC_DATA_ABORT_CURRENT_EL => {
+      match esr_el1 & ISS_WRITE {
+        0 => mm::Access::Read,
+        _ => mm::Access::Write,
+      }
+    }
+    _ => return false,
+  };
+  match esr_el1 & FSC_TYPE_MASK {
+    FSC_TRANSLATION | FSC_PERMISSION => {}
+    _ => return false,
+  }
+  let far: u64;
+  unsafe {
+    core::arch::asm!("mrs {}, far_el1", out(reg) far, options(nomem, nostack));
+  }
+  mm::handle_fault(far, access)
+}
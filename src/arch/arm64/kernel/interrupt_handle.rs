@@ -1,15 +1,50 @@
-use crate::{common::stream, interrupt};
+use super::debug;
+use super::fault;
+use crate::{common::stream, gdb, interrupt, log, process, syscall};
+
+// Exception type of sync_invalid_el1h, see interrupt.S
+const SYNC_INVALID_EL1H: u64 = 4;
+// ESR_EL1.EC of svc from AArch64
+const EC_SVC64: u64 = 0x15;
 
 extern "C" {
   static _irq_vectors: [u8; 0];
+  fn _enter_user(registers: &gdb::Registers) -> !;
 }
 
 #[no_mangle]
 extern "C" fn on_irq() {
-  stream::println!("Hey, an interrupt!");
+  log::trace!("IRQ");
   interrupt::serve_interrupt();
 }
 
+// Synchronous exceptions at EL1h: debug exceptions and page faults.
+#[no_mangle]
+extern "C" fn on_sync_exception(frame: &mut gdb::Registers, esr_el1: u64) {
+  if !debug::handle_exception(frame, esr_el1)
+    && !fault::handle_exception(esr_el1)
+  {
+    on_invalid_irq(SYNC_INVALID_EL1H, esr_el1, frame.pc);
+  }
+}
+
+// Synchronous exceptions from user space: syscalls and page faults. Other
+// exceptions end the process.
+#[no_mangle]
+extern "C" fn on_user_exception(frame: &mut gdb::Registers, esr_el1: u64) {
+  process::save(frame);
+  match esr_el1 >> 26 {
+    EC_SVC64 => syscall::handle(frame),
+    _ if fault::handle_exception(esr_el1) => {}
+    _ => process::kill_current(frame, esr_el1),
+  }
+  process::resume(frame);
+}
+
+fn enter_user(registers: &gdb::Registers) -> ! {
+  unsafe { _enter_user(registers) }
+}
+
 #[no_mangle]
 extern "C" fn on_invalid_irq(irq_type: u64, esr_el1: u64, elr_el1: u64) -> ! {
   stream::println!(
@@ -27,6 +62,7 @@ pub fn initialize() {
     core::arch::asm!("msr vbar_el1, {0:x}", in(reg) _irq_vectors.as_ptr())
   };
   unsafe { core::arch::asm!("nop") };
+  process::register(process::Ops { enter: enter_user });
 }
 
 pub fn enable_irq() {
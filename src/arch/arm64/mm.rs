@@ -0,0 +1,177 @@
+// MMU setup and the mm ops: kernel translation tables, TTBR0 switching and
+// TLB maintenance.
+// https://developer.arm.com/documentation/101811/0104
+//
+// The kernel runs from the linear map in TTBR1, see mm. TTBR0 identity maps
+// the image only while head.S moves there, then holds user address spaces.
+
+use crate::arch::arm64::asm;
+use crate::mm;
+use crate::mm::page_table::{self, Table, ENTRIES, LEVEL_SIZE};
+
+// MAIR_EL1, indexed by page_table::ATTR_*
+const MAIR_DEVICE_NGNRNE: u64 = 0x00;
+const MAIR_NORMAL_WRITE_BACK: u64 = 0xFF;
+const MAIR: u64 = MAIR_DEVICE_NGNRNE | MAIR_NORMAL_WRITE_BACK << 8;
+
+// TCR_EL1: 48 bit virtual addresses and 4 KiB granules for both halves,
+// write-back inner shareable walks, 32 bit physical addresses (IPS = 0) and
+// 8 bit ASIDs taken from TTBR0 (AS = 0, A1 = 0).
+const TCR_T0SZ: u64 = 16;
+const TCR_IRGN0_WBWA: u64 = 1 << 8;
+const TCR_ORGN0_WBWA: u64 = 1 << 10;
+const TCR_SH0_INNER: u64 = 3 << 12;
+const TCR_T1SZ: u64 = 16 << 16;
+const TCR_IRGN1_WBWA: u64 = 1 << 24;
+const TCR_ORGN1_WBWA: u64 = 1 << 26;
+const TCR_SH1_INNER: u64 = 3 << 28;
+const TCR_TG1_4K: u64 = 2 << 30;
+const TCR: u64 = TCR_T0SZ
+  | TCR_IRGN0_WBWA
+  | TCR_ORGN0_WBWA
+  | TCR_SH0_INNER
+  | TCR_T1SZ
+  | TCR_IRGN1_WBWA
+  | TCR_ORGN1_WBWA
+  | TCR_SH1_INNER
+  | TCR_TG1_4K;
+
+const SCTLR_M: u64 = 1 << 0;
+const TTBR_ASID_SHIFT: u64 = 48;
+
+// Peripherals from here on, see bcm_raspberrypi_common::mmio
+const DEVICE_START: u64 = 0x3F00_0000;
+// Level 1 entries of the linear map: RAM and peripherals in the first 2 GiB
+const LINEAR_ENTRIES: usize = 2;
+
+#[repr(C, align(4096))]
+struct Tables {
+  // TTBR1: the linear map at KERNEL_BASE
+  high_l0: Table,
+  high_l1: Table,
+  // TTBR0 while no address space is active, empty once the kernel runs
+  // in the high half
+  low_l0: Table,
+  low_l1: Table,
+  // 2 MiB blocks, used by both halves
+  l2: [Table; LINEAR_ENTRIES],
+}
+
+static mut TABLES: Tables = Tables {
+  high_l0: [0; ENTRIES],
+  high_l1: [0; ENTRIES],
+  low_l0: [0; ENTRIES],
+  low_l1: [0; ENTRIES],
+  l2: [[0; ENTRIES]; LINEAR_ENTRIES],
+};
+
+// Physical address of a table, whether reached with the MMU off or
+// through the linear map.
+fn address(table: &Table) -> u64 {
+  table.as_ptr() as u64 & !mm::KERNEL_BASE
+}
+
+fn low_root() -> u64 {
+  unsafe { address(&*core::ptr::addr_of!(TABLES.low_l0)) }
+}
+
+// Builds the kernel tables and turns the MMU on, from head.S while running
+// at the load address. Caches stay off.
+pub fn enable() {
+  let tables = unsafe { &mut *core::ptr::addr_of_mut!(TABLES) };
+  for (gib, l2) in tables.l2.iter_mut().enumerate() {
+    for (index, entry) in l2.iter_mut().enumerate() {
+      let pa = gib as u64 * LEVEL_SIZE[1] + index as u64 * LEVEL_SIZE[2];
+      let attr = match pa < DEVICE_START {
+        true => page_table::ATTR_NORMAL,
+        false => page_table::ATTR_DEVICE,
+      };
+      *entry = page_table::kernel_block(pa, attr);
+    }
+  }
+  let entries: [u64; LINEAR_ENTRIES] =
+    core::array::from_fn(|i| page_table::table(address(&tables.l2[i])));
+  tables.high_l1[..entries.len()].copy_from_slice(&entries);
+  tables.low_l1[..entries.len()].copy_from_slice(&entries);
+  tables.high_l0[0] = page_table::table(address(&tables.high_l1));
+  tables.low_l0[0] = page_table::table(address(&tables.low_l1));
+
+  asm::barrier::data_synchronization!("ish");
+  unsafe {
+    core::arch::asm!(
+      "msr mair_el1, {mair}",
+      "msr tcr_el1, {tcr}",
+      "msr ttbr0_el1, {ttbr0}",
+      "msr ttbr1_el1, {ttbr1}",
+      "isb",
+      "tlbi vmalle1",
+      "dsb ish",
+      "isb",
+      "mrs {sctlr}, sctlr_el1",
+      "orr {sctlr}, {sctlr}, {m}",
+      "msr sctlr_el1, {sctlr}",
+      "isb",
+      mair = in(reg) MAIR,
+      tcr = in(reg) TCR,
+      ttbr0 = in(reg) address(&tables.low_l0),
+      ttbr1 = in(reg) address(&tables.high_l0),
+      sctlr = out(reg) _,
+      m = in(reg) SCTLR_M,
+      options(nostack)
+    );
+  }
+}
+
+// Drops the identity map, the kernel runs in the high half from here on.
+pub fn initialize() {
+  unsafe {
+    (*core::ptr::addr_of_mut!(TABLES.low_l0))[0] = 0;
+  }
+  asm::barrier::data_synchronization!("ishst");
+  unsafe {
+    core::arch::asm!("tlbi vmalle1", options(nostack));
+  }
+  asm::barrier::data_synchronization!("ish");
+  asm::barrier::instruction_synchronization!();
+
+  mm::register(mm::Ops {
+    activate,
+    deactivate,
+    invalidate_page,
+    invalidate_asid,
+  });
+}
+
+// No TLB maintenance: the entries of other address spaces have other ASIDs.
+fn activate(root: u64, asid: u16) {
+  let ttbr0 = root | (asid as u64) << TTBR_ASID_SHIFT;
+  unsafe {
+    core::arch::asm!("msr ttbr0_el1, {}", in(reg) ttbr0, options(nostack));
+  }
+  asm::barrier::instruction_synchronization!();
+}
+
+fn deactivate() {
+  activate(low_root(), 0);
+}
+
+fn invalidate_page(asid: u16, va: u64) {
+  let operand = (asid as u64) << TTBR_ASID_SHIFT | (va >> 12) & 0xFFF_FFFF_FFFF;
+  // The table update must be visible to the walker first.
+  asm::barrier::data_synchronization!("ishst");
+  unsafe {
+    core::arch::asm!("tlbi vae1is, {}", in(reg) operand, options(nostack));
+  }
+  asm::barrier::data_synchronization!("ish");
+  asm::barrier::instruction_synchronization!();
+}
+
+fn invalidate_asid(asid: u16) {
+  let operand = (asid as u64) << TTBR_ASID_SHIFT;
+  asm::barrier::data_synchronization!("ishst");
+  unsafe {
+    core::arch::asm!("tlbi aside1is, {}", in(reg) operand, options(nostack));
+  }
+  asm::barrier::data_synchronization!("ish");
+  asm::barrier::instruction_synchronization!();
+}
@@ -1,10 +1,15 @@
 pub mod asm;
+pub mod boot;
 mod kernel {
   mod common_setup;
+  mod debug;
+  mod fault;
   mod head;
   mod interrupt;
   pub mod interrupt_handle;
 }
+pub mod mm;
+pub mod semihosting;
 pub(self) mod metadata {
   pub(super) mod cpu;
 }
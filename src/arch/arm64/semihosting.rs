@@ -0,0 +1,138 @@
+// ARM semihosting, see "Semihosting for AArch32 and AArch64".
+//
+// Calls are handled by the debugger or emulator (QEMU: -semihosting).
+// Without one, `hlt` is an undefined instruction.
+
+use crate::common::error::ErrorKind;
+use core::arch::asm;
+
+const SYS_OPEN: u64 = 0x01;
+const SYS_CLOSE: u64 = 0x02;
+const SYS_WRITE0: u64 = 0x04;
+const SYS_WRITE: u64 = 0x05;
+const SYS_READ: u64 = 0x06;
+const SYS_FLEN: u64 = 0x0C;
+const SYS_EXIT_EXTENDED: u64 = 0x20;
+const ADP_STOPPED_APPLICATION_EXIT: u64 = 0x20026;
+
+// Strings are copied to a NUL terminated buffer of this size.
+const PATH_SIZE: usize = 256;
+const WRITE0_CHUNK: usize = 128;
+
+// `param` is the operation's argument, usually a pointer to a parameter
+// block.
+unsafe fn call(operation: u64, param: u64) -> u64 {
+  let ret: u64;
+  asm!(
+    "hlt #0xf000",
+    inout("x0") operation => ret,
+    in("x1") param,
+    options(nostack)
+  );
+  ret
+}
+
+// Writes `s` to the debug console. NUL characters end the output early.
+pub fn write0(s: &str) {
+  let mut buffer = [0u8; WRITE0_CHUNK + 1];
+  for chunk in s.as_bytes().chunks(WRITE0_CHUNK) {
+    buffer[..chunk.len()].copy_from_slice(chunk);
+    buffer[chunk.len()] = 0;
+    unsafe { call(SYS_WRITE0, buffer.as_ptr() as u64) };
+  }
+}
+
+// fopen() modes, the value is the mode number of SYS_OPEN.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+  Read = 1,
+  ReadWrite = 3,
+  // Truncates or creates
+  Write = 5,
+  // Creates if missing
+  Append = 9,
+}
+
+pub struct File {
+  handle: u64,
+}
+
+// Opens `path` on the host, relative to the emulator's working directory.
+pub fn open(path: &str, mode: Mode) -> Result<File, ErrorKind> {
+  let mut buffer = [0u8; PATH_SIZE];
+  if path.len() >= PATH_SIZE || path.as_bytes().contains(&0) {
+    return Err(ErrorKind::InvalidInput);
+  }
+  buffer[..path.len()].copy_from_slice(path.as_bytes());
+  let block: [u64; 3] =
+    [buffer.as_ptr() as u64, mode as u64, path.len() as u64];
+  let handle = unsafe { call(SYS_OPEN, block.as_ptr() as u64) };
+  if handle as i64 == -1 {
+    return Err(ErrorKind::NotFound);
+  }
+  Ok(File { handle })
+}
+
+impl File {
+  // Number of bytes read, 0 at the end of the file.
+  pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, ErrorKind> {
+    let block: [u64; 3] =
+      [self.handle, buffer.as_mut_ptr() as u64, buffer.len() as u64];
+    // Returns the number of bytes not read.
+    let left = unsafe { call(SYS_READ, block.as_ptr() as u64) } as usize;
+    if left > buffer.len() {
+      return Err(ErrorKind::Other);
+    }
+    Ok(buffer.len() - left)
+  }
+
+  pub fn write(&mut self, data: &[u8]) -> Result<(), ErrorKind> {
+    let block: [u64; 3] =
+      [self.handle, data.as_ptr() as u64, data.len() as u64];
+    // Returns the number of bytes not written.
+    match unsafe { call(SYS_WRITE, block.as_ptr() as u64) } {
+      0 => Ok(()),
+      _ => Err(ErrorKind::WriteZero),
+    }
+  }
+
+  pub fn size(&self) -> Result<usize, ErrorKind> {
+    let block: [u64; 1] = [self.handle];
+    let len = unsafe { call(SYS_FLEN, block.as_ptr() as u64) };
+    if len as i64 == -1 {
+      return Err(ErrorKind::Other);
+    }
+    Ok(len as usize)
+  }
+
+  pub fn close(self) -> Result<(), ErrorKind> {
+    let block: [u64; 1] = [self.handle];
+    match unsafe { call(SYS_CLOSE, block.as_ptr() as u64) } {
+      0 => Ok(()),
+      _ => Err(ErrorKind::Other),
+    }
+  }
+}
+
+impl core::fmt::Write for File {
+  fn write_str(&mut self, s: &str) -> core::fmt::Result {
+    self.write(s.as_bytes()).map_err(|_| core::fmt::Error)
+  }
+}
+
+// Creates or truncates `path` on the host and writes `data` to it, e.g. for
+// crash logs.
+pub fn write_file(path: &str, data: &[u8]) -> Result<(), ErrorKind> {
+  let mut file = open(path, Mode::Write)?;
+  let result = file.write(data);
+  file.close()?;
+  result
+}
+
+// Stops the emulator, which exits with `code`.
+pub fn exit(code: u32) -> ! {
+  let block: [u64; 2] = [ADP_STOPPED_APPLICATION_EXIT, code as u64];
+  unsafe { call(SYS_EXIT_EXTENDED, block.as_ptr() as u64) };
+  // Not running under semihosting
+  loop {}
+}
@@ -0,0 +1,134 @@
+use super::*;
+
+fn frame(kind: u8, seq: u16, payload: &[u8]) -> Vec<u8> {
+  let mut body = vec![kind];
+  body.extend(seq.to_le_bytes());
+  body.extend((payload.len() as u16).to_le_bytes());
+  body.extend(payload);
+  let mut frame = SYNC.to_vec();
+  frame.extend(&body);
+  frame.extend(crc32(&body).to_le_bytes());
+  frame
+}
+
+fn header(image: &[u8]) -> Vec<u8> {
+  let mut payload = (image.len() as u32).to_le_bytes().to_vec();
+  payload.extend(crc32(image).to_le_bytes());
+  frame(KIND_HEADER, 0, &payload)
+}
+
+fn feed(receiver: &mut Receiver, bytes: &[u8]) -> Vec<Event> {
+  bytes.iter().filter_map(|&b| receiver.feed(b)).collect()
+}
+
+fn test_image(size: usize) -> Vec<u8> {
+  (0..size).map(|i| (i * 7) as u8).collect()
+}
+
+#[test]
+fn test_receive_image() {
+  let image = test_image(2 * MAX_CHUNK + 10);
+  let mut memory = vec![0u8; 4 * MAX_CHUNK];
+  let mut receiver = Receiver::new(&mut memory);
+
+  // Console noise before the first frame
+  assert_eq!(feed(&mut receiver, b"garbage B"), vec![]);
+  assert_eq!(feed(&mut receiver, &header(&image)), vec![Event::Accepted]);
+  for (seq, chunk) in image.chunks(MAX_CHUNK).enumerate() {
+    let expected = match seq {
+      2 => Event::Complete(image.len()),
+      _ => Event::Accepted,
+    };
+    assert_eq!(
+      feed(&mut receiver, &frame(KIND_DATA, seq as u16, chunk)),
+      vec![expected]
+    );
+  }
+  assert_eq!(&memory[..image.len()], &image[..]);
+}
+
+#[test]
+fn test_resend_and_duplicates() {
+  let image = test_image(30);
+  let mut memory = vec![0u8; 64];
+  let mut receiver = Receiver::new(&mut memory);
+  assert_eq!(feed(&mut receiver, &header(&image)), vec![Event::Accepted]);
+
+  // Corrupted frame
+  let mut corrupted = frame(KIND_DATA, 0, &image[..10]);
+  corrupted[8] ^= 0xFF;
+  assert_eq!(feed(&mut receiver, &corrupted), vec![Event::Rejected]);
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 0, &image[..10])),
+    vec![Event::Accepted]
+  );
+  // The ACK got lost, the frame is repeated.
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 0, &image[..10])),
+    vec![Event::Accepted]
+  );
+  // Out of order
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 2, &image[10..20])),
+    vec![Event::Rejected]
+  );
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 1, &image[10..30])),
+    vec![Event::Complete(30)]
+  );
+  assert_eq!(&memory[..30], &image[..]);
+}
+
+#[test]
+fn test_rejects_bad_transfers() {
+  let image = test_image(30);
+  let mut memory = vec![0u8; 16];
+  let mut receiver = Receiver::new(&mut memory);
+  // Data before a header
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 0, &image[..8])),
+    vec![Event::Rejected]
+  );
+  // Too large for the memory
+  assert_eq!(feed(&mut receiver, &header(&image)), vec![Event::Rejected]);
+  // Empty image
+  assert_eq!(feed(&mut receiver, &header(&[])), vec![Event::Rejected]);
+  // Length beyond MAX_CHUNK is rejected as soon as it is seen.
+  let mut long = SYNC.to_vec();
+  long.extend([KIND_DATA, 0, 0]);
+  long.extend(((MAX_CHUNK + 1) as u16).to_le_bytes());
+  assert_eq!(feed(&mut receiver, &long), vec![Event::Rejected]);
+
+  // Image CRC mismatch restarts
+  let mut payload = 8u32.to_le_bytes().to_vec();
+  payload.extend(0u32.to_le_bytes());
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_HEADER, 0, &payload)),
+    vec![Event::Accepted]
+  );
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 0, &image[..8])),
+    vec![Event::Rejected]
+  );
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 1, &image[..8])),
+    vec![Event::Rejected]
+  );
+}
+
+#[test]
+fn test_header_restarts_transfer() {
+  let image = test_image(20);
+  let mut memory = vec![0u8; 32];
+  let mut receiver = Receiver::new(&mut memory);
+  assert_eq!(feed(&mut receiver, &header(&image)), vec![Event::Accepted]);
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 0, &image[..10])),
+    vec![Event::Accepted]
+  );
+  assert_eq!(feed(&mut receiver, &header(&image)), vec![Event::Accepted]);
+  assert_eq!(
+    feed(&mut receiver, &frame(KIND_DATA, 0, &image)),
+    vec![Event::Complete(20)]
+  );
+}
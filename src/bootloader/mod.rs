@@ -0,0 +1,238 @@
+// Serial chain-loading bootloader, built with the `bootloader` feature.
+//
+// The bootloader image is linked above the kernel load address and moves
+// itself there on boot (see head.S), then receives a kernel image over the
+// console UART into LOAD_ADDRESS and jumps to it. tools/chainload sends
+// images.
+//
+// Frames, all integers little endian:
+//
+//   'B' 'L' kind:u8 seq:u16 len:u16 payload[len] crc32:u32
+//
+// where the CRC covers kind to payload. A transfer is a Header frame with the
+// image size and CRC32, followed by Data frames with seq counting from 0. Each
+// frame is answered with ACK or NAK, the sender resends on NAK or after a
+// timeout. The last Data frame is only acknowledged if the image CRC matches,
+// the bootloader then jumps to the image. A Header frame restarts the
+// transfer.
+
+use crate::common::crc32::{crc32, Crc32};
+use arrayvec::ArrayVec;
+
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+
+pub const SYNC: [u8; 2] = *b"BL";
+pub const KIND_HEADER: u8 = b'H';
+pub const KIND_DATA: u8 = b'D';
+pub const MAX_CHUNK: usize = 1024;
+
+pub const LOAD_ADDRESS: usize = 0x8_0000;
+// Below the bootloader and its stack, see build.rs
+pub const MAX_IMAGE_SIZE: usize = 16 << 20;
+
+// kind, seq, len
+const FRAME_HEADER_SIZE: usize = 5;
+const CRC_SIZE: usize = 4;
+const HEADER_PAYLOAD_SIZE: usize = 8;
+const FRAME_CAPACITY: usize = FRAME_HEADER_SIZE + MAX_CHUNK + CRC_SIZE;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+  // Answer with ACK
+  Accepted,
+  // Answer with NAK
+  Rejected,
+  // Image of this size received and verified. Answer with ACK and boot it.
+  Complete(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+  // Number of SYNC bytes matched
+  Sync(usize),
+  Frame,
+}
+
+#[derive(Clone, Copy)]
+struct Transfer {
+  size: usize,
+  crc: u32,
+  received: usize,
+  next_seq: u16,
+}
+
+pub struct Receiver<'a> {
+  image: &'a mut [u8],
+  state: State,
+  frame: ArrayVec<u8, FRAME_CAPACITY>,
+  transfer: Option<Transfer>,
+}
+
+impl<'a> Receiver<'a> {
+  // Receives into `image`, which limits the image size.
+  pub fn new(image: &'a mut [u8]) -> Receiver<'a> {
+    Receiver {
+      image,
+      state: State::Sync(0),
+      frame: ArrayVec::new(),
+      transfer: None,
+    }
+  }
+
+  pub fn feed(&mut self, byte: u8) -> Option<Event> {
+    match self.state {
+      State::Sync(matched) => {
+        self.state = if byte == SYNC[matched] {
+          match matched + 1 {
+            n if n == SYNC.len() => State::Frame,
+            n => State::Sync(n),
+          }
+        } else if byte == SYNC[0] {
+          State::Sync(1)
+        } else {
+          State::Sync(0)
+        };
+        self.frame.clear();
+        None
+      }
+      State::Frame => {
+        self.frame.push(byte);
+        if self.frame.len() < FRAME_HEADER_SIZE {
+          return None;
+        }
+        let len = u16::from_le_bytes([self.frame[3], self.frame[4]]) as usize;
+        if len > MAX_CHUNK {
+          self.state = State::Sync(0);
+          return Some(Event::Rejected);
+        }
+        if self.frame.len() < FRAME_HEADER_SIZE + len + CRC_SIZE {
+          return None;
+        }
+        self.state = State::Sync(0);
+        Some(self.process())
+      }
+    }
+  }
+
+  fn process(&mut self) -> Event {
+    let frame = core::mem::take(&mut self.frame);
+    let (body, crc) = frame.split_at(frame.len() - CRC_SIZE);
+    if crc32(body) != u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+      return Event::Rejected;
+    }
+    let kind = body[0];
+    let seq = u16::from_le_bytes([body[1], body[2]]);
+    let payload = &body[FRAME_HEADER_SIZE..];
+    match kind {
+      KIND_HEADER => self.start(payload),
+      KIND_DATA => self.receive(seq, payload),
+      _ => Event::Rejected,
+    }
+  }
+
+  fn start(&mut self, payload: &[u8]) -> Event {
+    self.transfer = None;
+    if payload.len() != HEADER_PAYLOAD_SIZE {
+      return Event::Rejected;
+    }
+    let size =
+      u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+    let crc =
+      u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+    let size = size as usize;
+    if size == 0 || size > self.image.len() {
+      return Event::Rejected;
+    }
+    self.transfer = Some(Transfer {
+      size,
+      crc,
+      received: 0,
+      next_seq: 0,
+    });
+    Event::Accepted
+  }
+
+  fn receive(&mut self, seq: u16, payload: &[u8]) -> Event {
+    let Some(transfer) = self.transfer.as_mut() else {
+      return Event::Rejected;
+    };
+    // Our ACK was lost, the sender repeats the previous frame.
+    if transfer.received > 0 && seq == transfer.next_seq.wrapping_sub(1) {
+      return Event::Accepted;
+    }
+    if seq != transfer.next_seq
+      || payload.is_empty()
+      || transfer.received + payload.len() > transfer.size
+    {
+      return Event::Rejected;
+    }
+    let start = transfer.received;
+    self.image[start..start + payload.len()].copy_from_slice(payload);
+    transfer.received += payload.len();
+    transfer.next_seq = transfer.next_seq.wrapping_add(1);
+    if transfer.received < transfer.size {
+      return Event::Accepted;
+    }
+
+    let mut crc = Crc32::new();
+    crc.update(&self.image[..transfer.size]);
+    let (size, expected) = (transfer.size, transfer.crc);
+    self.transfer = None;
+    if crc.finish() != expected {
+      return Event::Rejected;
+    }
+    Event::Complete(size)
+  }
+}
+
+pub struct Ops {
+  // Jumps to a kernel at `entry` with interrupts masked.
+  pub boot: fn(entry: usize) -> !,
+}
+
+static mut OPS: core::mem::MaybeUninit<Ops> =
+  core::mem::MaybeUninit::<Ops>::uninit();
+// This will be set to 0 during bss zero-ing.
+static mut SET: bool = false;
+
+pub fn register(ops: Ops) {
+  unsafe {
+    OPS = core::mem::MaybeUninit::<Ops>::new(ops);
+    SET = true;
+  };
+}
+
+#[cfg(feature = "bootloader")]
+pub fn run() -> ! {
+  use crate::common::stream;
+  use crate::io::uart;
+  use crate::timer;
+
+  // The bootloader lives above MAX_IMAGE_SIZE.
+  let image = unsafe {
+    core::slice::from_raw_parts_mut(LOAD_ADDRESS as *mut u8, MAX_IMAGE_SIZE)
+  };
+  let mut receiver = Receiver::new(image);
+  stream::println!("Bootloader: waiting for an image on the UART");
+  loop {
+    match receiver.feed(uart::getc()) {
+      None => {}
+      Some(Event::Accepted) => uart::putc(ACK),
+      Some(Event::Rejected) => uart::putc(NAK),
+      Some(Event::Complete(_)) => break,
+    }
+  }
+  uart::putc(ACK);
+  // Let the ACK leave the FIFO, the kernel resets the UART.
+  if let Some(start) = timer::uptime_us() {
+    while timer::uptime_us().unwrap_or(0) < start + 10_000 {}
+  }
+  unsafe {
+    assert!(SET, "No bootloader ops registered");
+    (OPS.assume_init_ref().boot)(LOAD_ADDRESS)
+  }
+}
+
+#[cfg(test)]
+mod bootloader_test;
@@ -0,0 +1,53 @@
+// CRC-32 as used by zlib, Ethernet and Python's binascii.crc32.
+
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+const TABLE: [u32; 256] = make_table();
+
+const fn make_table() -> [u32; 256] {
+  let mut table = [0u32; 256];
+  let mut i = 0;
+  while i < 256 {
+    let mut crc = i as u32;
+    let mut bit = 0;
+    while bit < 8 {
+      crc = if crc & 1 != 0 {
+        (crc >> 1) ^ POLYNOMIAL
+      } else {
+        crc >> 1
+      };
+      bit += 1;
+    }
+    table[i] = crc;
+    i += 1;
+  }
+  table
+}
+
+// For data arriving in pieces.
+#[derive(Clone, Copy)]
+pub struct Crc32 {
+  state: u32,
+}
+
+impl Crc32 {
+  pub const fn new() -> Crc32 {
+    Crc32 { state: !0 }
+  }
+
+  pub fn update(&mut self, data: &[u8]) {
+    for &byte in data {
+      let index = (self.state ^ byte as u32) & 0xFF;
+      self.state = (self.state >> 8) ^ TABLE[index as usize];
+    }
+  }
+
+  pub fn finish(&self) -> u32 {
+    !self.state
+  }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+  let mut crc = Crc32::new();
+  crc.update(data);
+  crc.finish()
+}
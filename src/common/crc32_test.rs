@@ -0,0 +1,17 @@
+use super::crc32::*;
+
+#[test]
+fn test_crc32_check_value() {
+  assert_eq!(crc32(b""), 0);
+  assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+  assert_eq!(crc32(&[0u8; 4]), 0x2144_DF1C);
+}
+
+#[test]
+fn test_crc32_incremental() {
+  let mut crc = Crc32::new();
+  crc.update(b"1234");
+  crc.update(b"");
+  crc.update(b"56789");
+  assert_eq!(crc.finish(), 0xCBF4_3926);
+}
@@ -1,7 +1,7 @@
 // Copy of https://doc.rust-lang.org/std/io/enum.ErrorKind.html
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ErrorKind {
   /// An entity was not found, often a file.
   NotFound,
@@ -1,7 +1,13 @@
 pub mod bit;
+pub mod crc32;
 pub mod error;
+pub mod level;
 pub mod stream;
 pub mod synchronization;
 
 #[cfg(test)]
 mod bit_test;
+#[cfg(test)]
+mod crc32_test;
+#[cfg(test)]
+mod stream_test;
@@ -1,15 +1,101 @@
-use crate::common::error;
-use core::mem::MaybeUninit;
+use crate::common::error::ErrorKind;
+use crate::common::level::Level;
+use arrayvec::ArrayString;
+use arrayvec::ArrayVec;
 
-static mut OUT: MaybeUninit<OutputOps> = MaybeUninit::<OutputOps>::uninit();
+pub const MAX_SINKS: usize = 8;
+// Output written before any sink is attached is kept here (until full) and
+// replayed to the first attached sink.
+pub const EARLY_BUFFER_SIZE: usize = 1024;
 
-pub struct OutputOps {
-  pub write: fn(&str) -> core::result::Result<(), error::ErrorKind>,
+static mut SINKS: ArrayVec<Sink, MAX_SINKS> = ArrayVec::new_const();
+static mut EARLY: ArrayString<EARLY_BUFFER_SIZE> = ArrayString::new_const();
+
+// Output destination. Receives writes at `level` or more severe.
+#[derive(Clone, Copy)]
+pub struct Sink {
+  pub name: &'static str,
+  pub level: Level,
+  pub write: fn(&str) -> Result<(), ErrorKind>,
+}
+
+pub fn attach(sink: Sink) -> Result<(), ErrorKind> {
+  unsafe {
+    if SINKS.iter().any(|s| s.name == sink.name) {
+      return Err(ErrorKind::AlreadyExists);
+    }
+    SINKS.try_push(sink).map_err(|_| ErrorKind::StorageFull)?;
+    if SINKS.len() == 1 && !EARLY.is_empty() {
+      let _ = (sink.write)(EARLY.as_str());
+      EARLY.clear();
+    }
+  }
+  Ok(())
+}
+
+pub fn detach(name: &str) -> Result<Sink, ErrorKind> {
+  unsafe {
+    let index = SINKS
+      .iter()
+      .position(|s| s.name == name)
+      .ok_or(ErrorKind::NotFound)?;
+    Ok(SINKS.remove(index))
+  }
+}
+
+pub fn set_level(name: &str, level: Level) -> Result<(), ErrorKind> {
+  unsafe {
+    let sink = SINKS
+      .iter_mut()
+      .find(|s| s.name == name)
+      .ok_or(ErrorKind::NotFound)?;
+    sink.level = level;
+  }
+  Ok(())
 }
 
-impl core::fmt::Write for OutputOps {
+pub fn sinks() -> ArrayVec<Sink, MAX_SINKS> {
+  unsafe { SINKS.clone() }
+}
+
+// Writes to every sink accepting `level`. Fails only if all of them failed.
+pub fn write(level: Level, s: &str) -> Result<(), ErrorKind> {
+  unsafe {
+    if SINKS.is_empty() {
+      // Dropped once the early buffer is full.
+      let _ = EARLY.try_push_str(s);
+      return Ok(());
+    }
+    let mut result = Ok(());
+    let mut written = false;
+    for sink in SINKS.iter().filter(|s| level <= s.level) {
+      match (sink.write)(s) {
+        Ok(()) => written = true,
+        Err(kind) => result = Err(kind),
+      }
+    }
+    if written {
+      Ok(())
+    } else {
+      result
+    }
+  }
+}
+
+pub struct Writer {
+  level: Level,
+}
+
+impl Writer {
+  // Allows write!() without importing core::fmt::Write.
+  pub fn write_fmt(&mut self, args: core::fmt::Arguments) -> core::fmt::Result {
+    core::fmt::Write::write_fmt(self, args)
+  }
+}
+
+impl core::fmt::Write for Writer {
   fn write_str(&mut self, s: &str) -> core::fmt::Result {
-    match (self.write)(s) {
+    match write(self.level, s) {
       Ok(()) => Ok(()),
       // This type does not support transmission of an error other than
       // that an error occurred.
@@ -18,14 +104,13 @@ impl core::fmt::Write for OutputOps {
   }
 }
 
-pub fn out() -> &'static mut dyn core::fmt::Write {
-  unsafe { OUT.assume_init_mut() }
+// Writer used by print!/println!.
+pub fn out() -> Writer {
+  out_at(Level::Info)
 }
 
-pub fn assign(streamer: OutputOps) {
-  unsafe {
-    OUT = MaybeUninit::<OutputOps>::new(streamer);
-  }
+pub fn out_at(level: Level) -> Writer {
+  Writer { level }
 }
 
 #[macro_export]
@@ -44,4 +129,5 @@ macro_rules! println {
   }}
 }
 
+pub use print;
 pub use println;
@@ -0,0 +1,128 @@
+use super::stream::*;
+use crate::common::error::ErrorKind;
+use crate::common::level::Level;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+// Sinks are global, run the tests one at a time.
+static LOCK: Mutex<()> = Mutex::new(());
+static SERIAL: Mutex<String> = Mutex::new(String::new());
+static SCREEN: Mutex<String> = Mutex::new(String::new());
+
+fn write_serial(s: &str) -> Result<(), ErrorKind> {
+  SERIAL.lock().unwrap().push_str(s);
+  Ok(())
+}
+
+fn write_screen(s: &str) -> Result<(), ErrorKind> {
+  SCREEN.lock().unwrap().push_str(s);
+  Ok(())
+}
+
+fn write_broken(_: &str) -> Result<(), ErrorKind> {
+  Err(ErrorKind::BrokenPipe)
+}
+
+fn setup() -> MutexGuard<'static, ()> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  for sink in sinks() {
+    detach(sink.name).unwrap();
+  }
+  SERIAL.lock().unwrap().clear();
+  SCREEN.lock().unwrap().clear();
+  guard
+}
+
+fn serial() -> Sink {
+  Sink {
+    name: "serial",
+    level: Level::Debug,
+    write: write_serial,
+  }
+}
+
+fn screen() -> Sink {
+  Sink {
+    name: "screen",
+    level: Level::Info,
+    write: write_screen,
+  }
+}
+
+#[test]
+fn test_stream_multiplexes() {
+  let _guard = setup();
+  attach(serial()).unwrap();
+  attach(screen()).unwrap();
+
+  crate::common::stream::println!("hello {}", 1);
+  write(Level::Debug, "debug\n").unwrap();
+
+  assert_eq!(*SERIAL.lock().unwrap(), "hello 1\ndebug\n");
+  assert_eq!(*SCREEN.lock().unwrap(), "hello 1\n");
+}
+
+#[test]
+fn test_stream_attach_detach() {
+  let _guard = setup();
+  attach(serial()).unwrap();
+  assert!(matches!(attach(serial()), Err(ErrorKind::AlreadyExists)));
+  attach(screen()).unwrap();
+
+  assert_eq!(detach("serial").unwrap().name, "serial");
+  assert!(matches!(detach("serial"), Err(ErrorKind::NotFound)));
+  write(Level::Info, "x").unwrap();
+  assert_eq!(*SERIAL.lock().unwrap(), "");
+  assert_eq!(*SCREEN.lock().unwrap(), "x");
+}
+
+#[test]
+fn test_stream_set_level() {
+  let _guard = setup();
+  attach(screen()).unwrap();
+  set_level("screen", Level::Error).unwrap();
+  write(Level::Warn, "hidden").unwrap();
+  write(Level::Error, "shown").unwrap();
+  assert_eq!(*SCREEN.lock().unwrap(), "shown");
+  assert!(matches!(
+    set_level("missing", Level::Info),
+    Err(ErrorKind::NotFound)
+  ));
+}
+
+#[test]
+fn test_stream_early_output_is_replayed() {
+  let _guard = setup();
+  // Nothing attached, must not fail.
+  crate::common::stream::print!("early ");
+  write(Level::Trace, "boot\n").unwrap();
+  attach(serial()).unwrap();
+  attach(screen()).unwrap();
+  write(Level::Info, "late\n").unwrap();
+
+  assert_eq!(*SERIAL.lock().unwrap(), "early boot\nlate\n");
+  assert_eq!(*SCREEN.lock().unwrap(), "late\n");
+}
+
+#[test]
+fn test_stream_errors() {
+  let _guard = setup();
+  attach(Sink {
+    name: "broken",
+    level: Level::Trace,
+    write: write_broken,
+  })
+  .unwrap();
+  assert!(matches!(
+    write(Level::Info, "x"),
+    Err(ErrorKind::BrokenPipe)
+  ));
+  // One working sink is enough.
+  attach(serial()).unwrap();
+  write(Level::Info, "x").unwrap();
+  assert_eq!(*SERIAL.lock().unwrap(), "x");
+  // Filtered out everywhere is not an error.
+  detach("broken").unwrap();
+  write(Level::Trace, "y").unwrap();
+  assert_eq!(*SERIAL.lock().unwrap(), "x");
+}
@@ -1,13 +1,11 @@
 use crate::common::synchronization;
 use crate::io::gpio;
 
-pub fn test_led_blink(gpio_ids: u64) {
-  gpio::set_function(gpio_ids, gpio::Function::Output);
-
+pub fn test_led_blink(led: gpio::AnyPin<gpio::Output>) -> ! {
   loop {
-    gpio::output_set(gpio_ids);
+    led.set_high();
     synchronization::sleep(500_000);
-    gpio::output_clear(gpio_ids);
+    led.set_low();
     synchronization::sleep(500_000);
   }
 }
@@ -8,13 +8,8 @@ use crate::io::mailbox::MessageView;
 use crate::io::uart;
 
 // Returns how many bytes printed
-fn print_message_buf<const N: usize>(message: &Message<N>) -> usize {
-  let buf: &[u32] = unsafe {
-    core::slice::from_raw_parts(
-      message as *const Message<N> as *const u32,
-      core::mem::size_of::<Message<N>>() / 4,
-    )
-  };
+fn print_message_buf(message: &Message) -> usize {
+  let buf: &[u32] = message.words();
 
   stream::println!("::META");
   // First 2 words = Message metadata
@@ -77,24 +72,29 @@ pub fn test_mailbox() -> ! {
     "GetClockState::Tag::MESSAGE_LEN = {}",
     GetClockState::Tag::MESSAGE_LEN
   );
-  type Message = mailbox::Message<
-    { GetClockRate::Tag::MESSAGE_LEN + GetClockState::Tag::MESSAGE_LEN },
-  >;
-
-  let mut message = Message::builder()
+  let mut message = Message::new();
+  message
     .add_tag(&clock_rate_tag)
-    .add_tag(&clock_state_tag)
-    .build();
+    .and_then(|message| message.add_tag(&clock_state_tag))
+    .expect("Message full");
 
   stream::println!("=========================");
   stream::println!("Request message");
   print_message_buf(&message);
 
-  message = mailbox::send(message);
+  if let Err(kind) = mailbox::send(&mut message) {
+    panic!("Mailbox request failed: {:?}", kind);
+  }
   stream::println!("=========================");
   stream::println!("Response message");
   let bytes_printed = print_message_buf(&message);
 
+  for tag in message.tags() {
+    match tag {
+      Ok(tag) => stream::println!("Tag {:#010X}: {:?}", tag.id, tag.status),
+      Err(kind) => stream::println!("Malformed tag: {:?}", kind),
+    }
+  }
   stream::println!(
     "Message bytes = {}, Printed bytes = {}",
     &message.size(),
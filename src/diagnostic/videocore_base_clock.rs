@@ -2,29 +2,12 @@ use crate::io::clock;
 use crate::io::clock::ClockId;
 use crate::io::uart;
 
-const CLOCK_IDS: [ClockId; 14] = [
-  ClockId::Emmc,
-  ClockId::Uart,
-  ClockId::Arm,
-  ClockId::Core,
-  ClockId::V3d,
-  ClockId::H264,
-  ClockId::Isp,
-  ClockId::Sdram,
-  ClockId::Pixel,
-  ClockId::Pwm,
-  ClockId::Hevc,
-  ClockId::Emmc2,
-  ClockId::M2mc,
-  ClockId::PixelBvb,
-];
-
 pub fn test_videocore_base_clock() -> ! {
   use crate::common::stream;
   // Test Clock mailbox
   stream::println!("Test VideoCore base clock (via mailbox)");
 
-  for clock_id in CLOCK_IDS {
+  for clock_id in ClockId::ALL {
     stream::println!("===================================");
     stream::println!("Clock: {}", clock_id);
     match clock::get_clock_info(clock_id) {
@@ -0,0 +1,442 @@
+// ELF64 loader for AArch64 programs.
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html
+//
+// Only the program headers are used: PT_LOAD segments are read from a Source
+// and mapped into a Target, the rest is ignored. ET_DYN images are placed at a base address but
+// not relocated, they must relocate themselves (static-pie).
+
+use crate::common::error::ErrorKind;
+pub use crate::mm::Flags;
+use crate::mm::PAGE_SIZE;
+use arrayvec::ArrayVec;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+// Program headers the parser keeps, static programs have about 10
+const MAX_PHDRS: usize = 32;
+
+const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EV_CURRENT: u8 = 1;
+const EM_AARCH64: u16 = 183;
+
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 1 << 0;
+const PF_W: u32 = 1 << 1;
+const PF_R: u32 = 1 << 2;
+
+// Auxiliary vector types
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_BASE: u64 = 7;
+const AT_ENTRY: u64 = 9;
+
+pub const MAX_ARGS: usize = 32;
+pub const MAX_ENV: usize = 32;
+const AUXV_COUNT: usize = 7;
+// argc, argv + NULL, envp + NULL, auxv pairs
+const MAX_STACK_WORDS: usize = 1 + MAX_ARGS + 1 + MAX_ENV + 1 + 2 * AUXV_COUNT;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+  Executable,
+  // Position independent, loaded at LoadParams::base
+  SharedObject,
+}
+
+fn flags(bits: u32) -> Flags {
+  Flags {
+    read: bits & PF_R != 0,
+    write: bits & PF_W != 0,
+    execute: bits & PF_X != 0,
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Segment {
+  pub flags: Flags,
+  pub offset: u64,
+  pub vaddr: u64,
+  pub file_size: u64,
+  pub mem_size: u64,
+}
+
+pub struct Elf {
+  pub kind: Kind,
+  pub entry: u64,
+  phoff: u64,
+  phnum: usize,
+  phdrs: [u8; MAX_PHDRS * PHDR_SIZE],
+}
+
+// Where the image is read from, e.g. an open file.
+pub trait Source {
+  fn size(&self) -> u64;
+  // Fills `buffer` from `offset`, UnexpectedEof past the end.
+  fn read_at(
+    &mut self,
+    offset: u64,
+    buffer: &mut [u8],
+  ) -> Result<(), ErrorKind>;
+}
+
+impl Source for &[u8] {
+  fn size(&self) -> u64 {
+    self.len() as u64
+  }
+
+  fn read_at(
+    &mut self,
+    offset: u64,
+    buffer: &mut [u8],
+  ) -> Result<(), ErrorKind> {
+    let start =
+      usize::try_from(offset).map_err(|_| ErrorKind::UnexpectedEof)?;
+    match start.checked_add(buffer.len()) {
+      Some(end) if end <= self.len() => {
+        buffer.copy_from_slice(&self[start..end]);
+        Ok(())
+      }
+      _ => Err(ErrorKind::UnexpectedEof),
+    }
+  }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+  u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+  let mut bytes = [0u8; 4];
+  bytes.copy_from_slice(&data[offset..offset + 4]);
+  u32::from_le_bytes(bytes)
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+  let mut bytes = [0u8; 8];
+  bytes.copy_from_slice(&data[offset..offset + 8]);
+  u64::from_le_bytes(bytes)
+}
+
+impl Elf {
+  // Reads and checks the header and all program headers. Unsupported for
+  // valid ELF files of another kind, InvalidData for broken ones.
+  pub fn parse(source: &mut impl Source) -> Result<Elf, ErrorKind> {
+    let size = source.size();
+    let mut header = [0u8; EHDR_SIZE];
+    if size < EHDR_SIZE as u64 {
+      return Err(ErrorKind::InvalidData);
+    }
+    source.read_at(0, &mut header)?;
+    let data = &header;
+    if data[..4] != ELF_MAGIC {
+      return Err(ErrorKind::InvalidData);
+    }
+    if data[4] != ELFCLASS64
+      || data[5] != ELFDATA2LSB
+      || data[6] != EV_CURRENT
+      || u16_at(data, 18) != EM_AARCH64
+    {
+      return Err(ErrorKind::Unsupported);
+    }
+    let kind = match u16_at(data, 16) {
+      ET_EXEC => Kind::Executable,
+      ET_DYN => Kind::SharedObject,
+      _ => return Err(ErrorKind::Unsupported),
+    };
+
+    let phoff = u64_at(data, 32);
+    let phentsize = u16_at(data, 54) as usize;
+    let phnum = u16_at(data, 56) as usize;
+    if phnum > 0 && phentsize != PHDR_SIZE {
+      return Err(ErrorKind::InvalidData);
+    }
+    match phoff.checked_add((phnum * PHDR_SIZE) as u64) {
+      Some(end) if end <= size => {}
+      _ => return Err(ErrorKind::InvalidData),
+    }
+    if phnum > MAX_PHDRS {
+      return Err(ErrorKind::Unsupported);
+    }
+
+    let mut elf = Elf {
+      kind,
+      entry: u64_at(data, 24),
+      phoff,
+      phnum,
+      phdrs: [0; MAX_PHDRS * PHDR_SIZE],
+    };
+    source.read_at(phoff, &mut elf.phdrs[..phnum * PHDR_SIZE])?;
+    for index in 0..phnum {
+      if u32_at(&elf.phdrs, elf.phdr(index)) == PT_LOAD {
+        elf.check_segment(index, size)?;
+      }
+    }
+    Ok(elf)
+  }
+
+  fn phdr(&self, index: usize) -> usize {
+    index * PHDR_SIZE
+  }
+
+  fn segment(&self, index: usize) -> Segment {
+    let phdr = self.phdr(index);
+    Segment {
+      flags: flags(u32_at(&self.phdrs, phdr + 4)),
+      offset: u64_at(&self.phdrs, phdr + 8),
+      vaddr: u64_at(&self.phdrs, phdr + 16),
+      file_size: u64_at(&self.phdrs, phdr + 32),
+      mem_size: u64_at(&self.phdrs, phdr + 40),
+    }
+  }
+
+  // Against the `size` of the file.
+  fn check_segment(&self, index: usize, size: u64) -> Result<(), ErrorKind> {
+    let segment = self.segment(index);
+    let align = u64_at(&self.phdrs, self.phdr(index) + 48);
+    let file_end = segment.offset.checked_add(segment.file_size);
+    let valid = matches!(file_end, Some(end) if end <= size)
+      && segment.file_size <= segment.mem_size
+      && segment.vaddr.checked_add(segment.mem_size).is_some()
+      && (align <= 1
+        || (align.is_power_of_two()
+          && segment.vaddr % align == segment.offset % align));
+    match valid {
+      true => Ok(()),
+      false => Err(ErrorKind::InvalidData),
+    }
+  }
+
+  // PT_LOAD segments, in file order.
+  pub fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+    (0..self.phnum)
+      .filter(|&index| u32_at(&self.phdrs, self.phdr(index)) == PT_LOAD)
+      .map(|index| self.segment(index))
+  }
+
+  // Address of the program headers once loaded, if a segment contains them.
+  fn loaded_phdr(&self, bias: u64) -> Option<u64> {
+    let phoff = self.phoff;
+    self
+      .segments()
+      .find(|s| s.offset <= phoff && phoff < s.offset + s.file_size)
+      .map(|s| s.vaddr + bias + (phoff - s.offset))
+  }
+}
+
+// Destination of a load, e.g. a fresh user address space.
+pub trait Target {
+  // Maps the page aligned range [vaddr, vaddr + size).
+  fn map(
+    &mut self,
+    vaddr: u64,
+    size: u64,
+    flags: Flags,
+  ) -> Result<(), ErrorKind>;
+  // Copies `data` to mapped memory, regardless of its flags.
+  fn write(&mut self, vaddr: u64, data: &[u8]) -> Result<(), ErrorKind>;
+}
+
+pub struct LoadParams<'a> {
+  // Load address of SharedObject images, page aligned
+  pub base: u64,
+  // Page aligned
+  pub stack_top: u64,
+  pub stack_size: u64,
+  pub args: &'a [&'a str],
+  pub env: &'a [&'a str],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Image {
+  pub entry: u64,
+  // Points to argc, 16 byte aligned
+  pub stack_pointer: u64,
+  // First page after the highest segment, start of the heap
+  pub brk: u64,
+}
+
+fn page_down(addr: u64) -> u64 {
+  addr & !(PAGE_SIZE - 1)
+}
+
+fn page_up(addr: u64) -> Option<u64> {
+  Some(addr.checked_add(PAGE_SIZE - 1)? & !(PAGE_SIZE - 1))
+}
+
+fn write_zeros(
+  target: &mut impl Target,
+  mut vaddr: u64,
+  mut size: u64,
+) -> Result<(), ErrorKind> {
+  const ZEROS: [u8; 256] = [0; 256];
+  while size > 0 {
+    let chunk = size.min(ZEROS.len() as u64);
+    target.write(vaddr, &ZEROS[..chunk as usize])?;
+    vaddr += chunk;
+    size -= chunk;
+  }
+  Ok(())
+}
+
+// Copies [offset, offset + size) of the file in pieces, there is no room
+// for whole segments.
+fn copy_data(
+  source: &mut impl Source,
+  target: &mut impl Target,
+  mut vaddr: u64,
+  mut offset: u64,
+  mut size: u64,
+) -> Result<(), ErrorKind> {
+  let mut buffer = [0u8; 512];
+  while size > 0 {
+    let len = size.min(buffer.len() as u64) as usize;
+    let chunk = &mut buffer[..len];
+    source.read_at(offset, chunk)?;
+    target.write(vaddr, chunk)?;
+    vaddr += chunk.len() as u64;
+    offset += chunk.len() as u64;
+    size -= chunk.len() as u64;
+  }
+  Ok(())
+}
+
+// Maps the segments read from `source` and sets up the stack for the entry
+// point, which is to be started at EL0 with the returned stack pointer.
+pub fn load(
+  source: &mut impl Source,
+  target: &mut impl Target,
+  params: &LoadParams,
+) -> Result<Image, ErrorKind> {
+  let elf = Elf::parse(source)?;
+  if params.args.len() > MAX_ARGS || params.env.len() > MAX_ENV {
+    return Err(ErrorKind::ArgumentListTooLong);
+  }
+  let bias = match elf.kind {
+    Kind::Executable => 0,
+    Kind::SharedObject => params.base,
+  };
+  if !bias.is_multiple_of(PAGE_SIZE)
+    || !params.stack_top.is_multiple_of(PAGE_SIZE)
+  {
+    return Err(ErrorKind::InvalidInput);
+  }
+
+  let mut brk = 0;
+  for segment in elf.segments() {
+    if segment.mem_size == 0 {
+      continue;
+    }
+    let vaddr = segment
+      .vaddr
+      .checked_add(bias)
+      .ok_or(ErrorKind::InvalidData)?;
+    let end = vaddr
+      .checked_add(segment.mem_size)
+      .and_then(page_up)
+      .ok_or(ErrorKind::InvalidData)?;
+    let start = page_down(vaddr);
+    target.map(start, end - start, segment.flags)?;
+    copy_data(source, target, vaddr, segment.offset, segment.file_size)?;
+    // .bss
+    write_zeros(
+      target,
+      vaddr + segment.file_size,
+      segment.mem_size - segment.file_size,
+    )?;
+    brk = brk.max(end);
+  }
+
+  let entry = elf.entry.checked_add(bias).ok_or(ErrorKind::InvalidData)?;
+  let auxv = [
+    (AT_PHDR, elf.loaded_phdr(bias).unwrap_or(0)),
+    (AT_PHENT, PHDR_SIZE as u64),
+    (AT_PHNUM, elf.phnum as u64),
+    (AT_PAGESZ, PAGE_SIZE),
+    (AT_BASE, 0),
+    (AT_ENTRY, entry),
+    (AT_NULL, 0),
+  ];
+  let stack_pointer = setup_stack(target, params, &auxv)?;
+  Ok(Image {
+    entry,
+    stack_pointer,
+    brk,
+  })
+}
+
+// Maps the stack and lays it out as the AArch64 Linux ABI does, from the
+// top: argument and environment strings, then (16 byte aligned) auxv,
+// envp + NULL, argv + NULL, argc.
+fn setup_stack(
+  target: &mut impl Target,
+  params: &LoadParams,
+  auxv: &[(u64, u64); AUXV_COUNT],
+) -> Result<u64, ErrorKind> {
+  let bottom = params
+    .stack_top
+    .checked_sub(params.stack_size)
+    .filter(|_| {
+      params.stack_size.is_multiple_of(PAGE_SIZE) && params.stack_size > 0
+    })
+    .ok_or(ErrorKind::InvalidInput)?;
+  target.map(
+    bottom,
+    params.stack_size,
+    Flags {
+      read: true,
+      write: true,
+      execute: false,
+    },
+  )?;
+
+  let mut top = params.stack_top;
+  let mut push_string = |s: &str| -> Result<u64, ErrorKind> {
+    let size = s.len() as u64 + 1;
+    top = top
+      .checked_sub(size)
+      .filter(|&t| t >= bottom)
+      .ok_or(ErrorKind::ArgumentListTooLong)?;
+    target.write(top, s.as_bytes())?;
+    target.write(top + s.len() as u64, &[0])?;
+    Ok(top)
+  };
+  let mut words = ArrayVec::<u64, MAX_STACK_WORDS>::new();
+  let mut env = ArrayVec::<u64, MAX_ENV>::new();
+  for s in params.env {
+    env.push(push_string(s)?);
+  }
+  words.push(params.args.len() as u64);
+  for s in params.args {
+    words.push(push_string(s)?);
+  }
+  words.push(0);
+  words.extend(env);
+  words.push(0);
+  for &(key, value) in auxv {
+    words.push(key);
+    words.push(value);
+  }
+
+  let size = (words.len() * 8) as u64;
+  let sp = top
+    .checked_sub(size)
+    .map(|sp| sp & !0xF)
+    .filter(|&sp| sp >= bottom)
+    .ok_or(ErrorKind::ArgumentListTooLong)?;
+  for (i, word) in words.iter().enumerate() {
+    target.write(sp + i as u64 * 8, &word.to_le_bytes())?;
+  }
+  Ok(sp)
+}
+
+#[cfg(test)]
+#[path = "elf_test.rs"]
+pub(crate) mod elf_test;
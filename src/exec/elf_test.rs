@@ -0,0 +1,391 @@
+use super::*;
+use std::collections::BTreeMap;
+
+pub const RX: u32 = PF_R | PF_X;
+pub const RW: u32 = PF_R | PF_W;
+
+pub struct SegmentSpec<'a> {
+  pub flags: u32,
+  pub vaddr: u64,
+  pub data: &'a [u8],
+  pub mem_size: u64,
+}
+
+// ELF with the program headers right after the header and each segment at
+// the next page aligned offset.
+pub fn build_elf(kind: u16, entry: u64, segments: &[SegmentSpec]) -> Vec<u8> {
+  let mut elf = vec![0u8; EHDR_SIZE + segments.len() * PHDR_SIZE];
+  elf[..4].copy_from_slice(&ELF_MAGIC);
+  elf[4] = ELFCLASS64;
+  elf[5] = ELFDATA2LSB;
+  elf[6] = EV_CURRENT;
+  elf[16..18].copy_from_slice(&kind.to_le_bytes());
+  elf[18..20].copy_from_slice(&EM_AARCH64.to_le_bytes());
+  elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+  elf[24..32].copy_from_slice(&entry.to_le_bytes());
+  elf[32..40].copy_from_slice(&(EHDR_SIZE as u64).to_le_bytes());
+  elf[52..54].copy_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+  elf[54..56].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+  elf[56..58].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+
+  for (i, segment) in segments.iter().enumerate() {
+    let offset = (elf.len() as u64).div_ceil(PAGE_SIZE) * PAGE_SIZE
+      + segment.vaddr % PAGE_SIZE;
+    elf.resize(offset as usize, 0);
+    elf.extend_from_slice(segment.data);
+
+    let phdr = EHDR_SIZE + i * PHDR_SIZE;
+    let fields: [(usize, u64); 6] = [
+      (8, offset),
+      (16, segment.vaddr),
+      (24, segment.vaddr),
+      (32, segment.data.len() as u64),
+      (40, segment.mem_size),
+      (48, PAGE_SIZE),
+    ];
+    elf[phdr..phdr + 4].copy_from_slice(&PT_LOAD.to_le_bytes());
+    elf[phdr + 4..phdr + 8].copy_from_slice(&segment.flags.to_le_bytes());
+    for (field, value) in fields {
+      elf[phdr + field..phdr + field + 8].copy_from_slice(&value.to_le_bytes());
+    }
+  }
+  elf
+}
+
+// For tests of the users of the loader.
+pub fn build_executable(entry: u64, segments: &[SegmentSpec]) -> Vec<u8> {
+  build_elf(ET_EXEC, entry, segments)
+}
+
+fn sample_elf() -> Vec<u8> {
+  build_elf(
+    ET_EXEC,
+    0x40_0010,
+    &[
+      SegmentSpec {
+        flags: RX,
+        vaddr: 0x40_0000,
+        data: &[0x11; 0x20],
+        mem_size: 0x20,
+      },
+      SegmentSpec {
+        flags: RW,
+        vaddr: 0x41_0100,
+        data: &[0x22; 0x10],
+        mem_size: 0x1800,
+      },
+    ],
+  )
+}
+
+// Fresh pages are filled with 0xAA to catch missing zeroing.
+const MAX_PAGES: u64 = 64;
+
+#[derive(Default)]
+struct FakeTarget {
+  pages: BTreeMap<u64, (Vec<u8>, Flags)>,
+}
+
+impl FakeTarget {
+  fn read(&self, vaddr: u64, size: usize) -> Vec<u8> {
+    (vaddr..vaddr + size as u64)
+      .map(|addr| {
+        let (page, _) = &self.pages[&page_down(addr)];
+        page[(addr % PAGE_SIZE) as usize]
+      })
+      .collect()
+  }
+
+  fn read_u64(&self, vaddr: u64) -> u64 {
+    u64::from_le_bytes(self.read(vaddr, 8).try_into().unwrap())
+  }
+
+  fn read_str(&self, vaddr: u64) -> String {
+    let mut bytes = Vec::new();
+    let mut addr = vaddr;
+    while self.read(addr, 1)[0] != 0 {
+      bytes.push(self.read(addr, 1)[0]);
+      addr += 1;
+    }
+    String::from_utf8(bytes).unwrap()
+  }
+
+  fn flags(&self, vaddr: u64) -> Option<Flags> {
+    self.pages.get(&page_down(vaddr)).map(|(_, flags)| *flags)
+  }
+}
+
+impl Target for FakeTarget {
+  fn map(
+    &mut self,
+    vaddr: u64,
+    size: u64,
+    flags: Flags,
+  ) -> Result<(), ErrorKind> {
+    assert_eq!(vaddr % PAGE_SIZE, 0);
+    assert_eq!(size % PAGE_SIZE, 0);
+    if self.pages.len() as u64 + size / PAGE_SIZE > MAX_PAGES {
+      return Err(ErrorKind::OutOfMemory);
+    }
+    for page in (vaddr..vaddr + size).step_by(PAGE_SIZE as usize) {
+      if self.pages.contains_key(&page) {
+        return Err(ErrorKind::AlreadyExists);
+      }
+      self
+        .pages
+        .insert(page, (vec![0xAA; PAGE_SIZE as usize], flags));
+    }
+    Ok(())
+  }
+
+  fn write(&mut self, vaddr: u64, data: &[u8]) -> Result<(), ErrorKind> {
+    for (i, byte) in data.iter().enumerate() {
+      let addr = vaddr + i as u64;
+      let (page, _) = self
+        .pages
+        .get_mut(&page_down(addr))
+        .ok_or(ErrorKind::NotFound)?;
+      page[(addr % PAGE_SIZE) as usize] = *byte;
+    }
+    Ok(())
+  }
+}
+
+const STACK_TOP: u64 = 0x8000_0000;
+
+fn params<'a>(args: &'a [&'a str], env: &'a [&'a str]) -> LoadParams<'a> {
+  LoadParams {
+    base: 0x1000_0000,
+    stack_top: STACK_TOP,
+    stack_size: 4 * PAGE_SIZE,
+    args,
+    env,
+  }
+}
+
+#[test]
+fn test_parse() {
+  let data = sample_elf();
+  let elf = Elf::parse(&mut data.as_slice()).unwrap();
+  assert_eq!(elf.kind, Kind::Executable);
+  assert_eq!(elf.entry, 0x40_0010);
+  let segments: Vec<Segment> = elf.segments().collect();
+  assert_eq!(segments.len(), 2);
+  assert_eq!(
+    segments[0].flags,
+    Flags {
+      read: true,
+      write: false,
+      execute: true
+    }
+  );
+  assert_eq!(segments[1].vaddr, 0x41_0100);
+  assert_eq!(segments[1].mem_size, 0x1800);
+  let offset = segments[1].offset as usize;
+  assert_eq!(&data[offset..offset + 0x10], &[0x22; 0x10]);
+  assert_eq!(segments[1].file_size, 0x10);
+}
+
+#[test]
+fn test_parse_rejects() {
+  let valid = sample_elf();
+  let broken = |offset: usize, value: u8| {
+    let mut data = valid.clone();
+    data[offset] = value;
+    Elf::parse(&mut data.as_slice()).err()
+  };
+  assert!(matches!(broken(0, 0), Some(ErrorKind::InvalidData)));
+  // 32 bit, big endian, x86-64, relocatable
+  assert!(matches!(broken(4, 1), Some(ErrorKind::Unsupported)));
+  assert!(matches!(broken(5, 2), Some(ErrorKind::Unsupported)));
+  assert!(matches!(broken(18, 62), Some(ErrorKind::Unsupported)));
+  assert!(matches!(broken(16, 1), Some(ErrorKind::Unsupported)));
+  // Program header size, count beyond the file
+  assert!(matches!(broken(54, 32), Some(ErrorKind::InvalidData)));
+  assert!(matches!(broken(57, 0xFF), Some(ErrorKind::InvalidData)));
+  // Segment data beyond the file, file size above memory size
+  assert!(matches!(
+    broken(64 + 14, 0x10),
+    Some(ErrorKind::InvalidData)
+  ));
+  assert!(matches!(
+    broken(64 + PHDR_SIZE + 33, 0x40),
+    Some(ErrorKind::InvalidData)
+  ));
+  assert!(matches!(
+    Elf::parse(&mut &valid[..40]),
+    Err(ErrorKind::InvalidData)
+  ));
+}
+
+#[test]
+fn test_load_segments() {
+  let data = sample_elf();
+  let mut target = FakeTarget::default();
+  let image =
+    load(&mut data.as_slice(), &mut target, &params(&[], &[])).unwrap();
+  assert_eq!(image.entry, 0x40_0010);
+  assert_eq!(image.brk, 0x41_2000);
+
+  assert_eq!(target.read(0x40_0000, 0x20), vec![0x11; 0x20]);
+  assert_eq!(target.read(0x41_0100, 0x10), vec![0x22; 0x10]);
+  // .bss is zeroed, the rest of the page is left alone.
+  assert!(target.read(0x41_0110, 0x17F0).iter().all(|&b| b == 0));
+  assert_eq!(target.read(0x41_1900, 1), vec![0xAA]);
+
+  let text = target.flags(0x40_0000).unwrap();
+  assert!(text.execute && !text.write);
+  let data_flags = target.flags(0x41_1000).unwrap();
+  assert!(data_flags.write && !data_flags.execute);
+  assert_eq!(target.flags(0x40_1000), None);
+}
+
+#[test]
+fn test_load_stack() {
+  let data = sample_elf();
+  let mut target = FakeTarget::default();
+  let args = ["init", "-v"];
+  let env = ["HOME=/"];
+  let image =
+    load(&mut data.as_slice(), &mut target, &params(&args, &env)).unwrap();
+  let sp = image.stack_pointer;
+  assert_eq!(sp % 16, 0);
+  assert!((STACK_TOP - 4 * PAGE_SIZE..STACK_TOP).contains(&sp));
+
+  assert_eq!(target.read_u64(sp), 2);
+  assert_eq!(target.read_str(target.read_u64(sp + 8)), "init");
+  assert_eq!(target.read_str(target.read_u64(sp + 16)), "-v");
+  assert_eq!(target.read_u64(sp + 24), 0);
+  assert_eq!(target.read_str(target.read_u64(sp + 32)), "HOME=/");
+  assert_eq!(target.read_u64(sp + 40), 0);
+
+  let auxv: Vec<(u64, u64)> = (0..AUXV_COUNT as u64)
+    .map(|i| {
+      let entry = sp + 48 + i * 16;
+      (target.read_u64(entry), target.read_u64(entry + 8))
+    })
+    .collect();
+  // The program headers are not part of a segment in the sample.
+  assert_eq!(auxv[0], (AT_PHDR, 0));
+  assert_eq!(auxv[2], (AT_PHNUM, 2));
+  assert_eq!(auxv[3], (AT_PAGESZ, PAGE_SIZE));
+  assert_eq!(auxv[5], (AT_ENTRY, 0x40_0010));
+  assert_eq!(auxv[6], (AT_NULL, 0));
+}
+
+#[test]
+fn test_load_in_pieces() {
+  let text: Vec<u8> = (0..0x1234).map(|i| i as u8).collect();
+  let data = build_executable(
+    0x40_0000,
+    &[SegmentSpec {
+      flags: RX,
+      vaddr: 0x40_0000,
+      data: &text,
+      mem_size: 0x1234,
+    }],
+  );
+  let mut target = FakeTarget::default();
+  load(&mut data.as_slice(), &mut target, &params(&[], &[])).unwrap();
+  assert_eq!(target.read(0x40_0000, text.len()), text);
+}
+
+#[test]
+fn test_parse_too_many_headers() {
+  let segments: Vec<SegmentSpec> = (0..MAX_PHDRS as u64 + 1)
+    .map(|i| SegmentSpec {
+      flags: RX,
+      vaddr: 0x40_0000 + i * PAGE_SIZE,
+      data: &[0],
+      mem_size: 1,
+    })
+    .collect();
+  let data = build_executable(0x40_0000, &segments);
+  assert!(matches!(
+    Elf::parse(&mut data.as_slice()),
+    Err(ErrorKind::Unsupported)
+  ));
+}
+
+#[test]
+fn test_load_shared_object() {
+  let data = build_elf(
+    ET_DYN,
+    0x100,
+    &[SegmentSpec {
+      flags: RX,
+      vaddr: 0,
+      data: &[0x33; 0x200],
+      mem_size: 0x200,
+    }],
+  );
+  let mut target = FakeTarget::default();
+  let image =
+    load(&mut data.as_slice(), &mut target, &params(&[], &[])).unwrap();
+  assert_eq!(image.entry, 0x1000_0100);
+  assert_eq!(target.read(0x1000_0000, 4), vec![0x33; 4]);
+}
+
+#[test]
+fn test_load_errors() {
+  let data = sample_elf();
+  let args = ["x"; MAX_ARGS + 1];
+  assert!(matches!(
+    load(
+      &mut data.as_slice(),
+      &mut FakeTarget::default(),
+      &params(&args, &[])
+    ),
+    Err(ErrorKind::ArgumentListTooLong)
+  ));
+  let long = "x".repeat(5 * PAGE_SIZE as usize);
+  assert!(matches!(
+    load(
+      &mut data.as_slice(),
+      &mut FakeTarget::default(),
+      &params(&[&long], &[])
+    ),
+    Err(ErrorKind::ArgumentListTooLong)
+  ));
+  // Stack overlapping a segment
+  let mut overlapping = params(&[], &[]);
+  overlapping.stack_top = 0x40_2000;
+  assert!(matches!(
+    load(
+      &mut data.as_slice(),
+      &mut FakeTarget::default(),
+      &overlapping
+    ),
+    Err(ErrorKind::AlreadyExists)
+  ));
+}
+
+// Random corruptions must be rejected or loaded, never panic.
+#[test]
+fn test_fuzz_corrupted() {
+  let valid = sample_elf();
+  let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+  let mut random = move || {
+    seed ^= seed << 13;
+    seed ^= seed >> 7;
+    seed ^= seed << 17;
+    seed
+  };
+  for _ in 0..2000 {
+    let mut data = valid.clone();
+    for _ in 0..1 + random() % 4 {
+      // Mostly the headers, where the parser looks
+      let offset = (random() % 0x100) as usize;
+      data[offset] = random() as u8;
+    }
+    let length = match random() % 8 {
+      0 => (random() as usize) % data.len(),
+      _ => data.len(),
+    };
+    let _ = load(
+      &mut &data[..length],
+      &mut FakeTarget::default(),
+      &params(&["a"], &[]),
+    );
+  }
+}
@@ -0,0 +1,55 @@
+pub mod elf;
+
+use crate::common::error::ErrorKind;
+use crate::fs::{self, vfs};
+
+// A program opened for execve(). The loader reads it a piece at a time, so
+// there is no copy of the whole image. Closed when dropped.
+pub struct Program {
+  file: vfs::FileId,
+  size: u64,
+}
+
+// Opens the program at `path`, which must be a regular file.
+pub fn open_program(path: &str) -> Result<Program, ErrorKind> {
+  let vfs = fs::vfs();
+  let file = vfs.open(path, vfs::O_RDONLY)?;
+  // Closes the file on errors.
+  let mut program = Program { file, size: 0 };
+  let stat = vfs.fstat(file)?;
+  if stat.kind != vfs::FileType::Regular {
+    return Err(ErrorKind::PermissionDenied);
+  }
+  program.size = stat.size;
+  Ok(program)
+}
+
+impl elf::Source for Program {
+  fn size(&self) -> u64 {
+    self.size
+  }
+
+  fn read_at(
+    &mut self,
+    offset: u64,
+    buffer: &mut [u8],
+  ) -> Result<(), ErrorKind> {
+    let vfs = fs::vfs();
+    let offset = i64::try_from(offset).map_err(|_| ErrorKind::UnexpectedEof)?;
+    vfs.seek(self.file, offset, vfs::SEEK_SET)?;
+    let mut len = 0;
+    while len < buffer.len() {
+      match vfs.read(self.file, &mut buffer[len..])? {
+        0 => return Err(ErrorKind::UnexpectedEof),
+        count => len += count,
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Drop for Program {
+  fn drop(&mut self) {
+    fs::vfs().release(self.file);
+  }
+}
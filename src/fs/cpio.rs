@@ -0,0 +1,135 @@
+// Reader for `cpio -H newc` archives: each entry is a 110 byte header of
+// ASCII hex fields, the NUL terminated name and the data, the last two
+// padded to 4 bytes. An entry named TRAILER!!! ends the archive.
+
+use crate::common::error::ErrorKind;
+use crate::fs::vfs::FileType;
+
+pub const HEADER_SIZE: usize = 110;
+const MAGIC: &[u8] = b"070701";
+// Same layout with a checksum
+const MAGIC_CRC: &[u8] = b"070702";
+const TRAILER: &str = "TRAILER!!!";
+
+// File types of `mode`
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+// Fields after the magic, 8 hex digits each
+const FIELD_MODE: usize = 1;
+const FIELD_FILESIZE: usize = 6;
+const FIELD_NAMESIZE: usize = 11;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Entry<'a> {
+  pub name: &'a str,
+  pub mode: u32,
+  pub data: &'a [u8],
+}
+
+impl Entry<'_> {
+  // None for symbolic links, devices and others we cannot store.
+  pub fn kind(&self) -> Option<FileType> {
+    match self.mode & S_IFMT {
+      S_IFDIR => Some(FileType::Directory),
+      S_IFREG => Some(FileType::Regular),
+      _ => None,
+    }
+  }
+}
+
+// True if `archive` starts like a newc archive.
+pub fn is_archive(archive: &[u8]) -> bool {
+  archive.starts_with(MAGIC) || archive.starts_with(MAGIC_CRC)
+}
+
+fn align(offset: usize) -> usize {
+  (offset + 3) & !3
+}
+
+fn field(header: &[u8], index: usize) -> Result<u32, ErrorKind> {
+  let start = MAGIC.len() + index * 8;
+  let digits = core::str::from_utf8(&header[start..start + 8])
+    .map_err(|_| ErrorKind::InvalidData)?;
+  u32::from_str_radix(digits, 16).map_err(|_| ErrorKind::InvalidData)
+}
+
+// The entries of an archive, up to the trailer.
+pub struct Reader<'a> {
+  archive: &'a [u8],
+  offset: usize,
+  done: bool,
+}
+
+impl<'a> Reader<'a> {
+  pub fn new(archive: &'a [u8]) -> Reader<'a> {
+    Reader {
+      archive,
+      offset: 0,
+      done: false,
+    }
+  }
+
+  // Bytes read so far, the archive's size once the trailer was read.
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+
+  fn next_entry(&mut self) -> Result<Option<Entry<'a>>, ErrorKind> {
+    let rest = &self.archive[self.offset..];
+    if rest.len() < HEADER_SIZE || !is_archive(rest) {
+      return Err(ErrorKind::InvalidData);
+    }
+    let mode = field(rest, FIELD_MODE)?;
+    let size = field(rest, FIELD_FILESIZE)? as usize;
+    let name_size = field(rest, FIELD_NAMESIZE)? as usize;
+    let name_end = HEADER_SIZE + name_size;
+    let data = align(name_end);
+    let end = data.checked_add(size).ok_or(ErrorKind::InvalidData)?;
+    // The name includes its NUL.
+    if name_size == 0 || end > rest.len() || rest[name_end - 1] != 0 {
+      return Err(ErrorKind::InvalidData);
+    }
+    let name = core::str::from_utf8(&rest[HEADER_SIZE..name_end - 1])
+      .map_err(|_| ErrorKind::InvalidData)?;
+    self.offset += align(end).min(rest.len());
+    if name == TRAILER {
+      return Ok(None);
+    }
+    Ok(Some(Entry {
+      name,
+      mode,
+      data: &rest[data..end],
+    }))
+  }
+}
+
+impl<'a> Iterator for Reader<'a> {
+  type Item = Result<Entry<'a>, ErrorKind>;
+
+  // Stops after the trailer or the first error.
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let entry = self.next_entry();
+    if !matches!(entry, Ok(Some(_))) {
+      self.done = true;
+    }
+    entry.transpose()
+  }
+}
+
+// Size of the archive at the start of `memory`, including the trailer.
+pub fn size(memory: &[u8]) -> Result<usize, ErrorKind> {
+  let mut reader = Reader::new(memory);
+  for entry in reader.by_ref() {
+    entry?;
+  }
+  Ok(reader.offset())
+}
+
+#[cfg(test)]
+#[path = "cpio_test.rs"]
+pub(crate) mod cpio_test;
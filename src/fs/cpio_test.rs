@@ -0,0 +1,106 @@
+use super::*;
+
+pub const DIR: u32 = 0o040755;
+pub const FILE: u32 = 0o100644;
+const SYMLINK: u32 = 0o120777;
+
+fn header(name: &str, mode: u32, size: usize) -> Vec<u8> {
+  // ino, mode, uid, gid, nlink, mtime, filesize, devmajor, devminor,
+  // rdevmajor, rdevminor, namesize, check
+  let fields = [1, mode, 0, 0, 1, 0, size as u32, 0, 0, 0, 0];
+  let mut header = String::from("070701");
+  for value in fields {
+    header += &format!("{:08X}", value);
+  }
+  header += &format!("{:08X}{:08X}", name.len() + 1, 0);
+  header.into_bytes()
+}
+
+fn pad(archive: &mut Vec<u8>) {
+  while !archive.len().is_multiple_of(4) {
+    archive.push(0);
+  }
+}
+
+// A newc archive of `entries` followed by the trailer.
+pub fn archive(entries: &[(&str, u32, &[u8])]) -> Vec<u8> {
+  let mut archive = Vec::new();
+  let trailer = [(TRAILER, 0, &[][..])];
+  for &(name, mode, data) in entries.iter().chain(&trailer) {
+    archive.extend(header(name, mode, data.len()));
+    archive.extend(name.as_bytes());
+    archive.push(0);
+    pad(&mut archive);
+    archive.extend(data);
+    pad(&mut archive);
+  }
+  archive
+}
+
+#[test]
+fn test_entries() {
+  let archive = archive(&[
+    (".", DIR, b""),
+    ("bin", DIR, b""),
+    ("bin/hello", FILE, b"hello world"),
+    ("bin/sh", SYMLINK, b"hello"),
+  ]);
+  assert!(is_archive(&archive));
+  let entries: Vec<_> = Reader::new(&archive).map(Result::unwrap).collect();
+  assert_eq!(entries.len(), 4);
+  assert_eq!(entries[0].name, ".");
+  assert_eq!(entries[1].kind(), Some(FileType::Directory));
+  assert_eq!(
+    entries[2],
+    Entry {
+      name: "bin/hello",
+      mode: FILE,
+      data: b"hello world",
+    }
+  );
+  assert_eq!(entries[2].kind(), Some(FileType::Regular));
+  assert_eq!(entries[3].kind(), None);
+}
+
+#[test]
+fn test_size() {
+  let mut memory = archive(&[("a", FILE, b"12345")]);
+  let len = memory.len();
+  assert_eq!(len % 4, 0);
+  // Whatever follows the archive is not part of it.
+  memory.extend(b"070701garbage");
+  assert_eq!(size(&memory), Ok(len));
+  assert_eq!(size(&archive(&[])), Ok(HEADER_SIZE + 14));
+}
+
+#[test]
+fn test_stops_at_trailer() {
+  let mut memory = archive(&[]);
+  memory.extend(archive(&[("after", FILE, b"")]));
+  assert_eq!(Reader::new(&memory).count(), 0);
+}
+
+#[test]
+fn test_invalid() {
+  assert!(!is_archive(b"070707"));
+  assert_eq!(size(b""), Err(ErrorKind::InvalidData));
+  assert_eq!(size(&[b'0'; HEADER_SIZE]), Err(ErrorKind::InvalidData));
+
+  let valid = archive(&[("file", FILE, b"data")]);
+  // Missing the trailer
+  let truncated = &valid[..valid.len() - HEADER_SIZE];
+  assert_eq!(size(truncated), Err(ErrorKind::InvalidData));
+  // Data past the end
+  assert_eq!(size(&valid[..HEADER_SIZE + 8]), Err(ErrorKind::InvalidData));
+
+  let mut bad_digit = valid.clone();
+  bad_digit[14] = b'x';
+  assert_eq!(size(&bad_digit), Err(ErrorKind::InvalidData));
+
+  // The name is not NUL terminated.
+  let mut no_nul = valid.clone();
+  no_nul[HEADER_SIZE + 4] = b'!';
+  let mut reader = Reader::new(&no_nul);
+  assert_eq!(reader.next(), Some(Err(ErrorKind::InvalidData)));
+  assert_eq!(reader.next(), None);
+}
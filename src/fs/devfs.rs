@@ -0,0 +1,105 @@
+// Device filesystem for /dev: a flat directory of character devices.
+
+use super::vfs::{DirEntry, FileSystem, FileType, InodeId, Stat};
+use crate::common::error::ErrorKind;
+use arrayvec::ArrayString;
+
+pub struct Device {
+  pub name: &'static str,
+  // Blocks until some data is available.
+  pub read: fn(buffer: &mut [u8]) -> Result<usize, ErrorKind>,
+  pub write: fn(data: &[u8]) -> Result<usize, ErrorKind>,
+}
+
+const ROOT: InodeId = 1;
+// Inode of the first device
+const FIRST_DEVICE: InodeId = 2;
+
+pub struct DevFs {
+  devices: &'static [Device],
+}
+
+impl DevFs {
+  pub const fn new(devices: &'static [Device]) -> DevFs {
+    DevFs { devices }
+  }
+
+  fn device(&self, inode: InodeId) -> Result<&'static Device, ErrorKind> {
+    match inode {
+      ROOT => Err(ErrorKind::IsADirectory),
+      _ => inode
+        .checked_sub(FIRST_DEVICE)
+        .and_then(|index| self.devices.get(index as usize))
+        .ok_or(ErrorKind::NotFound),
+    }
+  }
+}
+
+impl FileSystem for DevFs {
+  fn root(&self) -> InodeId {
+    ROOT
+  }
+
+  fn lookup(&mut self, dir: InodeId, name: &str) -> Result<InodeId, ErrorKind> {
+    if dir != ROOT {
+      return Err(ErrorKind::NotADirectory);
+    }
+    self
+      .devices
+      .iter()
+      .position(|device| device.name == name)
+      .map(|index| FIRST_DEVICE + index as InodeId)
+      .ok_or(ErrorKind::NotFound)
+  }
+
+  fn stat(&mut self, inode: InodeId) -> Result<Stat, ErrorKind> {
+    let kind = match inode {
+      ROOT => FileType::Directory,
+      _ => self.device(inode).map(|_| FileType::CharDevice)?,
+    };
+    Ok(Stat {
+      inode,
+      device: 0,
+      kind,
+      size: 0,
+      links: 1,
+    })
+  }
+
+  fn read(
+    &mut self,
+    inode: InodeId,
+    _: u64,
+    buffer: &mut [u8],
+  ) -> Result<usize, ErrorKind> {
+    (self.device(inode)?.read)(buffer)
+  }
+
+  fn write(
+    &mut self,
+    inode: InodeId,
+    _: u64,
+    data: &[u8],
+  ) -> Result<usize, ErrorKind> {
+    (self.device(inode)?.write)(data)
+  }
+
+  fn readdir(
+    &mut self,
+    dir: InodeId,
+    index: usize,
+  ) -> Result<Option<DirEntry>, ErrorKind> {
+    if dir != ROOT {
+      return Err(ErrorKind::NotADirectory);
+    }
+    Ok(self.devices.get(index).map(|device| DirEntry {
+      inode: FIRST_DEVICE + index as InodeId,
+      kind: FileType::CharDevice,
+      name: ArrayString::from(device.name).unwrap(),
+    }))
+  }
+}
+
+#[cfg(test)]
+#[path = "devfs_test.rs"]
+mod devfs_test;
@@ -0,0 +1,77 @@
+use super::*;
+
+fn read_zero(buffer: &mut [u8]) -> Result<usize, ErrorKind> {
+  buffer.fill(0);
+  Ok(buffer.len())
+}
+
+fn write_null(data: &[u8]) -> Result<usize, ErrorKind> {
+  Ok(data.len())
+}
+
+static DEVICES: [Device; 2] = [
+  Device {
+    name: "zero",
+    read: read_zero,
+    write: write_null,
+  },
+  Device {
+    name: "null",
+    read: |_| Ok(0),
+    write: write_null,
+  },
+];
+
+#[test]
+fn test_lookup_and_readdir() {
+  let mut devfs = DevFs::new(&DEVICES);
+  let null = devfs.lookup(ROOT, "null").unwrap();
+  assert_eq!(devfs.stat(null).unwrap().kind, FileType::CharDevice);
+  assert_eq!(devfs.stat(ROOT).unwrap().kind, FileType::Directory);
+  assert_eq!(devfs.lookup(ROOT, "tty"), Err(ErrorKind::NotFound));
+  assert_eq!(devfs.lookup(null, "x"), Err(ErrorKind::NotADirectory));
+
+  let names: Vec<_> = (0..)
+    .map_while(|i| devfs.readdir(ROOT, i).unwrap())
+    .map(|entry| entry.name.to_string())
+    .collect();
+  assert_eq!(names, ["zero", "null"]);
+}
+
+#[test]
+fn test_read_write() {
+  let mut devfs = DevFs::new(&DEVICES);
+  let zero = devfs.lookup(ROOT, "zero").unwrap();
+  let mut buffer = [1; 4];
+  assert_eq!(devfs.read(zero, 100, &mut buffer), Ok(4));
+  assert_eq!(buffer, [0; 4]);
+  assert_eq!(devfs.write(zero, 0, b"abc"), Ok(3));
+  assert_eq!(
+    devfs.read(ROOT, 0, &mut buffer),
+    Err(ErrorKind::IsADirectory)
+  );
+  assert_eq!(devfs.read(9, 0, &mut buffer), Err(ErrorKind::NotFound));
+}
+
+#[test]
+#[cfg(feature = "host")]
+fn test_console() {
+  use crate::io::uart;
+
+  let _guard = uart::mock::initialize();
+  // Edited with backspace, echoed, then handed out in pieces
+  uart::mock::set_input("ax\x7fbc\r\x04");
+  let mut devfs = DevFs::new(&crate::fs::DEVICES);
+  let console = devfs.lookup(ROOT, "console").unwrap();
+  let mut buffer = [0; 8];
+  assert_eq!(devfs.read(console, 0, &mut buffer[..2]), Ok(2));
+  assert_eq!(devfs.read(console, 0, &mut buffer[2..]), Ok(2));
+  assert_eq!(&buffer[..4], b"abc\n");
+  assert_eq!(uart::mock::get_output(), b"ax\x08 \x08bc\r\n");
+  // Ctrl-D on an empty line
+  assert_eq!(devfs.read(console, 0, &mut buffer), Ok(0));
+
+  let tty = devfs.lookup(ROOT, "ttyS0").unwrap();
+  assert_eq!(devfs.write(tty, 0, b"ok\n"), Ok(3));
+  assert!(uart::mock::get_output().ends_with(b"\r\nok\r\n"));
+}
@@ -0,0 +1,8 @@
+use super::*;
+
+#[test]
+fn test_initialize_without_frames() {
+  assert_eq!(initialize(), Err(ErrorKind::NotConnected));
+  // Nothing was mounted.
+  assert!(vfs().resolve("/").is_err());
+}
@@ -0,0 +1,77 @@
+// The initial root filesystem: cpio newc archives unpacked into the ramfs
+// mounted at /. One is linked into the kernel by build.rs, another may be
+// loaded by the firmware with a config.txt line like
+//   initramfs initramfs.cpio 0x4000000
+// at the address the board's memory setup looks at.
+
+use crate::common::error::ErrorKind;
+use crate::fs::cpio;
+use crate::fs::vfs::{self, FileType, Vfs};
+use crate::mm::{self, PAGE_SIZE};
+
+// Empty unless OSDEV_INITRAMFS or OSDEV_USER_BIN was set, see build.rs
+static LINKED: &[u8] =
+  include_bytes!(concat!(env!("OUT_DIR"), "/initramfs.cpio"));
+
+// Physical address and contents of the archive the firmware loaded, whose
+// frames are reserved until it is unpacked
+static mut LOADED: Option<(u64, &'static [u8])> = None;
+
+pub fn set_loaded(pa: u64, archive: &'static [u8]) {
+  unsafe { LOADED = Some((pa, archive)) };
+}
+
+// Creates the directories and regular files of `archive` in `vfs`, files
+// replace existing ones. Returns the number of entries created. Symbolic
+// links and device nodes are skipped.
+pub fn unpack(vfs: &mut Vfs, archive: &[u8]) -> Result<usize, ErrorKind> {
+  let mut count = 0;
+  for entry in cpio::Reader::new(archive) {
+    let entry = entry?;
+    let path = vfs::normalize(entry.name)?;
+    match entry.kind() {
+      Some(FileType::Directory) => {
+        match vfs.create(&path, FileType::Directory) {
+          Ok(_) => count += 1,
+          Err(ErrorKind::AlreadyExists) => {}
+          Err(e) => return Err(e),
+        }
+      }
+      Some(FileType::Regular) => {
+        let flags = vfs::O_WRONLY | vfs::O_CREAT | vfs::O_TRUNC;
+        let file = vfs.open(&path, flags)?;
+        let written = vfs.write(file, entry.data);
+        vfs.release(file);
+        if written? < entry.data.len() {
+          return Err(ErrorKind::OutOfMemory);
+        }
+        count += 1;
+      }
+      _ => {}
+    }
+  }
+  Ok(count)
+}
+
+// Unpacks the linked archive, then the loaded one, whose frames are freed.
+pub fn load(vfs: &mut Vfs) -> Result<usize, ErrorKind> {
+  let mut count = 0;
+  if !LINKED.is_empty() {
+    count += unpack(vfs, LINKED)?;
+  }
+  let loaded = unsafe { (*core::ptr::addr_of_mut!(LOADED)).take() };
+  if let Some((pa, archive)) = loaded {
+    let result = unpack(vfs, archive);
+    let frames = mm::frames();
+    let end = pa + archive.len() as u64;
+    for page in (pa..end).step_by(PAGE_SIZE as usize) {
+      frames.release(page);
+    }
+    count += result?;
+  }
+  Ok(count)
+}
+
+#[cfg(test)]
+#[path = "initramfs_test.rs"]
+mod initramfs_test;
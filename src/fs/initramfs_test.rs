@@ -0,0 +1,64 @@
+use super::*;
+use crate::fs::cpio::cpio_test::{archive, DIR, FILE};
+use crate::fs::ramfs::RamFs;
+use crate::mm::frame_test::host_frames;
+
+fn root() -> Vfs {
+  let frames = Box::leak(Box::new(host_frames(64)));
+  let mut vfs = Vfs::new();
+  vfs
+    .mount("/", Box::leak(Box::new(RamFs::new(frames))))
+    .unwrap();
+  vfs
+}
+
+fn contents(vfs: &mut Vfs, path: &str) -> Vec<u8> {
+  let file = vfs.open(path, vfs::O_RDONLY).unwrap();
+  let mut buffer = vec![0; 64];
+  let len = vfs.read(file, &mut buffer).unwrap();
+  vfs.release(file);
+  buffer.truncate(len);
+  buffer
+}
+
+#[test]
+fn test_unpack() {
+  let mut vfs = root();
+  let archive = archive(&[
+    (".", DIR, b""),
+    ("./bin", DIR, b""),
+    ("bin/hello", FILE, b"hello world"),
+    ("etc", DIR, b""),
+    ("etc/link", 0o120777, b"../bin/hello"),
+  ]);
+  assert_eq!(unpack(&mut vfs, &archive), Ok(3));
+  assert_eq!(contents(&mut vfs, "/bin/hello"), b"hello world");
+  let etc = vfs.resolve("/etc").unwrap();
+  assert_eq!(vfs.stat(etc).unwrap().kind, FileType::Directory);
+  assert_eq!(vfs.resolve("/etc/link"), Err(ErrorKind::NotFound));
+  assert_eq!(vfs.open_count(), 0);
+}
+
+#[test]
+fn test_unpack_replaces_files() {
+  let mut vfs = root();
+  let first = archive(&[("bin", DIR, b""), ("bin/sh", FILE, b"old shell")]);
+  let second = archive(&[("bin", DIR, b""), ("bin/sh", FILE, b"new")]);
+  unpack(&mut vfs, &first).unwrap();
+  assert_eq!(unpack(&mut vfs, &second), Ok(1));
+  assert_eq!(contents(&mut vfs, "/bin/sh"), b"new");
+}
+
+#[test]
+fn test_unpack_errors() {
+  let mut vfs = root();
+  // The parent directory is missing.
+  let orphan = archive(&[("bin/sh", FILE, b"")]);
+  assert_eq!(unpack(&mut vfs, &orphan), Err(ErrorKind::NotFound));
+
+  let valid = archive(&[("init", FILE, b"")]);
+  assert_eq!(
+    unpack(&mut vfs, &valid[..valid.len() - 4]),
+    Err(ErrorKind::InvalidData)
+  );
+}
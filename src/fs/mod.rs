@@ -0,0 +1,119 @@
+// Filesystems and the VFS they are mounted in.
+
+pub mod cpio;
+pub mod devfs;
+pub mod initramfs;
+pub mod ramfs;
+pub mod vfs;
+
+use crate::common::error::ErrorKind;
+use crate::io::uart;
+use crate::log;
+use crate::mm;
+use crate::tty::{self, Tty, TtyError, TtyStreamAdapter};
+use arrayvec::ArrayVec;
+use devfs::{DevFs, Device};
+use ramfs::RamFs;
+use vfs::{FileType, Vfs};
+
+// The console UART behind the tty line discipline, see Console.
+static DEVICES: [Device; 2] = [
+  Device {
+    name: "console",
+    read: tty_read,
+    write: tty_write,
+  },
+  Device {
+    name: "ttyS0",
+    read: tty_read,
+    write: tty_write,
+  },
+];
+
+// A line and its LF
+const LINE_SIZE: usize = tty::INPUT_BUFFER_SIZE + 1;
+
+// Reads return whole lines, echoed and edited as kshell's are. Ctrl-D on an
+// empty line reads as end of file, Ctrl-C discards the line. LF is written
+// as CR LF.
+struct Console {
+  tty: Tty,
+  // Rest of the last line, for reads with a smaller buffer
+  pending: ArrayVec<u8, LINE_SIZE>,
+}
+
+static mut VFS: Vfs = Vfs::new();
+static mut DEVFS: DevFs = DevFs::new(&DEVICES);
+static mut RAMFS: Option<RamFs<'static>> = None;
+static mut CONSOLE: Option<Console> = None;
+
+pub fn vfs() -> &'static mut Vfs {
+  unsafe { &mut *core::ptr::addr_of_mut!(VFS) }
+}
+
+// Mounts a ramfs at / with the initramfs unpacked, and /dev. The ramfs
+// stores files in frames, so the frame allocator must be initialized.
+pub fn initialize() -> Result<(), ErrorKind> {
+  let frames = mm::try_frames().ok_or(ErrorKind::NotConnected)?;
+  let vfs = vfs();
+  let root = unsafe { &mut *core::ptr::addr_of_mut!(RAMFS) };
+  vfs.mount("/", root.insert(RamFs::new(frames)))?;
+  vfs.create("/dev", FileType::Directory)?;
+  vfs.mount("/dev", unsafe { &mut *core::ptr::addr_of_mut!(DEVFS) })?;
+  let count = initramfs::load(vfs)?;
+  log::info!("initramfs: {} entries", count);
+  Ok(())
+}
+
+fn console() -> &'static mut Console {
+  let console = unsafe { &mut *core::ptr::addr_of_mut!(CONSOLE) };
+  console.get_or_insert_with(|| Console {
+    tty: Tty::new(TtyStreamAdapter {
+      read_char: uart::getc,
+      write_char: console_putc,
+    }),
+    pending: ArrayVec::new(),
+  })
+}
+
+fn console_putc(byte: u8) {
+  if byte == b'\n' {
+    uart::putc(b'\r');
+  }
+  uart::putc(byte);
+}
+
+// Blocks until a line was entered, then returns as much of it as fits.
+fn tty_read(buffer: &mut [u8]) -> Result<usize, ErrorKind> {
+  if buffer.is_empty() {
+    return Ok(0);
+  }
+  let console = console();
+  if console.pending.is_empty() {
+    match console.tty.read_line() {
+      Ok(line) => {
+        console.pending.extend(line.bytes());
+        console.pending.push(b'\n');
+      }
+      Err(TtyError::EndOfFile) => return Ok(0),
+      Err(TtyError::ReadError) => return Err(ErrorKind::Interrupted),
+      Err(TtyError::WriteError) => return Err(ErrorKind::Other),
+    }
+  }
+  let len = buffer.len().min(console.pending.len());
+  buffer[..len].copy_from_slice(&console.pending[..len]);
+  console.pending.drain(..len);
+  Ok(len)
+}
+
+fn tty_write(data: &[u8]) -> Result<usize, ErrorKind> {
+  let tty = &mut console().tty;
+  for &byte in data {
+    tty.write_char(byte);
+  }
+  tty.flush();
+  Ok(data.len())
+}
+
+#[cfg(test)]
+mod fs_test;
@@ -0,0 +1,267 @@
+// Filesystem in RAM: inodes in a fixed table, file data in frames of the
+// page allocator. Each file has an index frame holding the physical
+// addresses of its data pages, 0 for holes, which read as zeros.
+
+use crate::common::error::ErrorKind;
+use crate::fs::vfs::{DirEntry, FileSystem, FileType, InodeId, Stat, MAX_NAME};
+use crate::mm::{Frames, PAGE_SIZE};
+use arrayvec::ArrayString;
+
+pub const MAX_NODES: usize = 128;
+// Data pages listed in an index frame
+const INDEX_ENTRIES: usize = PAGE_SIZE as usize / 8;
+pub const MAX_FILE_SIZE: u64 = INDEX_ENTRIES as u64 * PAGE_SIZE;
+const ROOT: InodeId = 1;
+
+#[derive(Clone, Copy)]
+struct Node {
+  parent: InodeId,
+  name: ArrayString<MAX_NAME>,
+  kind: FileType,
+  size: u64,
+  // Index frame, 0 before the first write
+  index: u64,
+}
+
+pub struct RamFs<'a> {
+  frames: &'a mut Frames,
+  // Inode n is nodes[n - 1].
+  nodes: [Option<Node>; MAX_NODES],
+}
+
+impl<'a> RamFs<'a> {
+  // An empty root directory.
+  pub fn new(frames: &'a mut Frames) -> RamFs<'a> {
+    let mut nodes = [None; MAX_NODES];
+    nodes[0] = Some(Node {
+      parent: 0,
+      name: ArrayString::new(),
+      kind: FileType::Directory,
+      size: 0,
+      index: 0,
+    });
+    RamFs { frames, nodes }
+  }
+
+  fn node(&mut self, inode: InodeId) -> Result<&mut Node, ErrorKind> {
+    let index = (inode as usize).checked_sub(1).ok_or(ErrorKind::NotFound)?;
+    self
+      .nodes
+      .get_mut(index)
+      .and_then(Option::as_mut)
+      .ok_or(ErrorKind::NotFound)
+  }
+
+  fn file(&mut self, inode: InodeId) -> Result<&mut Node, ErrorKind> {
+    let node = self.node(inode)?;
+    match node.kind {
+      FileType::Directory => Err(ErrorKind::IsADirectory),
+      _ => Ok(node),
+    }
+  }
+
+  fn dir(&mut self, inode: InodeId) -> Result<(), ErrorKind> {
+    match self.node(inode)?.kind {
+      FileType::Directory => Ok(()),
+      _ => Err(ErrorKind::NotADirectory),
+    }
+  }
+
+  fn children(&self, dir: InodeId) -> impl Iterator<Item = (InodeId, &Node)> {
+    self.nodes.iter().enumerate().filter_map(move |(i, node)| {
+      let node = node.as_ref().filter(|node| node.parent == dir)?;
+      Some((i as InodeId + 1, node))
+    })
+  }
+
+  fn entry(&mut self, index: u64, page: usize) -> u64 {
+    let at = page * 8;
+    let bytes = &self.frames.page(index)[at..at + 8];
+    u64::from_le_bytes(bytes.try_into().unwrap())
+  }
+
+  fn set_entry(&mut self, index: u64, page: usize, pa: u64) {
+    let at = page * 8;
+    self.frames.page(index)[at..at + 8].copy_from_slice(&pa.to_le_bytes());
+  }
+
+  // The data page `page` of the file with `index`, allocated if missing.
+  fn data_page(&mut self, index: u64, page: usize) -> Result<u64, ErrorKind> {
+    let pa = self.entry(index, page);
+    if pa != 0 {
+      return Ok(pa);
+    }
+    let pa = self.frames.alloc()?;
+    self.set_entry(index, page, pa);
+    Ok(pa)
+  }
+}
+
+impl FileSystem for RamFs<'_> {
+  fn root(&self) -> InodeId {
+    ROOT
+  }
+
+  fn lookup(&mut self, dir: InodeId, name: &str) -> Result<InodeId, ErrorKind> {
+    self.dir(dir)?;
+    self
+      .children(dir)
+      .find(|(_, node)| node.name.as_str() == name)
+      .map(|(inode, _)| inode)
+      .ok_or(ErrorKind::NotFound)
+  }
+
+  fn stat(&mut self, inode: InodeId) -> Result<Stat, ErrorKind> {
+    let node = *self.node(inode)?;
+    let links = match node.kind {
+      FileType::Directory => 2,
+      _ => 1,
+    };
+    Ok(Stat {
+      inode,
+      device: 0,
+      kind: node.kind,
+      size: node.size,
+      links,
+    })
+  }
+
+  fn read(
+    &mut self,
+    inode: InodeId,
+    offset: u64,
+    buffer: &mut [u8],
+  ) -> Result<usize, ErrorKind> {
+    let node = *self.file(inode)?;
+    let count = node.size.saturating_sub(offset).min(buffer.len() as u64);
+    let mut done = 0;
+    while done < count as usize {
+      let at = offset + done as u64;
+      let start = (at % PAGE_SIZE) as usize;
+      let len = (PAGE_SIZE as usize - start).min(count as usize - done);
+      let part = &mut buffer[done..done + len];
+      let pa = match node.index {
+        0 => 0,
+        index => self.entry(index, (at / PAGE_SIZE) as usize),
+      };
+      match pa {
+        0 => part.fill(0),
+        pa => part.copy_from_slice(&self.frames.page(pa)[start..start + len]),
+      }
+      done += len;
+    }
+    Ok(done)
+  }
+
+  // Short if memory runs out after the first page.
+  fn write(
+    &mut self,
+    inode: InodeId,
+    offset: u64,
+    data: &[u8],
+  ) -> Result<usize, ErrorKind> {
+    let mut node = *self.file(inode)?;
+    offset
+      .checked_add(data.len() as u64)
+      .filter(|&end| end <= MAX_FILE_SIZE)
+      .ok_or(ErrorKind::FileTooLarge)?;
+    if data.is_empty() {
+      return Ok(0);
+    }
+    if node.index == 0 {
+      node.index = self.frames.alloc()?;
+      self.file(inode)?.index = node.index;
+    }
+    let mut done = 0;
+    while done < data.len() {
+      let at = offset + done as u64;
+      let start = (at % PAGE_SIZE) as usize;
+      let len = (PAGE_SIZE as usize - start).min(data.len() - done);
+      let pa = match self.data_page(node.index, (at / PAGE_SIZE) as usize) {
+        Ok(pa) => pa,
+        Err(e) if done == 0 => return Err(e),
+        Err(_) => break,
+      };
+      self.frames.page(pa)[start..start + len]
+        .copy_from_slice(&data[done..done + len]);
+      done += len;
+    }
+    let node = self.file(inode)?;
+    node.size = node.size.max(offset + done as u64);
+    Ok(done)
+  }
+
+  fn readdir(
+    &mut self,
+    dir: InodeId,
+    index: usize,
+  ) -> Result<Option<DirEntry>, ErrorKind> {
+    self.dir(dir)?;
+    Ok(self.children(dir).nth(index).map(|(inode, node)| DirEntry {
+      inode,
+      kind: node.kind,
+      name: node.name,
+    }))
+  }
+
+  fn create(
+    &mut self,
+    dir: InodeId,
+    name: &str,
+    kind: FileType,
+  ) -> Result<InodeId, ErrorKind> {
+    self.dir(dir)?;
+    let name =
+      ArrayString::from(name).map_err(|_| ErrorKind::InvalidFilename)?;
+    if self.lookup(dir, &name).is_ok() {
+      return Err(ErrorKind::AlreadyExists);
+    }
+    let slot = self
+      .nodes
+      .iter()
+      .position(Option::is_none)
+      .ok_or(ErrorKind::StorageFull)?;
+    self.nodes[slot] = Some(Node {
+      parent: dir,
+      name,
+      kind,
+      size: 0,
+      index: 0,
+    });
+    Ok(slot as InodeId + 1)
+  }
+
+  // Releases the pages past `size`.
+  fn truncate(&mut self, inode: InodeId, size: u64) -> Result<(), ErrorKind> {
+    let node = *self.file(inode)?;
+    if size > MAX_FILE_SIZE {
+      return Err(ErrorKind::FileTooLarge);
+    }
+    if node.index != 0 {
+      let kept = size.div_ceil(PAGE_SIZE) as usize;
+      for page in kept..INDEX_ENTRIES {
+        let pa = self.entry(node.index, page);
+        if pa != 0 {
+          self.frames.release(pa);
+          self.set_entry(node.index, page, 0);
+        }
+      }
+      // Growing the file again must read zeros.
+      let tail = (size % PAGE_SIZE) as usize;
+      let last = self.entry(node.index, kept.saturating_sub(1));
+      if tail != 0 && last != 0 {
+        self.frames.page(last)[tail..].fill(0);
+      }
+      if size == 0 {
+        self.frames.release(node.index);
+        self.file(inode)?.index = 0;
+      }
+    }
+    self.file(inode)?.size = size;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+#[path = "ramfs_test.rs"]
+mod ramfs_test;
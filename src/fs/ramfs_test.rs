@@ -0,0 +1,132 @@
+use super::*;
+use crate::mm::frame_test::host_frames;
+
+fn ramfs(pages: usize) -> RamFs<'static> {
+  RamFs::new(Box::leak(Box::new(host_frames(pages))))
+}
+
+#[test]
+fn test_create_and_lookup() {
+  let mut fs = ramfs(16);
+  let bin = fs.create(ROOT, "bin", FileType::Directory).unwrap();
+  let sh = fs.create(bin, "sh", FileType::Regular).unwrap();
+  assert_eq!(fs.lookup(ROOT, "bin"), Ok(bin));
+  assert_eq!(fs.lookup(bin, "sh"), Ok(sh));
+  assert_eq!(fs.lookup(ROOT, "sh"), Err(ErrorKind::NotFound));
+  assert_eq!(fs.lookup(sh, "x"), Err(ErrorKind::NotADirectory));
+  assert_eq!(
+    fs.create(bin, "sh", FileType::Directory),
+    Err(ErrorKind::AlreadyExists)
+  );
+  assert_eq!(
+    fs.create(sh, "x", FileType::Regular),
+    Err(ErrorKind::NotADirectory)
+  );
+  assert_eq!(
+    fs.create(ROOT, &"x".repeat(MAX_NAME + 1), FileType::Regular),
+    Err(ErrorKind::InvalidFilename)
+  );
+  assert_eq!(fs.stat(bin).unwrap().kind, FileType::Directory);
+  assert_eq!(fs.stat(99), Err(ErrorKind::NotFound));
+}
+
+#[test]
+fn test_readdir() {
+  let mut fs = ramfs(16);
+  let bin = fs.create(ROOT, "bin", FileType::Directory).unwrap();
+  fs.create(ROOT, "init", FileType::Regular).unwrap();
+  fs.create(bin, "sh", FileType::Regular).unwrap();
+  let names: Vec<_> = (0..)
+    .map_while(|i| fs.readdir(ROOT, i).unwrap())
+    .map(|entry| (entry.name.to_string(), entry.kind))
+    .collect();
+  assert_eq!(
+    names,
+    [
+      ("bin".to_string(), FileType::Directory),
+      ("init".to_string(), FileType::Regular)
+    ]
+  );
+}
+
+#[test]
+fn test_read_write_across_pages() {
+  let mut fs = ramfs(16);
+  let file = fs.create(ROOT, "file", FileType::Regular).unwrap();
+  let data: Vec<u8> = (0..3 * PAGE_SIZE as usize).map(|i| i as u8).collect();
+  assert_eq!(fs.write(file, 100, &data), Ok(data.len()));
+  assert_eq!(fs.stat(file).unwrap().size, 100 + data.len() as u64);
+
+  let mut buffer = vec![0xFF; data.len() + 200];
+  assert_eq!(fs.read(file, 0, &mut buffer), Ok(100 + data.len()));
+  assert!(buffer[..100].iter().all(|&byte| byte == 0));
+  assert_eq!(buffer[100..100 + data.len()], data);
+  assert_eq!(fs.read(file, 1 << 20, &mut buffer), Ok(0));
+
+  let mut buffer = [0; 4];
+  assert_eq!(fs.read(ROOT, 0, &mut buffer), Err(ErrorKind::IsADirectory));
+  assert_eq!(fs.write(ROOT, 0, b"x"), Err(ErrorKind::IsADirectory));
+  assert_eq!(
+    fs.write(file, MAX_FILE_SIZE, b"x"),
+    Err(ErrorKind::FileTooLarge)
+  );
+}
+
+#[test]
+fn test_holes_read_as_zeros() {
+  let mut fs = ramfs(16);
+  let file = fs.create(ROOT, "sparse", FileType::Regular).unwrap();
+  let free = fs.frames.free_count();
+  fs.write(file, 2 * PAGE_SIZE, b"end").unwrap();
+  // The index and one data page
+  assert_eq!(fs.frames.free_count(), free - 2);
+  let mut buffer = [0xFF; 8];
+  assert_eq!(fs.read(file, PAGE_SIZE, &mut buffer), Ok(8));
+  assert_eq!(buffer, [0; 8]);
+}
+
+#[test]
+fn test_truncate() {
+  let mut fs = ramfs(16);
+  let file = fs.create(ROOT, "file", FileType::Regular).unwrap();
+  let free = fs.frames.free_count();
+  fs.write(file, 0, &[1; 2 * PAGE_SIZE as usize]).unwrap();
+  fs.truncate(file, 10).unwrap();
+  assert_eq!(fs.stat(file).unwrap().size, 10);
+  assert_eq!(fs.frames.free_count(), free - 2);
+
+  // Bytes past the old end read as zeros.
+  fs.truncate(file, 20).unwrap();
+  let mut buffer = [0xFF; 20];
+  assert_eq!(fs.read(file, 0, &mut buffer), Ok(20));
+  assert_eq!(buffer[..10], [1; 10]);
+  assert_eq!(buffer[10..], [0; 10]);
+
+  fs.truncate(file, 0).unwrap();
+  assert_eq!(fs.frames.free_count(), free);
+}
+
+#[test]
+fn test_out_of_space() {
+  let mut fs = ramfs(4);
+  let file = fs.create(ROOT, "file", FileType::Regular).unwrap();
+  let free = fs.frames.free_count();
+  let data = vec![1; (free + 1) * PAGE_SIZE as usize];
+  // All but the index frame hold data.
+  let written = fs.write(file, 0, &data).unwrap();
+  assert_eq!(written, (free - 1) * PAGE_SIZE as usize);
+  assert_eq!(fs.stat(file).unwrap().size, written as u64);
+  assert_eq!(
+    fs.write(file, written as u64, b"x"),
+    Err(ErrorKind::OutOfMemory)
+  );
+
+  for i in 0..MAX_NODES - 2 {
+    fs.create(ROOT, &i.to_string(), FileType::Directory)
+      .unwrap();
+  }
+  assert_eq!(
+    fs.create(ROOT, "full", FileType::Regular),
+    Err(ErrorKind::StorageFull)
+  );
+}
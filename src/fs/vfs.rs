@@ -0,0 +1,489 @@
+// Virtual filesystem: the mount table, path resolution through a dentry
+// cache, and open files shared by file descriptors.
+//
+// Filesystems implement FileSystem over their own inode numbers, the VFS
+// names an inode by its mount and number, see Inode. Mounts are matched by
+// path, so a filesystem can be mounted on a path the covering filesystem
+// has no directory for.
+
+use crate::common::error::ErrorKind;
+use arrayvec::{ArrayString, ArrayVec};
+
+pub const MAX_NAME: usize = 255;
+pub const MAX_PATH: usize = 256;
+pub const MAX_MOUNTS: usize = 8;
+// Open files of all processes
+pub const MAX_FILES: usize = 64;
+// File descriptors of a process
+pub const MAX_FDS: usize = 16;
+const DENTRIES: usize = 32;
+
+// open() flags, as on Linux
+pub const O_RDONLY: u32 = 0;
+pub const O_WRONLY: u32 = 1;
+pub const O_RDWR: u32 = 2;
+const O_ACCMODE: u32 = 3;
+pub const O_CREAT: u32 = 0o100;
+pub const O_EXCL: u32 = 0o200;
+pub const O_TRUNC: u32 = 0o1000;
+pub const O_APPEND: u32 = 0o2000;
+pub const O_DIRECTORY: u32 = 0o200000;
+
+// lseek() whence
+pub const SEEK_SET: u32 = 0;
+pub const SEEK_CUR: u32 = 1;
+pub const SEEK_END: u32 = 2;
+
+pub type InodeId = u64;
+// Index into the open files
+pub type FileId = usize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileType {
+  Regular,
+  Directory,
+  CharDevice,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Inode {
+  pub mount: usize,
+  pub id: InodeId,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Stat {
+  pub inode: InodeId,
+  // Index of the mount, set by the VFS
+  pub device: u64,
+  pub kind: FileType,
+  pub size: u64,
+  pub links: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DirEntry {
+  pub inode: InodeId,
+  pub kind: FileType,
+  pub name: ArrayString<MAX_NAME>,
+}
+
+// Operations of a mounted filesystem on its inodes. Offsets of character
+// devices have no meaning.
+pub trait FileSystem {
+  fn root(&self) -> InodeId;
+  // NotADirectory if `dir` is not a directory
+  fn lookup(&mut self, dir: InodeId, name: &str) -> Result<InodeId, ErrorKind>;
+  fn stat(&mut self, inode: InodeId) -> Result<Stat, ErrorKind>;
+  fn read(
+    &mut self,
+    inode: InodeId,
+    offset: u64,
+    buffer: &mut [u8],
+  ) -> Result<usize, ErrorKind>;
+  fn write(
+    &mut self,
+    inode: InodeId,
+    offset: u64,
+    data: &[u8],
+  ) -> Result<usize, ErrorKind>;
+  // The entry at `index` of `dir`, None past the last one
+  fn readdir(
+    &mut self,
+    dir: InodeId,
+    index: usize,
+  ) -> Result<Option<DirEntry>, ErrorKind>;
+
+  fn create(
+    &mut self,
+    _dir: InodeId,
+    _name: &str,
+    _kind: FileType,
+  ) -> Result<InodeId, ErrorKind> {
+    Err(ErrorKind::ReadOnlyFilesystem)
+  }
+
+  fn truncate(&mut self, _inode: InodeId, _size: u64) -> Result<(), ErrorKind> {
+    Err(ErrorKind::ReadOnlyFilesystem)
+  }
+}
+
+// `path` from the root, without ".", ".." and repeated slashes. Relative
+// paths start at the root.
+pub fn normalize(path: &str) -> Result<ArrayString<MAX_PATH>, ErrorKind> {
+  if path.is_empty() {
+    return Err(ErrorKind::NotFound);
+  }
+  let mut normal = ArrayString::<MAX_PATH>::new();
+  for name in path.split('/') {
+    match name {
+      "" | "." => {}
+      ".." => normal.truncate(normal.rfind('/').unwrap_or(0)),
+      _ if name.len() > MAX_NAME => return Err(ErrorKind::InvalidFilename),
+      _ => {
+        normal
+          .try_push('/')
+          .map_err(|_| ErrorKind::InvalidFilename)?;
+        normal
+          .try_push_str(name)
+          .map_err(|_| ErrorKind::InvalidFilename)?;
+      }
+    }
+  }
+  if normal.is_empty() {
+    normal.push('/');
+  }
+  Ok(normal)
+}
+
+struct Mount {
+  path: ArrayString<MAX_PATH>,
+  fs: &'static mut dyn FileSystem,
+}
+
+// A cached lookup of `name` in `parent`.
+struct Dentry {
+  parent: Inode,
+  name: ArrayString<MAX_NAME>,
+  inode: Inode,
+}
+
+// An open file, shared by the descriptors it was duplicated to. Offsets of
+// directories count entries.
+#[derive(Clone, Copy)]
+struct File {
+  inode: Inode,
+  kind: FileType,
+  offset: u64,
+  flags: u32,
+  refs: usize,
+}
+
+pub struct Vfs {
+  mounts: ArrayVec<Mount, MAX_MOUNTS>,
+  dentries: ArrayVec<Dentry, DENTRIES>,
+  // Cache entry replaced next once the cache is full
+  victim: usize,
+  files: [Option<File>; MAX_FILES],
+}
+
+impl Vfs {
+  pub const fn new() -> Vfs {
+    Vfs {
+      mounts: ArrayVec::new_const(),
+      dentries: ArrayVec::new_const(),
+      victim: 0,
+      files: [None; MAX_FILES],
+    }
+  }
+
+  pub fn mount(
+    &mut self,
+    path: &str,
+    fs: &'static mut dyn FileSystem,
+  ) -> Result<(), ErrorKind> {
+    let path = normalize(path)?;
+    if self.mounts.iter().any(|mount| mount.path == path) {
+      return Err(ErrorKind::ResourceBusy);
+    }
+    self
+      .mounts
+      .try_push(Mount { path, fs })
+      .map_err(|_| ErrorKind::OutOfMemory)
+  }
+
+  fn fs(&mut self, inode: Inode) -> &mut dyn FileSystem {
+    &mut *self.mounts[inode.mount].fs
+  }
+
+  // The mount with the longest path covering `path`, and the rest of
+  // `path` in it.
+  fn find_mount<'a>(
+    &self,
+    path: &'a str,
+  ) -> Result<(usize, &'a str), ErrorKind> {
+    let mut found: Option<(usize, usize)> = None;
+    for (index, mount) in self.mounts.iter().enumerate() {
+      let prefix = mount.path.as_str();
+      let covers = prefix == "/"
+        || path
+          .strip_prefix(prefix)
+          .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+      if covers && found.is_none_or(|(_, len)| prefix.len() > len) {
+        found = Some((index, prefix.len()));
+      }
+    }
+    let (index, len) = found.ok_or(ErrorKind::NotFound)?;
+    Ok((index, &path[len..]))
+  }
+
+  fn lookup(&mut self, dir: Inode, name: &str) -> Result<Inode, ErrorKind> {
+    let cached = self
+      .dentries
+      .iter()
+      .find(|d| d.parent == dir && d.name.as_str() == name);
+    if let Some(dentry) = cached {
+      return Ok(dentry.inode);
+    }
+    let id = self.fs(dir).lookup(dir.id, name)?;
+    let inode = Inode {
+      mount: dir.mount,
+      id,
+    };
+    self.cache(dir, name, inode);
+    Ok(inode)
+  }
+
+  fn cache(&mut self, parent: Inode, name: &str, inode: Inode) {
+    let Ok(name) = ArrayString::from(name) else {
+      return;
+    };
+    let dentry = Dentry {
+      parent,
+      name,
+      inode,
+    };
+    if let Err(e) = self.dentries.try_push(dentry) {
+      self.dentries[self.victim] = e.element();
+      self.victim = (self.victim + 1) % DENTRIES;
+    }
+  }
+
+  pub fn resolve(&mut self, path: &str) -> Result<Inode, ErrorKind> {
+    let path = normalize(path)?;
+    let (mount, rest) = self.find_mount(&path)?;
+    let mut inode = Inode {
+      mount,
+      id: self.mounts[mount].fs.root(),
+    };
+    for name in rest.split('/').filter(|name| !name.is_empty()) {
+      inode = self.lookup(inode, name)?;
+    }
+    Ok(inode)
+  }
+
+  pub fn stat(&mut self, inode: Inode) -> Result<Stat, ErrorKind> {
+    let mut stat = self.fs(inode).stat(inode.id)?;
+    stat.device = inode.mount as u64;
+    Ok(stat)
+  }
+
+  // Creates a file or directory at `path` in an existing directory.
+  pub fn create(
+    &mut self,
+    path: &str,
+    kind: FileType,
+  ) -> Result<Inode, ErrorKind> {
+    let path = normalize(path)?;
+    if self.resolve(&path).is_ok() {
+      return Err(ErrorKind::AlreadyExists);
+    }
+    let (parent, name) = path.rsplit_once('/').unwrap();
+    let dir = self.resolve(if parent.is_empty() { "/" } else { parent })?;
+    let id = self.fs(dir).create(dir.id, name, kind)?;
+    let inode = Inode {
+      mount: dir.mount,
+      id,
+    };
+    self.cache(dir, name, inode);
+    Ok(inode)
+  }
+
+  pub fn open(&mut self, path: &str, flags: u32) -> Result<FileId, ErrorKind> {
+    let inode = match self.resolve(path) {
+      Ok(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => {
+        return Err(ErrorKind::AlreadyExists);
+      }
+      Ok(inode) => inode,
+      Err(ErrorKind::NotFound) if flags & O_CREAT != 0 => {
+        self.create(path, FileType::Regular)?
+      }
+      Err(e) => return Err(e),
+    };
+    let kind = self.stat(inode)?.kind;
+    let writable = flags & O_ACCMODE != O_RDONLY;
+    match kind {
+      FileType::Directory if writable => return Err(ErrorKind::IsADirectory),
+      FileType::Directory => {}
+      _ if flags & O_DIRECTORY != 0 => return Err(ErrorKind::NotADirectory),
+      FileType::Regular if writable && flags & O_TRUNC != 0 => {
+        self.fs(inode).truncate(inode.id, 0)?;
+      }
+      _ => {}
+    }
+    let slot = self
+      .files
+      .iter()
+      .position(Option::is_none)
+      .ok_or(ErrorKind::ResourceBusy)?;
+    self.files[slot] = Some(File {
+      inode,
+      kind,
+      offset: 0,
+      flags,
+      refs: 1,
+    });
+    Ok(slot)
+  }
+
+  fn file(&mut self, file: FileId) -> Result<&mut File, ErrorKind> {
+    self
+      .files
+      .get_mut(file)
+      .and_then(Option::as_mut)
+      .ok_or(ErrorKind::NotFound)
+  }
+
+  // Another reference to `file`, e.g. from a forked descriptor table.
+  pub fn retain(&mut self, file: FileId) {
+    if let Ok(file) = self.file(file) {
+      file.refs += 1;
+    }
+  }
+
+  // Closes `file` with its last reference.
+  pub fn release(&mut self, file: FileId) {
+    if let Ok(open) = self.file(file) {
+      open.refs -= 1;
+      if open.refs == 0 {
+        self.files[file] = None;
+      }
+    }
+  }
+
+  pub fn open_count(&self) -> usize {
+    self.files.iter().flatten().count()
+  }
+
+  // PermissionDenied if `file` was opened write only.
+  pub fn read(
+    &mut self,
+    file: FileId,
+    buffer: &mut [u8],
+  ) -> Result<usize, ErrorKind> {
+    let open = *self.file(file)?;
+    if open.flags & O_ACCMODE == O_WRONLY {
+      return Err(ErrorKind::PermissionDenied);
+    }
+    if open.kind == FileType::Directory {
+      return Err(ErrorKind::IsADirectory);
+    }
+    let count = self
+      .fs(open.inode)
+      .read(open.inode.id, open.offset, buffer)?;
+    self.file(file)?.offset = open.offset + count as u64;
+    Ok(count)
+  }
+
+  // PermissionDenied if `file` was opened read only.
+  pub fn write(
+    &mut self,
+    file: FileId,
+    data: &[u8],
+  ) -> Result<usize, ErrorKind> {
+    let open = *self.file(file)?;
+    if open.flags & O_ACCMODE == O_RDONLY {
+      return Err(ErrorKind::PermissionDenied);
+    }
+    let offset = match open.flags & O_APPEND {
+      0 => open.offset,
+      _ => self.stat(open.inode)?.size,
+    };
+    let count = self.fs(open.inode).write(open.inode.id, offset, data)?;
+    self.file(file)?.offset = offset + count as u64;
+    Ok(count)
+  }
+
+  pub fn seek(
+    &mut self,
+    file: FileId,
+    offset: i64,
+    whence: u32,
+  ) -> Result<u64, ErrorKind> {
+    let open = *self.file(file)?;
+    if open.kind == FileType::CharDevice {
+      return Err(ErrorKind::NotSeekable);
+    }
+    let base = match whence {
+      SEEK_SET => 0,
+      SEEK_CUR => open.offset,
+      SEEK_END => self.stat(open.inode)?.size,
+      _ => return Err(ErrorKind::InvalidInput),
+    };
+    let offset = base
+      .checked_add_signed(offset)
+      .ok_or(ErrorKind::InvalidInput)?;
+    self.file(file)?.offset = offset;
+    Ok(offset)
+  }
+
+  // The next entry of a directory, None after the last one.
+  pub fn readdir(
+    &mut self,
+    file: FileId,
+  ) -> Result<Option<DirEntry>, ErrorKind> {
+    let open = *self.file(file)?;
+    if open.kind != FileType::Directory {
+      return Err(ErrorKind::NotADirectory);
+    }
+    let entry = self
+      .fs(open.inode)
+      .readdir(open.inode.id, open.offset as usize)?;
+    if entry.is_some() {
+      self.file(file)?.offset += 1;
+    }
+    Ok(entry)
+  }
+
+  pub fn fstat(&mut self, file: FileId) -> Result<Stat, ErrorKind> {
+    let inode = self.file(file)?.inode;
+    self.stat(inode)
+  }
+}
+
+// File descriptors of a process, referring to open files of the VFS.
+#[derive(Clone, Copy)]
+pub struct FdTable {
+  fds: [Option<FileId>; MAX_FDS],
+}
+
+impl FdTable {
+  pub const fn new() -> FdTable {
+    FdTable {
+      fds: [None; MAX_FDS],
+    }
+  }
+
+  // The lowest free descriptor, None if there is none.
+  pub fn insert(&mut self, file: FileId) -> Option<usize> {
+    let fd = self.fds.iter().position(Option::is_none)?;
+    self.fds[fd] = Some(file);
+    Some(fd)
+  }
+
+  pub fn get(&self, fd: u64) -> Option<FileId> {
+    *self.fds.get(usize::try_from(fd).ok()?)?
+  }
+
+  pub fn remove(&mut self, fd: u64) -> Option<FileId> {
+    self.fds.get_mut(usize::try_from(fd).ok()?)?.take()
+  }
+
+  // A copy sharing the open files.
+  pub fn duplicate(&self, vfs: &mut Vfs) -> FdTable {
+    for &file in self.fds.iter().flatten() {
+      vfs.retain(file);
+    }
+    *self
+  }
+
+  pub fn close_all(&mut self, vfs: &mut Vfs) {
+    for file in self.fds.iter_mut().filter_map(Option::take) {
+      vfs.release(file);
+    }
+  }
+}
+
+#[cfg(test)]
+#[path = "vfs_test.rs"]
+mod vfs_test;
@@ -0,0 +1,355 @@
+use super::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+// Inode n is nodes[n - 1].
+struct Node {
+  parent: InodeId,
+  name: String,
+  kind: FileType,
+  data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct TestFs {
+  nodes: Vec<Node>,
+  lookups: Rc<Cell<usize>>,
+}
+
+impl TestFs {
+  fn new() -> &'static mut TestFs {
+    let mut fs = TestFs::default();
+    fs.add(0, "", FileType::Directory);
+    Box::leak(Box::new(fs))
+  }
+
+  fn add(&mut self, parent: InodeId, name: &str, kind: FileType) -> InodeId {
+    self.nodes.push(Node {
+      parent,
+      name: name.into(),
+      kind,
+      data: Vec::new(),
+    });
+    self.nodes.len() as InodeId
+  }
+
+  fn node(&mut self, inode: InodeId) -> Result<&mut Node, ErrorKind> {
+    self
+      .nodes
+      .get_mut(inode as usize - 1)
+      .ok_or(ErrorKind::NotFound)
+  }
+
+  fn children(&self, dir: InodeId) -> impl Iterator<Item = InodeId> + '_ {
+    (1..=self.nodes.len() as InodeId)
+      .filter(move |&i| i != 1 && self.nodes[i as usize - 1].parent == dir)
+  }
+}
+
+impl FileSystem for TestFs {
+  fn root(&self) -> InodeId {
+    1
+  }
+
+  fn lookup(&mut self, dir: InodeId, name: &str) -> Result<InodeId, ErrorKind> {
+    self.lookups.set(self.lookups.get() + 1);
+    if self.node(dir)?.kind != FileType::Directory {
+      return Err(ErrorKind::NotADirectory);
+    }
+    let found = self
+      .children(dir)
+      .find(|&i| self.nodes[i as usize - 1].name == name);
+    found.ok_or(ErrorKind::NotFound)
+  }
+
+  fn stat(&mut self, inode: InodeId) -> Result<Stat, ErrorKind> {
+    let node = self.node(inode)?;
+    Ok(Stat {
+      inode,
+      device: 0,
+      kind: node.kind,
+      size: node.data.len() as u64,
+      links: 1,
+    })
+  }
+
+  fn read(
+    &mut self,
+    inode: InodeId,
+    offset: u64,
+    buffer: &mut [u8],
+  ) -> Result<usize, ErrorKind> {
+    let data = &self.node(inode)?.data;
+    let start = (offset as usize).min(data.len());
+    let count = buffer.len().min(data.len() - start);
+    buffer[..count].copy_from_slice(&data[start..start + count]);
+    Ok(count)
+  }
+
+  fn write(
+    &mut self,
+    inode: InodeId,
+    offset: u64,
+    data: &[u8],
+  ) -> Result<usize, ErrorKind> {
+    let file = &mut self.node(inode)?.data;
+    let end = offset as usize + data.len();
+    if file.len() < end {
+      file.resize(end, 0);
+    }
+    file[offset as usize..end].copy_from_slice(data);
+    Ok(data.len())
+  }
+
+  fn readdir(
+    &mut self,
+    dir: InodeId,
+    index: usize,
+  ) -> Result<Option<DirEntry>, ErrorKind> {
+    let Some(inode) = self.children(dir).nth(index) else {
+      return Ok(None);
+    };
+    let node = self.node(inode)?;
+    Ok(Some(DirEntry {
+      inode,
+      kind: node.kind,
+      name: ArrayString::from(&node.name).unwrap(),
+    }))
+  }
+
+  fn create(
+    &mut self,
+    dir: InodeId,
+    name: &str,
+    kind: FileType,
+  ) -> Result<InodeId, ErrorKind> {
+    Ok(self.add(dir, name, kind))
+  }
+
+  fn truncate(&mut self, inode: InodeId, size: u64) -> Result<(), ErrorKind> {
+    self.node(inode)?.data.truncate(size as usize);
+    Ok(())
+  }
+}
+
+// "/" with /etc/motd, and "/mnt" with "/mnt/file" on another filesystem.
+fn sample_vfs() -> Vfs {
+  let root = TestFs::new();
+  let etc = root.add(1, "etc", FileType::Directory);
+  let motd = root.add(etc, "motd", FileType::Regular);
+  root.node(motd).unwrap().data = b"hello".to_vec();
+  let mnt = TestFs::new();
+  mnt.add(1, "file", FileType::Regular);
+  let mut vfs = Vfs::new();
+  vfs.mount("/", root).unwrap();
+  vfs.mount("/mnt", mnt).unwrap();
+  vfs
+}
+
+#[test]
+fn test_normalize() {
+  let cases = [
+    ("/", "/"),
+    ("//a//b/", "/a/b"),
+    ("a/./b", "/a/b"),
+    ("/a/../b", "/b"),
+    ("/../..", "/"),
+    ("/a/b/..", "/a"),
+  ];
+  for (path, normal) in cases {
+    assert_eq!(normalize(path).unwrap().as_str(), normal, "{}", path);
+  }
+  assert_eq!(normalize(""), Err(ErrorKind::NotFound));
+  let long_name = "x".repeat(MAX_NAME + 1);
+  assert_eq!(normalize(&long_name), Err(ErrorKind::InvalidFilename));
+  let long_path = "/abc".repeat(MAX_PATH / 4 + 1);
+  assert_eq!(normalize(&long_path), Err(ErrorKind::InvalidFilename));
+}
+
+#[test]
+fn test_resolve_across_mounts() {
+  let mut vfs = sample_vfs();
+  let motd = vfs.resolve("/etc/motd").unwrap();
+  assert_eq!(motd.mount, 0);
+  assert_eq!(vfs.stat(motd).unwrap().size, 5);
+  let mnt = vfs.resolve("/mnt").unwrap();
+  assert_eq!(mnt, Inode { mount: 1, id: 1 });
+  assert_eq!(vfs.stat(mnt).unwrap().device, 1);
+  assert_eq!(vfs.resolve("/etc/../mnt/file").unwrap().mount, 1);
+  // A prefix of a mount path is not the mount.
+  assert_eq!(vfs.resolve("/mn"), Err(ErrorKind::NotFound));
+  assert_eq!(vfs.resolve("/etc/motd/x"), Err(ErrorKind::NotADirectory));
+  assert_eq!(
+    vfs.mount("/mnt/", TestFs::new()),
+    Err(ErrorKind::ResourceBusy)
+  );
+}
+
+#[test]
+fn test_no_root() {
+  let mut vfs = Vfs::new();
+  vfs.mount("/dev", TestFs::new()).unwrap();
+  assert!(vfs.resolve("/dev").is_ok());
+  assert_eq!(vfs.resolve("/"), Err(ErrorKind::NotFound));
+}
+
+#[test]
+fn test_dentry_cache() {
+  let root = TestFs::new();
+  let lookups = root.lookups.clone();
+  let mut dir = root.root();
+  for i in 0..DENTRIES + 4 {
+    dir = root.add(dir, &format!("d{}", i), FileType::Directory);
+  }
+  let mut vfs = Vfs::new();
+  vfs.mount("/", root).unwrap();
+  let lookups = || lookups.get();
+
+  vfs.resolve("/d0/d1").unwrap();
+  assert_eq!(lookups(), 2);
+  vfs.resolve("/d0/d1").unwrap();
+  assert_eq!(lookups(), 2);
+
+  // Older entries are replaced once the cache is full.
+  let path: String = (0..DENTRIES + 4).map(|i| format!("/d{}", i)).collect();
+  assert_eq!(vfs.resolve(&path).unwrap().id, dir);
+  let before = lookups();
+  vfs.resolve("/d0").unwrap();
+  assert_eq!(lookups(), before + 1);
+}
+
+#[test]
+fn test_open_read_seek() {
+  let mut vfs = sample_vfs();
+  let file = vfs.open("/etc/motd", O_RDONLY).unwrap();
+  let mut buffer = [0; 3];
+  assert_eq!(vfs.read(file, &mut buffer), Ok(3));
+  assert_eq!(&buffer, b"hel");
+  assert_eq!(vfs.read(file, &mut buffer), Ok(2));
+  assert_eq!(&buffer[..2], b"lo");
+  assert_eq!(vfs.read(file, &mut buffer), Ok(0));
+
+  assert_eq!(vfs.seek(file, -4, SEEK_END), Ok(1));
+  assert_eq!(vfs.seek(file, 1, SEEK_CUR), Ok(2));
+  assert_eq!(vfs.read(file, &mut buffer), Ok(3));
+  assert_eq!(&buffer, b"llo");
+  assert_eq!(vfs.seek(file, -1, SEEK_SET), Err(ErrorKind::InvalidInput));
+  assert_eq!(vfs.seek(file, 0, 7), Err(ErrorKind::InvalidInput));
+  assert_eq!(vfs.write(file, b"x"), Err(ErrorKind::PermissionDenied));
+  assert_eq!(vfs.fstat(file).unwrap().size, 5);
+}
+
+#[test]
+fn test_open_flags() {
+  let mut vfs = sample_vfs();
+  assert_eq!(vfs.open("/etc/new", O_RDWR), Err(ErrorKind::NotFound));
+  let file = vfs.open("/etc/new", O_WRONLY | O_CREAT).unwrap();
+  assert_eq!(vfs.write(file, b"abc"), Ok(3));
+  let mut buffer = [0; 4];
+  assert_eq!(
+    vfs.read(file, &mut buffer),
+    Err(ErrorKind::PermissionDenied)
+  );
+  assert_eq!(
+    vfs.open("/etc/new", O_WRONLY | O_CREAT | O_EXCL),
+    Err(ErrorKind::AlreadyExists)
+  );
+  assert_eq!(
+    vfs.open("/nodir/new", O_WRONLY | O_CREAT),
+    Err(ErrorKind::NotFound)
+  );
+
+  let append = vfs.open("/etc/new", O_WRONLY | O_APPEND).unwrap();
+  assert_eq!(vfs.write(append, b"de"), Ok(2));
+  assert_eq!(
+    vfs.resolve("/etc/new").map(|i| vfs.stat(i).unwrap().size),
+    Ok(5)
+  );
+
+  let truncated = vfs.open("/etc/new", O_RDWR | O_TRUNC).unwrap();
+  assert_eq!(vfs.fstat(truncated).unwrap().size, 0);
+  // Truncating needs write access.
+  vfs.open("/etc/motd", O_RDONLY | O_TRUNC).unwrap();
+  assert_eq!(
+    vfs.resolve("/etc/motd").map(|i| vfs.stat(i).unwrap().size),
+    Ok(5)
+  );
+
+  assert_eq!(vfs.open("/etc", O_RDWR), Err(ErrorKind::IsADirectory));
+  assert_eq!(
+    vfs.open("/etc/motd", O_RDONLY | O_DIRECTORY),
+    Err(ErrorKind::NotADirectory)
+  );
+  let dir = vfs.open("/etc", O_RDONLY | O_DIRECTORY).unwrap();
+  assert_eq!(vfs.read(dir, &mut buffer), Err(ErrorKind::IsADirectory));
+}
+
+#[test]
+fn test_readdir() {
+  let mut vfs = sample_vfs();
+  vfs.create("/etc/conf.d", FileType::Directory).unwrap();
+  let dir = vfs.open("/etc", O_RDONLY).unwrap();
+  let mut names = Vec::new();
+  while let Some(entry) = vfs.readdir(dir).unwrap() {
+    names.push((entry.name.to_string(), entry.kind));
+  }
+  assert_eq!(
+    names,
+    [
+      ("motd".to_string(), FileType::Regular),
+      ("conf.d".to_string(), FileType::Directory)
+    ]
+  );
+  assert_eq!(vfs.seek(dir, 0, SEEK_SET), Ok(0));
+  assert!(vfs.readdir(dir).unwrap().is_some());
+
+  let file = vfs.open("/etc/motd", O_RDONLY).unwrap();
+  assert_eq!(vfs.readdir(file), Err(ErrorKind::NotADirectory));
+  assert_eq!(
+    vfs.create("/etc/motd", FileType::Regular),
+    Err(ErrorKind::AlreadyExists)
+  );
+}
+
+#[test]
+fn test_shared_files() {
+  let mut vfs = sample_vfs();
+  let file = vfs.open("/etc/motd", O_RDONLY).unwrap();
+  let mut fds = FdTable::new();
+  assert_eq!(fds.insert(file), Some(0));
+  let mut child = fds.duplicate(&mut vfs);
+
+  // The offset is shared.
+  let mut buffer = [0; 2];
+  vfs.read(child.get(0).unwrap(), &mut buffer).unwrap();
+  vfs.read(fds.get(0).unwrap(), &mut buffer).unwrap();
+  assert_eq!(&buffer, b"ll");
+
+  child.close_all(&mut vfs);
+  assert_eq!(vfs.open_count(), 1);
+  assert_eq!(fds.remove(0), Some(file));
+  assert_eq!(fds.remove(0), None);
+  vfs.release(file);
+  assert_eq!(vfs.open_count(), 0);
+  assert_eq!(vfs.read(file, &mut buffer), Err(ErrorKind::NotFound));
+}
+
+#[test]
+fn test_descriptor_limits() {
+  let mut vfs = sample_vfs();
+  let mut fds = FdTable::new();
+  for fd in 0..MAX_FDS {
+    let file = vfs.open("/etc/motd", O_RDONLY).unwrap();
+    assert_eq!(fds.insert(file), Some(fd));
+  }
+  assert_eq!(fds.insert(0), None);
+  assert_eq!(fds.get(MAX_FDS as u64), None);
+  assert_eq!(fds.get(u64::MAX), None);
+  for _ in MAX_FDS..MAX_FILES {
+    vfs.open("/etc/motd", O_RDONLY).unwrap();
+  }
+  assert_eq!(
+    vfs.open("/etc/motd", O_RDONLY),
+    Err(ErrorKind::ResourceBusy)
+  );
+}
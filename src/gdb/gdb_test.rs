@@ -0,0 +1,222 @@
+use super::*;
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+// Fake memory at MEMORY_BASE, serial input and output.
+const MEMORY_BASE: u64 = 0x8_0000;
+static MEMORY: Mutex<[u8; 64]> = Mutex::new([0; 64]);
+static INPUT: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+static OUTPUT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+fn range(addr: u64, len: usize) -> Result<core::ops::Range<usize>, ErrorKind> {
+  let start = addr
+    .checked_sub(MEMORY_BASE)
+    .ok_or(ErrorKind::InvalidInput)?;
+  let end = start as usize + len;
+  if end > 64 {
+    return Err(ErrorKind::InvalidInput);
+  }
+  Ok(start as usize..end)
+}
+
+fn fake_read_memory(addr: u64, buffer: &mut [u8]) -> Result<(), ErrorKind> {
+  let range = range(addr, buffer.len())?;
+  buffer.copy_from_slice(&MEMORY.lock().unwrap()[range]);
+  Ok(())
+}
+
+fn fake_write_memory(addr: u64, data: &[u8]) -> Result<(), ErrorKind> {
+  let range = range(addr, data.len())?;
+  MEMORY.lock().unwrap()[range].copy_from_slice(data);
+  Ok(())
+}
+
+fn getc() -> u8 {
+  INPUT.lock().unwrap().pop_front().expect("Out of input")
+}
+
+fn putc(c: u8) {
+  OUTPUT.lock().unwrap().push(c);
+}
+
+fn setup() -> MutexGuard<'static, ()> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  register(Ops {
+    read_memory: fake_read_memory,
+    write_memory: fake_write_memory,
+    sync_icache: |_, _| {},
+    breakpoint: || {},
+  });
+  unsafe {
+    SERIAL = None;
+    RUNNING = false;
+    BREAKPOINTS.clear();
+  }
+  let mut memory = MEMORY.lock().unwrap();
+  for (i, byte) in memory.iter_mut().enumerate() {
+    *byte = i as u8;
+  }
+  INPUT.lock().unwrap().clear();
+  OUTPUT.lock().unwrap().clear();
+  guard
+}
+
+fn run(command: &str, regs: &mut Registers) -> (Action, String) {
+  let mut reply = Packet::new();
+  let action = process(command.as_bytes(), regs, &mut reply);
+  (action, String::from_utf8(reply.to_vec()).unwrap())
+}
+
+fn reply(command: &str, regs: &mut Registers) -> String {
+  let (action, reply) = run(command, regs);
+  assert_eq!(action, Action::Reply);
+  reply
+}
+
+fn framed(data: &str) -> String {
+  format!("${}#{:02x}", data, packet::checksum(data.as_bytes()))
+}
+
+#[test]
+fn test_registers() {
+  let _guard = setup();
+  let mut regs = Registers::default();
+  regs.x[0] = 0x0123_4567_89AB_CDEF;
+  regs.sp = 0x8_0000;
+  regs.pc = 0x8_1000;
+  regs.pstate = 0x3C5;
+
+  let all = reply("g", &mut regs);
+  assert_eq!(all.len(), (33 * 8 + 4) * 2);
+  assert!(all.starts_with("efcdab8967452301"));
+  // sp, pc, cpsr
+  assert!(all.ends_with(concat!(
+    "0000080000000000",
+    "0010080000000000",
+    "c5030000"
+  )));
+
+  assert_eq!(reply("p0", &mut regs), "efcdab8967452301");
+  assert_eq!(reply("p21", &mut regs), "c5030000");
+  assert_eq!(reply("p22", &mut regs), "E01");
+
+  assert_eq!(reply("P1e=1100000000000000", &mut regs), "OK");
+  assert_eq!(regs.x[30], 0x11);
+  assert_eq!(reply("P20=0020080000000000", &mut regs), "OK");
+  assert_eq!(regs.pc, 0x8_2000);
+  // sp is not writable
+  assert_eq!(reply("P1f=0000000000000000", &mut regs), "OK");
+  assert_eq!(regs.sp, 0x8_0000);
+  assert_eq!(reply("P0=12", &mut regs), "E01");
+
+  // G round trips g
+  let mut other = Registers::default();
+  assert_eq!(reply(&format!("G{}", all), &mut other), "OK");
+  assert_eq!(other.x[0], 0x0123_4567_89AB_CDEF);
+  assert_eq!(other.pc, 0x8_1000);
+  assert_eq!(other.pstate, 0x3C5);
+  assert_eq!(reply("G0011", &mut other), "E01");
+}
+
+#[test]
+fn test_memory() {
+  let _guard = setup();
+  let mut regs = Registers::default();
+  assert_eq!(reply("m80004,4", &mut regs), "04050607");
+  assert_eq!(reply("M80004,2:abcd", &mut regs), "OK");
+  assert_eq!(reply("m80004,4", &mut regs), "abcd0607");
+  // Outside of the fake memory
+  assert_eq!(reply("m90000,4", &mut regs), "E01");
+  assert_eq!(reply("M80004,2:ab", &mut regs), "E01");
+  assert_eq!(reply("m80004", &mut regs), "E01");
+}
+
+#[test]
+fn test_breakpoints() {
+  let _guard = setup();
+  let mut regs = Registers::default();
+  assert_eq!(reply("Z0,80008,4", &mut regs), "OK");
+  assert_eq!(reply("m80008,4", &mut regs), "000020d4");
+  assert!(is_breakpoint(0x8_0008));
+  // Inserting twice keeps the original instruction.
+  assert_eq!(reply("Z0,80008,4", &mut regs), "OK");
+  assert_eq!(reply("z0,80008,4", &mut regs), "OK");
+  assert_eq!(reply("m80008,4", &mut regs), "08090a0b");
+  assert_eq!(reply("z0,80008,4", &mut regs), "E01");
+  // Hardware breakpoints and watchpoints are not supported.
+  assert_eq!(reply("Z1,80008,4", &mut regs), "");
+}
+
+#[test]
+fn test_resume_and_queries() {
+  let _guard = setup();
+  let mut regs = Registers::default();
+  assert_eq!(reply("?", &mut regs), "S05");
+  assert_eq!(
+    reply("qSupported:multiprocess+", &mut regs),
+    "PacketSize=400"
+  );
+  assert_eq!(
+    format!("{:x}", packet::PACKET_SIZE),
+    "400",
+    "PacketSize reply out of date"
+  );
+  assert_eq!(reply("vMustReplyEmpty", &mut regs), "");
+
+  assert_eq!(run("c", &mut regs).0, Action::Resume(Resume::Continue));
+  assert_eq!(run("s80010", &mut regs).0, Action::Resume(Resume::Step));
+  assert_eq!(regs.pc, 0x8_0010);
+  assert_eq!(run("D", &mut regs), (Action::Detach, "OK".to_string()));
+}
+
+#[test]
+fn test_handle_stop_session() {
+  let _guard = setup();
+  let mut regs = Registers::default();
+  assert_eq!(handle_stop(&mut regs, StopReason::Breakpoint), None);
+
+  attach(Serial { getc, putc });
+  // A compiled in breakpoint, GDB sets one and continues.
+  regs.pc = 0x8_0000;
+  let input = format!(
+    "{}+{}{}+{}",
+    framed("?"),
+    "$g#00", // bad checksum, GDB would resend
+    framed("Z0,80004,4"),
+    framed("c")
+  );
+  INPUT.lock().unwrap().extend(input.as_bytes());
+  assert_eq!(
+    handle_stop(&mut regs, StopReason::Breakpoint),
+    Some(Resume::Continue)
+  );
+  // Stepped over the compiled in breakpoint.
+  assert_eq!(regs.pc, 0x8_0004);
+  let output = String::from_utf8(OUTPUT.lock().unwrap().clone()).unwrap();
+  assert_eq!(output, format!("+{}-+{}+", framed("S05"), framed("OK")));
+
+  // Stopped at the inserted breakpoint, GDB is waiting for the stop reply.
+  OUTPUT.lock().unwrap().clear();
+  let input = format!("-+{}+", framed("D"));
+  INPUT.lock().unwrap().extend(input.as_bytes());
+  assert_eq!(
+    handle_stop(&mut regs, StopReason::Breakpoint),
+    Some(Resume::Continue)
+  );
+  assert_eq!(regs.pc, 0x8_0004);
+  // The stop reply was resent after the nak, detaching removes breakpoints.
+  let output = String::from_utf8(OUTPUT.lock().unwrap().clone()).unwrap();
+  assert_eq!(output, format!("{0}{0}+{1}", framed("S05"), framed("OK")));
+  assert!(!is_breakpoint(0x8_0004));
+  assert_eq!(&MEMORY.lock().unwrap()[4..8], &[4, 5, 6, 7]);
+}
+
+#[test]
+fn test_breakpoint_requires_serial() {
+  let _guard = setup();
+  assert!(matches!(breakpoint(), Err(ErrorKind::NotConnected)));
+  attach(Serial { getc, putc });
+  assert!(breakpoint().is_ok());
+}
@@ -0,0 +1,399 @@
+// GDB remote serial protocol stub.
+//
+// The stub runs in the debug exception handler. When a breakpoint or single
+// step stops the kernel, it talks to GDB over the attached serial port until
+// GDB continues or steps. The serial port is polled, interrupts stay masked
+// while stopped.
+//
+// Supported: registers (g/G/p/P), memory (m/M), software breakpoints
+// (Z0/z0), continue (c), step (s) and detach (D). Writes to sp are ignored,
+// the handler returns on the stack it was entered with.
+
+mod packet;
+
+use crate::common::error::ErrorKind;
+use arrayvec::ArrayVec;
+use packet::{Decoder, Event, Packet};
+
+pub const MAX_BREAKPOINTS: usize = 16;
+// `brk #0`, the same instruction GDB uses.
+pub const BREAK_INSTRUCTION: u32 = 0xD420_0000;
+const INSTRUCTION_SIZE: u64 = 4;
+
+// Registers of the stopped context, in GDB's aarch64 order. The layout is
+// shared with the exception entry code.
+#[repr(C)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Registers {
+  pub x: [u64; 31],
+  pub sp: u64,
+  pub pc: u64,
+  pub pstate: u64,
+}
+
+// GDB register numbers
+const REG_SP: usize = 31;
+const REG_PC: usize = 32;
+const REG_CPSR: usize = 33;
+
+pub struct Ops {
+  pub read_memory: fn(addr: u64, buffer: &mut [u8]) -> Result<(), ErrorKind>,
+  pub write_memory: fn(addr: u64, data: &[u8]) -> Result<(), ErrorKind>,
+  // Makes instructions written to [addr, addr + len) visible for execution.
+  pub sync_icache: fn(addr: u64, len: usize),
+  // Stops in the stub, as if a breakpoint was hit.
+  pub breakpoint: fn(),
+}
+
+#[derive(Clone, Copy)]
+pub struct Serial {
+  // Blocking
+  pub getc: fn() -> u8,
+  pub putc: fn(u8),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopReason {
+  Breakpoint,
+  Step,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Resume {
+  Continue,
+  // Execute one instruction, then stop with StopReason::Step
+  Step,
+}
+
+#[derive(Clone, Copy)]
+struct Breakpoint {
+  addr: u64,
+  // Replaced instruction
+  original: [u8; 4],
+}
+
+static mut OPS: core::mem::MaybeUninit<Ops> =
+  core::mem::MaybeUninit::<Ops>::uninit();
+// These will be set to 0 during bss zero-ing.
+static mut SET: bool = false;
+static mut SERIAL: Option<Serial> = None;
+// GDB waits for a stop reply after c/s.
+static mut RUNNING: bool = false;
+static mut BREAKPOINTS: ArrayVec<Breakpoint, MAX_BREAKPOINTS> =
+  ArrayVec::new_const();
+
+pub fn register(ops: Ops) {
+  unsafe {
+    OPS = core::mem::MaybeUninit::<Ops>::new(ops);
+    SET = true;
+  };
+}
+
+fn ops() -> &'static Ops {
+  unsafe {
+    assert!(SET, "GDB stub not registered");
+    OPS.assume_init_ref()
+  }
+}
+
+// Binds the stub to a serial port. Stops are handled from now on.
+pub fn attach(serial: Serial) {
+  unsafe {
+    SERIAL = Some(serial);
+    RUNNING = false;
+  }
+}
+
+pub fn is_attached() -> bool {
+  unsafe { SERIAL.is_some() }
+}
+
+// Stops in the stub and waits for GDB. NotConnected if no serial port is
+// attached.
+pub fn breakpoint() -> Result<(), ErrorKind> {
+  if !is_attached() {
+    return Err(ErrorKind::NotConnected);
+  }
+  (ops().breakpoint)();
+  Ok(())
+}
+
+// Called by the debug exception handler. Serves GDB until it resumes the
+// target. None if no serial port is attached, the exception is not ours then.
+pub fn handle_stop(regs: &mut Registers, reason: StopReason) -> Option<Resume> {
+  let serial = unsafe { SERIAL }?;
+  // Resuming at a compiled in `brk` would hit it again.
+  if reason == StopReason::Breakpoint && !is_breakpoint(regs.pc) {
+    regs.pc += INSTRUCTION_SIZE;
+  }
+  if unsafe { RUNNING } {
+    packet::send(&serial, b"S05");
+  }
+
+  let mut decoder = Decoder::new();
+  let mut reply = Packet::new();
+  loop {
+    match decoder.feed((serial.getc)()) {
+      None => continue,
+      Some(Event::BadPacket) => {
+        (serial.putc)(b'-');
+        continue;
+      }
+      // Already stopped
+      Some(Event::Interrupt) => {
+        packet::send(&serial, b"S02");
+        continue;
+      }
+      Some(Event::Packet) => (serial.putc)(b'+'),
+    }
+
+    reply.clear();
+    let action = process(decoder.packet(), regs, &mut reply);
+    match action {
+      Action::Reply => packet::send(&serial, &reply),
+      Action::Resume(resume) => {
+        unsafe { RUNNING = true };
+        return Some(resume);
+      }
+      Action::Detach => {
+        // Nothing is sent for `k`, GDB is gone.
+        if !reply.is_empty() {
+          packet::send(&serial, &reply);
+        }
+        remove_all_breakpoints();
+        unsafe { RUNNING = false };
+        return Some(Resume::Continue);
+      }
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Action {
+  Reply,
+  Resume(Resume),
+  Detach,
+}
+
+// Handles one command. Unsupported commands get an empty reply.
+fn process(command: &[u8], regs: &mut Registers, reply: &mut Packet) -> Action {
+  let Some((&kind, args)) = command.split_first() else {
+    return Action::Reply;
+  };
+  let result = match kind {
+    b'?' => reply_str(reply, "S05"),
+    b'g' => read_registers(regs, reply),
+    b'G' => write_registers(regs, args).map(|_| reply_ok(reply)),
+    b'p' => read_register(regs, args, reply),
+    b'P' => write_register(regs, args).map(|_| reply_ok(reply)),
+    b'm' => read_memory(args, reply),
+    b'M' => write_memory(args).map(|_| reply_ok(reply)),
+    b'Z' | b'z' => match args.split_first() {
+      // Software breakpoints only, GDB falls back to M for the others.
+      Some((b'0', args)) => {
+        set_breakpoint(kind == b'Z', args).map(|_| reply_ok(reply))
+      }
+      _ => Some(()),
+    },
+    b'c' | b's' => {
+      if !args.is_empty() {
+        match packet::parse_hex(args) {
+          Some(pc) => regs.pc = pc,
+          None => return error(reply),
+        }
+      }
+      return Action::Resume(match kind {
+        b'c' => Resume::Continue,
+        _ => Resume::Step,
+      });
+    }
+    b'D' => {
+      reply_ok(reply);
+      return Action::Detach;
+    }
+    // Nothing to kill, leave the kernel running.
+    b'k' => return Action::Detach,
+    b'H' => reply_str(reply, "OK"),
+    b'q' => query(args, reply),
+    _ => Some(()),
+  };
+  match result {
+    Some(()) => Action::Reply,
+    None => error(reply),
+  }
+}
+
+fn reply_str(reply: &mut Packet, s: &str) -> Option<()> {
+  reply.clear();
+  reply.try_extend_from_slice(s.as_bytes()).ok()
+}
+
+fn reply_ok(reply: &mut Packet) {
+  let _ = reply_str(reply, "OK");
+}
+
+fn error(reply: &mut Packet) -> Action {
+  let _ = reply_str(reply, "E01");
+  Action::Reply
+}
+
+fn query(args: &[u8], reply: &mut Packet) -> Option<()> {
+  if args.starts_with(b"Supported") {
+    // PACKET_SIZE in hex
+    return reply_str(reply, "PacketSize=400");
+  }
+  if args == b"Attached" {
+    return reply_str(reply, "1");
+  }
+  Some(())
+}
+
+// Little endian value of register `number` and its size in bytes.
+fn register_value(regs: &Registers, number: usize) -> Option<(u64, usize)> {
+  match number {
+    0..=30 => Some((regs.x[number], 8)),
+    REG_SP => Some((regs.sp, 8)),
+    REG_PC => Some((regs.pc, 8)),
+    REG_CPSR => Some((regs.pstate, 4)),
+    _ => None,
+  }
+}
+
+fn set_register_value(regs: &mut Registers, number: usize, value: u64) {
+  match number {
+    0..=30 => regs.x[number] = value,
+    REG_PC => regs.pc = value,
+    REG_CPSR => regs.pstate = value & 0xFFFF_FFFF,
+    _ => {}
+  }
+}
+
+fn read_registers(regs: &Registers, reply: &mut Packet) -> Option<()> {
+  (0..=REG_CPSR).try_for_each(|number| {
+    let (value, size) = register_value(regs, number)?;
+    packet::push_hex(reply, &value.to_le_bytes()[..size]).then_some(())
+  })
+}
+
+fn write_registers(regs: &mut Registers, args: &[u8]) -> Option<()> {
+  let mut rest = args;
+  let mut updated = *regs;
+  for number in 0..=REG_CPSR {
+    let (_, size) = register_value(regs, number)?;
+    // GDB may send fewer registers than it knows about.
+    if rest.is_empty() {
+      break;
+    }
+    let mut bytes = [0u8; 8];
+    packet::decode_hex(rest.get(..size * 2)?, &mut bytes[..size])?;
+    set_register_value(&mut updated, number, u64::from_le_bytes(bytes));
+    rest = &rest[size * 2..];
+  }
+  *regs = updated;
+  Some(())
+}
+
+fn read_register(
+  regs: &Registers,
+  args: &[u8],
+  reply: &mut Packet,
+) -> Option<()> {
+  let number = packet::parse_hex(args)? as usize;
+  let (value, size) = register_value(regs, number)?;
+  packet::push_hex(reply, &value.to_le_bytes()[..size]).then_some(())
+}
+
+fn write_register(regs: &mut Registers, args: &[u8]) -> Option<()> {
+  let (number, value) = split(args, b'=')?;
+  let number = packet::parse_hex(number)? as usize;
+  let (_, size) = register_value(regs, number)?;
+  let mut bytes = [0u8; 8];
+  packet::decode_hex(value, &mut bytes[..size])?;
+  set_register_value(regs, number, u64::from_le_bytes(bytes));
+  Some(())
+}
+
+fn split(s: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+  let index = s.iter().position(|&c| c == separator)?;
+  Some((&s[..index], &s[index + 1..]))
+}
+
+// `addr,length`
+fn parse_range(args: &[u8]) -> Option<(u64, usize)> {
+  let (addr, length) = split(args, b',')?;
+  Some((
+    packet::parse_hex(addr)?,
+    packet::parse_hex(length)? as usize,
+  ))
+}
+
+fn read_memory(args: &[u8], reply: &mut Packet) -> Option<()> {
+  let (addr, length) = parse_range(args)?;
+  let mut buffer = [0u8; packet::PACKET_SIZE / 2];
+  let buffer = buffer.get_mut(..length)?;
+  (ops().read_memory)(addr, buffer).ok()?;
+  packet::push_hex(reply, buffer).then_some(())
+}
+
+fn write_memory(args: &[u8]) -> Option<()> {
+  let (range, data) = split(args, b':')?;
+  let (addr, length) = parse_range(range)?;
+  let mut buffer = [0u8; packet::PACKET_SIZE / 2];
+  let buffer = buffer.get_mut(..length)?;
+  packet::decode_hex(data, buffer)?;
+  (ops().write_memory)(addr, buffer).ok()?;
+  // Could be code
+  (ops().sync_icache)(addr, length);
+  Some(())
+}
+
+// `,addr,kind`, kind is the instruction size.
+fn set_breakpoint(insert: bool, args: &[u8]) -> Option<()> {
+  let (addr, _kind) = parse_range(args.strip_prefix(b",")?)?;
+  let result = match insert {
+    true => insert_breakpoint(addr),
+    false => remove_breakpoint(addr),
+  };
+  result.ok()
+}
+
+fn is_breakpoint(addr: u64) -> bool {
+  unsafe { BREAKPOINTS.iter().any(|b| b.addr == addr) }
+}
+
+fn insert_breakpoint(addr: u64) -> Result<(), ErrorKind> {
+  if is_breakpoint(addr) {
+    return Ok(());
+  }
+  let breakpoints = unsafe { &mut BREAKPOINTS };
+  if breakpoints.is_full() {
+    return Err(ErrorKind::StorageFull);
+  }
+  let mut original = [0u8; 4];
+  (ops().read_memory)(addr, &mut original)?;
+  (ops().write_memory)(addr, &BREAK_INSTRUCTION.to_le_bytes())?;
+  (ops().sync_icache)(addr, original.len());
+  breakpoints.push(Breakpoint { addr, original });
+  Ok(())
+}
+
+fn remove_breakpoint(addr: u64) -> Result<(), ErrorKind> {
+  let breakpoints = unsafe { &mut BREAKPOINTS };
+  let index = breakpoints
+    .iter()
+    .position(|b| b.addr == addr)
+    .ok_or(ErrorKind::NotFound)?;
+  let breakpoint = breakpoints.remove(index);
+  (ops().write_memory)(addr, &breakpoint.original)?;
+  (ops().sync_icache)(addr, breakpoint.original.len());
+  Ok(())
+}
+
+fn remove_all_breakpoints() {
+  while let Some(addr) = unsafe { BREAKPOINTS.last().map(|b| b.addr) } {
+    let _ = remove_breakpoint(addr);
+  }
+}
+
+#[cfg(test)]
+mod gdb_test;
@@ -0,0 +1,176 @@
+// Packet framing of the GDB remote serial protocol: `$<data>#<checksum>`,
+// where the checksum is the sum of the data bytes modulo 256 in two hex
+// digits. Every packet is acknowledged with `+`, or `-` to request a resend.
+
+use super::Serial;
+use arrayvec::ArrayVec;
+
+pub const PACKET_SIZE: usize = 1024;
+pub type Packet = ArrayVec<u8, PACKET_SIZE>;
+
+// Sent by GDB outside of packets to stop the target.
+pub const INTERRUPT: u8 = 0x03;
+const MAX_RESENDS: usize = 8;
+
+pub fn checksum(data: &[u8]) -> u8 {
+  data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+  // A complete packet, see Decoder::packet()
+  Packet,
+  // Checksum mismatch or too long, must be answered with `-`
+  BadPacket,
+  Interrupt,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+  Idle,
+  Data,
+  Checksum,
+  // First checksum digit seen
+  ChecksumLow(u8),
+}
+
+pub struct Decoder {
+  state: State,
+  packet: Packet,
+  overflow: bool,
+}
+
+impl Decoder {
+  pub const fn new() -> Decoder {
+    Decoder {
+      state: State::Idle,
+      packet: Packet::new_const(),
+      overflow: false,
+    }
+  }
+
+  // Data of the last Event::Packet.
+  pub fn packet(&self) -> &[u8] {
+    &self.packet
+  }
+
+  pub fn feed(&mut self, byte: u8) -> Option<Event> {
+    match (self.state, byte) {
+      // A new packet starts anywhere, dropping a partial one.
+      (_, b'$') => {
+        self.state = State::Data;
+        self.packet.clear();
+        self.overflow = false;
+        None
+      }
+      (State::Idle, INTERRUPT) => Some(Event::Interrupt),
+      // Acks and noise
+      (State::Idle, _) => None,
+      (State::Data, b'#') => {
+        self.state = State::Checksum;
+        None
+      }
+      (State::Data, _) => {
+        self.overflow |= self.packet.try_push(byte).is_err();
+        None
+      }
+      (State::Checksum, _) => match hex_digit(byte) {
+        Some(high) => {
+          self.state = State::ChecksumLow(high);
+          None
+        }
+        None => self.bad_packet(),
+      },
+      (State::ChecksumLow(high), _) => {
+        let Some(low) = hex_digit(byte) else {
+          return self.bad_packet();
+        };
+        if self.overflow || checksum(&self.packet) != (high << 4 | low) {
+          return self.bad_packet();
+        }
+        self.state = State::Idle;
+        Some(Event::Packet)
+      }
+    }
+  }
+
+  fn bad_packet(&mut self) -> Option<Event> {
+    self.state = State::Idle;
+    self.packet.clear();
+    Some(Event::BadPacket)
+  }
+}
+
+// Sends `data` as a packet until GDB acknowledges it.
+pub fn send(serial: &Serial, data: &[u8]) {
+  for _ in 0..MAX_RESENDS {
+    (serial.putc)(b'$');
+    let mut sum = 0u8;
+    for &byte in data {
+      // Escaped as `}` followed by the byte xor 0x20.
+      let escaped: &[u8] = match byte {
+        b'#' | b'$' | b'}' | b'*' => &[b'}', byte ^ 0x20],
+        _ => &[byte],
+      };
+      for &out in escaped {
+        (serial.putc)(out);
+        sum = sum.wrapping_add(out);
+      }
+    }
+    (serial.putc)(b'#');
+    (serial.putc)(HEX[(sum >> 4) as usize]);
+    (serial.putc)(HEX[(sum & 0xF) as usize]);
+
+    loop {
+      match (serial.getc)() {
+        b'+' => return,
+        b'-' => break,
+        // GDB does not send anything else before acknowledging.
+        _ => continue,
+      }
+    }
+  }
+}
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+pub fn hex_digit(c: u8) -> Option<u8> {
+  match c {
+    b'0'..=b'9' => Some(c - b'0'),
+    b'a'..=b'f' => Some(c - b'a' + 10),
+    b'A'..=b'F' => Some(c - b'A' + 10),
+    _ => None,
+  }
+}
+
+// Big endian hex number, e.g. an address.
+pub fn parse_hex(s: &[u8]) -> Option<u64> {
+  if s.is_empty() || s.len() > 16 {
+    return None;
+  }
+  s.iter()
+    .try_fold(0u64, |value, &c| Some(value << 4 | hex_digit(c)? as u64))
+}
+
+// Hex encoded bytes into `out`, which must be exactly half as long.
+pub fn decode_hex(s: &[u8], out: &mut [u8]) -> Option<()> {
+  if s.len() != out.len() * 2 {
+    return None;
+  }
+  for (pair, byte) in s.chunks(2).zip(out.iter_mut()) {
+    *byte = hex_digit(pair[0])? << 4 | hex_digit(pair[1])?;
+  }
+  Some(())
+}
+
+// False if `packet` is full.
+pub fn push_hex(packet: &mut Packet, bytes: &[u8]) -> bool {
+  bytes.iter().all(|&byte| {
+    packet.try_push(HEX[(byte >> 4) as usize]).is_ok()
+      && packet.try_push(HEX[(byte & 0xF) as usize]).is_ok()
+  })
+}
+
+#[cfg(test)]
+#[path = "packet_test.rs"]
+mod packet_test;
@@ -0,0 +1,66 @@
+use super::*;
+
+fn feed_all(decoder: &mut Decoder, bytes: &[u8]) -> Vec<Event> {
+  bytes.iter().filter_map(|&b| decoder.feed(b)).collect()
+}
+
+#[test]
+fn test_decoder_packet() {
+  let mut decoder = Decoder::new();
+  // Acks before the packet are skipped.
+  assert_eq!(feed_all(&mut decoder, b"+$g#67"), vec![Event::Packet]);
+  assert_eq!(decoder.packet(), b"g");
+  assert_eq!(feed_all(&mut decoder, b"$m80000,4#c5"), vec![Event::Packet]);
+  assert_eq!(decoder.packet(), b"m80000,4");
+}
+
+#[test]
+fn test_decoder_bad_checksum() {
+  let mut decoder = Decoder::new();
+  assert_eq!(feed_all(&mut decoder, b"$g#68"), vec![Event::BadPacket]);
+  assert_eq!(feed_all(&mut decoder, b"$g#x7"), vec![Event::BadPacket]);
+  assert_eq!(feed_all(&mut decoder, b"$g#67"), vec![Event::Packet]);
+}
+
+#[test]
+fn test_decoder_restart_and_interrupt() {
+  let mut decoder = Decoder::new();
+  // A `$` drops the partial packet.
+  assert_eq!(feed_all(&mut decoder, b"$mxx$g#67"), vec![Event::Packet]);
+  assert_eq!(decoder.packet(), b"g");
+  assert_eq!(feed_all(&mut decoder, &[INTERRUPT]), vec![Event::Interrupt]);
+}
+
+#[test]
+fn test_decoder_overflow() {
+  let mut decoder = Decoder::new();
+  let mut bytes = vec![b'$'];
+  bytes.extend(core::iter::repeat_n(b'0', PACKET_SIZE + 1));
+  bytes.extend(b"#00");
+  assert_eq!(feed_all(&mut decoder, &bytes), vec![Event::BadPacket]);
+}
+
+#[test]
+fn test_hex_helpers() {
+  assert_eq!(parse_hex(b"80000"), Some(0x80000));
+  assert_eq!(parse_hex(b"FfFf"), Some(0xFFFF));
+  assert_eq!(parse_hex(b""), None);
+  assert_eq!(parse_hex(b"12g"), None);
+  assert_eq!(parse_hex(b"11112222333344445"), None);
+
+  let mut out = [0u8; 2];
+  assert_eq!(decode_hex(b"1fA0", &mut out), Some(()));
+  assert_eq!(out, [0x1F, 0xA0]);
+  assert_eq!(decode_hex(b"1f", &mut out), None);
+
+  let mut packet = Packet::new();
+  assert!(push_hex(&mut packet, &[0x00, 0xAB]));
+  assert_eq!(&packet[..], b"00ab");
+}
+
+#[test]
+fn test_checksum() {
+  assert_eq!(checksum(b""), 0);
+  assert_eq!(checksum(b"OK"), 0x9A);
+  assert_eq!(checksum(&[0xFF, 0x02]), 0x01);
+}
@@ -143,7 +143,7 @@ fn unmask_interrupt(channel: interrupt::IrqChannel) {
   panic!("Unknown domain");
 }
 
-fn serve_interrupt(handlers: &[interrupt::HandlerMeta]) {
+fn serve_interrupt(handlers: &mut [interrupt::HandlerMeta]) {
   let b = mmio::read(Reg::IRQ_BASIC_PENDING);
   let p1 = mmio::read(Reg::IRQ_PENDING_1);
   let p2 = mmio::read(Reg::IRQ_PENDING_2);
@@ -189,7 +189,7 @@ fn serve_interrupt(handlers: &[interrupt::HandlerMeta]) {
 
 #[inline(always)]
 fn handle_if(
-  handlers: &[interrupt::HandlerMeta],
+  handlers: &mut [interrupt::HandlerMeta],
   val: u32,
   mask: u32,
   domain: interrupt::IrqDomain,
@@ -200,10 +200,14 @@ fn handle_if(
   }
 }
 
-fn handle(handlers: &[interrupt::HandlerMeta], channel: interrupt::IrqChannel) {
+fn handle(
+  handlers: &mut [interrupt::HandlerMeta],
+  channel: interrupt::IrqChannel,
+) {
   // could've been better lmao
   for handler in handlers {
     if handler.channel == channel {
+      handler.count += 1;
       return (handler.handler)();
     }
   }
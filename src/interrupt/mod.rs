@@ -32,6 +32,13 @@ type HandlerContainer = ArrayVec<HandlerMeta, 128>;
 struct HandlerMeta {
   channel: IrqChannel,
   handler: fn(),
+  // Number of times the handler has been served.
+  count: u64,
+}
+
+pub struct IrqStat {
+  pub channel: IrqChannel,
+  pub count: u64,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -43,15 +50,17 @@ pub struct IrqChannel {
 struct Ops {
   mask_interrupt: fn(channel: IrqChannel),
   unmask_interrupt: fn(channel: IrqChannel),
-  pub serve_interrupt: fn(handlers: &[HandlerMeta]),
+  pub serve_interrupt: fn(handlers: &mut [HandlerMeta]),
 }
 
 pub fn set_handler(channel: IrqChannel, handler: fn()) {
   unsafe {
     assert!(IMPL_SET, "Impl not set");
-    HANDLERS
-      .assume_init_mut()
-      .push(HandlerMeta { channel, handler });
+    HANDLERS.assume_init_mut().push(HandlerMeta {
+      channel,
+      handler,
+      count: 0,
+    });
   }
 }
 
@@ -73,11 +82,26 @@ pub fn serve_interrupt() {
   unsafe {
     assert!(IMPL_SET, "Impl not set");
     (OPS.assume_init_ref().serve_interrupt)(
-      HANDLERS.assume_init_ref().as_slice(),
+      HANDLERS.assume_init_mut().as_mut_slice(),
     );
   }
 }
 
+// Snapshot of how many times each registered handler was served.
+pub fn stats() -> ArrayVec<IrqStat, 128> {
+  unsafe {
+    assert!(IMPL_SET, "Impl not set");
+    HANDLERS
+      .assume_init_ref()
+      .iter()
+      .map(|h| IrqStat {
+        channel: h.channel,
+        count: h.count,
+      })
+      .collect()
+  }
+}
+
 fn register_device(ops: Ops) {
   unsafe {
     if !IMPL_SET {
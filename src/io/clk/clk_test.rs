@@ -0,0 +1,134 @@
+use super::*;
+use std::sync::Mutex;
+
+// The tree is global, run the tests one at a time.
+static LOCK: Mutex<()> = Mutex::new(());
+// Rate and state of each clock id, the mock firmware.
+static RATES: Mutex<[u32; 16]> = Mutex::new([0; 16]);
+static ENABLED: Mutex<[bool; 16]> = Mutex::new([false; 16]);
+static CHANGES: Mutex<Vec<(ClockId, u32, u32)>> = Mutex::new(Vec::new());
+
+// Core feeds Emmc and Uart, Arm is a root.
+fn topology_mock() -> Result<Topology, ErrorKind> {
+  let mut pairs = Topology::new();
+  pairs.push((ClockId::Core as u32, ClockId::Emmc as u32));
+  pairs.push((0, ClockId::Core as u32));
+  pairs.push((ClockId::Core as u32, ClockId::Uart as u32));
+  pairs.push((0, ClockId::Arm as u32));
+  // Unknown clocks are skipped
+  pairs.push((0, 0x42));
+  Ok(pairs)
+}
+
+fn get_rate_mock(id: ClockId) -> Result<u32, ErrorKind> {
+  Ok(RATES.lock().unwrap()[id as usize])
+}
+
+// Core runs children at half its rate.
+fn set_rate_mock(id: ClockId, rate_hz: u32) -> Result<u32, ErrorKind> {
+  let mut rates = RATES.lock().unwrap();
+  // Rounded to kHz like a real divider would.
+  rates[id as usize] = rate_hz / 1000 * 1000;
+  if id == ClockId::Core {
+    rates[ClockId::Uart as usize] = rate_hz / 2;
+    rates[ClockId::Emmc as usize] = rate_hz / 2;
+  }
+  Ok(rates[id as usize])
+}
+
+fn is_enabled_mock(id: ClockId) -> Result<bool, ErrorKind> {
+  Ok(ENABLED.lock().unwrap()[id as usize])
+}
+
+fn set_enabled_mock(id: ClockId, enabled: bool) -> Result<(), ErrorKind> {
+  ENABLED.lock().unwrap()[id as usize] = enabled;
+  Ok(())
+}
+
+fn notifier_mock(change: &RateChange) {
+  CHANGES.lock().unwrap().push((
+    change.clock.id(),
+    change.old_hz,
+    change.new_hz,
+  ));
+}
+
+fn setup() -> std::sync::MutexGuard<'static, ()> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  *RATES.lock().unwrap() = [0; 16];
+  RATES.lock().unwrap()[ClockId::Core as usize] = 250_000_000;
+  RATES.lock().unwrap()[ClockId::Uart as usize] = 48_000_000;
+  *ENABLED.lock().unwrap() = [false; 16];
+  CHANGES.lock().unwrap().clear();
+  register(Ops {
+    topology: topology_mock,
+    get_rate: get_rate_mock,
+    set_rate: set_rate_mock,
+    is_enabled: is_enabled_mock,
+    set_enabled: set_enabled_mock,
+  })
+  .unwrap();
+  guard
+}
+
+#[test]
+fn test_clk_tree() {
+  let _guard = setup();
+  let uart = get("uart").unwrap();
+  assert_eq!(uart.id(), ClockId::Uart);
+  assert_eq!(uart.rate(), 48_000_000);
+  assert_eq!(uart.parent(), Some(get("CORE").unwrap()));
+  assert_eq!(get("arm").unwrap().parent(), None);
+  assert!(matches!(get("pwm"), Err(ErrorKind::NotFound)));
+
+  let mut visited = Vec::new();
+  walk(|clock, depth| visited.push((clock.name(), depth)));
+  assert_eq!(visited, [("Core", 0), ("Emmc", 1), ("Uart", 1), ("Arm", 0)]);
+}
+
+#[test]
+fn test_clk_enable_refcount() {
+  let _guard = setup();
+  let uart = get("uart").unwrap();
+  let emmc = get("emmc").unwrap();
+  let core = get("core").unwrap();
+
+  uart.enable().unwrap();
+  emmc.enable().unwrap();
+  assert!(uart.is_enabled().unwrap());
+  assert!(core.is_enabled().unwrap());
+  assert_eq!(core.enable_count(), 2);
+
+  uart.disable().unwrap();
+  assert!(!uart.is_enabled().unwrap());
+  // Still used by emmc
+  assert!(core.is_enabled().unwrap());
+  emmc.disable().unwrap();
+  assert!(!core.is_enabled().unwrap());
+  assert!(matches!(core.disable(), Err(ErrorKind::InvalidInput)));
+}
+
+#[test]
+fn test_clk_set_rate_notifies_descendants() {
+  let _guard = setup();
+  let uart = get("uart").unwrap();
+  let core = get("core").unwrap();
+  uart.add_notifier(notifier_mock).unwrap();
+  core.add_notifier(notifier_mock).unwrap();
+
+  assert_eq!(core.set_rate(400_000_500).unwrap(), 400_000_000);
+  assert_eq!(core.rate(), 400_000_000);
+  assert_eq!(uart.rate(), 200_000_250);
+  assert_eq!(
+    *CHANGES.lock().unwrap(),
+    [
+      (ClockId::Core, 250_000_000, 400_000_000),
+      (ClockId::Uart, 48_000_000, 200_000_250),
+    ]
+  );
+
+  // Same rate, no notification
+  CHANGES.lock().unwrap().clear();
+  core.set_rate(400_000_500).unwrap();
+  assert!(CHANGES.lock().unwrap().is_empty());
+}
@@ -0,0 +1,96 @@
+// Clocks managed by the VideoCore firmware, through the property mailbox.
+use super::{Ops, Topology};
+use crate::common::error::ErrorKind;
+use crate::io::clock::ClockId;
+use crate::io::mailbox;
+use crate::io::mailbox::tag::{
+  GetClockRate, GetClockState, HwGetClocks, SetClockRate, SetClockState,
+};
+
+// GetClockState / SetClockState bits
+const STATE_ON: u32 = 1 << 0;
+const STATE_NOT_EXIST: u32 = 1 << 1;
+
+fn topology() -> Result<Topology, ErrorKind> {
+  let mut message = mailbox::Message::new();
+  message.add_tag(&HwGetClocks::Request {}.to_tag())?;
+  mailbox::send(&mut message)?;
+  let pairs = HwGetClocks::read_response(&message)?.parent_clock_pair();
+  let len = HwGetClocks::response_len(&message)?;
+  Ok(
+    pairs[..len / 4]
+      .chunks_exact(2)
+      .map(|pair| (pair[0], pair[1]))
+      .take(super::MAX_CLOCKS)
+      .collect(),
+  )
+}
+
+fn get_rate(id: ClockId) -> Result<u32, ErrorKind> {
+  let tag = GetClockRate::Request {
+    clock_id: id as u32,
+  }
+  .to_tag();
+  let mut message = mailbox::Message::new();
+  message.add_tag(&tag)?;
+  mailbox::send(&mut message)?;
+  Ok(GetClockRate::read_response(&message)?.rate_hz())
+}
+
+fn set_rate(id: ClockId, rate_hz: u32) -> Result<u32, ErrorKind> {
+  let tag = SetClockRate::Request {
+    clock_id: id as u32,
+    rate_hz,
+    skip_setting_turbo: 1,
+  }
+  .to_tag();
+  let mut message = mailbox::Message::new();
+  message.add_tag(&tag)?;
+  mailbox::send(&mut message)?;
+  // 0 when the clock does not exist
+  match SetClockRate::read_response(&message)?.rate_hz() {
+    0 => Err(ErrorKind::NotFound),
+    rate_hz => Ok(rate_hz),
+  }
+}
+
+fn is_enabled(id: ClockId) -> Result<bool, ErrorKind> {
+  let tag = GetClockState::Request {
+    clock_id: id as u32,
+  }
+  .to_tag();
+  let mut message = mailbox::Message::new();
+  message.add_tag(&tag)?;
+  mailbox::send(&mut message)?;
+  let state_bits = GetClockState::read_response(&message)?.state_bits();
+  if state_bits & STATE_NOT_EXIST != 0 {
+    return Err(ErrorKind::NotFound);
+  }
+  Ok(state_bits & STATE_ON != 0)
+}
+
+fn set_enabled(id: ClockId, enabled: bool) -> Result<(), ErrorKind> {
+  let tag = SetClockState::Request {
+    clock_id: id as u32,
+    state_bits: if enabled { STATE_ON } else { 0 },
+  }
+  .to_tag();
+  let mut message = mailbox::Message::new();
+  message.add_tag(&tag)?;
+  mailbox::send(&mut message)?;
+  let state_bits = SetClockState::read_response(&message)?.state_bits();
+  if state_bits & STATE_NOT_EXIST != 0 {
+    return Err(ErrorKind::NotFound);
+  }
+  Ok(())
+}
+
+pub fn initialize() -> Result<(), ErrorKind> {
+  super::register(Ops {
+    topology,
+    get_rate,
+    set_rate,
+    is_enabled,
+    set_enabled,
+  })
+}
@@ -0,0 +1,282 @@
+// Clock framework. Builds the clock tree reported by the firmware, caches
+// rates and hands out references to drivers:
+//
+//   let clock = clk::get("uart")?;
+//   clock.enable()?;
+//   clock.add_notifier(on_rate_change)?;
+//
+// Consumers registered with add_notifier() are called after the rate of the
+// clock or one of its ancestors changed, e.g. to recompute baud divisors.
+
+pub mod mailbox_clk;
+
+use crate::common::error::ErrorKind;
+use crate::io::clock::ClockId;
+use crate::log;
+use arrayvec::ArrayVec;
+
+pub const MAX_CLOCKS: usize = 32;
+const MAX_NOTIFIERS: usize = 4;
+
+pub struct RateChange {
+  pub clock: Clk,
+  pub old_hz: u32,
+  pub new_hz: u32,
+}
+
+pub type Notifier = fn(&RateChange);
+
+// (parent, clock) pairs, parent 0 for root clocks.
+pub type Topology = ArrayVec<(u32, u32), MAX_CLOCKS>;
+
+pub struct Ops {
+  pub topology: fn() -> Result<Topology, ErrorKind>,
+  pub get_rate: fn(id: ClockId) -> Result<u32, ErrorKind>,
+  // Returns the rate actually set.
+  pub set_rate: fn(id: ClockId, rate_hz: u32) -> Result<u32, ErrorKind>,
+  pub is_enabled: fn(id: ClockId) -> Result<bool, ErrorKind>,
+  pub set_enabled: fn(id: ClockId, enabled: bool) -> Result<(), ErrorKind>,
+}
+
+struct Node {
+  id: ClockId,
+  parent: Option<usize>,
+  // Cached, refreshed on set_rate() and refresh().
+  rate_hz: u32,
+  enable_count: u32,
+  notifiers: ArrayVec<Notifier, MAX_NOTIFIERS>,
+}
+
+static mut OPS: core::mem::MaybeUninit<Ops> =
+  core::mem::MaybeUninit::<Ops>::uninit();
+static mut TREE: ArrayVec<Node, MAX_CLOCKS> = ArrayVec::new_const();
+// This will be set to 0 during bss zero-ing.
+static mut SET: bool = false;
+
+// Reference to a clock of the tree.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Clk {
+  index: usize,
+}
+
+fn ops() -> &'static Ops {
+  unsafe { OPS.assume_init_ref() }
+}
+
+fn node(index: usize) -> &'static mut Node {
+  unsafe { &mut TREE[index] }
+}
+
+fn find(id: u32) -> Option<usize> {
+  unsafe { TREE.iter().position(|node| node.id as u32 == id) }
+}
+
+// Registers the backend and builds the tree. Clocks are roots when the
+// backend has no topology.
+pub fn register(ops: Ops) -> Result<(), ErrorKind> {
+  let pairs = (ops.topology)().unwrap_or_else(|e| {
+    log::warn!("Clock topology unavailable: {:?}", e);
+    ClockId::ALL.iter().map(|id| (0, *id as u32)).collect()
+  });
+  unsafe {
+    OPS = core::mem::MaybeUninit::<Ops>::new(ops);
+    TREE.clear();
+    SET = true;
+  }
+
+  for (_, id) in pairs.iter() {
+    let Some(id) = ClockId::from_u32(*id) else {
+      log::debug!("Unknown clock {:#X}", id);
+      continue;
+    };
+    if find(id as u32).is_some() {
+      continue;
+    }
+    let rate_hz = (self::ops().get_rate)(id).unwrap_or(0);
+    unsafe {
+      TREE
+        .try_push(Node {
+          id,
+          parent: None,
+          rate_hz,
+          enable_count: 0,
+          notifiers: ArrayVec::new(),
+        })
+        .map_err(|_| ErrorKind::StorageFull)?;
+    }
+  }
+  // Link once every clock exists, pairs are not sorted by parent.
+  for (parent, id) in pairs.iter() {
+    if let Some(index) = find(*id) {
+      node(index).parent = find(*parent);
+    }
+  }
+  unsafe { log::info!("{} clocks registered", TREE.len()) };
+  Ok(())
+}
+
+// Looks a clock up by name, case insensitive ("uart", "emmc", ...).
+pub fn get(name: &str) -> Result<Clk, ErrorKind> {
+  unsafe {
+    if !SET {
+      return Err(ErrorKind::NotFound);
+    }
+    TREE
+      .iter()
+      .position(|node| node.id.as_str().eq_ignore_ascii_case(name))
+      .map(|index| Clk { index })
+      .ok_or(ErrorKind::NotFound)
+  }
+}
+
+pub fn get_by_id(id: ClockId) -> Result<Clk, ErrorKind> {
+  unsafe {
+    if !SET {
+      return Err(ErrorKind::NotFound);
+    }
+  }
+  find(id as u32)
+    .map(|index| Clk { index })
+    .ok_or(ErrorKind::NotFound)
+}
+
+// Visits the tree depth first, with the depth of each clock.
+pub fn walk(mut f: impl FnMut(Clk, usize)) {
+  fn visit(
+    parent: Option<usize>,
+    depth: usize,
+    f: &mut impl FnMut(Clk, usize),
+  ) {
+    let count = unsafe { TREE.len() };
+    for index in 0..count {
+      if node(index).parent == parent {
+        f(Clk { index }, depth);
+        visit(Some(index), depth + 1, f);
+      }
+    }
+  }
+  unsafe {
+    if !SET {
+      return;
+    }
+  }
+  visit(None, 0, &mut f);
+}
+
+impl Clk {
+  pub fn id(&self) -> ClockId {
+    node(self.index).id
+  }
+
+  pub fn name(&self) -> &'static str {
+    node(self.index).id.as_str()
+  }
+
+  pub fn parent(&self) -> Option<Clk> {
+    node(self.index).parent.map(|index| Clk { index })
+  }
+
+  // Cached rate.
+  pub fn rate(&self) -> u32 {
+    node(self.index).rate_hz
+  }
+
+  pub fn enable_count(&self) -> u32 {
+    node(self.index).enable_count
+  }
+
+  pub fn is_enabled(&self) -> Result<bool, ErrorKind> {
+    (ops().is_enabled)(self.id())
+  }
+
+  // Enables the parents first. Counted, each enable() needs a disable().
+  pub fn enable(&self) -> Result<(), ErrorKind> {
+    if let Some(parent) = self.parent() {
+      parent.enable()?;
+    }
+    let node = node(self.index);
+    if node.enable_count == 0 {
+      if let Err(e) = (ops().set_enabled)(node.id, true) {
+        if let Some(parent) = self.parent() {
+          let _ = parent.disable();
+        }
+        return Err(e);
+      }
+    }
+    node.enable_count += 1;
+    Ok(())
+  }
+
+  pub fn disable(&self) -> Result<(), ErrorKind> {
+    let node = node(self.index);
+    if node.enable_count == 0 {
+      return Err(ErrorKind::InvalidInput);
+    }
+    if node.enable_count == 1 {
+      (ops().set_enabled)(node.id, false)?;
+    }
+    node.enable_count -= 1;
+    match self.parent() {
+      Some(parent) => parent.disable(),
+      None => Ok(()),
+    }
+  }
+
+  // Returns the rate actually set. Notifies consumers of this clock and of
+  // the clocks below it whose rate changed.
+  pub fn set_rate(&self, rate_hz: u32) -> Result<u32, ErrorKind> {
+    let new_hz = (ops().set_rate)(self.id(), rate_hz)?;
+    self.update(new_hz);
+    self.refresh_children();
+    Ok(new_hz)
+  }
+
+  // Re-reads the rate from the backend, notifying on change.
+  pub fn refresh(&self) -> Result<u32, ErrorKind> {
+    let rate_hz = (ops().get_rate)(self.id())?;
+    self.update(rate_hz);
+    Ok(rate_hz)
+  }
+
+  pub fn add_notifier(&self, notifier: Notifier) -> Result<(), ErrorKind> {
+    node(self.index)
+      .notifiers
+      .try_push(notifier)
+      .map_err(|_| ErrorKind::StorageFull)
+  }
+
+  fn update(&self, new_hz: u32) {
+    let node = node(self.index);
+    let old_hz = node.rate_hz;
+    node.rate_hz = new_hz;
+    if old_hz == new_hz {
+      return;
+    }
+    log::debug!("{}: {} Hz -> {} Hz", self.name(), old_hz, new_hz);
+    // Notifiers may use the clock, don't hold on to the node.
+    let notifiers = node.notifiers.clone();
+    let change = RateChange {
+      clock: *self,
+      old_hz,
+      new_hz,
+    };
+    for notifier in notifiers {
+      notifier(&change);
+    }
+  }
+
+  fn refresh_children(&self) {
+    let count = unsafe { TREE.len() };
+    for index in 0..count {
+      if node(index).parent == Some(self.index) {
+        let child = Clk { index };
+        if child.refresh().is_ok() {
+          child.refresh_children();
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod clk_test;
@@ -7,7 +7,7 @@ use crate::io::mailbox::Message;
 // Clock IDs
 #[repr(u32)]
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ClockId {
   Emmc = 0x1,
   Uart = 0x2,
@@ -26,6 +26,29 @@ pub enum ClockId {
 }
 
 impl ClockId {
+  pub const ALL: [ClockId; 14] = [
+    ClockId::Emmc,
+    ClockId::Uart,
+    ClockId::Arm,
+    ClockId::Core,
+    ClockId::V3d,
+    ClockId::H264,
+    ClockId::Isp,
+    ClockId::Sdram,
+    ClockId::Pixel,
+    ClockId::Pwm,
+    ClockId::Hevc,
+    ClockId::Emmc2,
+    ClockId::M2mc,
+    ClockId::PixelBvb,
+  ];
+
+  pub fn from_u32(id: u32) -> Option<ClockId> {
+    ClockId::ALL
+      .into_iter()
+      .find(|clock_id| *clock_id as u32 == id)
+  }
+
   pub fn as_str(&self) -> &str {
     match self {
       ClockId::Emmc => "Emmc",
@@ -74,14 +97,11 @@ pub fn get_clock_info(clock_id: ClockId) -> Result<ClockInfo, ErrorKind> {
   }
   .into();
 
-  let message = mailbox::send(
-    Message::<
-      { GetClockRate::Tag::MESSAGE_LEN + GetClockState::Tag::MESSAGE_LEN },
-    >::builder()
-    .add_tag(&clock_rate_tag)
-    .add_tag(&clock_state_tag)
-    .build(),
-  );
+  let mut message = Message::new();
+  message
+    .add_tag(&clock_rate_tag)?
+    .add_tag(&clock_state_tag)?;
+  mailbox::send(&mut message)?;
 
   match GetClockRate::read_response(&message) {
     Ok(response) => {
@@ -0,0 +1,16 @@
+use crate::arch::arm64::asm;
+use crate::io::dma;
+
+pub fn initialize(bus_alias: u32) {
+  assert!(
+    asm::cache::dcache_line_size() <= dma::CACHE_LINE_ALIGN,
+    "Cache line larger than CACHE_LINE_ALIGN"
+  );
+  dma::register(
+    dma::Ops {
+      clean: asm::cache::clean_range,
+      invalidate: asm::cache::invalidate_range,
+    },
+    bus_alias,
+  );
+}
@@ -0,0 +1,50 @@
+use super::*;
+use std::sync::Mutex;
+
+// Ops are global, run the tests one at a time.
+static LOCK: Mutex<()> = Mutex::new(());
+static CALLS: Mutex<Vec<(&'static str, usize, usize)>> = Mutex::new(Vec::new());
+
+fn clean_mock(start: usize, size: usize) {
+  CALLS.lock().unwrap().push(("clean", start, size));
+}
+
+fn invalidate_mock(start: usize, size: usize) {
+  CALLS.lock().unwrap().push(("invalidate", start, size));
+}
+
+fn setup() -> std::sync::MutexGuard<'static, ()> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  register(
+    Ops {
+      clean: clean_mock,
+      invalidate: invalidate_mock,
+    },
+    0xC000_0000,
+  );
+  CALLS.lock().unwrap().clear();
+  guard
+}
+
+#[test]
+fn test_bus_address_translation() {
+  let _guard = setup();
+  assert_eq!(phys_to_bus(0x0008_0000), 0xC008_0000);
+  assert_eq!(bus_to_phys(0xC008_0000), 0x0008_0000);
+  // Framebuffer addresses from the firmware may use any alias.
+  assert_eq!(bus_to_phys(0x4E00_0000), 0x0E00_0000);
+  assert_eq!(bus_to_phys(phys_to_bus(0x3B40_0000)), 0x3B40_0000);
+}
+
+#[test]
+fn test_cache_maintenance_range() {
+  let _guard = setup();
+  #[repr(C, align(64))]
+  struct Buffer([u8; 100]);
+  let buf = Buffer([0; 100]);
+  clean(&buf);
+  invalidate(&buf.0[..10]);
+  let start = &buf as *const Buffer as usize;
+  let calls = CALLS.lock().unwrap().clone();
+  assert_eq!(calls, [("clean", start, 128), ("invalidate", start, 10)]);
+}
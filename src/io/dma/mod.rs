@@ -0,0 +1,88 @@
+// Helpers for memory shared with other bus masters (VideoCore, DMA engines).
+//
+// Peripherals see memory through bus addresses: the ARM physical address
+// plus an alias selecting how the VideoCore L2 cache is used. On the Pi 2/3
+// 0xC000_0000 is the uncached alias.
+// https://datasheets.raspberrypi.com/bcm2835/bcm2835-peripherals.pdf
+// (1.2.3 ARM physical addresses, 1.2.4 Bus addresses)
+//
+// The ARM data cache is not coherent with these masters. Buffers have to be
+// cleaned before a master reads them and invalidated before the CPU reads
+// what a master wrote. Keep shared buffers cache line aligned and sized, see
+// CACHE_LINE_ALIGN, so invalidation does not discard neighbouring data.
+
+use crate::mm;
+
+#[cfg(target_arch = "aarch64")]
+pub mod arm64_cache;
+
+// Upper bound of the data cache line size of supported CPUs.
+pub const CACHE_LINE_ALIGN: usize = 64;
+// Bits of a bus address selecting the alias.
+const BUS_ALIAS_MASK: u32 = 0xC000_0000;
+
+pub struct Ops {
+  // Write back [start, start + size) to memory.
+  pub clean: fn(start: usize, size: usize),
+  // Drop cached copies of [start, start + size).
+  pub invalidate: fn(start: usize, size: usize),
+}
+
+static mut OPS: core::mem::MaybeUninit<Ops> =
+  core::mem::MaybeUninit::<Ops>::uninit();
+static mut BUS_ALIAS: u32 = 0;
+// This will be set to 0 during bss zero-ing.
+static mut SET: bool = false;
+
+pub fn register(ops: Ops, bus_alias: u32) {
+  assert!(bus_alias & !BUS_ALIAS_MASK == 0, "Invalid bus alias");
+  unsafe {
+    OPS = core::mem::MaybeUninit::<Ops>::new(ops);
+    BUS_ALIAS = bus_alias;
+    SET = true;
+  }
+}
+
+pub fn phys_to_bus(phys: u64) -> u32 {
+  unsafe {
+    assert!(SET, "DMA not set");
+    assert!(
+      phys < BUS_ALIAS_MASK as u64,
+      "Address not reachable from bus"
+    );
+    phys as u32 | BUS_ALIAS
+  }
+}
+
+pub fn bus_to_phys(bus: u32) -> u64 {
+  (bus & !BUS_ALIAS_MASK) as u64
+}
+
+pub fn virt_to_bus<T>(ptr: *const T) -> u32 {
+  phys_to_bus(mm::virt_to_phys(ptr as u64))
+}
+
+// Call before a bus master reads the buffer.
+pub fn clean<T: ?Sized>(buf: &T) {
+  unsafe {
+    assert!(SET, "DMA not set");
+    (OPS.assume_init_ref().clean)(
+      buf as *const T as *const u8 as usize,
+      core::mem::size_of_val(buf),
+    );
+  }
+}
+
+// Call after a bus master wrote the buffer, before reading it.
+pub fn invalidate<T: ?Sized>(buf: &T) {
+  unsafe {
+    assert!(SET, "DMA not set");
+    (OPS.assume_init_ref().invalidate)(
+      buf as *const T as *const u8 as usize,
+      core::mem::size_of_val(buf),
+    );
+  }
+}
+
+#[cfg(test)]
+mod dma_test;
@@ -1,4 +1,5 @@
 use crate::common::synchronization;
+use crate::interrupt;
 use crate::io::gpio;
 use crate::io::mmio;
 
@@ -36,6 +37,19 @@ impl Reg {
   const GPCLR0: u64 = Reg::BASE + 0x28; // GPIO Pin Output Clear 0
   const GPCLR1: u64 = Reg::BASE + 0x2C; // GPIO Pin Output Clear 1
 
+  const GPLEV0: u64 = Reg::BASE + 0x34; // GPIO Pin Level 0
+  const GPLEV1: u64 = Reg::BASE + 0x38; // GPIO Pin Level 1
+  const GPEDS0: u64 = Reg::BASE + 0x40; // GPIO Pin Event Detect Status 0
+  const GPEDS1: u64 = Reg::BASE + 0x44; // GPIO Pin Event Detect Status 1
+
+  // Event detect enable registers, each followed by its bank 1 register.
+  const GPREN0: u64 = Reg::BASE + 0x4C; // Rising Edge Detect Enable 0
+  const GPFEN0: u64 = Reg::BASE + 0x58; // Falling Edge Detect Enable 0
+  const GPHEN0: u64 = Reg::BASE + 0x64; // High Detect Enable 0
+  const GPLEN0: u64 = Reg::BASE + 0x70; // Low Detect Enable 0
+  const GPAREN0: u64 = Reg::BASE + 0x7C; // Async. Rising Edge Detect 0
+  const GPAFEN0: u64 = Reg::BASE + 0x88; // Async. Falling Edge Detect 0
+
   const GPPUD: u64 = Reg::BASE + 0x94; // GPIO Pin Pull-up/down Enable
   const GPPUDCLK0: u64 = Reg::BASE + 0x98; // GPIO Pin Pull-up/down Enable Clock 0
   const GPPUDCLK1: u64 = Reg::BASE + 0x9C; // GPIO Pin Pull-up/down Enable Clock 1
@@ -163,6 +177,43 @@ fn output_clear(mut gpios: u64) {
   mmio::write(Reg::GPCLR1, gpios_1);
 }
 
+fn read_level() -> u64 {
+  let level_0 = mmio::read(Reg::GPLEV0) as u64;
+  let level_1 = mmio::read(Reg::GPLEV1) as u64;
+  ((level_1 << 32) | level_0) & ((1 << NUM_GPIOS) - 1)
+}
+
+fn set_event_detect(mut gpios: u64, detect: gpio::Detect, enable: bool) {
+  gpios = gpios & ((1 << NUM_GPIOS) - 1);
+  let bank_0 = match detect {
+    gpio::Detect::RisingEdge => Reg::GPREN0,
+    gpio::Detect::FallingEdge => Reg::GPFEN0,
+    gpio::Detect::High => Reg::GPHEN0,
+    gpio::Detect::Low => Reg::GPLEN0,
+    gpio::Detect::AsyncRisingEdge => Reg::GPAREN0,
+    gpio::Detect::AsyncFallingEdge => Reg::GPAFEN0,
+  };
+  for (reg, bits) in
+    [(bank_0, gpios as u32), (bank_0 + 4, (gpios >> 32) as u32)]
+  {
+    let val = mmio::read(reg);
+    mmio::write(reg, if enable { val | bits } else { val & !bits });
+  }
+}
+
+fn read_events() -> u64 {
+  let events_0 = mmio::read(Reg::GPEDS0) as u64;
+  let events_1 = mmio::read(Reg::GPEDS1) as u64;
+  ((events_1 << 32) | events_0) & ((1 << NUM_GPIOS) - 1)
+}
+
+// Writing 1 clears the event.
+fn clear_events(mut gpios: u64) {
+  gpios = gpios & ((1 << NUM_GPIOS) - 1);
+  mmio::write(Reg::GPEDS0, gpios as u32);
+  mmio::write(Reg::GPEDS1, (gpios >> 32) as u32);
+}
+
 // Initialize device driver
 pub fn initialize() {
   gpio::register_device(gpio::Ops {
@@ -170,5 +221,19 @@ pub fn initialize() {
     output_clear,
     set_pull_mode,
     set_function,
+    read_level,
+    set_event_detect,
+    read_events,
+    clear_events,
   });
 }
+
+// Serves events from the IRQ line of all banks (gpio_int[3]).
+pub fn initialize_interrupt(irq_channel: interrupt::IrqChannel) {
+  interrupt::set_handler(irq_channel, gpio::handle_events);
+  interrupt::unmask_interrupt(irq_channel);
+}
+
+#[cfg(test)]
+#[path = "bcm2837_gpio_test.rs"]
+mod bcm2837_gpio_test;
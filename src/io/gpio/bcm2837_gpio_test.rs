@@ -0,0 +1,72 @@
+use super::*;
+use crate::io::mmio::mock::{self, Access};
+
+#[test]
+fn test_set_function_read_modify_write() {
+  let _guard = mock::initialize();
+  // Pin 13 is an output and must stay one.
+  mock::set(Reg::GPFSEL1, 0b001 << 9);
+  set_function((1 << 14) | (1 << 15), gpio::Function::Func0);
+
+  // All banks are read, then written back.
+  let mut expected: Vec<Access> = Reg::GPFSEL_BANK
+    .iter()
+    .map(|reg| Access::Read(*reg, 0))
+    .collect();
+  expected[1] = Access::Read(Reg::GPFSEL1, 0b001 << 9);
+  for reg in Reg::GPFSEL_BANK {
+    let data = if reg == Reg::GPFSEL1 {
+      (0b001 << 9) | (0b100 << 12) | (0b100 << 15)
+    } else {
+      0
+    };
+    expected.push(Access::Write(reg, data));
+  }
+  assert_eq!(mock::accesses(), expected);
+}
+
+#[test]
+fn test_output_and_level() {
+  let _guard = mock::initialize();
+  output_set((1 << 5) | (1 << 40));
+  output_clear(1 << 53);
+  assert_eq!(
+    mock::writes(),
+    [
+      (Reg::GPSET0, 1 << 5),
+      (Reg::GPSET1, 1 << 8),
+      (Reg::GPCLR0, 0),
+      (Reg::GPCLR1, 1 << 21),
+    ]
+  );
+
+  mock::set(Reg::GPLEV0, 1 << 17);
+  // Bits past pin 53 are ignored
+  mock::set(Reg::GPLEV1, (1 << 8) | (1 << 31));
+  assert_eq!(read_level(), (1 << 17) | (1 << 40));
+}
+
+#[test]
+fn test_event_detect() {
+  let _guard = mock::initialize();
+  mock::set(Reg::GPFEN0, 1 << 3);
+  set_event_detect((1 << 17) | (1 << 33), gpio::Detect::FallingEdge, true);
+  assert_eq!(mock::get(Reg::GPFEN0), (1 << 3) | (1 << 17));
+  assert_eq!(mock::get(Reg::GPFEN0 + 4), 1 << 1);
+  set_event_detect(1 << 3, gpio::Detect::FallingEdge, false);
+  assert_eq!(mock::get(Reg::GPFEN0), 1 << 17);
+
+  mock::clear_log();
+  set_event_detect(1 << 2, gpio::Detect::AsyncRisingEdge, true);
+  assert_eq!(
+    mock::writes(),
+    [(Reg::GPAREN0, 1 << 2), (Reg::GPAREN0 + 4, 0)]
+  );
+
+  // Write 1 to clear
+  mock::set(Reg::GPEDS0, 1 << 17);
+  assert_eq!(read_events(), 1 << 17);
+  mock::clear_log();
+  clear_events(1 << 17);
+  assert_eq!(mock::writes(), [(Reg::GPEDS0, 1 << 17), (Reg::GPEDS1, 0)]);
+}
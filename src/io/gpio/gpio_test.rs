@@ -0,0 +1,148 @@
+use super::*;
+use std::sync::Mutex;
+
+// Handlers and ops are global, run the tests one at a time.
+static LOCK: Mutex<()> = Mutex::new(());
+// Mock controller: levels, pending events and enabled detectors.
+static LEVELS: Mutex<u64> = Mutex::new(0);
+static EVENTS: Mutex<u64> = Mutex::new(0);
+pub(super) static FUNCTIONS: Mutex<Vec<(u64, Function)>> =
+  Mutex::new(Vec::new());
+static DETECTS: Mutex<Vec<(u64, Detect)>> = Mutex::new(Vec::new());
+static CALLS: Mutex<Vec<(u8, bool)>> = Mutex::new(Vec::new());
+
+fn noop(_: u64) {}
+fn set_pull_mode_mock(_: u64, _: PullMode) {}
+fn set_function_mock(gpios: u64, function: Function) {
+  FUNCTIONS.lock().unwrap().push((gpios, function));
+}
+
+fn read_level_mock() -> u64 {
+  *LEVELS.lock().unwrap()
+}
+
+fn set_event_detect_mock(gpios: u64, detect: Detect, enable: bool) {
+  let mut detects = DETECTS.lock().unwrap();
+  if enable {
+    detects.push((gpios, detect));
+  } else {
+    detects.retain(|d| *d != (gpios, detect));
+  }
+}
+
+fn read_events_mock() -> u64 {
+  *EVENTS.lock().unwrap()
+}
+
+fn clear_events_mock(gpios: u64) {
+  *EVENTS.lock().unwrap() &= !gpios;
+}
+
+fn callback_mock(pin: u8, level: bool) {
+  CALLS.lock().unwrap().push((pin, level));
+}
+
+// Also used by the pin tests.
+pub(super) fn setup() -> std::sync::MutexGuard<'static, ()> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  register_device(Ops {
+    output_set: noop,
+    output_clear: noop,
+    set_pull_mode: set_pull_mode_mock,
+    set_function: set_function_mock,
+    read_level: read_level_mock,
+    set_event_detect: set_event_detect_mock,
+    read_events: read_events_mock,
+    clear_events: clear_events_mock,
+  });
+  unsafe {
+    EVENT_HANDLERS = [None; MAX_GPIOS];
+    pin::CLAIMS = [None; MAX_GPIOS];
+  }
+  *LEVELS.lock().unwrap() = 0;
+  *EVENTS.lock().unwrap() = 0;
+  DETECTS.lock().unwrap().clear();
+  FUNCTIONS.lock().unwrap().clear();
+  CALLS.lock().unwrap().clear();
+  guard
+}
+
+#[test]
+fn test_gpio_read() {
+  let _guard = setup();
+  *LEVELS.lock().unwrap() = (1 << 17) | (1 << 40);
+  assert_eq!(read(1 << 17), 1 << 17);
+  assert_eq!(read(1 << 18), 0);
+  assert_eq!(read(u64::MAX), (1 << 17) | (1 << 40));
+}
+
+#[test]
+fn test_gpio_on_event() {
+  let _guard = setup();
+  // Stale event, dropped when the handler is set.
+  *EVENTS.lock().unwrap() = 1 << 17;
+  on_event(17, EdgeKind::Both, callback_mock).unwrap();
+  assert_eq!(*EVENTS.lock().unwrap(), 0);
+  assert_eq!(
+    *DETECTS.lock().unwrap(),
+    [
+      (1 << 17, Detect::RisingEdge),
+      (1 << 17, Detect::FallingEdge)
+    ]
+  );
+  assert!(matches!(
+    on_event(17, EdgeKind::Rising, callback_mock),
+    Err(ErrorKind::ResourceBusy)
+  ));
+  assert!(matches!(
+    on_event(64, EdgeKind::Rising, callback_mock),
+    Err(ErrorKind::InvalidInput)
+  ));
+
+  *LEVELS.lock().unwrap() = 1 << 17;
+  *EVENTS.lock().unwrap() = (1 << 17) | (1 << 5);
+  handle_events();
+  // Pin 5 has no handler, its event is cleared anyway.
+  assert_eq!(*EVENTS.lock().unwrap(), 0);
+  assert_eq!(*CALLS.lock().unwrap(), [(17, true)]);
+
+  remove_event(17).unwrap();
+  assert!(DETECTS.lock().unwrap().is_empty());
+  assert!(matches!(remove_event(17), Err(ErrorKind::NotFound)));
+}
+
+#[test]
+fn test_gpio_level_event_is_one_shot() {
+  let _guard = setup();
+  on_event(4, EdgeKind::Low, callback_mock).unwrap();
+  dispatch(1 << 4, 0, None);
+  dispatch(1 << 4, 0, None);
+  assert_eq!(*CALLS.lock().unwrap(), [(4, false)]);
+  assert!(DETECTS.lock().unwrap().is_empty());
+  // Re-arm
+  on_event(4, EdgeKind::Low, callback_mock).unwrap();
+  assert_eq!(*DETECTS.lock().unwrap(), [(1 << 4, Detect::Low)]);
+}
+
+#[test]
+fn test_gpio_rearm_level_event_as_edge() {
+  let _guard = setup();
+  on_event(4, EdgeKind::High, callback_mock).unwrap();
+  // The armed level detector goes with the old handler.
+  on_event(4, EdgeKind::Rising, callback_mock).unwrap();
+  assert_eq!(*DETECTS.lock().unwrap(), [(1 << 4, Detect::RisingEdge)]);
+}
+
+#[test]
+fn test_gpio_debounce() {
+  let _guard = setup();
+  on_event_debounced(6, EdgeKind::Falling, 10_000, callback_mock).unwrap();
+  dispatch(1 << 6, 0, Some(100_000));
+  // Bounces
+  dispatch(1 << 6, 0, Some(101_000));
+  dispatch(1 << 6, 0, Some(109_999));
+  dispatch(1 << 6, 0, Some(110_000));
+  // Without a timer every event is accepted.
+  dispatch(1 << 6, 0, None);
+  assert_eq!(*CALLS.lock().unwrap(), [(6, false), (6, false), (6, false)]);
+}
@@ -23,15 +23,36 @@
 //! output_clear(1 << 5);
 //! ```
 //!
+//! Drivers should own their pins through `claim`, which hands out typed
+//! handles and records the owner for `status`:
+//! ```
+//! let txd = claim::<14>("uart0")?.into_uart0_txd();
+//! let led = claim_any(27, "led")?.into_output();
+//! led.set_high();
+//! ```
+//!
 //! # Functionality
 //! - **Pull Mode**: Set the pull-up/pull-down mode for GPIO pins to control their default state.
 //! - **Function Selection**: Configure GPIO pins to act as input, output, or to be associated with alternate functions.
 //! - **Output Control**: Set or clear GPIO output levels.
+//! - **Input**: Read GPIO levels.
+//! - **Events**: Run a callback on edges or levels of a pin, optionally
+//!   debounced. See `on_event`.
 //!
 //! # Implementation Details
 //! GPIO pins are controlled by manipulating bits within various hardware registers. Each register manages a specific range of pins.
 
 pub mod bcm2837_gpio;
+mod pin;
+
+pub use pin::*;
+
+use crate::common::error::ErrorKind;
+use crate::log;
+use crate::timer;
+
+// Pins are passed as u64 bitmasks.
+const MAX_GPIOS: usize = 64;
 
 static mut OPS: core::mem::MaybeUninit<Ops> =
   core::mem::MaybeUninit::<Ops>::uninit();
@@ -43,10 +64,84 @@ struct Ops {
   output_clear: fn(u64),
   set_pull_mode: fn(u64, PullMode),
   set_function: fn(u64, Function),
+  read_level: fn() -> u64,
+  set_event_detect: fn(u64, Detect, bool),
+  // Pins with a detected event
+  read_events: fn() -> u64,
+  clear_events: fn(u64),
+}
+
+// This is run in IRQ context. Don't do too much inside!
+static mut EVENT_HANDLERS: [Option<EventHandler>; MAX_GPIOS] =
+  [None; MAX_GPIOS];
+
+/// Hardware event detectors of a pin.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Detect {
+  /// Synchronous rising edge (GPREN)
+  RisingEdge,
+  /// Synchronous falling edge (GPFEN)
+  FallingEdge,
+  /// High level (GPHEN)
+  High,
+  /// Low level (GPLEN)
+  Low,
+  /// Asynchronous rising edge, not sampled by the system clock (GPAREN)
+  AsyncRisingEdge,
+  /// Asynchronous falling edge (GPAFEN)
+  AsyncFallingEdge,
+}
+
+/// Events `on_event` can wait for.
+///
+/// Level events fire once: the detector is disabled after the callback, call
+/// `on_event` again to re-arm it. Otherwise a held level would raise the IRQ
+/// forever.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EdgeKind {
+  Rising,
+  Falling,
+  Both,
+  High,
+  Low,
+  AsyncRising,
+  AsyncFalling,
+}
+
+impl EdgeKind {
+  fn detects(&self) -> &'static [Detect] {
+    match self {
+      EdgeKind::Rising => &[Detect::RisingEdge],
+      EdgeKind::Falling => &[Detect::FallingEdge],
+      EdgeKind::Both => &[Detect::RisingEdge, Detect::FallingEdge],
+      EdgeKind::High => &[Detect::High],
+      EdgeKind::Low => &[Detect::Low],
+      EdgeKind::AsyncRising => &[Detect::AsyncRisingEdge],
+      EdgeKind::AsyncFalling => &[Detect::AsyncFallingEdge],
+    }
+  }
+
+  fn is_level(&self) -> bool {
+    matches!(self, EdgeKind::High | EdgeKind::Low)
+  }
+}
+
+/// Called in IRQ context with the pin and its level when the event was
+/// handled.
+pub type EventCallback = fn(pin: u8, level: bool);
+
+#[derive(Clone, Copy)]
+struct EventHandler {
+  kind: EdgeKind,
+  callback: EventCallback,
+  // Events closer than this to the last accepted one are dropped.
+  debounce_us: u64,
+  last_us: Option<u64>,
 }
 
 // Pull up/down control mode.
 #[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum PullMode {
   // Off – disable pull-up/down
   Disabled,
@@ -60,6 +155,7 @@ pub enum PullMode {
 ///
 /// Different modes a GPIO pin can be set to.
 #[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Function {
   Input,
   Output,
@@ -147,9 +243,150 @@ pub fn output_clear(gpios: u64) {
   }
 }
 
+/// Reads the level of the specified GPIO pins, 1 for high.
+///
+/// # Examples
+/// ```
+/// // Is GPIO 17 high?
+/// let high = read(1 << 17) != 0;
+/// ```
+#[inline(always)]
+pub fn read(gpios: u64) -> u64 {
+  unsafe {
+    assert!(SET, "GPIO handler not set");
+    (OPS.assume_init_ref().read_level)() & gpios
+  }
+}
+
+/// Enables or disables an event detector of the specified GPIO pins.
+/// `on_event` manages the detectors itself, this is for polling with
+/// `take_events`.
+pub fn set_event_detect(gpios: u64, detect: Detect, enable: bool) {
+  unsafe {
+    assert!(SET, "GPIO handler not set");
+    (OPS.assume_init_ref().set_event_detect)(gpios, detect, enable);
+  }
+}
+
+/// Returns and clears the detected events of the specified GPIO pins.
+pub fn take_events(gpios: u64) -> u64 {
+  unsafe {
+    assert!(SET, "GPIO handler not set");
+    let ops = OPS.assume_init_ref();
+    let events = (ops.read_events)() & gpios;
+    (ops.clear_events)(events);
+    events
+  }
+}
+
+/// Runs `callback` from the GPIO IRQ when `kind` happens on `pin`. The pin
+/// should be configured as input.
+///
+/// # Examples
+/// ```
+/// fn on_button(pin: u8, level: bool) {}
+/// on_event(17, EdgeKind::Falling, on_button)?;
+/// ```
+pub fn on_event(
+  pin: u8,
+  kind: EdgeKind,
+  callback: EventCallback,
+) -> Result<(), ErrorKind> {
+  on_event_debounced(pin, kind, 0, callback)
+}
+
+/// Same as `on_event`, dropping events less than `debounce_us` after the
+/// last accepted one. Uses the timer uptime, no debouncing without a timer.
+pub fn on_event_debounced(
+  pin: u8,
+  kind: EdgeKind,
+  debounce_us: u64,
+  callback: EventCallback,
+) -> Result<(), ErrorKind> {
+  if pin as usize >= MAX_GPIOS {
+    return Err(ErrorKind::InvalidInput);
+  }
+  unsafe {
+    let handler = &mut EVENT_HANDLERS[pin as usize];
+    // Re-arming a level event is allowed, also as another kind.
+    if let Some(old) = *handler {
+      if !old.kind.is_level() {
+        return Err(ErrorKind::ResourceBusy);
+      }
+      for detect in old.kind.detects() {
+        set_event_detect(1 << pin, *detect, false);
+      }
+    }
+    *handler = Some(EventHandler {
+      kind,
+      callback,
+      debounce_us,
+      last_us: None,
+    });
+  }
+  // Drop events detected before the handler was set.
+  take_events(1 << pin);
+  for detect in kind.detects() {
+    set_event_detect(1 << pin, *detect, true);
+  }
+  Ok(())
+}
+
+/// Disables the events of `pin` and removes its callback.
+pub fn remove_event(pin: u8) -> Result<(), ErrorKind> {
+  if pin as usize >= MAX_GPIOS {
+    return Err(ErrorKind::InvalidInput);
+  }
+  let handler = unsafe { EVENT_HANDLERS[pin as usize].take() };
+  let handler = handler.ok_or(ErrorKind::NotFound)?;
+  for detect in handler.kind.detects() {
+    set_event_detect(1 << pin, *detect, false);
+  }
+  take_events(1 << pin);
+  Ok(())
+}
+
+/// Serves pending events. Called by the driver's IRQ handler.
+pub fn handle_events() {
+  let events = take_events(u64::MAX);
+  let levels = read(events);
+  dispatch(events, levels, timer::uptime_us());
+}
+
+// Runs the callbacks of `events`.
+fn dispatch(events: u64, levels: u64, now_us: Option<u64>) {
+  for (pin, slot) in unsafe { EVENT_HANDLERS.iter_mut() }.enumerate() {
+    if events & (1 << pin) == 0 {
+      continue;
+    }
+    let Some(handler) = slot.as_mut() else {
+      log::warn!("Unhandled GPIO {} event", pin);
+      continue;
+    };
+    if let (Some(now), Some(last)) = (now_us, handler.last_us) {
+      if now.saturating_sub(last) < handler.debounce_us {
+        continue;
+      }
+    }
+    handler.last_us = now_us;
+    let callback = handler.callback;
+    if handler.kind.is_level() {
+      // Before the callback, which may re-arm it.
+      for detect in handler.kind.detects() {
+        set_event_detect(1 << pin, *detect, false);
+      }
+      *slot = None;
+    }
+    callback(pin as u8, levels & (1 << pin) != 0);
+  }
+}
+
 fn register_device(ops: Ops) {
   unsafe {
     OPS = core::mem::MaybeUninit::<Ops>::new(ops);
     SET = true;
   };
 }
+
+#[cfg(test)]
+mod gpio_test;
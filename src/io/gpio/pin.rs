@@ -0,0 +1,303 @@
+// Typed GPIO pin handles with ownership tracking.
+//
+// A pin is owned by whoever claimed it until released. The mode is part of
+// the handle type, so e.g. only output pins can be driven. Pins whose number
+// is known at compile time are `Pin<N, Mode>` and get the alternate
+// functions of that pin as methods. `AnyPin<Mode>` is for numbers known at
+// run time only.
+//
+// Dropping a handle keeps the claim, call release() to give the pin back.
+
+use super::{EdgeKind, EventCallback, Function, PullMode, MAX_GPIOS};
+use crate::common::error::ErrorKind;
+use arrayvec::ArrayVec;
+use core::marker::PhantomData;
+
+// Modes
+pub struct Unconfigured;
+pub struct Input;
+pub struct Output;
+pub struct Alt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PinMode {
+  Unconfigured,
+  Input,
+  Output,
+  // Function and signal name, e.g. (Func0, "TXD0")
+  Alt(Function, &'static str),
+}
+
+impl core::fmt::Display for PinMode {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      PinMode::Unconfigured => write!(f, "-"),
+      PinMode::Input => write!(f, "input"),
+      PinMode::Output => write!(f, "output"),
+      PinMode::Alt(function, signal) => write!(f, "{:?} {}", function, signal),
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct Claim {
+  owner: &'static str,
+  mode: PinMode,
+}
+
+pub struct PinStatus {
+  pub number: u8,
+  pub owner: &'static str,
+  pub mode: PinMode,
+}
+
+pub(super) static mut CLAIMS: [Option<Claim>; MAX_GPIOS] = [None; MAX_GPIOS];
+
+fn take(number: u8, owner: &'static str) -> Result<(), ErrorKind> {
+  if number as usize >= MAX_GPIOS {
+    return Err(ErrorKind::InvalidInput);
+  }
+  let claim = unsafe { &mut CLAIMS[number as usize] };
+  if claim.is_some() {
+    return Err(ErrorKind::ResourceBusy);
+  }
+  *claim = Some(Claim {
+    owner,
+    mode: PinMode::Unconfigured,
+  });
+  Ok(())
+}
+
+fn set_mode(number: u8, mode: PinMode) {
+  let function = match mode {
+    PinMode::Unconfigured => return,
+    PinMode::Input => Function::Input,
+    PinMode::Output => Function::Output,
+    PinMode::Alt(function, _) => function,
+  };
+  super::set_function(1 << number, function);
+  unsafe {
+    if let Some(claim) = CLAIMS[number as usize].as_mut() {
+      claim.mode = mode;
+    }
+  }
+}
+
+fn release(number: u8) {
+  unsafe { CLAIMS[number as usize] = None };
+}
+
+/// Claims pin `N` for `owner`. ResourceBusy if it is already owned.
+pub fn claim<const N: u8>(
+  owner: &'static str,
+) -> Result<Pin<N, Unconfigured>, ErrorKind> {
+  take(N, owner)?;
+  Ok(Pin { _mode: PhantomData })
+}
+
+/// Claims pin `number` for `owner`. ResourceBusy if it is already owned.
+pub fn claim_any(
+  number: u8,
+  owner: &'static str,
+) -> Result<AnyPin<Unconfigured>, ErrorKind> {
+  take(number, owner)?;
+  Ok(AnyPin {
+    number,
+    _mode: PhantomData,
+  })
+}
+
+/// Owner of pin `number`, if claimed.
+pub fn owner(number: u8) -> Option<&'static str> {
+  unsafe { CLAIMS.get(number as usize)?.map(|claim| claim.owner) }
+}
+
+/// Claimed pins, in pin order.
+pub fn status() -> ArrayVec<PinStatus, MAX_GPIOS> {
+  unsafe {
+    CLAIMS
+      .iter()
+      .enumerate()
+      .filter_map(|(number, claim)| {
+        claim.map(|claim| PinStatus {
+          number: number as u8,
+          owner: claim.owner,
+          mode: claim.mode,
+        })
+      })
+      .collect()
+  }
+}
+
+pub struct Pin<const N: u8, Mode> {
+  _mode: PhantomData<Mode>,
+}
+
+pub struct AnyPin<Mode> {
+  number: u8,
+  _mode: PhantomData<Mode>,
+}
+
+impl<const N: u8, Mode> Pin<N, Mode> {
+  fn into_mode<NewMode>(self, mode: PinMode) -> Pin<N, NewMode> {
+    set_mode(N, mode);
+    Pin { _mode: PhantomData }
+  }
+
+  // Used by the alternate function tables below.
+  fn into_alt(self, function: Function, signal: &'static str) -> Pin<N, Alt> {
+    self.into_mode(PinMode::Alt(function, signal))
+  }
+
+  /// Forgets the pin number in the type.
+  pub fn erase(self) -> AnyPin<Mode> {
+    AnyPin {
+      number: N,
+      _mode: PhantomData,
+    }
+  }
+}
+
+impl<Mode> AnyPin<Mode> {
+  fn into_mode<NewMode>(self, mode: PinMode) -> AnyPin<NewMode> {
+    set_mode(self.number, mode);
+    AnyPin {
+      number: self.number,
+      _mode: PhantomData,
+    }
+  }
+
+  /// Any function, unchecked against the pin's alternate functions.
+  pub fn into_alt(self, function: Function) -> AnyPin<Alt> {
+    self.into_mode(PinMode::Alt(function, "?"))
+  }
+}
+
+// Methods shared by Pin and AnyPin.
+macro_rules! impl_pin {
+  ([$($generic:tt)*] $any:ty, $input:ty, $output:ty) => {
+    impl<$($generic)* Mode> $any {
+      pub fn number(&self) -> u8 {
+        self.pin_number()
+      }
+
+      pub fn into_input(self) -> $input {
+        self.into_mode(PinMode::Input)
+      }
+
+      pub fn into_output(self) -> $output {
+        self.into_mode(PinMode::Output)
+      }
+
+      pub fn set_pull(&self, mode: PullMode) {
+        super::set_pull_mode(1 << self.pin_number(), mode);
+      }
+
+      /// Gives the pin back. Its configuration is left as is.
+      pub fn release(self) {
+        release(self.pin_number());
+      }
+    }
+
+    impl<$($generic)*> $input {
+      pub fn is_high(&self) -> bool {
+        super::read(1 << self.pin_number()) != 0
+      }
+
+      pub fn on_event(
+        &self,
+        kind: EdgeKind,
+        callback: EventCallback,
+      ) -> Result<(), ErrorKind> {
+        super::on_event(self.pin_number(), kind, callback)
+      }
+
+      pub fn on_event_debounced(
+        &self,
+        kind: EdgeKind,
+        debounce_us: u64,
+        callback: EventCallback,
+      ) -> Result<(), ErrorKind> {
+        super::on_event_debounced(
+          self.pin_number(),
+          kind,
+          debounce_us,
+          callback,
+        )
+      }
+    }
+
+    impl<$($generic)*> $output {
+      pub fn set_high(&self) {
+        super::output_set(1 << self.pin_number());
+      }
+
+      pub fn set_low(&self) {
+        super::output_clear(1 << self.pin_number());
+      }
+
+      pub fn toggle(&self) {
+        if super::read(1 << self.pin_number()) != 0 {
+          self.set_low();
+        } else {
+          self.set_high();
+        }
+      }
+    }
+  };
+}
+
+impl<const N: u8, Mode> Pin<N, Mode> {
+  fn pin_number(&self) -> u8 {
+    N
+  }
+}
+
+impl<Mode> AnyPin<Mode> {
+  fn pin_number(&self) -> u8 {
+    self.number
+  }
+}
+
+impl_pin!([const N: u8,] Pin<N, Mode>, Pin<N, Input>, Pin<N, Output>);
+impl_pin!([] AnyPin<Mode>, AnyPin<Input>, AnyPin<Output>);
+
+// Alternate functions of BCM2837 pins, see BCM2835 ARM Peripherals 6.2.
+macro_rules! alt_functions {
+  ($($n:literal => { $($name:ident: $function:ident $signal:literal),* })*) => {
+    $(
+      impl<Mode> Pin<$n, Mode> {
+        $(
+          pub fn $name(self) -> Pin<$n, Alt> {
+            self.into_alt(Function::$function, $signal)
+          }
+        )*
+      }
+    )*
+  };
+}
+
+alt_functions! {
+  2 => { into_i2c1_sda: Func0 "SDA1" }
+  3 => { into_i2c1_scl: Func0 "SCL1" }
+  4 => { into_gpclk0: Func0 "GPCLK0" }
+  7 => { into_spi0_ce1: Func0 "SPI0_CE1_N" }
+  8 => { into_spi0_ce0: Func0 "SPI0_CE0_N" }
+  9 => { into_spi0_miso: Func0 "SPI0_MISO" }
+  10 => { into_spi0_mosi: Func0 "SPI0_MOSI" }
+  11 => { into_spi0_sclk: Func0 "SPI0_SCLK" }
+  12 => { into_pwm0: Func0 "PWM0" }
+  13 => { into_pwm1: Func0 "PWM1" }
+  14 => { into_uart0_txd: Func0 "TXD0", into_uart1_txd: Func5 "TXD1" }
+  15 => { into_uart0_rxd: Func0 "RXD0", into_uart1_rxd: Func5 "RXD1" }
+  18 => { into_pcm_clk: Func0 "PCM_CLK", into_pwm0_alt5: Func5 "PWM0" }
+  19 => { into_pcm_fs: Func0 "PCM_FS", into_pwm1_alt5: Func5 "PWM1" }
+  20 => { into_pcm_din: Func0 "PCM_DIN" }
+  21 => { into_pcm_dout: Func0 "PCM_DOUT" }
+  32 => { into_uart0_txd_alt3: Func3 "TXD0", into_uart1_txd_alt5: Func5 "TXD1" }
+  33 => { into_uart0_rxd_alt3: Func3 "RXD0", into_uart1_rxd_alt5: Func5 "RXD1" }
+}
+
+#[cfg(test)]
+#[path = "pin_test.rs"]
+mod pin_test;
@@ -0,0 +1,57 @@
+use super::*;
+use crate::io::gpio::gpio_test::{setup, FUNCTIONS};
+
+#[test]
+fn test_pin_claim_ownership() {
+  let _guard = setup();
+  let txd = claim::<14>("uart0").unwrap();
+  assert!(matches!(claim::<14>("other"), Err(ErrorKind::ResourceBusy)));
+  assert!(matches!(
+    claim_any(14, "other"),
+    Err(ErrorKind::ResourceBusy)
+  ));
+  assert!(matches!(
+    claim_any(64, "other"),
+    Err(ErrorKind::InvalidInput)
+  ));
+  assert_eq!(owner(14), Some("uart0"));
+  assert_eq!(owner(15), None);
+
+  txd.release();
+  assert_eq!(owner(14), None);
+  claim_any(14, "other").unwrap();
+  assert_eq!(owner(14), Some("other"));
+}
+
+#[test]
+fn test_pin_modes() {
+  let _guard = setup();
+  let txd = claim::<14>("uart0").unwrap().into_uart0_txd();
+  let led = claim_any(27, "led").unwrap().into_output();
+  let button = claim::<17>("button").unwrap().into_input();
+  assert_eq!(led.number(), 27);
+  assert_eq!(button.erase().number(), 17);
+  assert_eq!(txd.number(), 14);
+
+  assert_eq!(
+    *FUNCTIONS.lock().unwrap(),
+    [
+      (1 << 14, Function::Func0),
+      (1 << 27, Function::Output),
+      (1 << 17, Function::Input),
+    ]
+  );
+  let status = status();
+  let modes: Vec<_> = status
+    .iter()
+    .map(|pin| (pin.number, pin.owner, pin.mode))
+    .collect();
+  assert_eq!(
+    modes,
+    [
+      (14, "uart0", PinMode::Alt(Function::Func0, "TXD0")),
+      (17, "button", PinMode::Input),
+      (27, "led", PinMode::Output),
+    ]
+  );
+}
@@ -0,0 +1,178 @@
+use super::queue::{Queue, Ticket};
+use crate::common::error::ErrorKind;
+use crate::interrupt;
+use crate::io::mmio;
+use crate::log;
+
+struct Reg {}
+impl Reg {
+  const BASE: u64 = 0x0000_B_000;
+  const MAIL0_READ: u64 = Reg::BASE + 0x880; // Read VC -> ARM
+  const MAIL0_STA: u64 = Reg::BASE + 0x898; // Mailbox status
+  const MAIL0_CNF: u64 = Reg::BASE + 0x89c; // Interrupt configuration
+
+  const MAIL1_WRITE: u64 = Reg::BASE + 0x8a0; // Write ARM -> VC
+  const MAIL1_STA: u64 = Reg::BASE + 0x8b8;
+}
+
+struct Bit {}
+impl Bit {
+  const MAIL_STATUS_FULL: u32 = 0x8000_0000;
+  const MAIL_STATUS_EMPTY: u32 = 0x4000_0000;
+  // IRQ when mailbox 0 is not empty
+  const MAIL_CNF_IRQ_DATA: u32 = 0x1;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Channel {
+  PowerManagement = 0,
+  Framebuffer = 1,
+  VirtualUart = 2,
+  Vchiq = 3,
+  Leds = 4,
+  Buttons = 5,
+  TouchScreen = 6,
+  PropertyArmToVc = 8,
+  PropertyVcToArm = 9,
+}
+
+static mut QUEUE: Queue = Queue::new();
+// Set while the queue is in use, including by the IRQ handler.
+static mut LOCKED: bool = false;
+static mut IRQ_CHANNEL: Option<interrupt::IrqChannel> = None;
+
+// Exclusive access to QUEUE. The mailbox IRQ is masked while held. On a
+// single core a nested holder (another IRQ handler) releases before the
+// outer one resumes, so the check-then-set below is not racy.
+struct Guard {}
+
+impl Guard {
+  fn acquire() -> Result<Guard, ErrorKind> {
+    unsafe {
+      if LOCKED {
+        return Err(ErrorKind::ResourceBusy);
+      }
+      LOCKED = true;
+      if let Some(channel) = IRQ_CHANNEL {
+        interrupt::mask_interrupt(channel);
+      }
+    }
+    Ok(Guard {})
+  }
+
+  // Waits until the queue is free. Only for callers that cannot run nested
+  // in a holder: the mailbox IRQ is masked while the guard is held and
+  // handlers of other IRQs do not use the mailbox.
+  fn lock() -> Guard {
+    loop {
+      if let Ok(guard) = Guard::acquire() {
+        return guard;
+      }
+      core::hint::spin_loop();
+    }
+  }
+
+  fn queue(&mut self) -> &mut Queue {
+    unsafe { &mut QUEUE }
+  }
+}
+
+impl Drop for Guard {
+  fn drop(&mut self) {
+    unsafe {
+      if let Some(channel) = IRQ_CHANNEL {
+        interrupt::unmask_interrupt(channel);
+      }
+      LOCKED = false;
+    }
+  }
+}
+
+// Handle to a submitted request.
+pub struct Completion {
+  ticket: Ticket,
+}
+
+impl Completion {
+  // Response data (lower 4 bits cleared) once the VideoCore answered.
+  // Consumes the result: it is returned once.
+  pub fn poll(&self) -> Option<u32> {
+    let mut guard = Guard::acquire().ok()?;
+    guard.queue().take(self.ticket)
+  }
+
+  // Blocks until the VideoCore answered. Drives the mailbox itself, so it
+  // also works before interrupts are enabled.
+  pub fn wait(self) -> u32 {
+    loop {
+      if let Some(response) = self.poll() {
+        return response;
+      }
+      service();
+      core::hint::spin_loop();
+    }
+  }
+}
+
+impl Drop for Completion {
+  // Frees the slot of a request nobody waits for anymore. Without this,
+  // abandoned requests would fill the queue for good.
+  fn drop(&mut self) {
+    Guard::lock().queue().cancel(self.ticket);
+  }
+}
+
+// Queues `data` for `channel`. The lower 4 bits of `data` must be clear. For
+// property channels `data` is the bus address of a message, which must stay
+// in place until completion.
+pub fn submit(channel: Channel, data: u32) -> Result<Completion, ErrorKind> {
+  let mut guard = Guard::acquire()?;
+  let ticket = guard.queue().push(channel as u8, data)?;
+  flush(guard.queue());
+  Ok(Completion { ticket })
+}
+
+// Writes queued mails while the VideoCore accepts them.
+fn flush(queue: &mut Queue) {
+  while mmio::read(Reg::MAIL1_STA) & Bit::MAIL_STATUS_FULL == 0 {
+    match queue.next_to_send() {
+      Some(mail) => mmio::write(Reg::MAIL1_WRITE, mail),
+      None => break,
+    }
+  }
+}
+
+// Completes answered requests and sends the queued ones.
+fn drain(queue: &mut Queue) {
+  while mmio::read(Reg::MAIL0_STA) & Bit::MAIL_STATUS_EMPTY == 0 {
+    let mail = mmio::read(Reg::MAIL0_READ);
+    if queue.complete(mail).is_err() {
+      log::warn!("Unexpected mail {:#010X}", mail);
+    }
+  }
+  flush(queue);
+}
+
+// Polls the mailbox once. No-op when the queue is in use.
+pub fn service() {
+  if let Ok(mut guard) = Guard::acquire() {
+    drain(guard.queue());
+  }
+}
+
+fn handle_irq() {
+  // Reading mailbox 0 until empty clears the interrupt.
+  service();
+}
+
+// Completes requests from the mailbox 0 IRQ.
+pub fn initialize_interrupt(irq_channel: interrupt::IrqChannel) {
+  interrupt::set_handler(irq_channel, handle_irq);
+  mmio::write(Reg::MAIL0_CNF, Bit::MAIL_CNF_IRQ_DATA);
+  unsafe { IRQ_CHANNEL = Some(irq_channel) };
+  interrupt::unmask_interrupt(irq_channel);
+}
+
+#[cfg(test)]
+#[path = "channel_test.rs"]
+mod channel_test;
@@ -0,0 +1,112 @@
+use super::*;
+use crate::io::mailbox::queue::MAX_PENDING;
+use crate::io::mmio::mock::{self, Access};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// Fake VideoCore answering each mail with the same mail, as it does for
+// property messages. Mails stay in mailbox 1 until answered.
+fn fake_videocore(answer: bool) -> Arc<Mutex<VecDeque<u32>>> {
+  let mailbox_0 = Arc::new(Mutex::new(VecDeque::new()));
+  let (status, read, write) =
+    (mailbox_0.clone(), mailbox_0.clone(), mailbox_0.clone());
+  mock::on_write(Reg::MAIL1_WRITE, move |_, mail| {
+    if answer {
+      write.lock().unwrap().push_back(mail);
+    }
+  });
+  mock::on_read(Reg::MAIL0_STA, move |_| {
+    if status.lock().unwrap().is_empty() {
+      Bit::MAIL_STATUS_EMPTY
+    } else {
+      0
+    }
+  });
+  mock::on_read(Reg::MAIL0_READ, move |_| {
+    read.lock().unwrap().pop_front().unwrap()
+  });
+  mailbox_0
+}
+
+#[test]
+fn test_channel_round_trip() {
+  let _guard = mock::initialize();
+  fake_videocore(true);
+  let completion = submit(Channel::PropertyArmToVc, 0xC008_0000).unwrap();
+  assert_eq!(
+    mock::accesses(),
+    [
+      Access::Read(Reg::MAIL1_STA, 0),
+      Access::Write(Reg::MAIL1_WRITE, 0xC008_0008),
+      Access::Read(Reg::MAIL1_STA, 0),
+    ]
+  );
+  assert_eq!(completion.poll(), None);
+  assert_eq!(completion.wait(), 0xC008_0000);
+}
+
+#[test]
+fn test_channel_serializes_requests() {
+  let _guard = mock::initialize();
+  let mailbox_0 = fake_videocore(false);
+  let first = submit(Channel::PropertyArmToVc, 0x1000).unwrap();
+  let second = submit(Channel::PropertyArmToVc, 0x2000).unwrap();
+  let other = submit(Channel::Framebuffer, 0x3000).unwrap();
+  // The second property request waits for the first one.
+  assert_eq!(
+    mock::writes(),
+    [(Reg::MAIL1_WRITE, 0x1008), (Reg::MAIL1_WRITE, 0x3001)]
+  );
+
+  // Answers as the IRQ handler would see them.
+  mailbox_0.lock().unwrap().extend([0x0001, 0x1008]);
+  mock::clear_log();
+  handle_irq();
+  assert_eq!(mock::writes(), [(Reg::MAIL1_WRITE, 0x2008)]);
+  assert_eq!(other.poll(), Some(0));
+  assert_eq!(first.poll(), Some(0x1000));
+  assert_eq!(second.poll(), None);
+
+  mailbox_0.lock().unwrap().push_back(0x2008);
+  assert_eq!(second.wait(), 0x2000);
+}
+
+#[test]
+fn test_channel_waits_for_room() {
+  let _guard = mock::initialize();
+  fake_videocore(true);
+  // Mailbox 1 full for one poll
+  let mut polls = 0;
+  mock::on_read(Reg::MAIL1_STA, move |_| {
+    polls += 1;
+    if polls == 1 {
+      Bit::MAIL_STATUS_FULL
+    } else {
+      0
+    }
+  });
+  let completion = submit(Channel::Vchiq, 0x4000).unwrap();
+  // Nothing written yet, the next service() sends it.
+  assert!(mock::writes().is_empty());
+  assert_eq!(completion.wait(), 0x4000);
+  assert_eq!(mock::writes(), [(Reg::MAIL1_WRITE, 0x4003)]);
+}
+
+#[test]
+fn test_channel_dropped_completion_frees_slot() {
+  let _guard = mock::initialize();
+  let mailbox_0 = fake_videocore(false);
+  // Dropped unanswered: one in flight, the others still queued
+  for _ in 0..MAX_PENDING {
+    submit(Channel::PropertyArmToVc, 0x1000).unwrap();
+  }
+  let queued = submit(Channel::PropertyArmToVc, 0x2000).unwrap();
+  mock::clear_log();
+
+  // The abandoned request in flight is answered, then ours goes out.
+  mailbox_0.lock().unwrap().push_back(0x1008);
+  service();
+  assert_eq!(mock::writes(), [(Reg::MAIL1_WRITE, 0x2008)]);
+  mailbox_0.lock().unwrap().push_back(0x2008);
+  assert_eq!(queued.wait(), 0x2000);
+}
@@ -1,6 +1,23 @@
 #[macro_export]
 macro_rules! make_tag {
   ($tag_name:ident, $tag_id:expr,
+   request { $( $req_ident:ident : $req_type:ty ),* },
+   response { $( $resp_ident:ident : $resp_type:ty ),* }) => {
+    $crate::make_tag!(@impl $tag_name, $tag_id, false,
+      request { $( $req_ident : $req_type ),* },
+      response { $( $resp_ident : $resp_type ),* });
+  };
+  // The firmware may return fewer bytes than the response struct holds, e.g.
+  // strings. See response_len().
+  ($tag_name:ident, $tag_id:expr,
+   request { $( $req_ident:ident : $req_type:ty ),* },
+   response { $( $resp_ident:ident : $resp_type:ty ),* },
+   variable_length) => {
+    $crate::make_tag!(@impl $tag_name, $tag_id, true,
+      request { $( $req_ident : $req_type ),* },
+      response { $( $resp_ident : $resp_type ),* });
+  };
+  (@impl $tag_name:ident, $tag_id:expr, $variable_length:expr,
    request { $( $req_ident:ident : $req_type:ty ),* },
    response { $( $resp_ident:ident : $resp_type:ty ),* }) => {
 
@@ -9,6 +26,7 @@ macro_rules! make_tag {
     pub mod $tag_name {
       use crate::io::mailbox::tag::TagId;
       use crate::io::mailbox::MessageView;
+      use crate::io::mailbox::TagStatus;
       use crate::common::error::ErrorKind;
 
       // These request/response structs are packed, meaning that Rust does not
@@ -81,45 +99,68 @@ macro_rules! make_tag {
         pub const SIZE_BYTES: usize = core::mem::size_of::<Self>();
         pub const MESSAGE_LEN: usize = (Self::SIZE_BYTES / 4);
         pub const PAYLOAD_SIZE_BYTES: usize = core::mem::size_of::<Payload>();
+        pub const VARIABLE_LENGTH: bool = $variable_length;
       }
 
       fn make(request: Request) -> Tag {
         // Assertion
         let tag_id: TagId = $tag_id;
+        // Zero the bytes past the request when the response is larger.
+        let mut payload: Payload = unsafe { core::mem::zeroed() };
+        payload.request = request;
         Tag {
           id: tag_id as u32,
           payload_size_bytes: Tag::PAYLOAD_SIZE_BYTES as u32,
           code: 0x0000_0000,
-          payload: Payload { request }
+          payload,
         }
       }
 
       pub fn read_response(message: &dyn MessageView) -> Result<&Response, ErrorKind> {
+        read(message).map(|(response, _)| response)
+      }
+
+      pub fn status(message: &dyn MessageView) -> Result<TagStatus, ErrorKind> {
+        message.tag_status($tag_id as u32)
+      }
+
+      // Number of valid bytes in the response.
+      pub fn response_len(message: &dyn MessageView) -> Result<usize, ErrorKind> {
+        read(message).map(|(_, len)| len)
+      }
+
+      // InvalidData if the firmware's answer does not fit the tag.
+      fn read(message: &dyn MessageView) -> Result<(&Response, usize), ErrorKind> {
         let tag_buf = message.tag_buffer_lookup($tag_id as u32)?;
         if tag_buf.len() != core::mem::size_of::<Tag>() {
-          panic!("Unexpected mailbox tag size");
+          return Err(ErrorKind::InvalidData);
         }
         // This should pass. Tag payloads are aligned to 4bytes, including the metadata.
         // The message containing the tags are 16 bytes aligned.
-        if tag_buf.as_ptr() as usize % core::mem::align_of::<Tag>() != 0 {
-          panic!("Tag buffer has wrong alignment");
+        if !(tag_buf.as_ptr() as usize).is_multiple_of(core::mem::align_of::<Tag>()) {
+          return Err(ErrorKind::InvalidData);
         }
 
         let tag = unsafe { &*(tag_buf.as_ptr() as *const Tag) };
-        if tag.id != $tag_id as u32 {
-          panic!("Received tag with wrong ID");
+        if tag.id != $tag_id as u32
+          || tag.payload_size_bytes != Tag::PAYLOAD_SIZE_BYTES as u32
+        {
+          return Err(ErrorKind::InvalidData);
         }
-        if tag.payload_size_bytes != Tag::PAYLOAD_SIZE_BYTES as u32 {
-          panic!("Payload size is invalid");
-        }
-        if (tag.code >> 31 != 1) {
+        let len = match status(message)? {
           // This is not a response.
-          return Err(ErrorKind::InvalidInput);
-        }
-        if (tag.code & !(1 << 31)) as usize != core::mem::size_of::<Response>() {
-          panic!("Response length is invalid");
+          TagStatus::NotProcessed => return Err(ErrorKind::InvalidInput),
+          TagStatus::Response { len } => len,
+        };
+        if Tag::VARIABLE_LENGTH {
+          // Firmware reports the required length if the buffer was too small.
+          if len > core::mem::size_of::<Response>() {
+            return Err(ErrorKind::InvalidData);
+          }
+        } else if len != core::mem::size_of::<Response>() {
+          return Err(ErrorKind::InvalidData);
         }
-        unsafe { Ok(&tag.payload.response) }
+        unsafe { Ok((&tag.payload.response, len)) }
       }
 
       impl crate::io::mailbox::tag::MessageTag for Tag {
@@ -1,23 +1,9 @@
+use super::channel::{self, Channel};
 use crate::common::error::ErrorKind;
+use crate::io::dma;
 use crate::io::mailbox::tag::MessageTag;
-use crate::io::mmio;
 
-struct Reg {}
-impl Reg {
-  const BASE: u64 = 0x0000_B_000;
-  const MAIL0_READ: u64 = Reg::BASE + 0x880; // Read VC -> ARM
-  const MAIL0_STA: u64 = Reg::BASE + 0x898; // Mailbox status
-
-  const MAIL1_WRITE: u64 = Reg::BASE + 0x8a0; // Write ARM -> VC
-}
-
-struct Bit {}
-impl Bit {
-  const CHANNEL_MASK: u32 = 0xF;
-  const CHANNEL_ARM_TO_VC: u32 = 0x8;
-  const MAIL_STATUS_FULL: u32 = 0x8000_0000;
-  const MAIL_STATUS_EMPTY: u32 = 0x4000_0000;
-}
+const CHANNEL_MASK: u32 = 0xF;
 
 pub struct ResponseCode {}
 impl ResponseCode {
@@ -52,45 +38,170 @@ Buffer contents:
     u8...: padding
 */
 
-#[repr(C, align(16))]
-pub struct Message<const N: usize> {
+// Capacity of the tag area. Large enough for the biggest tag
+// (ConfigGetCommandLine) plus a handful of small ones.
+pub const MESSAGE_CAP_WORDS: usize = 512;
+// Size and code words, then the end tag
+const EMPTY_MESSAGE_BYTES: usize = 12;
+const TAG_HEADER_BYTES: usize = 12;
+const TAG_CODE_RESPONSE: u32 = 1 << 31;
+
+// The VideoCore needs 16 byte alignment. Cache line alignment keeps cache
+// maintenance on the message from touching neighbouring data.
+#[repr(C, align(64))]
+pub struct Message {
   buf_len_bytes: u32,
   // Always 0 for request
   code: u32,
-  // Raw tag buffer. align to 32 bits
-  tag_buf: [u32; N],
-  // Always 0
-  tag_end: u32,
+  // Raw tag buffer followed by the end tag. align to 32 bits
+  tag_buf: [u32; MESSAGE_CAP_WORDS + 1],
+}
+
+// Status of a single tag, from the code word of its header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TagStatus {
+  // b31 clear: the firmware did not process the tag.
+  NotProcessed,
+  // b31 set: the firmware wrote `len` bytes. A length larger than the value
+  // buffer means the response was truncated.
+  Response { len: usize },
+}
+
+pub struct TagEntry<'a> {
+  pub id: u32,
+  pub status: TagStatus,
+  // Header and value buffer
+  pub buf: &'a [u8],
 }
 
-// Provides a convenient view for a generic length Message.
+// Provides a view of the message buffer.
 pub trait MessageView {
   fn size(&self) -> usize;
   fn code(&self) -> u32;
   fn tag_buffer_lookup(&self, tag_id: u32) -> Result<&[u8], ErrorKind>;
+  // NotFound if the tag is not part of the message.
+  fn tag_status(&self, tag_id: u32) -> Result<TagStatus, ErrorKind>;
 }
 
-// Allocates a message that can hold N words (4 bytes) of tag data.
-pub struct MessageBuilder<const N: usize> {
-  data: Message<N>,
-  fill_count_bytes: usize,
-}
+impl Message {
+  // An empty request. Tags are added in place, the message is too large to
+  // be moved around.
+  pub const fn new() -> Message {
+    Message {
+      buf_len_bytes: EMPTY_MESSAGE_BYTES as u32,
+      code: 0x0000_0000,
+      tag_buf: [0; MESSAGE_CAP_WORDS + 1],
+    }
+  }
+
+  // Appends a tag with ID and buffer, the size follows the tags added.
+  pub fn add_tag(
+    &mut self,
+    tag: &impl MessageTag,
+  ) -> Result<&mut Message, ErrorKind> {
+    // + (id, 4b) + (value_buffer_size, 4b) + (code, 4b)
+    let size: usize = TAG_HEADER_BYTES + tag.payload_size_bytes();
+    let tag_words = (self.buf_len_bytes as usize - EMPTY_MESSAGE_BYTES) / 4;
+    // Tags are padded to 32 bits.
+    let end_words = tag_words + size.div_ceil(4);
+    if end_words > MESSAGE_CAP_WORDS {
+      return Err(ErrorKind::StorageFull);
+    }
 
-impl<const N: usize> Message<N> {
-  pub fn builder() -> MessageBuilder<N> {
-    MessageBuilder::<N> {
-      data: Message::<N> {
-        buf_len_bytes: 4 * (3 + N as u32),
-        code: 0x0000_0000,
-        tag_buf: [0; N],
-        tag_end: 0,
+    // Make u8 view of the tag words here.
+    let buf: &mut [u8] = unsafe {
+      core::slice::from_raw_parts_mut(
+        self.tag_buf[tag_words..end_words].as_mut_ptr() as *mut u8,
+        (end_words - tag_words) * 4,
+      )
+    };
+    buf[..size].clone_from_slice(&tag.buf()[0..size]);
+    buf[size..].fill(0);
+    self.tag_buf[end_words] = 0;
+    self.buf_len_bytes = (EMPTY_MESSAGE_BYTES + 4 * end_words) as u32;
+
+    Ok(self)
+  }
+
+  // Whole message as words, header included.
+  pub fn words(&self) -> &[u32] {
+    unsafe {
+      core::slice::from_raw_parts(
+        self as *const Message as *const u32,
+        (self.buf_len_bytes as usize / 4).min(2 + MESSAGE_CAP_WORDS + 1),
+      )
+    }
+  }
+
+  // Malformed tags end the iteration with InvalidData.
+  pub fn tags(&self) -> TagIter<'_> {
+    // Clamped, the size word is the firmware's after send() failed.
+    let len_u8 = (self.buf_len_bytes as usize)
+      .saturating_sub(8)
+      .min(4 * (MESSAGE_CAP_WORDS + 1));
+    TagIter {
+      buf: unsafe {
+        core::slice::from_raw_parts(self.tag_buf.as_ptr() as *const u8, len_u8)
       },
-      fill_count_bytes: 0,
+      idx_u8: 0,
     }
   }
 }
 
-impl<const N: usize> MessageView for Message<N> {
+pub struct TagIter<'a> {
+  buf: &'a [u8],
+  idx_u8: usize,
+}
+
+impl<'a> Iterator for TagIter<'a> {
+  type Item = Result<TagEntry<'a>, ErrorKind>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let word = |i: usize| {
+      let at = self.idx_u8 + 4 * i;
+      u32::from_le_bytes([
+        self.buf[at],
+        self.buf[at + 1],
+        self.buf[at + 2],
+        self.buf[at + 3],
+      ])
+    };
+    if self.idx_u8 + 4 > self.buf.len() || word(0) == 0 {
+      // We reached end of tag
+      return None;
+    }
+    // First 12 bytes are metadata. A tag running past the message ends the
+    // iteration.
+    let next = match self.idx_u8 + TAG_HEADER_BYTES {
+      header_end if header_end <= self.buf.len() => {
+        header_end.checked_add(word(1) as usize)
+      }
+      _ => None,
+    };
+    let Some(next) = next.filter(|&next| next <= self.buf.len()) else {
+      self.idx_u8 = self.buf.len();
+      return Some(Err(ErrorKind::InvalidData));
+    };
+    let id = word(0);
+    let code = word(2);
+    let status = if code & TAG_CODE_RESPONSE != 0 {
+      TagStatus::Response {
+        len: (code & !TAG_CODE_RESPONSE) as usize,
+      }
+    } else {
+      TagStatus::NotProcessed
+    };
+    let entry = TagEntry {
+      id,
+      status,
+      buf: &self.buf[self.idx_u8..next],
+    };
+    self.idx_u8 = next;
+    Some(Ok(entry))
+  }
+}
+
+impl MessageView for Message {
   fn size(&self) -> usize {
     return self.buf_len_bytes as usize;
   }
@@ -98,102 +209,63 @@ impl<const N: usize> MessageView for Message<N> {
     return self.code;
   }
   fn tag_buffer_lookup(&self, tag_id: u32) -> Result<&[u8], ErrorKind> {
-    let len_u8 = self.tag_buf.len() * 4;
-    let tag_buf_u8 = unsafe {
-      core::slice::from_raw_parts(
-        self.tag_buf.as_ptr() as *const u8,
-        len_u8 as usize,
-      )
-    };
-
-    let mut idx_u8 = 0;
-    while idx_u8 < len_u8 {
-      // First 12 bytes are metadata.
-      if idx_u8 + 12 > len_u8 {
-        panic!("Invalid message structure!");
-      }
-      let meta_u32 = unsafe {
-        core::slice::from_raw_parts(
-          tag_buf_u8[idx_u8..].as_ptr() as *const u32,
-          3,
-        )
-      };
-      let id = meta_u32[0];
-      let tag_size_bytes = meta_u32[1] as usize;
-      // meta_u32[2] = req/resp. code.
-      let next = idx_u8 + 12 + tag_size_bytes;
-
-      if id == tag_id {
-        if next > len_u8 {
-          panic!("Tag size exceeds message buffer");
-        }
-        return Ok(&tag_buf_u8[idx_u8..next]);
-      }
+    self.tag(tag_id).map(|tag| tag.buf)
+  }
+  fn tag_status(&self, tag_id: u32) -> Result<TagStatus, ErrorKind> {
+    self.tag(tag_id).map(|tag| tag.status)
+  }
+}
 
-      if id == 0 {
-        // We reached end of tag
-        break;
+impl Message {
+  fn tag(&self, tag_id: u32) -> Result<TagEntry<'_>, ErrorKind> {
+    for tag in self.tags() {
+      let tag = tag?;
+      if tag.id == tag_id {
+        return Ok(tag);
       }
-      idx_u8 = next;
     }
-
     Err(ErrorKind::NotFound)
   }
 }
 
-impl<const N: usize> MessageBuilder<N> {
-  // Fill the message with a tag with ID and buffer.
-  pub fn add_tag(mut self, tag: &impl MessageTag) -> MessageBuilder<N> {
-    // let buf = tag.value_buf();
-    // + (id, 4b) + (value_buffer_size, 4b) + (code, 4b)
-    let size: usize = tag.size_bytes();
-    if self.fill_count_bytes + size > N * 4 {
-      panic!("Message full");
+// Sends `message` and waits for the firmware to write the responses into
+// it. InvalidData if the firmware did not process the whole message.
+pub fn send(message: &mut Message) -> Result<(), ErrorKind> {
+  // https://bitbanged.com/posts/understanding-rpi/the-mailbox/
+  // The VideoCore reads and writes the buffer in memory, bypassing the ARM
+  // data cache.
+  let len = message.buf_len_bytes;
+  dma::clean(message);
+  let data = dma::virt_to_bus(message) & !CHANNEL_MASK;
+  // Queued behind other callers, the property channel answers in order.
+  let response = loop {
+    match channel::submit(Channel::PropertyArmToVc, data) {
+      Ok(completion) => break completion.wait(),
+      // Another caller holds the queue or all slots are pending.
+      Err(ErrorKind::ResourceBusy | ErrorKind::StorageFull) => {
+        channel::service();
+        core::hint::spin_loop();
+      }
+      Err(e) => return Err(e),
     }
+  };
+  debug_assert_eq!(response, data);
 
-    // Make u8 view of data.tag_buf here.
-    let buf: &mut [u8] = unsafe {
-      core::slice::from_raw_parts_mut(
-        self.data.tag_buf.as_mut_ptr() as *mut u8,
-        N * 4,
-      )
-    };
-    let tag_buf: &[u8] = tag.buf();
-
-    // First 3 words (4b) are metadata
-    buf[self.fill_count_bytes..(self.fill_count_bytes + 12)]
-      .clone_from_slice(&tag_buf[0..12]);
-    self.fill_count_bytes += 12;
-
-    buf[self.fill_count_bytes
-      ..(self.fill_count_bytes + tag.payload_size_bytes())]
-      .clone_from_slice(&tag_buf[12..(12 + tag.payload_size_bytes())]);
-    self.fill_count_bytes += tag.payload_size_bytes();
-
-    self
-  }
-
-  pub fn build(self) -> Message<N> {
-    self.data
+  // We re-read the header written by VC. The caller reads the rest through
+  // the reference it lent us.
+  dma::invalidate(message);
+  let (response_len, code) = unsafe {
+    (
+      core::ptr::read_volatile(&message.buf_len_bytes),
+      core::ptr::read_volatile(&message.code),
+    )
+  };
+  match (response_len, code) {
+    (size, ResponseCode::CODE_REQUEST_SUCCESS) if size == len => Ok(()),
+    _ => Err(ErrorKind::InvalidData),
   }
 }
 
-// we only support single threaded calls now...
-pub fn send<const N: usize>(message: Message<N>) -> Message<N> {
-  // https://bitbanged.com/posts/understanding-rpi/the-mailbox/
-  // Wait until the read end can accommodate new mails
-  let raw_buf_ptr = &message as *const Message<N>;
-  // Only support channel 8 for now (ARM to GPU)
-  let message =
-    raw_buf_ptr as u32 & !Bit::CHANNEL_MASK | Bit::CHANNEL_ARM_TO_VC;
-  while mmio::read(Reg::MAIL0_STA) & Bit::MAIL_STATUS_FULL != 0 {}
-  mmio::write(Reg::MAIL1_WRITE, message);
-
-  // Wait until the read end receives the mail
-  while (mmio::read(Reg::MAIL0_STA) & Bit::MAIL_STATUS_EMPTY != 0)
-    || (mmio::read(Reg::MAIL0_READ) != message)
-  {}
-
-  // We re-read the message written by VC.
-  unsafe { ::core::ptr::read_volatile(raw_buf_ptr) }
-}
+#[cfg(test)]
+#[path = "message_test.rs"]
+mod message_test;
@@ -0,0 +1,108 @@
+use super::*;
+use crate::io::mailbox::tag::ConfigGetCommandLine;
+use crate::io::mailbox::tag::GetClockRate;
+use crate::io::mailbox::tag::GetClockState;
+use crate::io::mailbox::tag::HwGetBoardRevision;
+
+#[test]
+fn test_message_size_from_tags() {
+  let mut message = Message::new();
+  assert_eq!(message.words(), [12, 0, 0]);
+
+  message
+    .add_tag(&GetClockRate::Request { clock_id: 2 }.to_tag())
+    .unwrap()
+    .add_tag(&HwGetBoardRevision::Request {}.to_tag())
+    .unwrap();
+  assert_eq!(
+    message.words(),
+    [
+      // Header
+      4 * 12,
+      0,
+      // GetClockRate
+      0x00030002,
+      8,
+      0,
+      2,
+      0,
+      // HwGetBoardRevision
+      0x00010002,
+      4,
+      0,
+      0,
+      // End tag
+      0,
+    ]
+  );
+  assert_eq!(message.size(), 4 * 12);
+}
+
+#[test]
+fn test_message_overflow() {
+  let tag = ConfigGetCommandLine::Request {}.to_tag();
+  let mut message = Message::new();
+  message.add_tag(&tag).unwrap();
+  assert!(matches!(message.add_tag(&tag), Err(ErrorKind::StorageFull)));
+  // Left as it was
+  assert_eq!(message.size(), 12 + 12 + tag.payload_size_bytes());
+}
+
+#[test]
+fn test_message_tag_status() {
+  let mut rate_tag = GetClockRate::Request { clock_id: 2 }.to_tag();
+  rate_tag.code = TAG_CODE_RESPONSE | 8;
+  let state_tag = GetClockState::Request { clock_id: 2 }.to_tag();
+  let mut message = Message::new();
+  message
+    .add_tag(&rate_tag)
+    .unwrap()
+    .add_tag(&state_tag)
+    .unwrap();
+
+  assert_eq!(
+    GetClockRate::status(&message).unwrap(),
+    TagStatus::Response { len: 8 }
+  );
+  assert_eq!(
+    GetClockState::status(&message).unwrap(),
+    TagStatus::NotProcessed
+  );
+  assert!(matches!(
+    HwGetBoardRevision::status(&message),
+    Err(ErrorKind::NotFound)
+  ));
+  assert!(matches!(
+    GetClockState::read_response(&message),
+    Err(ErrorKind::InvalidInput)
+  ));
+
+  let tags: Vec<(u32, TagStatus)> = message
+    .tags()
+    .map(|tag| tag.map(|tag| (tag.id, tag.status)).unwrap())
+    .collect();
+  assert_eq!(
+    tags,
+    [
+      (0x00030002, TagStatus::Response { len: 8 }),
+      (0x00030001, TagStatus::NotProcessed)
+    ]
+  );
+}
+
+#[test]
+fn test_message_malformed_tag() {
+  let tag = GetClockRate::Request { clock_id: 2 }.to_tag();
+  let mut message = Message::new();
+  message.add_tag(&tag).unwrap();
+  // The firmware claims a value buffer past the message.
+  message.tag_buf[1] = 0x1000;
+  assert!(matches!(
+    message.tags().collect::<Vec<_>>()[..],
+    [Err(ErrorKind::InvalidData)]
+  ));
+  assert!(matches!(
+    GetClockRate::read_response(&message),
+    Err(ErrorKind::InvalidData)
+  ));
+}
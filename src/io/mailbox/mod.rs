@@ -15,9 +15,12 @@ The ARM should never write MB 0 or read MB 1.
 
 #[macro_use]
 mod macros;
+pub mod channel;
 mod message;
+mod queue;
 pub mod tag;
 
 pub use message::send;
 pub use message::Message;
 pub use message::MessageView;
+pub use message::TagStatus;
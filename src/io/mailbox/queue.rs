@@ -0,0 +1,150 @@
+use crate::common::error::ErrorKind;
+use arrayvec::ArrayVec;
+
+pub const MAX_PENDING: usize = 8;
+pub const CHANNEL_COUNT: usize = 16;
+const CHANNEL_MASK: u32 = 0xF;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+  Free,
+  // Waiting for the channel to become idle
+  Queued,
+  // Written to the mailbox, waiting for the response
+  InFlight,
+  // In flight but nobody waits for it, freed by its response
+  Abandoned,
+  Done,
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+  state: State,
+  // Mail to write, channel in the lower 4 bits
+  mail: u32,
+  // Response data, lower 4 bits cleared
+  response: u32,
+  // Distinguishes reuses of the slot
+  generation: u32,
+}
+
+// Refers to a request, stale once the response was taken.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Ticket {
+  slot: usize,
+  generation: u32,
+}
+
+// Requests in submission order. Channels answer in order, so only one
+// request per channel is in flight and the response of a channel belongs to
+// its in flight request.
+pub struct Queue {
+  slots: [Slot; MAX_PENDING],
+  queued: ArrayVec<usize, MAX_PENDING>,
+  in_flight: [Option<usize>; CHANNEL_COUNT],
+}
+
+impl Queue {
+  pub const fn new() -> Queue {
+    Queue {
+      slots: [Slot {
+        state: State::Free,
+        mail: 0,
+        response: 0,
+        generation: 0,
+      }; MAX_PENDING],
+      queued: ArrayVec::new_const(),
+      in_flight: [None; CHANNEL_COUNT],
+    }
+  }
+
+  // `data` must have the lower 4 bits clear.
+  pub fn push(&mut self, channel: u8, data: u32) -> Result<Ticket, ErrorKind> {
+    if channel as usize >= CHANNEL_COUNT || data & CHANNEL_MASK != 0 {
+      return Err(ErrorKind::InvalidInput);
+    }
+    let slot = self
+      .slots
+      .iter()
+      .position(|s| s.state == State::Free)
+      .ok_or(ErrorKind::StorageFull)?;
+    let entry = &mut self.slots[slot];
+    entry.state = State::Queued;
+    entry.mail = data | channel as u32;
+    entry.generation = entry.generation.wrapping_add(1);
+    self.queued.push(slot);
+    Ok(Ticket {
+      slot,
+      generation: entry.generation,
+    })
+  }
+
+  // Oldest queued mail whose channel is idle. The caller must write it to
+  // the mailbox, it is considered in flight from now on.
+  pub fn next_to_send(&mut self) -> Option<u32> {
+    let index = self.queued.iter().position(|&slot| {
+      let channel = (self.slots[slot].mail & CHANNEL_MASK) as usize;
+      self.in_flight[channel].is_none()
+    })?;
+    let slot = self.queued.remove(index);
+    let entry = &mut self.slots[slot];
+    entry.state = State::InFlight;
+    self.in_flight[(entry.mail & CHANNEL_MASK) as usize] = Some(slot);
+    Some(entry.mail)
+  }
+
+  // Records a mail read from the mailbox. NotFound if nothing was in flight
+  // on its channel.
+  pub fn complete(&mut self, mail: u32) -> Result<(), ErrorKind> {
+    let channel = (mail & CHANNEL_MASK) as usize;
+    let slot = self.in_flight[channel].take().ok_or(ErrorKind::NotFound)?;
+    let entry = &mut self.slots[slot];
+    entry.state = match entry.state {
+      State::Abandoned => State::Free,
+      _ => State::Done,
+    };
+    entry.response = mail & !CHANNEL_MASK;
+    Ok(())
+  }
+
+  // Frees the slot of a request nobody waits for. A request in flight keeps
+  // its channel busy until its response arrives. No-op for stale tickets.
+  pub fn cancel(&mut self, ticket: Ticket) {
+    let entry = &mut self.slots[ticket.slot];
+    if entry.generation != ticket.generation {
+      return;
+    }
+    match entry.state {
+      State::Queued => {
+        self.queued.retain(|slot| *slot != ticket.slot);
+        entry.state = State::Free;
+      }
+      State::InFlight => entry.state = State::Abandoned,
+      State::Done => entry.state = State::Free,
+      State::Free | State::Abandoned => {}
+    }
+  }
+
+  pub fn is_done(&self, ticket: Ticket) -> bool {
+    let entry = &self.slots[ticket.slot];
+    entry.generation == ticket.generation && entry.state == State::Done
+  }
+
+  // Returns the response and frees the slot.
+  pub fn take(&mut self, ticket: Ticket) -> Option<u32> {
+    if !self.is_done(ticket) {
+      return None;
+    }
+    let entry = &mut self.slots[ticket.slot];
+    entry.state = State::Free;
+    Some(entry.response)
+  }
+
+  pub fn has_in_flight(&self) -> bool {
+    self.in_flight.iter().any(|slot| slot.is_some())
+  }
+}
+
+#[cfg(test)]
+#[path = "queue_test.rs"]
+mod queue_test;
@@ -0,0 +1,111 @@
+use super::*;
+
+const PROPERTY: u8 = 8;
+const FRAMEBUFFER: u8 = 1;
+
+#[test]
+fn test_queue_round_trip() {
+  let mut queue = Queue::new();
+  let ticket = queue.push(PROPERTY, 0xC008_0000).unwrap();
+  assert!(!queue.is_done(ticket));
+  assert_eq!(queue.next_to_send(), Some(0xC008_0008));
+  assert_eq!(queue.next_to_send(), None);
+  assert!(queue.has_in_flight());
+
+  queue.complete(0xC008_0008).unwrap();
+  assert!(queue.is_done(ticket));
+  assert_eq!(queue.take(ticket), Some(0xC008_0000));
+  // Taken once
+  assert_eq!(queue.take(ticket), None);
+  assert!(!queue.has_in_flight());
+}
+
+#[test]
+fn test_queue_one_in_flight_per_channel() {
+  let mut queue = Queue::new();
+  let first = queue.push(PROPERTY, 0x100).unwrap();
+  let second = queue.push(PROPERTY, 0x200).unwrap();
+  let other = queue.push(FRAMEBUFFER, 0x300).unwrap();
+
+  assert_eq!(queue.next_to_send(), Some(0x108));
+  // Property channel busy, framebuffer goes first.
+  assert_eq!(queue.next_to_send(), Some(0x301));
+  assert_eq!(queue.next_to_send(), None);
+
+  // Legacy channels answer with a status, not the address.
+  queue.complete(0x001).unwrap();
+  assert_eq!(queue.take(other), Some(0x000));
+  queue.complete(0x108).unwrap();
+  assert_eq!(queue.next_to_send(), Some(0x208));
+  queue.complete(0x208).unwrap();
+  assert_eq!(queue.take(second), Some(0x200));
+  assert_eq!(queue.take(first), Some(0x100));
+}
+
+#[test]
+fn test_queue_errors() {
+  let mut queue = Queue::new();
+  assert!(matches!(
+    queue.push(PROPERTY, 0x101),
+    Err(ErrorKind::InvalidInput)
+  ));
+  assert!(matches!(
+    queue.push(16, 0x100),
+    Err(ErrorKind::InvalidInput)
+  ));
+  assert!(matches!(queue.complete(0x108), Err(ErrorKind::NotFound)));
+
+  for _ in 0..MAX_PENDING {
+    queue.push(PROPERTY, 0x100).unwrap();
+  }
+  assert!(matches!(
+    queue.push(PROPERTY, 0x100),
+    Err(ErrorKind::StorageFull)
+  ));
+}
+
+#[test]
+fn test_queue_stale_ticket() {
+  let mut queue = Queue::new();
+  let first = queue.push(PROPERTY, 0x100).unwrap();
+  assert_eq!(queue.next_to_send(), Some(0x108));
+
+  queue.complete(0x108).unwrap();
+  assert_eq!(queue.take(first), Some(0x100));
+  // The slot is reused with a new generation.
+  let reused = queue.push(PROPERTY, 0x400).unwrap();
+  assert_ne!(reused, first);
+  assert!(!queue.is_done(first));
+}
+
+#[test]
+fn test_queue_cancel() {
+  let mut queue = Queue::new();
+  let in_flight = queue.push(PROPERTY, 0x100).unwrap();
+  let queued = queue.push(PROPERTY, 0x200).unwrap();
+  let done = queue.push(FRAMEBUFFER, 0x300).unwrap();
+  assert_eq!(queue.next_to_send(), Some(0x108));
+  assert_eq!(queue.next_to_send(), Some(0x301));
+  queue.complete(0x001).unwrap();
+
+  queue.cancel(queued);
+  queue.cancel(done);
+  queue.cancel(in_flight);
+  // The cancelled request is never sent.
+  assert_eq!(queue.next_to_send(), None);
+  for _ in 0..MAX_PENDING - 1 {
+    queue.push(FRAMEBUFFER, 0x400).unwrap();
+  }
+  // The slot in flight is still taken, and so is the channel.
+  assert!(matches!(
+    queue.push(PROPERTY, 0x500),
+    Err(ErrorKind::StorageFull)
+  ));
+  assert!(queue.has_in_flight());
+
+  queue.complete(0x108).unwrap();
+  assert!(!queue.is_done(in_flight));
+  assert!(queue.push(PROPERTY, 0x500).is_ok());
+  // Stale now
+  queue.cancel(done);
+}
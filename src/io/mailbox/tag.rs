@@ -15,11 +15,85 @@ pub enum TagId {
   HwGetArmMemory = 0x00010005,
   HwGetVideocoreMemory = 0x00010006,
   HwGetClocks = 0x00010007,
+  // Config
+  ConfigGetCommandLine = 0x00050001,
+  // Shared resource management
+  ResourceGetDmaChannels = 0x00060001,
+  // Power
+  GetPowerState = 0x00020001,
+  SetPowerState = 0x00028001,
+  // Clocks
   GetClockState = 0x00030001,
   SetClockState = 0x00038001,
   GetClockRate = 0x00030002,
+  SetClockRate = 0x00038002,
+  GetMaxClockRate = 0x00030004,
+  GetMinClockRate = 0x00030007,
+  GetTurbo = 0x00030009,
+  SetTurbo = 0x00038009,
+  // Voltage
+  GetVoltage = 0x00030003,
+  SetVoltage = 0x00038003,
+  GetMaxVoltage = 0x00030005,
+  GetMinVoltage = 0x00030008,
+  // Temperature
+  GetTemperature = 0x00030006,
+  GetMaxTemperature = 0x0003000a,
+  // EDID
+  GetEdidBlock = 0x00030020,
+  // Framebuffer
+  FramebufferAllocate = 0x00040001,
+  FramebufferGetPitch = 0x00040008,
+  FramebufferSetPhysicalSize = 0x00048003,
+  FramebufferSetVirtualSize = 0x00048004,
+  FramebufferSetDepth = 0x00048005,
+  FramebufferSetPixelOrder = 0x00048006,
+  FramebufferSetVirtualOffset = 0x00048009,
 }
 
+// Device IDs of the power tags
+#[repr(u32)]
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum PowerDeviceId {
+  SdCard = 0x0,
+  Uart0 = 0x1,
+  Uart1 = 0x2,
+  UsbHcd = 0x3,
+  I2c0 = 0x4,
+  I2c1 = 0x5,
+  I2c2 = 0x6,
+  Spi = 0x7,
+  Ccp2tx = 0x8,
+}
+
+pub struct PowerState {}
+impl PowerState {
+  // Request and response: device is on
+  pub const ON: u32 = 1 << 0;
+  // Request: wait until the device is stable
+  pub const WAIT: u32 = 1 << 1;
+  // Response: device does not exist
+  pub const NO_DEVICE: u32 = 1 << 1;
+}
+
+// Voltage IDs. Values are offsets from 1.2V in 0.025V steps.
+#[repr(u32)]
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum VoltageId {
+  Core = 0x1,
+  SdramC = 0x2,
+  SdramP = 0x3,
+  SdramI = 0x4,
+}
+
+// The only temperature ID
+pub const TEMPERATURE_ID_SOC: u32 = 0;
+
+pub const COMMAND_LINE_CAP: usize = 1024;
+pub const EDID_BLOCK_SIZE: usize = 128;
+
 // Message tag buffer must be 4 byte aligned.
 // FIXME: could have better design
 pub trait MessageTag {
@@ -48,6 +122,19 @@ macros::make_tag!(
   }
 );
 
+macros::make_tag!(
+  SetClockState,
+  TagId::SetClockState,
+  request {
+    clock_id: u32,
+    state_bits: u32
+  },
+  response {
+    clock_id: u32,
+    state_bits: u32
+  }
+);
+
 macros::make_tag!(
   GetClockRate,
   TagId::GetClockRate,
@@ -126,5 +213,279 @@ macros::make_tag!(
     // Allocate 64 entries. 32 clocks.
     // even index is parent clock id, odd is clock id.
     parent_clock_pair: [u32; 64]
+  },
+  variable_length
+);
+
+// Framebuffer
+// Allocation happens only when the tag is sent together with the geometry
+// tags in a single message.
+macros::make_tag!(
+  FramebufferAllocate,
+  TagId::FramebufferAllocate,
+  request {
+    alignment_bytes: u32
+  },
+  response {
+    // VideoCore bus address
+    base_address: u32,
+    size_bytes: u32
+  }
+);
+
+macros::make_tag!(
+  FramebufferGetPitch,
+  TagId::FramebufferGetPitch,
+  request {},
+  response {
+    bytes_per_line: u32
+  }
+);
+
+macros::make_tag!(
+  FramebufferSetPhysicalSize,
+  TagId::FramebufferSetPhysicalSize,
+  request {
+    width: u32,
+    height: u32
+  },
+  response {
+    width: u32,
+    height: u32
+  }
+);
+
+macros::make_tag!(
+  FramebufferSetVirtualSize,
+  TagId::FramebufferSetVirtualSize,
+  request {
+    width: u32,
+    height: u32
+  },
+  response {
+    width: u32,
+    height: u32
+  }
+);
+
+macros::make_tag!(
+  FramebufferSetDepth,
+  TagId::FramebufferSetDepth,
+  request {
+    bits_per_pixel: u32
+  },
+  response {
+    bits_per_pixel: u32
+  }
+);
+
+macros::make_tag!(
+  FramebufferSetPixelOrder,
+  TagId::FramebufferSetPixelOrder,
+  // 0: BGR, 1: RGB
+  request { order: u32 },
+  response { order: u32 }
+);
+
+macros::make_tag!(
+  FramebufferSetVirtualOffset,
+  TagId::FramebufferSetVirtualOffset,
+  request { x: u32, y: u32 },
+  response { x: u32, y: u32 }
+);
+
+// Config
+macros::make_tag!(
+  ConfigGetCommandLine,
+  TagId::ConfigGetCommandLine,
+  request {},
+  response {
+    // ASCII, not NUL terminated. See response_len().
+    command_line: [u8; super::COMMAND_LINE_CAP]
+  },
+  variable_length
+);
+
+// Shared resource management
+macros::make_tag!(
+  ResourceGetDmaChannels,
+  TagId::ResourceGetDmaChannels,
+  request {},
+  response {
+    // Bits 0-15: DMA channels 0-15 usable by the ARM
+    channel_mask: u32
+  }
+);
+
+// Power
+macros::make_tag!(
+  GetPowerState,
+  TagId::GetPowerState,
+  request { device_id: u32 },
+  response {
+    device_id: u32,
+    state_bits: u32
+  }
+);
+
+macros::make_tag!(
+  SetPowerState,
+  TagId::SetPowerState,
+  request {
+    device_id: u32,
+    state_bits: u32
+  },
+  response {
+    device_id: u32,
+    state_bits: u32
+  }
+);
+
+// Clocks
+macros::make_tag!(
+  SetClockRate,
+  TagId::SetClockRate,
+  request {
+    clock_id: u32,
+    rate_hz: u32,
+    // 1 keeps the turbo setting of the ARM clock
+    skip_setting_turbo: u32
+  },
+  response {
+    clock_id: u32,
+    rate_hz: u32
+  }
+);
+
+macros::make_tag!(
+  GetMaxClockRate,
+  TagId::GetMaxClockRate,
+  request { clock_id: u32 },
+  response {
+    clock_id: u32,
+    rate_hz: u32
+  }
+);
+
+macros::make_tag!(
+  GetMinClockRate,
+  TagId::GetMinClockRate,
+  request { clock_id: u32 },
+  response {
+    clock_id: u32,
+    rate_hz: u32
   }
 );
+
+macros::make_tag!(
+  GetTurbo,
+  TagId::GetTurbo,
+  // ID is always 0
+  request { id: u32 },
+  response {
+    id: u32,
+    level: u32
+  }
+);
+
+macros::make_tag!(
+  SetTurbo,
+  TagId::SetTurbo,
+  request {
+    id: u32,
+    // 0: off, 1: on
+    level: u32
+  },
+  response {
+    id: u32,
+    level: u32
+  }
+);
+
+// Voltage
+macros::make_tag!(
+  GetVoltage,
+  TagId::GetVoltage,
+  request { voltage_id: u32 },
+  response {
+    voltage_id: u32,
+    value: u32
+  }
+);
+
+macros::make_tag!(
+  SetVoltage,
+  TagId::SetVoltage,
+  request {
+    voltage_id: u32,
+    value: u32
+  },
+  response {
+    voltage_id: u32,
+    value: u32
+  }
+);
+
+macros::make_tag!(
+  GetMaxVoltage,
+  TagId::GetMaxVoltage,
+  request { voltage_id: u32 },
+  response {
+    voltage_id: u32,
+    value: u32
+  }
+);
+
+macros::make_tag!(
+  GetMinVoltage,
+  TagId::GetMinVoltage,
+  request { voltage_id: u32 },
+  response {
+    voltage_id: u32,
+    value: u32
+  }
+);
+
+// Temperature
+macros::make_tag!(
+  GetTemperature,
+  TagId::GetTemperature,
+  request {
+    temperature_id: u32
+  },
+  response {
+    temperature_id: u32,
+    // Thousandths of a degree C
+    value: u32
+  }
+);
+
+macros::make_tag!(
+  GetMaxTemperature,
+  TagId::GetMaxTemperature,
+  request {
+    temperature_id: u32
+  },
+  response {
+    temperature_id: u32,
+    // Thousandths of a degree C
+    value: u32
+  }
+);
+
+// EDID
+macros::make_tag!(
+  GetEdidBlock,
+  TagId::GetEdidBlock,
+  request { block_number: u32 },
+  response {
+    block_number: u32,
+    // 0: success
+    status: u32,
+    edid: [u8; super::EDID_BLOCK_SIZE]
+  }
+);
+
+#[cfg(test)]
+#[path = "tag_test.rs"]
+mod tag_test;
@@ -0,0 +1,152 @@
+// Byte layouts follow the examples in
+// https://github.com/raspberrypi/firmware/wiki/Mailbox-property-interface
+use super::*;
+use crate::io::mailbox::Message;
+
+// Tag as little endian words: id, value buffer size, code, values...
+fn words(tag: &impl MessageTag) -> Vec<u32> {
+  tag
+    .buf()
+    .chunks(4)
+    .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+    .collect()
+}
+
+// Marks the tag as a response of `len` bytes, as the firmware would.
+fn respond(code: &mut u32, len: usize) {
+  *code = 0x8000_0000 | len as u32;
+}
+
+#[test]
+fn test_power_state_layout() {
+  let tag = GetPowerState::Request {
+    device_id: PowerDeviceId::Uart0 as u32,
+  }
+  .to_tag();
+  assert_eq!(words(&tag), [0x00020001, 8, 0, 1, 0]);
+
+  let tag = SetPowerState::Request {
+    device_id: PowerDeviceId::UsbHcd as u32,
+    state_bits: PowerState::ON | PowerState::WAIT,
+  }
+  .to_tag();
+  assert_eq!(words(&tag), [0x00028001, 8, 0, 3, 3]);
+}
+
+#[test]
+fn test_clock_layout() {
+  let tag = SetClockState::Request {
+    clock_id: 2,
+    state_bits: 1,
+  }
+  .to_tag();
+  assert_eq!(words(&tag), [0x00038001, 8, 0, 2, 1]);
+
+  // Request is 12 bytes, response 8. The value buffer holds the larger one.
+  let tag = SetClockRate::Request {
+    clock_id: 3,
+    rate_hz: 1_200_000_000,
+    skip_setting_turbo: 1,
+  }
+  .to_tag();
+  assert_eq!(words(&tag), [0x00038002, 12, 0, 3, 1_200_000_000, 1]);
+  assert_eq!(SetClockRate::Tag::MESSAGE_LEN, 6);
+
+  let tag = GetMaxClockRate::Request { clock_id: 3 }.to_tag();
+  assert_eq!(words(&tag), [0x00030004, 8, 0, 3, 0]);
+  let tag = GetMinClockRate::Request { clock_id: 3 }.to_tag();
+  assert_eq!(words(&tag), [0x00030007, 8, 0, 3, 0]);
+  let tag = GetTurbo::Request { id: 0 }.to_tag();
+  assert_eq!(words(&tag), [0x00030009, 8, 0, 0, 0]);
+  let tag = SetTurbo::Request { id: 0, level: 1 }.to_tag();
+  assert_eq!(words(&tag), [0x00038009, 8, 0, 0, 1]);
+}
+
+#[test]
+fn test_voltage_and_temperature_layout() {
+  let tag = GetVoltage::Request {
+    voltage_id: VoltageId::Core as u32,
+  }
+  .to_tag();
+  assert_eq!(words(&tag), [0x00030003, 8, 0, 1, 0]);
+  let tag = SetVoltage::Request {
+    voltage_id: VoltageId::SdramI as u32,
+    value: 4,
+  }
+  .to_tag();
+  assert_eq!(words(&tag), [0x00038003, 8, 0, 4, 4]);
+  let tag = GetMaxVoltage::Request { voltage_id: 1 }.to_tag();
+  assert_eq!(words(&tag)[0], 0x00030005);
+  let tag = GetMinVoltage::Request { voltage_id: 1 }.to_tag();
+  assert_eq!(words(&tag)[0], 0x00030008);
+
+  let tag = GetTemperature::Request {
+    temperature_id: TEMPERATURE_ID_SOC,
+  }
+  .to_tag();
+  assert_eq!(words(&tag), [0x00030006, 8, 0, 0, 0]);
+  let tag = GetMaxTemperature::Request {
+    temperature_id: TEMPERATURE_ID_SOC,
+  }
+  .to_tag();
+  assert_eq!(words(&tag), [0x0003000a, 8, 0, 0, 0]);
+}
+
+#[test]
+fn test_misc_layout() {
+  let tag = ResourceGetDmaChannels::Request {}.to_tag();
+  assert_eq!(words(&tag), [0x00060001, 4, 0, 0]);
+
+  let tag = GetEdidBlock::Request { block_number: 1 }.to_tag();
+  let w = words(&tag);
+  assert_eq!(w[0..4], [0x00030020, 136, 0, 1]);
+  assert_eq!(w.len(), 3 + 136 / 4);
+
+  let tag = ConfigGetCommandLine::Request {}.to_tag();
+  assert_eq!(words(&tag)[0..3], [0x00050001, COMMAND_LINE_CAP as u32, 0]);
+}
+
+#[test]
+fn test_read_response() {
+  let mut tag = GetTemperature::Request { temperature_id: 0 }.to_tag();
+  respond(&mut tag.code, 8);
+  tag.payload.response = GetTemperature::Response {
+    temperature_id: 0,
+    value: 47_850,
+  };
+  let mut message = Message::new();
+  message.add_tag(&tag).unwrap();
+  let response = GetTemperature::read_response(&message).unwrap();
+  assert_eq!(response.value(), 47_850);
+  assert_eq!(GetTemperature::response_len(&message).unwrap(), 8);
+
+  // Not processed by the firmware
+  let tag = GetTurbo::Request { id: 0 }.to_tag();
+  let mut message = Message::new();
+  message.add_tag(&tag).unwrap();
+  assert!(GetTurbo::read_response(&message).is_err());
+}
+
+#[test]
+fn test_read_variable_length_response() {
+  let mut tag = ConfigGetCommandLine::Request {}.to_tag();
+  let cmdline = b"console=ttyAMA0 root=/dev/ram0";
+  let mut response = ConfigGetCommandLine::Response {
+    command_line: [0; COMMAND_LINE_CAP],
+  };
+  response.command_line[..cmdline.len()].copy_from_slice(cmdline);
+  tag.payload.response = response;
+  respond(&mut tag.code, cmdline.len());
+  let mut message = Message::new();
+  message.add_tag(&tag).unwrap();
+  let len = ConfigGetCommandLine::response_len(&message).unwrap();
+  let response = ConfigGetCommandLine::read_response(&message).unwrap();
+  assert_eq!(&response.command_line()[..len], cmdline);
+
+  // Buffer too small, the firmware reports the required length.
+  let mut tag = ConfigGetCommandLine::Request {}.to_tag();
+  respond(&mut tag.code, COMMAND_LINE_CAP + 1);
+  let mut message = Message::new();
+  message.add_tag(&tag).unwrap();
+  assert!(ConfigGetCommandLine::read_response(&message).is_err());
+}
@@ -0,0 +1,139 @@
+// Simulated MMIO for host tests.
+//
+// Registers are a sparse map, unset registers read 0. Hooks script device
+// behaviour per address and every access is logged, so tests can assert the
+// exact register sequence of a driver:
+//
+//   let _guard = mock::initialize();
+//   mock::set(FR, FR_TXFE);
+//   mock::on_write(DR, |regs, data| regs.set(FR, FR_TXFF));
+//   driver_under_test();
+//   assert_eq!(mock::writes(), [(DR, b'a' as u32)]);
+//
+// The simulated device is global. initialize() returns a guard that
+// serializes the tests using it.
+
+use crate::io::mmio;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+  Read(u64, u32),
+  Write(u64, u32),
+}
+
+#[derive(Default)]
+pub struct Registers {
+  values: HashMap<u64, u32>,
+}
+
+impl Registers {
+  pub fn get(&self, addr: u64) -> u32 {
+    self.values.get(&addr).copied().unwrap_or(0)
+  }
+
+  pub fn set(&mut self, addr: u64, data: u32) {
+    self.values.insert(addr, data);
+  }
+}
+
+// Hooks replace the default behaviour: reads return the hook's value, writes
+// are not stored. Hooks must not access the MMIO, use the registers passed.
+type ReadHook = Box<dyn FnMut(&mut Registers) -> u32 + Send>;
+type WriteHook = Box<dyn FnMut(&mut Registers, u32) + Send>;
+
+#[derive(Default)]
+struct Device {
+  registers: Registers,
+  read_hooks: HashMap<u64, ReadHook>,
+  write_hooks: HashMap<u64, WriteHook>,
+  log: Vec<Access>,
+}
+
+static LOCK: Mutex<()> = Mutex::new(());
+static DEVICE: Mutex<Option<Device>> = Mutex::new(None);
+
+fn with_device<R>(f: impl FnOnce(&mut Device) -> R) -> R {
+  let mut device = DEVICE.lock().unwrap_or_else(|e| e.into_inner());
+  f(device.get_or_insert_with(Device::default))
+}
+
+// Resets the simulated device and registers it as the MMIO backend.
+pub fn initialize() -> MutexGuard<'static, ()> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  *DEVICE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Device::default());
+  mmio::register_device(mmio::Ops { write, read });
+  guard
+}
+
+fn write(addr: u64, data: u32) {
+  with_device(|device| {
+    device.log.push(Access::Write(addr, data));
+    match device.write_hooks.get_mut(&addr) {
+      Some(hook) => hook(&mut device.registers, data),
+      None => device.registers.set(addr, data),
+    }
+  })
+}
+
+fn read(addr: u64) -> u32 {
+  with_device(|device| {
+    let data = match device.read_hooks.get_mut(&addr) {
+      Some(hook) => hook(&mut device.registers),
+      None => device.registers.get(addr),
+    };
+    device.log.push(Access::Read(addr, data));
+    data
+  })
+}
+
+// Sets a register without logging an access.
+pub fn set(addr: u64, data: u32) {
+  with_device(|device| device.registers.set(addr, data))
+}
+
+// Reads a register without logging an access.
+pub fn get(addr: u64) -> u32 {
+  with_device(|device| device.registers.get(addr))
+}
+
+pub fn on_read(
+  addr: u64,
+  hook: impl FnMut(&mut Registers) -> u32 + Send + 'static,
+) {
+  with_device(|device| device.read_hooks.insert(addr, Box::new(hook)));
+}
+
+pub fn on_write(
+  addr: u64,
+  hook: impl FnMut(&mut Registers, u32) + Send + 'static,
+) {
+  with_device(|device| device.write_hooks.insert(addr, Box::new(hook)));
+}
+
+pub fn accesses() -> Vec<Access> {
+  with_device(|device| device.log.clone())
+}
+
+// Writes only, as (address, data).
+pub fn writes() -> Vec<(u64, u32)> {
+  with_device(|device| {
+    device
+      .log
+      .iter()
+      .filter_map(|access| match access {
+        Access::Write(addr, data) => Some((*addr, *data)),
+        Access::Read(..) => None,
+      })
+      .collect()
+  })
+}
+
+pub fn clear_log() {
+  with_device(|device| device.log.clear())
+}
+
+#[cfg(test)]
+#[path = "mock_test.rs"]
+mod mock_test;
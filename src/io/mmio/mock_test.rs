@@ -0,0 +1,50 @@
+use super::*;
+
+#[test]
+fn test_mock_registers_and_log() {
+  let _guard = initialize();
+  assert_eq!(mmio::read(0x10), 0);
+  mmio::write(0x10, 0xABCD);
+  assert_eq!(mmio::read(0x10), 0xABCD);
+  set(0x20, 7);
+  assert_eq!(get(0x20), 7);
+  assert_eq!(
+    accesses(),
+    [
+      Access::Read(0x10, 0),
+      Access::Write(0x10, 0xABCD),
+      Access::Read(0x10, 0xABCD),
+    ]
+  );
+  assert_eq!(writes(), [(0x10, 0xABCD)]);
+  clear_log();
+  assert!(accesses().is_empty());
+}
+
+#[test]
+fn test_mock_hooks() {
+  let _guard = initialize();
+  // A FIFO data register with a status register.
+  let mut fifo = vec![3, 2, 1];
+  set(0x18, 0);
+  on_read(0x00, move |regs| {
+    let data = fifo.pop().unwrap_or(0);
+    if fifo.is_empty() {
+      regs.set(0x18, 1);
+    }
+    data
+  });
+  on_write(0x04, |regs, data| regs.set(0x08, regs.get(0x08) + data));
+
+  let mut received = Vec::new();
+  while mmio::read(0x18) == 0 {
+    received.push(mmio::read(0x00));
+  }
+  assert_eq!(received, [1, 2, 3]);
+
+  mmio::write(0x04, 5);
+  mmio::write(0x04, 6);
+  // Hooked writes are not stored
+  assert_eq!(get(0x04), 0);
+  assert_eq!(get(0x08), 11);
+}
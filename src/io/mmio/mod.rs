@@ -1,4 +1,6 @@
 pub mod arm64_generic_mmio;
+#[cfg(feature = "host")]
+pub mod mock;
 
 pub struct Ops {
   write: fn(address: u64, data: u32),
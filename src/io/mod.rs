@@ -1,5 +1,8 @@
+pub mod clk;
 pub mod clock;
+pub mod dma;
 pub mod gpio;
 pub mod mailbox;
 pub mod mmio;
+pub mod power;
 pub mod uart;
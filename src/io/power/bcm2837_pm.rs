@@ -0,0 +1,45 @@
+// BCM2837 power management block.
+// The register layout is not in the peripherals datasheet, this follows
+// Linux drivers/watchdog/bcm2835_wdt.c.
+
+use crate::io::mmio;
+use crate::io::power;
+
+struct Reg;
+#[allow(dead_code)]
+impl Reg {
+  const BASE: u64 = 0x0010_00_00;
+  const PM_RSTC: u64 = Reg::BASE + 0x1C; // Reset control
+  const PM_RSTS: u64 = Reg::BASE + 0x20; // Reset status
+  const PM_WDOG: u64 = Reg::BASE + 0x24; // Watchdog timer
+}
+
+struct Bit;
+#[allow(dead_code)]
+impl Bit {
+  // Every write to PM registers must carry this password.
+  const PM_PASSWORD: u32 = 0x5A00_0000;
+  const PM_RSTC_WRCFG_CLR: u32 = 0xFFFF_FFCF;
+  const PM_RSTC_WRCFG_FULL_RESET: u32 = 0x0000_0020;
+  // Watchdog time unit is 16us.
+  const PM_WDOG_TIME_SET: u32 = 0x000F_FFFF;
+}
+
+fn reboot() -> ! {
+  // Arm the watchdog with a short timeout and let it do a full reset.
+  mmio::write(
+    Reg::PM_WDOG,
+    Bit::PM_PASSWORD | (10 & Bit::PM_WDOG_TIME_SET),
+  );
+  let rstc = mmio::read(Reg::PM_RSTC) & Bit::PM_RSTC_WRCFG_CLR;
+  mmio::write(
+    Reg::PM_RSTC,
+    Bit::PM_PASSWORD | rstc | Bit::PM_RSTC_WRCFG_FULL_RESET,
+  );
+  loop {}
+}
+
+// Initialize device driver
+pub fn initialize() {
+  power::register_device(power::Ops { reboot });
+}
@@ -0,0 +1,28 @@
+// Board power management (reset/halt).
+
+pub mod bcm2837_pm;
+
+static mut OPS: core::mem::MaybeUninit<Ops> =
+  core::mem::MaybeUninit::<Ops>::uninit();
+// This will be set to 0 during bss zero-ing.
+static mut SET: bool = false;
+
+struct Ops {
+  reboot: fn() -> !,
+}
+
+// Resets the whole board. Does not return.
+#[inline(always)]
+pub fn reboot() -> ! {
+  unsafe {
+    assert!(SET, "Power handler not set");
+    (OPS.assume_init_ref().reboot)()
+  }
+}
+
+fn register_device(ops: Ops) {
+  unsafe {
+    OPS = core::mem::MaybeUninit::<Ops>::new(ops);
+    SET = true;
+  };
+}
@@ -1,14 +1,19 @@
 use crate::common::bit;
 use crate::common::error;
 use crate::interrupt;
+use crate::io::clk;
 use crate::io::gpio;
 use crate::io::mmio;
 use crate::io::uart;
+use crate::log;
 
 use super::interrupt_supported;
 
 // BCM2837 implementation of UART0/PL011
 
+const BAUD_RATE: u32 = 115200;
+const GPIO_OWNER: &str = "uart0";
+
 pub struct InitParams {
   // Corresponding IRQ channel connected to this peripheral.
   pub irq_channel: interrupt::IrqChannel,
@@ -146,6 +151,11 @@ static HANDLE_INTERRUPTS: u32 = Bit::IMSC_RXIM /*| Bit::IMSC_TXIM*/;
 pub fn initialize(params: InitParams) {
   controller_setup();
   interrupt_setup(params.irq_channel);
+  if let Ok(clock) = clk::get("uart") {
+    clock
+      .add_notifier(on_clock_rate_change)
+      .unwrap_or_else(|e| log::warn!("No UART clock notifier: {:?}", e));
+  }
   // Register the device to UART subsystem
   uart::register_device(uart::Ops {
     getc,
@@ -154,38 +164,37 @@ pub fn initialize(params: InitParams) {
   });
 }
 
-fn controller_setup() {
+// Also runs when the panic handler re-initializes the UART, the pins are
+// then already ours.
+fn claim_pins() {
+  if gpio::owner(14) == Some(GPIO_OWNER) && gpio::owner(15) == Some(GPIO_OWNER)
+  {
+    return;
+  }
   // https://elinux.org/RPi_BCM2835_GPIOs
   // Func0 is TXD0/RXD0
-  gpio::set_function((1 << 14) | (1 << 15), gpio::Function::Func0);
+  let txd = gpio::claim::<14>(GPIO_OWNER)
+    .expect("GPIO 14 is taken")
+    .into_uart0_txd();
+  let rxd = gpio::claim::<15>(GPIO_OWNER)
+    .expect("GPIO 15 is taken")
+    .into_uart0_rxd();
   // Disable pull up/down for GPIO pin 14, 15.
-  gpio::set_pull_mode((1 << 14) | (1 << 15), gpio::PullMode::Disabled);
+  txd.set_pull(gpio::PullMode::Disabled);
+  rxd.set_pull(gpio::PullMode::Disabled);
+}
+
+fn controller_setup() {
+  claim_pins();
   // Disable everything first
   mmio::write(Reg::CR, 0x00);
   // Clear pending interrupts
   mmio::write(Reg::ICR, Bit::ICR_ALL);
 
-  // The baud rate divisor is calculated as follows:
-  // Baud rate divisor BAUDDIV = (FUARTCLK/(16 Baud rate))
-  // where FUARTCLK is the UART reference clock frequency.
-  // The BAUDDIV is comprised of the integer value IBRD and the fractional value FBRD.
-  // let base_clk_rate_hz = clock::get_clock_info(clock::ClockId::UART)
-  //   .and_then(|c| Ok(c.rate_hz))
-  //   .unwrap_or(3000000);
-
-  // Set baud rate to 115200bps
-  // Divider = 3000000 / (16 * 115200) = 1.627 = ~1.
-  // only first 16 bits
-  // let divider_integer: u32 = base_clk_rate_hz / (16 * 115200);
-  // mmio::write(Reg::IBRD, divider_integer & 0x0000_FFFF);
-  // Get the fractional part without floating point manipulation
-  // let divider_fractional = {
-  //   let remainder = base_clk_rate_hz % (16 * 115200);
-  //   // Bring the first 6 bits to integer part
-  //   (remainder * (1 << 6)) / (16 * 115200)
-  // };
-  // Fractional part register = (.627 * 64) + 0.5 = 40.6 = ~40.
-  // mmio::write(Reg::FBRD, divider_fractional & 0x00000_003F);
+  // Without a clock framework keep the divisors set by the firmware.
+  if let Ok(clock) = clk::get("uart") {
+    set_baud_divisor(clock.rate());
+  }
 
   // Enable FIFO
   // 8 bit data transmission (1 stop bit, no parity).
@@ -215,6 +224,37 @@ fn interrupt_enable() {
   unsafe { interrupt::unmask_interrupt(IRQ_CHANNEL.assume_init()) };
 }
 
+// The baud rate divisor is calculated as follows:
+// Baud rate divisor BAUDDIV = (FUARTCLK/(16 Baud rate))
+// where FUARTCLK is the UART reference clock frequency.
+// The BAUDDIV is comprised of the integer value IBRD and the fractional value
+// FBRD. Takes effect on the next LCRH write.
+fn set_baud_divisor(clock_hz: u32) {
+  if clock_hz == 0 {
+    return;
+  }
+  // e.g. 48MHz: 48000000 / (16 * 115200) = 26.04 -> IBRD 26, FBRD 3
+  let divider_integer: u32 = clock_hz / (16 * BAUD_RATE);
+  // Get the fractional part without floating point manipulation, rounded
+  let divider_fractional = {
+    let remainder = clock_hz % (16 * BAUD_RATE);
+    (remainder * (1 << 6) + 8 * BAUD_RATE) / (16 * BAUD_RATE)
+  };
+  mmio::write(Reg::IBRD, divider_integer & 0x0000_FFFF);
+  mmio::write(Reg::FBRD, divider_fractional & 0x0000_003F);
+}
+
+fn on_clock_rate_change(change: &clk::RateChange) {
+  // Let the transmitter finish, then reprogram with the UART disabled.
+  while mmio::read(Reg::FR) & Bit::FR_BUSY != 0 {}
+  let cr = mmio::read(Reg::CR);
+  mmio::write(Reg::CR, 0x00);
+  set_baud_divisor(change.new_hz);
+  mmio::write(Reg::LCRH, mmio::read(Reg::LCRH));
+  mmio::write(Reg::CR, cr);
+  log::info!("UART clock {} Hz, baud divisor updated", change.new_hz);
+}
+
 fn interrupt_setup(irq_channel: interrupt::IrqChannel) {
   // Enable relevant interrupts
   // only Receive
@@ -243,10 +283,7 @@ fn handle_irq() {
   }
 
   if interrupts_unmasked != Bit::RIS_RXRIS {
-    crate::common::stream::println!(
-      "UNHANDLED INTERRUPT: {:b}",
-      interrupts_unmasked
-    );
+    log::error!("Unhandled interrupt: {:b}", interrupts_unmasked);
     panic!("Unhandled interrupt {}", interrupts_unmasked);
   }
 }
@@ -266,3 +303,7 @@ fn on_rx_fifo() {
     uart::on_receive(payload);
   }
 }
+
+#[cfg(test)]
+#[path = "bcm2837_pl011_test.rs"]
+mod bcm2837_pl011_test;
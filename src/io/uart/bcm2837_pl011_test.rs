@@ -0,0 +1,69 @@
+use super::*;
+use crate::io::mmio::mock::{self, Access};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_putc_waits_for_tx_fifo() {
+  let _guard = mock::initialize();
+  // TX FIFO full for two polls
+  let mut polls = 0;
+  mock::on_read(Reg::FR, move |_| {
+    polls += 1;
+    if polls <= 2 {
+      Bit::FR_TXFF
+    } else {
+      0
+    }
+  });
+  putc(b'a');
+  assert_eq!(
+    mock::accesses(),
+    [
+      Access::Read(Reg::FR, Bit::FR_TXFF),
+      Access::Read(Reg::FR, Bit::FR_TXFF),
+      Access::Read(Reg::FR, 0),
+      Access::Write(Reg::DR, b'a' as u32),
+    ]
+  );
+}
+
+#[test]
+fn test_getc_reads_rx_fifo() {
+  let _guard = mock::initialize();
+  let fifo = Arc::new(Mutex::new(b"ok".to_vec()));
+  let status = fifo.clone();
+  mock::on_read(Reg::FR, move |_| {
+    if status.lock().unwrap().is_empty() {
+      Bit::FR_RXFE
+    } else {
+      0
+    }
+  });
+  // Upper bits carry error flags
+  mock::on_read(Reg::DR, move |_| {
+    0x0F00 | fifo.lock().unwrap().remove(0) as u32
+  });
+  assert_eq!(getc(), b'o');
+  assert_eq!(getc(), b'k');
+  assert_eq!(mmio::read(Reg::FR), Bit::FR_RXFE);
+}
+
+#[test]
+fn test_baud_divisor() {
+  let _guard = mock::initialize();
+  // 48MHz / (16 * 115200) = 26.04
+  set_baud_divisor(48_000_000);
+  // 3MHz / (16 * 115200) = 1.627
+  set_baud_divisor(3_000_000);
+  // Unknown rate, keep the firmware setting
+  set_baud_divisor(0);
+  assert_eq!(
+    mock::writes(),
+    [
+      (Reg::IBRD, 26),
+      (Reg::FBRD, 3),
+      (Reg::IBRD, 1),
+      (Reg::FBRD, 40)
+    ]
+  );
+}
@@ -1,12 +1,17 @@
+// The mock is global. initialize() returns a guard that serializes the
+// tests using it.
+
 use crate::io::uart;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
 
 // Mock UART buffer for testing
 static MOCK_UART_BUFFER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
 static MOCK_INPUT: Mutex<Vec<char>> = Mutex::new(Vec::new());
+static LOCK: Mutex<()> = Mutex::new(());
 
 // Test helper functions
-pub fn initialize() {
+pub fn initialize() -> MutexGuard<'static, ()> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
   *MOCK_UART_BUFFER.lock().unwrap() = Vec::new();
   *MOCK_INPUT.lock().unwrap() = Vec::new();
 
@@ -15,6 +20,7 @@ pub fn initialize() {
     getc: mock_getc,
     interrupt_enable: None,
   });
+  guard
 }
 
 fn mock_putc(c: u8) {
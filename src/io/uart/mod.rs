@@ -3,6 +3,7 @@ pub mod bcm2837_pl011;
 pub mod mock;
 
 use crate::common;
+use crate::log;
 use crate::tty;
 use arrayvec::ArrayVec;
 
@@ -75,9 +76,18 @@ fn on_receive(payload: Payload) {
   }
 }
 
-pub fn set_as_stream() {
+// Serial output accepts everything up to Info, see `common::stream`.
+pub fn stream_sink() -> common::stream::Sink {
   unsafe { assert!(SET, "UART not set") };
-  common::stream::assign(common::stream::OutputOps { write: puts_ok })
+  common::stream::Sink {
+    name: "uart",
+    level: log::Level::Info,
+    write: puts_ok,
+  }
+}
+
+pub fn set_as_stream() -> Result<(), common::error::ErrorKind> {
+  common::stream::attach(stream_sink())
 }
 
 pub fn as_tty_adapter() -> tty::TtyStreamAdapter {
@@ -0,0 +1,396 @@
+use super::parser::parse_u64;
+use super::{Args, Command};
+use crate::common::error::ErrorKind;
+use crate::common::stream;
+use crate::container::arrayvec_extensions;
+use crate::diagnostic;
+use crate::gdb;
+use crate::interrupt;
+use crate::interrupt::bcm2837_interrupt;
+use crate::io::clk;
+use crate::io::clock;
+use crate::io::gpio;
+use crate::io::mailbox;
+use crate::io::power;
+use crate::io::uart;
+use crate::log;
+use crate::metadata::board;
+use crate::video::framebuffer;
+
+const BUILTINS: [Command; 16] = [
+  Command {
+    name: "help",
+    help: "List available commands",
+    run: help,
+  },
+  Command {
+    name: "echo",
+    help: "Print arguments",
+    run: echo,
+  },
+  Command {
+    name: "boardinfo",
+    help: "Print board model, serial and attributes",
+    run: boardinfo,
+  },
+  Command {
+    name: "clocks",
+    help: "Print VideoCore clock states and rates",
+    run: clocks,
+  },
+  Command {
+    name: "clk",
+    help: "clk [<name> <rate_hz>], print the clock tree or set a rate",
+    run: clk,
+  },
+  Command {
+    name: "meminfo",
+    help: "Print ARM and VideoCore memory split",
+    run: meminfo,
+  },
+  Command {
+    name: "sensors",
+    help: "Print SoC temperature, core voltage and turbo state",
+    run: sensors,
+  },
+  Command {
+    name: "irqstat",
+    help: "Print served count of each IRQ handler",
+    run: irqstat,
+  },
+  Command {
+    name: "gpio",
+    help: "gpio status, list claimed pins with owner, mode and level",
+    run: gpio,
+  },
+  Command {
+    name: "fb",
+    help: "Print framebuffer geometry, `fb test` draws colour bars",
+    run: fb,
+  },
+  Command {
+    name: "dmesg",
+    help: "Print the kernel log buffer, `dmesg -c` clears it",
+    run: dmesg,
+  },
+  Command {
+    name: "loglevel",
+    help: "loglevel [<module>|default|sink:<name>] <level>",
+    run: loglevel,
+  },
+  Command {
+    name: "streams",
+    help: "streams [<name> <level>], list or configure output streams",
+    run: streams,
+  },
+  Command {
+    name: "gdb",
+    help: "Stop and wait for GDB on the console UART",
+    run: gdb,
+  },
+  Command {
+    name: "reboot",
+    help: "Reset the board",
+    run: reboot,
+  },
+  Command {
+    name: "diag",
+    help: "Run a diagnostic (does not return), see `diag` for the list",
+    run: diag,
+  },
+];
+
+pub fn register_all() {
+  for command in BUILTINS {
+    super::register(command).expect("Failed to register builtin");
+  }
+}
+
+fn help(_: &Args) -> Result<(), ErrorKind> {
+  for command in super::registry().iter() {
+    stream::println!("{:<12}{}", command.name, command.help);
+  }
+  Ok(())
+}
+
+fn echo(args: &Args) -> Result<(), ErrorKind> {
+  for (idx, arg) in args.iter().skip(1).enumerate() {
+    if idx > 0 {
+      stream::print!(" ");
+    }
+    stream::print!("{}", arg);
+  }
+  stream::println!();
+  Ok(())
+}
+
+fn boardinfo(_: &Args) -> Result<(), ErrorKind> {
+  let board_info = board::get_board_info();
+  stream::println!("Model: {}", board_info.model);
+  stream::println!("Serial: {}", board_info.serial);
+  for attribute in &board_info.attributes {
+    stream::println!("{}: {}", attribute.key, attribute.value);
+  }
+  Ok(())
+}
+
+fn clocks(_: &Args) -> Result<(), ErrorKind> {
+  stream::println!(
+    "{:<10}{:<8}{:<8}{}",
+    "CLOCK",
+    "EXISTS",
+    "ACTIVE",
+    "RATE(Hz)"
+  );
+  for clock_id in clock::ClockId::ALL {
+    let info = clock::get_clock_info(clock_id)?;
+    stream::println!(
+      "{:<10}{:<8}{:<8}{}",
+      clock_id.as_str(),
+      info.exists,
+      info.active,
+      info.rate_hz
+    );
+  }
+  Ok(())
+}
+
+fn clk(args: &Args) -> Result<(), ErrorKind> {
+  if let (Some(name), Some(rate)) = (args.get(1), args.get(2)) {
+    let rate_hz = parse_u64(rate).ok_or(ErrorKind::InvalidInput)?;
+    let rate_hz =
+      u32::try_from(rate_hz).map_err(|_| ErrorKind::InvalidInput)?;
+    let rate_hz = clk::get(name)?.set_rate(rate_hz)?;
+    stream::println!("{}: {} Hz", name, rate_hz);
+    return Ok(());
+  }
+  stream::println!("{:<16}{:<8}{}", "CLOCK", "USERS", "RATE(Hz)");
+  clk::walk(|clock, depth| {
+    let indent = 2 * depth;
+    let width = 16 - indent;
+    stream::println!(
+      "{:indent$}{:<width$}{:<8}{}",
+      "",
+      clock.name(),
+      clock.enable_count(),
+      clock.rate()
+    );
+  });
+  Ok(())
+}
+
+fn meminfo(_: &Args) -> Result<(), ErrorKind> {
+  use mailbox::tag::{HwGetArmMemory, HwGetVideocoreMemory};
+
+  let mut message = mailbox::Message::new();
+  message
+    .add_tag(&HwGetArmMemory::Request {}.to_tag())?
+    .add_tag(&HwGetVideocoreMemory::Request {}.to_tag())?;
+  mailbox::send(&mut message)?;
+  let arm = HwGetArmMemory::read_response(&message)?;
+  let vc = HwGetVideocoreMemory::read_response(&message)?;
+  stream::println!(
+    "ARM:       base {:#010X}, {} KiB",
+    arm.base_address(),
+    arm.size_bytes() / 1024
+  );
+  stream::println!(
+    "VideoCore: base {:#010X}, {} KiB",
+    vc.base_address(),
+    vc.size_bytes() / 1024
+  );
+  Ok(())
+}
+
+fn sensors(_: &Args) -> Result<(), ErrorKind> {
+  use mailbox::tag::{GetMaxTemperature, GetTemperature, GetTurbo, GetVoltage};
+
+  let temperature_tag = GetTemperature::Request {
+    temperature_id: mailbox::tag::TEMPERATURE_ID_SOC,
+  }
+  .to_tag();
+  let max_temperature_tag = GetMaxTemperature::Request {
+    temperature_id: mailbox::tag::TEMPERATURE_ID_SOC,
+  }
+  .to_tag();
+  let voltage_tag = GetVoltage::Request {
+    voltage_id: mailbox::tag::VoltageId::Core as u32,
+  }
+  .to_tag();
+  let mut message = mailbox::Message::new();
+  message
+    .add_tag(&temperature_tag)?
+    .add_tag(&max_temperature_tag)?
+    .add_tag(&voltage_tag)?
+    .add_tag(&GetTurbo::Request { id: 0 }.to_tag())?;
+  mailbox::send(&mut message)?;
+  let temperature = GetTemperature::read_response(&message)?.value();
+  let max_temperature = GetMaxTemperature::read_response(&message)?.value();
+  // Offset from 1.2V in 25mV steps
+  let core_mv = 1200 + 25 * GetVoltage::read_response(&message)?.value() as i32;
+  let turbo = GetTurbo::read_response(&message)?.level();
+  stream::println!(
+    "Temperature: {}.{:03} C (max {}.{:03} C)",
+    temperature / 1000,
+    temperature % 1000,
+    max_temperature / 1000,
+    max_temperature % 1000
+  );
+  stream::println!("Core voltage: {} mV", core_mv);
+  stream::println!("Turbo: {}", if turbo != 0 { "on" } else { "off" });
+  Ok(())
+}
+
+fn irqstat(_: &Args) -> Result<(), ErrorKind> {
+  stream::println!("{:<12}{:<8}{}", "DOMAIN", "IRQ", "COUNT");
+  for stat in interrupt::stats() {
+    let domain = if stat.channel.domain == bcm2837_interrupt::domains::ARM {
+      "arm"
+    } else if stat.channel.domain == bcm2837_interrupt::domains::PERIPHERAL {
+      "peripheral"
+    } else {
+      "?"
+    };
+    stream::println!("{:<12}{:<8}{}", domain, stat.channel.number, stat.count);
+  }
+  Ok(())
+}
+
+fn gpio(args: &Args) -> Result<(), ErrorKind> {
+  if args.get(1) != Some("status") {
+    stream::println!("usage: gpio status");
+    return Ok(());
+  }
+  stream::println!("{:<6}{:<12}{:<20}{}", "PIN", "OWNER", "MODE", "LEVEL");
+  for pin in gpio::status() {
+    // Padding needs a single string
+    let mode = arrayvec_extensions::capped_format!(20, "{}", pin.mode);
+    let level = gpio::read(1 << pin.number) != 0;
+    stream::println!(
+      "{:<6}{:<12}{:<20}{}",
+      pin.number,
+      pin.owner,
+      mode,
+      level as u8
+    );
+  }
+  Ok(())
+}
+
+fn fb(args: &Args) -> Result<(), ErrorKind> {
+  let fb = framebuffer::get().ok_or(ErrorKind::NotFound)?;
+  let info = fb.info();
+  stream::println!(
+    "{}x{} depth {} pitch {} order {:?}",
+    info.width,
+    info.height,
+    info.depth,
+    info.pitch,
+    info.order
+  );
+  if args.get(1) == Some("test") {
+    const BARS: [framebuffer::Color; 8] = [
+      framebuffer::Color::WHITE,
+      framebuffer::Color::YELLOW,
+      framebuffer::Color::CYAN,
+      framebuffer::Color::GREEN,
+      framebuffer::Color::MAGENTA,
+      framebuffer::Color::RED,
+      framebuffer::Color::BLUE,
+      framebuffer::Color::BLACK,
+    ];
+    let bar_width = info.width / BARS.len() as u32;
+    for (i, color) in BARS.iter().enumerate() {
+      fb.fill_rect(i as u32 * bar_width, 0, bar_width, info.height, *color);
+    }
+  }
+  Ok(())
+}
+
+fn dmesg(args: &Args) -> Result<(), ErrorKind> {
+  log::dmesg(|line| stream::println!("{}", line));
+  if args.get(1) == Some("-c") {
+    log::clear_buffer();
+  }
+  Ok(())
+}
+
+fn loglevel(args: &Args) -> Result<(), ErrorKind> {
+  let (Some(target), Some(level)) = (args.get(1), args.get(2)) else {
+    stream::println!("default: {}", log::default_level());
+    return Ok(());
+  };
+  let level = log::Level::from_str(level).ok_or(ErrorKind::InvalidInput)?;
+  match target {
+    "default" => log::set_default_level(level),
+    _ => match target.strip_prefix("sink:") {
+      Some(sink) => log::set_sink_level(sink, level)?,
+      None => log::set_level(target, level)?,
+    },
+  }
+  Ok(())
+}
+
+fn streams(args: &Args) -> Result<(), ErrorKind> {
+  if let (Some(name), Some(level)) = (args.get(1), args.get(2)) {
+    let level = log::Level::from_str(level).ok_or(ErrorKind::InvalidInput)?;
+    return stream::set_level(name, level);
+  }
+  for sink in stream::sinks() {
+    stream::println!("{:<12}{}", sink.name, sink.level);
+  }
+  Ok(())
+}
+
+fn gdb(_: &Args) -> Result<(), ErrorKind> {
+  stream::println!("Waiting for GDB, detach the terminal and connect");
+  gdb::attach(gdb::Serial {
+    getc: uart::getc,
+    putc: uart::putc,
+  });
+  gdb::breakpoint()
+}
+
+fn reboot(_: &Args) -> Result<(), ErrorKind> {
+  stream::println!("Rebooting...");
+  power::reboot();
+}
+
+const DIAGNOSTICS: [&str; 8] = [
+  "interrupt",
+  "uart",
+  "uart_interrupt",
+  "panic",
+  "board_info",
+  "mailbox",
+  "clock",
+  "led <gpio>",
+];
+
+fn diag(args: &Args) -> Result<(), ErrorKind> {
+  match args.get(1) {
+    Some("interrupt") => diagnostic::test_interrupt(),
+    Some("uart") => diagnostic::test_uart(),
+    Some("uart_interrupt") => diagnostic::test_uart_interrupt(),
+    Some("panic") => diagnostic::test_panic(),
+    Some("board_info") => diagnostic::test_board_info(),
+    Some("mailbox") => diagnostic::test_mailbox(),
+    Some("clock") => diagnostic::test_videocore_base_clock(),
+    Some("led") => {
+      let gpio = args
+        .get(2)
+        .and_then(parse_u64)
+        .ok_or(ErrorKind::InvalidInput)?;
+      let gpio = u8::try_from(gpio).map_err(|_| ErrorKind::InvalidInput)?;
+      let led = gpio::claim_any(gpio, "diag")?.into_output();
+      diagnostic::test_led_blink(led);
+    }
+    _ => {
+      stream::println!("usage: diag <name>");
+      for name in DIAGNOSTICS {
+        stream::println!("  {}", name);
+      }
+      Ok(())
+    }
+  }
+}
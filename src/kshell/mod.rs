@@ -0,0 +1,153 @@
+// Interactive kernel shell over a Tty.
+//
+// Commands are plain functions registered by name. The built-in commands are
+// in builtins.rs; other modules may register their own before run().
+
+mod builtins;
+pub mod parser;
+
+use crate::common::error::ErrorKind;
+use crate::common::stream;
+use crate::tty::completion::{Candidates, Completer};
+use crate::tty::{Tty, TtyError};
+use arrayvec::{ArrayString, ArrayVec};
+pub use parser::Args;
+
+const PROMPT: &str = "kshell> ";
+pub const MAX_COMMANDS: usize = 32;
+
+pub type CommandFn = fn(&Args) -> Result<(), ErrorKind>;
+
+#[derive(Clone, Copy)]
+pub struct Command {
+  pub name: &'static str,
+  // One line description for `help`.
+  pub help: &'static str,
+  pub run: CommandFn,
+}
+
+pub struct Registry {
+  commands: ArrayVec<Command, MAX_COMMANDS>,
+}
+
+impl Registry {
+  pub const fn new() -> Self {
+    Self {
+      commands: ArrayVec::new_const(),
+    }
+  }
+
+  pub fn register(&mut self, command: Command) -> Result<(), ErrorKind> {
+    if self.find(command.name).is_some() {
+      return Err(ErrorKind::AlreadyExists);
+    }
+    self
+      .commands
+      .try_push(command)
+      .map_err(|_| ErrorKind::StorageFull)
+  }
+
+  pub fn find(&self, name: &str) -> Option<&Command> {
+    self.commands.iter().find(|command| command.name == name)
+  }
+
+  // Commands whose name starts with `prefix`, in registration order.
+  pub fn complete<'a>(
+    &'a self,
+    prefix: &'a str,
+  ) -> impl Iterator<Item = &'a Command> + 'a {
+    self
+      .commands
+      .iter()
+      .filter(move |command| command.name.starts_with(prefix))
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &Command> {
+    self.commands.iter()
+  }
+
+  pub fn execute(&self, args: &Args) -> Result<(), ErrorKind> {
+    match self.find(args.name()) {
+      Some(command) => (command.run)(args),
+      None => Err(ErrorKind::NotFound),
+    }
+  }
+}
+
+// The shell is single threaded and never runs in IRQ context.
+static mut REGISTRY: Registry = Registry::new();
+
+pub fn registry() -> &'static Registry {
+  unsafe { &REGISTRY }
+}
+
+pub fn register(command: Command) -> Result<(), ErrorKind> {
+  unsafe { REGISTRY.register(command) }
+}
+
+// Registers the built-in commands.
+pub fn init() {
+  builtins::register_all();
+}
+
+// Parses and executes a single command line.
+pub fn execute_line(line: &str) {
+  let args = match parser::parse(line) {
+    Ok(args) => args,
+    Err(err) => {
+      stream::println!("parse error: {:?}", err);
+      return;
+    }
+  };
+  if args.is_empty() {
+    return;
+  }
+
+  match registry().execute(&args) {
+    Ok(()) => {}
+    Err(ErrorKind::NotFound) if registry().find(args.name()).is_none() => {
+      stream::println!("{}: command not found", args.name());
+    }
+    Err(kind) => stream::println!("{}: {}", args.name(), kind),
+  }
+}
+
+// Completes command names in the first word of the line.
+struct CommandCompleter;
+
+impl Completer for CommandCompleter {
+  fn complete(&self, line: &str, candidates: &mut Candidates) -> usize {
+    let start = line.rfind([' ', '\t']).map_or(0, |idx| idx + 1);
+    if !line[..start].trim().is_empty() {
+      return start;
+    }
+    for command in registry().complete(&line[start..]) {
+      if let Ok(name) = ArrayString::from(command.name) {
+        if candidates.try_push(name).is_err() {
+          break;
+        }
+      }
+    }
+    start
+  }
+}
+
+static COMPLETER: CommandCompleter = CommandCompleter;
+
+pub fn run(tty: &mut Tty) -> ! {
+  tty.set_prompt(PROMPT);
+  tty.set_completer(&COMPLETER);
+  loop {
+    match tty.read_line() {
+      Ok(line) => execute_line(line),
+      // Ctrl-C or Ctrl-D, start over on a new line.
+      Err(TtyError::ReadError) | Err(TtyError::EndOfFile) => {
+        let _ = tty.write("\n");
+      }
+      Err(TtyError::WriteError) => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod registry_test;
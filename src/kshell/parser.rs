@@ -0,0 +1,125 @@
+use arrayvec::{ArrayString, ArrayVec};
+
+pub const MAX_ARGS: usize = 16;
+pub const ARG_CAP: usize = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+  UnterminatedQuote,
+  TooManyArgs,
+  ArgTooLong,
+}
+
+// Parsed command line. argv[0] is the command name.
+#[derive(Debug)]
+pub struct Args {
+  argv: ArrayVec<ArrayString<ARG_CAP>, MAX_ARGS>,
+}
+
+impl Args {
+  pub fn len(&self) -> usize {
+    self.argv.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.argv.is_empty()
+  }
+
+  pub fn get(&self, idx: usize) -> Option<&str> {
+    self.argv.get(idx).map(|arg| arg.as_str())
+  }
+
+  pub fn name(&self) -> &str {
+    self.get(0).unwrap_or("")
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &str> {
+    self.argv.iter().map(|arg| arg.as_str())
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Quote {
+  None,
+  Single,
+  Double,
+}
+
+// Splits a line into whitespace separated arguments.
+//
+// - '...' takes everything literally
+// - "..." allows \" and \\ escapes
+// - \ outside of quotes escapes the next character
+pub fn parse(line: &str) -> Result<Args, ParseError> {
+  let mut argv = ArrayVec::<ArrayString<ARG_CAP>, MAX_ARGS>::new();
+  let mut current = ArrayString::<ARG_CAP>::new();
+  // An argument was started, even if it's empty (e.g. "").
+  let mut in_arg = false;
+  let mut quote = Quote::None;
+  let mut chars = line.chars();
+
+  while let Some(ch) = chars.next() {
+    let literal = match (quote, ch) {
+      (Quote::None, ' ' | '\t') => {
+        if in_arg {
+          argv
+            .try_push(current)
+            .map_err(|_| ParseError::TooManyArgs)?;
+          current = ArrayString::new();
+          in_arg = false;
+        }
+        continue;
+      }
+      (Quote::None, '\'') => {
+        quote = Quote::Single;
+        in_arg = true;
+        continue;
+      }
+      (Quote::None, '"') => {
+        quote = Quote::Double;
+        in_arg = true;
+        continue;
+      }
+      (Quote::Single, '\'') | (Quote::Double, '"') => {
+        quote = Quote::None;
+        continue;
+      }
+      (Quote::None, '\\') => chars.next().unwrap_or('\\'),
+      (Quote::Double, '\\') => match chars.next() {
+        Some(escaped @ ('"' | '\\')) => escaped,
+        Some(other) => {
+          current.try_push('\\').map_err(|_| ParseError::ArgTooLong)?;
+          other
+        }
+        None => return Err(ParseError::UnterminatedQuote),
+      },
+      (_, ch) => ch,
+    };
+    current
+      .try_push(literal)
+      .map_err(|_| ParseError::ArgTooLong)?;
+    in_arg = true;
+  }
+
+  if quote != Quote::None {
+    return Err(ParseError::UnterminatedQuote);
+  }
+  if in_arg {
+    argv
+      .try_push(current)
+      .map_err(|_| ParseError::TooManyArgs)?;
+  }
+  Ok(Args { argv })
+}
+
+// Parses decimal or 0x-prefixed hexadecimal numbers.
+pub fn parse_u64(arg: &str) -> Option<u64> {
+  match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+    Some(hex) => u64::from_str_radix(hex, 16).ok(),
+    None => arg.parse::<u64>().ok(),
+  }
+}
+
+#[cfg(test)]
+#[path = "parser_test.rs"]
+mod parser_test;
@@ -0,0 +1,61 @@
+use super::*;
+
+fn argv(line: &str) -> Vec<String> {
+  parse(line)
+    .expect("Parse should succeed")
+    .iter()
+    .map(String::from)
+    .collect()
+}
+
+#[test]
+fn test_parse_words() {
+  assert_eq!(argv("help"), ["help"]);
+  assert_eq!(argv("  diag   uart  "), ["diag", "uart"]);
+  assert_eq!(argv("a\tb"), ["a", "b"]);
+  assert!(parse("").unwrap().is_empty());
+  assert!(parse("   ").unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_quotes() {
+  assert_eq!(argv("echo \"hello world\""), ["echo", "hello world"]);
+  assert_eq!(argv("echo 'a \"b\" c'"), ["echo", "a \"b\" c"]);
+  assert_eq!(argv("echo \"\""), ["echo", ""]);
+  assert_eq!(argv("echo ab\"c d\"e"), ["echo", "abc de"]);
+}
+
+#[test]
+fn test_parse_escapes() {
+  assert_eq!(argv("echo a\\ b"), ["echo", "a b"]);
+  assert_eq!(argv("echo \"a\\\"b\""), ["echo", "a\"b"]);
+  assert_eq!(argv("echo \"a\\nb\""), ["echo", "a\\nb"]);
+  assert_eq!(argv("echo 'a\\b'"), ["echo", "a\\b"]);
+}
+
+#[test]
+fn test_parse_errors() {
+  assert_eq!(
+    parse("echo \"abc").unwrap_err(),
+    ParseError::UnterminatedQuote
+  );
+  assert_eq!(
+    parse("echo 'abc").unwrap_err(),
+    ParseError::UnterminatedQuote
+  );
+
+  let too_many = "a ".repeat(MAX_ARGS + 1);
+  assert_eq!(parse(&too_many).unwrap_err(), ParseError::TooManyArgs);
+
+  let too_long = "a".repeat(ARG_CAP + 1);
+  assert_eq!(parse(&too_long).unwrap_err(), ParseError::ArgTooLong);
+}
+
+#[test]
+fn test_parse_u64() {
+  assert_eq!(parse_u64("42"), Some(42));
+  assert_eq!(parse_u64("0x1F"), Some(0x1F));
+  assert_eq!(parse_u64("0X1f"), Some(0x1F));
+  assert_eq!(parse_u64("abc"), None);
+  assert_eq!(parse_u64("0x"), None);
+}
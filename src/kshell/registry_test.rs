@@ -0,0 +1,62 @@
+use super::*;
+use crate::common::error::ErrorKind;
+
+fn ok(_: &Args) -> Result<(), ErrorKind> {
+  Ok(())
+}
+
+fn fail(_: &Args) -> Result<(), ErrorKind> {
+  Err(ErrorKind::InvalidInput)
+}
+
+fn command(name: &'static str, run: CommandFn) -> Command {
+  Command {
+    name,
+    help: "",
+    run,
+  }
+}
+
+#[test]
+fn test_registry_register() {
+  let mut registry = Registry::new();
+  assert!(registry.register(command("clocks", ok)).is_ok());
+  assert!(matches!(
+    registry.register(command("clocks", ok)),
+    Err(ErrorKind::AlreadyExists)
+  ));
+  assert!(registry.find("clocks").is_some());
+  assert!(registry.find("clock").is_none());
+}
+
+#[test]
+fn test_registry_execute() {
+  let mut registry = Registry::new();
+  registry.register(command("ok", ok)).unwrap();
+  registry.register(command("fail", fail)).unwrap();
+
+  let args = parser::parse("ok a b").unwrap();
+  assert!(registry.execute(&args).is_ok());
+  let args = parser::parse("fail").unwrap();
+  assert!(matches!(
+    registry.execute(&args),
+    Err(ErrorKind::InvalidInput)
+  ));
+  let args = parser::parse("missing").unwrap();
+  assert!(matches!(registry.execute(&args), Err(ErrorKind::NotFound)));
+}
+
+#[test]
+fn test_registry_complete() {
+  let mut registry = Registry::new();
+  registry.register(command("clocks", ok)).unwrap();
+  registry.register(command("boardinfo", ok)).unwrap();
+  registry.register(command("clear", ok)).unwrap();
+
+  let names: Vec<&str> = registry.complete("cl").map(|c| c.name).collect();
+  assert_eq!(names, ["clocks", "clear"]);
+  let names: Vec<&str> = registry.complete("b").map(|c| c.name).collect();
+  assert_eq!(names, ["boardinfo"]);
+  assert_eq!(registry.complete("x").count(), 0);
+  assert_eq!(registry.complete("").count(), 3);
+}
@@ -0,0 +1,71 @@
+use super::*;
+
+fn passes() -> Outcome {
+  ktest_assert_eq!(1 + 1, 2);
+  Ok(())
+}
+
+fn fails() -> Outcome {
+  let value = 3;
+  ktest_assert!(value < 2, "value is {}", value);
+  Ok(())
+}
+
+fn fails_eq() -> Outcome {
+  ktest_assert_eq!("a", "b");
+  Ok(())
+}
+
+const TESTS: [KTest; 3] = [
+  KTest {
+    name: "passes",
+    run: passes,
+  },
+  KTest {
+    name: "fails",
+    run: fails,
+  },
+  KTest {
+    name: "fails_eq",
+    run: fails_eq,
+  },
+];
+
+#[test]
+fn test_run_all_reports() {
+  let mut out = String::new();
+  let summary = run_all(&TESTS, &mut out);
+  assert_eq!(
+    summary,
+    Summary {
+      passed: 1,
+      failed: 2
+    }
+  );
+  assert_eq!(summary.exit_code(), 1);
+
+  let lines: Vec<&str> = out.lines().collect();
+  assert_eq!(lines[0], "KTEST BEGIN 3");
+  assert_eq!(lines[1], "KTEST RUN passes");
+  assert_eq!(lines[2], "KTEST PASS passes");
+  assert_eq!(lines[3], "KTEST RUN fails");
+  assert!(lines[4].starts_with("KTEST FAIL fails src/ktest/ktest_test.rs:"));
+  assert!(lines[4].ends_with(" value is 3"));
+  assert!(lines[6].ends_with(" \"a\" != \"b\""));
+  assert_eq!(lines[7], "KTEST END passed=1 failed=2");
+}
+
+#[test]
+fn test_run_all_empty() {
+  let mut out = String::new();
+  let summary = run_all(&[], &mut out);
+  assert_eq!(summary.exit_code(), 0);
+  assert_eq!(out, "KTEST BEGIN 0\nKTEST END passed=0 failed=0\n");
+}
+
+#[test]
+fn test_on_panic_outside_test() {
+  let mut out = String::new();
+  on_panic(&mut out, "boom", "x.rs", 1);
+  assert!(out.is_empty());
+}
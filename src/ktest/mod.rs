@@ -0,0 +1,194 @@
+// In-kernel tests, run on the device or QEMU by the `ktest` build.
+//
+// Tests are functions marked #[ktest]. They are collected in the `.ktest`
+// linker section and run by the ktest kernel_main. Results are reported on
+// the stream, one line per event:
+//
+//   KTEST BEGIN <count>
+//   KTEST RUN <name>
+//   KTEST PASS <name>
+//   KTEST FAIL <name> <file>:<line> <message>
+//   KTEST END passed=<passed> failed=<failed>
+//
+// and the kernel exits with 0 if everything passed, 1 otherwise. A test
+// fails by returning Err (see ktest_assert!) or by panicking. A panic ends
+// the run, the remaining tests are not run.
+
+use arrayvec::ArrayString;
+
+#[cfg(feature = "ktest")]
+pub use ktest_macros::ktest;
+
+#[cfg(feature = "ktest")]
+mod smoke;
+
+// Keeps Outcome small enough to return by value.
+pub const MESSAGE_SIZE: usize = 96;
+
+pub struct Failure {
+  pub message: ArrayString<MESSAGE_SIZE>,
+  pub file: &'static str,
+  pub line: u32,
+}
+
+pub type Outcome = Result<(), Failure>;
+
+pub struct KTest {
+  pub name: &'static str,
+  pub run: fn() -> Outcome,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Summary {
+  pub passed: usize,
+  pub failed: usize,
+}
+
+impl Summary {
+  pub fn exit_code(&self) -> u32 {
+    if self.failed == 0 {
+      0
+    } else {
+      1
+    }
+  }
+}
+
+pub struct Ops {
+  // Stops the machine, reporting `code` to the host.
+  pub exit: fn(code: u32) -> !,
+}
+
+static mut OPS: core::mem::MaybeUninit<Ops> =
+  core::mem::MaybeUninit::<Ops>::uninit();
+// This will be set to 0 during bss zero-ing.
+static mut SET: bool = false;
+// Test being run, for panics. Zeroed with bss as well.
+static mut CURRENT: Option<&'static str> = None;
+static mut SUMMARY: Summary = Summary {
+  passed: 0,
+  failed: 0,
+};
+
+pub fn register(ops: Ops) {
+  unsafe {
+    OPS = core::mem::MaybeUninit::<Ops>::new(ops);
+    SET = true;
+  };
+}
+
+#[macro_export]
+macro_rules! ktest_assert {
+  ($cond:expr) => {
+    $crate::ktest_assert!($cond, "assertion failed: {}", stringify!($cond))
+  };
+  ($cond:expr, $( $arg:expr ),+) => {
+    if !$cond {
+      return Err($crate::ktest::Failure {
+        message: $crate::container::arrayvec_extensions::capped_format!(
+          { $crate::ktest::MESSAGE_SIZE },
+          $($arg),+
+        ),
+        file: file!(),
+        line: line!(),
+      });
+    }
+  };
+}
+
+#[macro_export]
+macro_rules! ktest_assert_eq {
+  ($left:expr, $right:expr) => {{
+    let (left, right) = (&$left, &$right);
+    $crate::ktest_assert!(*left == *right, "{:?} != {:?}", left, right);
+  }};
+}
+
+#[cfg(feature = "ktest")]
+pub use ktest_assert;
+#[cfg(feature = "ktest")]
+pub use ktest_assert_eq;
+
+// Runs `tests` in order, reporting to `out`.
+pub fn run_all(tests: &[KTest], out: &mut impl core::fmt::Write) -> Summary {
+  let mut summary = Summary::default();
+  let _ = writeln!(out, "KTEST BEGIN {}", tests.len());
+  for test in tests {
+    let _ = writeln!(out, "KTEST RUN {}", test.name);
+    unsafe { CURRENT = Some(test.name) };
+    match (test.run)() {
+      Ok(()) => {
+        summary.passed += 1;
+        let _ = writeln!(out, "KTEST PASS {}", test.name);
+      }
+      Err(failure) => {
+        summary.failed += 1;
+        let _ = writeln!(
+          out,
+          "KTEST FAIL {} {}:{} {}",
+          test.name, failure.file, failure.line, failure.message
+        );
+      }
+    }
+    unsafe {
+      CURRENT = None;
+      SUMMARY = summary;
+    }
+  }
+  let _ = writeln!(
+    out,
+    "KTEST END passed={} failed={}",
+    summary.passed, summary.failed
+  );
+  summary
+}
+
+// Called by the panic handler. Reports the test being run as failed and
+// exits. Returns if no test is running or no exit is registered.
+pub fn on_panic(
+  out: &mut impl core::fmt::Write,
+  message: &str,
+  file: &str,
+  line: u32,
+) {
+  let Some(name) = (unsafe { CURRENT.take() }) else {
+    return;
+  };
+  let (passed, failed) = unsafe { (SUMMARY.passed, SUMMARY.failed + 1) };
+  let _ = writeln!(
+    out,
+    "KTEST FAIL {} {}:{} panic: {}",
+    name, file, line, message
+  );
+  let _ = writeln!(out, "KTEST END passed={} failed={}", passed, failed);
+  if unsafe { SET } {
+    unsafe { (OPS.assume_init_ref().exit)(1) };
+  }
+}
+
+// Tests placed in the `.ktest` section, see linker.ld.
+#[cfg(feature = "ktest")]
+pub fn tests() -> &'static [KTest] {
+  extern "C" {
+    static __ktest_start: KTest;
+    static __ktest_end: KTest;
+  }
+  unsafe {
+    let start = core::ptr::addr_of!(__ktest_start);
+    let end = core::ptr::addr_of!(__ktest_end);
+    core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+  }
+}
+
+#[cfg(feature = "ktest")]
+pub fn run_and_exit() -> ! {
+  let summary = run_all(tests(), &mut crate::common::stream::out());
+  unsafe {
+    assert!(SET, "No ktest exit registered");
+    (OPS.assume_init_ref().exit)(summary.exit_code())
+  }
+}
+
+#[cfg(test)]
+#[path = "ktest_test.rs"]
+mod ktest_test;
@@ -0,0 +1,86 @@
+// Checks that the devices set up by board_setup answer.
+
+use super::{ktest, ktest_assert, ktest_assert_eq, Outcome};
+use crate::io::clk;
+use crate::io::gpio;
+use crate::io::mailbox;
+use crate::mm;
+use crate::timer;
+
+#[ktest]
+fn mailbox_board_revision() -> Outcome {
+  let tag = mailbox::tag::HwGetBoardRevision::Request {}.to_tag();
+  let mut message = mailbox::Message::new();
+  message.add_tag(&tag).expect("Message too long");
+  ktest_assert!(mailbox::send(&mut message).is_ok(), "Mailbox failed");
+  let response = mailbox::tag::HwGetBoardRevision::read_response(&message);
+  ktest_assert!(response.is_ok(), "No board revision");
+  Ok(())
+}
+
+#[ktest]
+fn timer_uptime_increases() -> Outcome {
+  let before = timer::uptime_us();
+  ktest_assert!(before.is_some(), "No timer registered");
+  crate::common::synchronization::sleep(100_000);
+  ktest_assert!(timer::uptime_us() > before);
+  Ok(())
+}
+
+#[ktest]
+fn clk_uart_has_rate() -> Outcome {
+  let uart = clk::get("uart");
+  ktest_assert!(uart.is_ok(), "No uart clock");
+  ktest_assert!(uart.unwrap().rate() > 0);
+  Ok(())
+}
+
+#[ktest]
+fn gpio_claim_is_exclusive() -> Outcome {
+  let pin = gpio::claim_any(5, "ktest");
+  ktest_assert!(pin.is_ok(), "GPIO 5 is taken");
+  ktest_assert!(gpio::claim_any(5, "ktest").is_err());
+  ktest_assert_eq!(gpio::owner(5), Some("ktest"));
+  pin.unwrap().release();
+  ktest_assert_eq!(gpio::owner(5), None);
+  Ok(())
+}
+
+#[ktest]
+fn uart_pins_are_claimed() {
+  assert_eq!(gpio::owner(14), Some("uart0"));
+  assert_eq!(gpio::owner(15), Some("uart0"));
+}
+
+// A store to a reserved user page faults it in through TTBR0.
+#[ktest]
+fn mm_demand_paging() -> Outcome {
+  let space = mm::AddressSpace::new(mm::frames());
+  ktest_assert!(space.is_ok(), "No address space");
+  let mut space = space.unwrap();
+  let rw = mm::Flags {
+    read: true,
+    write: true,
+    execute: false,
+  };
+  ktest_assert!(space.reserve(mm::USER_BASE, mm::PAGE_SIZE, rw).is_ok());
+  mm::activate(&mut space);
+  unsafe { core::ptr::write_volatile(mm::USER_BASE as *mut u64, 0x1234) };
+  mm::deactivate();
+  let mut value = [0u8; 8];
+  let copied = space.copy_in(mm::frames(), mm::USER_BASE, &mut value);
+  space.release(mm::frames());
+  ktest_assert!(copied.is_ok());
+  ktest_assert_eq!(u64::from_le_bytes(value), 0x1234);
+  Ok(())
+}
+
+// Code and data are reached through the linear map in TTBR1.
+#[ktest]
+fn mm_kernel_in_high_half() -> Outcome {
+  let code = mm_kernel_in_high_half as usize as u64;
+  ktest_assert!(code >= mm::KERNEL_BASE, "Running at {:#x}", code);
+  let stack = core::ptr::addr_of!(code) as u64;
+  ktest_assert!(stack >= mm::KERNEL_BASE, "Stack at {:#x}", stack);
+  Ok(())
+}
@@ -0,0 +1,86 @@
+// dmesg style in-memory log store.
+//
+// A byte ring of formatted log lines. Once full, the oldest bytes are
+// overwritten; a line that got partially overwritten is skipped on read.
+
+pub const LOG_BUFFER_SIZE: usize = 16 * 1024;
+
+pub struct LogBuffer<const N: usize> {
+  data: [u8; N],
+  // Where the next byte goes.
+  head: usize,
+  len: usize,
+  // The oldest line lost its start to an overwrite.
+  cut: bool,
+}
+
+impl<const N: usize> LogBuffer<N> {
+  pub const fn new() -> Self {
+    Self {
+      data: [0; N],
+      head: 0,
+      len: 0,
+      cut: false,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  pub fn clear(&mut self) {
+    self.head = 0;
+    self.len = 0;
+    self.cut = false;
+  }
+
+  pub fn push(&mut self, bytes: &[u8]) {
+    for &byte in bytes {
+      if self.len == N {
+        // Filling up exactly loses nothing, the oldest line stays whole
+        // until a byte other than its preceding newline is overwritten.
+        self.cut = self.data[self.head] != b'\n';
+      } else {
+        self.len += 1;
+      }
+      self.data[self.head] = byte;
+      self.head = (self.head + 1) % N;
+    }
+  }
+
+  // Calls `f` with every complete line, oldest first, without the trailing
+  // newline.
+  pub fn for_each_line(&self, mut f: impl FnMut(&str)) {
+    let start = (self.head + N - self.len) % N;
+    let mut line = [0u8; 256];
+    let mut line_len = 0;
+    let mut skipping = self.cut;
+    for offset in 0..self.len {
+      let byte = self.data[(start + offset) % N];
+      if byte == b'\n' {
+        if !skipping {
+          f(core::str::from_utf8(&line[..line_len]).unwrap_or("<invalid>"));
+        }
+        skipping = false;
+        line_len = 0;
+        continue;
+      }
+      // Overlong lines are truncated.
+      if line_len < line.len() {
+        line[line_len] = byte;
+        line_len += 1;
+      }
+    }
+  }
+}
+
+impl<const N: usize> core::fmt::Write for LogBuffer<N> {
+  fn write_str(&mut self, s: &str) -> core::fmt::Result {
+    self.push(s.as_bytes());
+    Ok(())
+  }
+}
@@ -0,0 +1,192 @@
+use super::*;
+use std::sync::{Mutex, MutexGuard};
+
+// Serializes the tests changing the global filters, sinks and buffer
+static LOCK: Mutex<()> = Mutex::new(());
+static CAPTURED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Records from other tests running in parallel are not ours.
+fn capture(record: &Record) {
+  if record.module != module_tag(module_path!()) {
+    return;
+  }
+  CAPTURED.lock().unwrap().push(format!(
+    "{} {}: {}",
+    record.level, record.module, record.args
+  ));
+}
+
+fn setup() -> MutexGuard<'static, ()> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  clear_levels();
+  clear_buffer();
+  set_default_level(Level::Info);
+  let _ = remove_sink("capture");
+  let _ = remove_sink("memory");
+  CAPTURED.lock().unwrap().clear();
+  add_sink(Sink {
+    name: "capture",
+    level: Level::Trace,
+    write: capture,
+  })
+  .unwrap();
+  guard
+}
+
+fn captured() -> Vec<String> {
+  CAPTURED.lock().unwrap().clone()
+}
+
+#[test]
+fn test_log_default_level() {
+  let _guard = setup();
+
+  crate::log::info!("shown {}", 1);
+  crate::log::debug!("hidden");
+  crate::log::error!("shown {}", 2);
+
+  assert_eq!(
+    captured(),
+    [
+      "info log::log_test: shown 1",
+      "error log::log_test: shown 2"
+    ]
+  );
+}
+
+#[test]
+fn test_log_trace_trailing_comma() {
+  let _guard = setup();
+  set_default_level(Level::Trace);
+
+  crate::log::trace!("traced {}", 1,);
+  crate::log::info!("shown",);
+
+  assert_eq!(
+    captured(),
+    ["trace log::log_test: traced 1", "info log::log_test: shown"]
+  );
+}
+
+#[test]
+fn test_log_module_level() {
+  let _guard = setup();
+  set_level("interrupt", Level::Warn).unwrap();
+  set_level("interrupt::bcm2837_interrupt", Level::Trace).unwrap();
+
+  assert!(!enabled(Level::Info, "interrupt"));
+  assert!(enabled(Level::Warn, "interrupt"));
+  assert!(enabled(Level::Trace, "interrupt::bcm2837_interrupt"));
+  // Prefix must end on a module boundary
+  assert!(enabled(Level::Info, "interrupts"));
+  assert_eq!(level_for("io::uart"), Level::Info);
+
+  set_level("log", Level::Error).unwrap();
+  crate::log::warn!("hidden");
+  assert!(captured().is_empty());
+}
+
+#[test]
+fn test_log_sink_level() {
+  let _guard = setup();
+  set_default_level(Level::Trace);
+  set_sink_level("capture", Level::Warn).unwrap();
+
+  crate::log::info!("hidden");
+  crate::log::warn!("shown");
+  assert_eq!(captured(), ["warn log::log_test: shown"]);
+
+  assert!(matches!(
+    set_sink_level("missing", Level::Info),
+    Err(ErrorKind::NotFound)
+  ));
+  assert!(matches!(
+    add_sink(Sink {
+      name: "capture",
+      level: Level::Info,
+      write: capture,
+    }),
+    Err(ErrorKind::AlreadyExists)
+  ));
+}
+
+#[test]
+fn test_log_memory_sink() {
+  let _guard = setup();
+  add_sink(memory_sink()).unwrap();
+
+  crate::log::info!("first");
+  crate::log::info!("second");
+
+  let mut lines = Vec::new();
+  dmesg(|line| {
+    if line.contains("log::log_test") {
+      lines.push(line.to_string())
+    }
+  });
+  assert_eq!(
+    lines,
+    [
+      "[    0.000000] info  log::log_test: first",
+      "[    0.000000] info  log::log_test: second"
+    ]
+  );
+}
+
+#[test]
+fn test_log_buffer_wrap() {
+  let mut buffer = buffer::LogBuffer::<16>::new();
+  buffer.push(b"one\ntwo\n");
+  buffer.push(b"three\nfour\n");
+  assert_eq!(buffer.len(), 16);
+
+  let mut lines = Vec::new();
+  buffer.for_each_line(|line| lines.push(line.to_string()));
+  // "one" was overwritten
+  assert_eq!(lines, ["two", "three", "four"]);
+
+  buffer.push(b"x\n");
+  let mut lines = Vec::new();
+  buffer.for_each_line(|line| lines.push(line.to_string()));
+  // "two" is cut off and skipped
+  assert_eq!(lines, ["three", "four", "x"]);
+
+  buffer.clear();
+  assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_log_buffer_exactly_full() {
+  let mut buffer = buffer::LogBuffer::<16>::new();
+  buffer.push(b"fifteen bytes!\n");
+  buffer.push(b"\n");
+  assert_eq!(buffer.len(), 16);
+
+  let mut lines = Vec::new();
+  buffer.for_each_line(|line| lines.push(line.to_string()));
+  // Nothing was overwritten yet.
+  assert_eq!(lines, ["fifteen bytes!", ""]);
+
+  // Overwriting exactly the first line keeps the second whole.
+  buffer.clear();
+  buffer.push(b"ab\n");
+  buffer.push(b"sixteen bytes..\n");
+  let mut lines = Vec::new();
+  buffer.for_each_line(|line| lines.push(line.to_string()));
+  assert_eq!(lines, ["sixteen bytes.."]);
+
+  // A 16 byte message without its newline is cut once the newline lands.
+  buffer.clear();
+  buffer.push(b"0123456789abcdef");
+  buffer.push(b"\n");
+  let mut lines = Vec::new();
+  buffer.for_each_line(|line| lines.push(line.to_string()));
+  assert!(lines.is_empty());
+}
+
+#[test]
+fn test_level_from_str() {
+  assert_eq!(Level::from_str("debug"), Some(Level::Debug));
+  assert_eq!(Level::from_str("verbose"), None);
+  assert!(Level::Error < Level::Trace);
+}
@@ -0,0 +1,293 @@
+// Leveled kernel logging.
+//
+// log::error!/warn!/info!/debug!/trace! tag a record with the calling module
+// and the system timer, drop it if the module's level filters it out, then
+// hand it to every sink that accepts the level. The memory sink keeps a
+// dmesg style ring buffer that can be read back later, the stream sink
+// forwards coloured records to `common::stream` which applies per-output
+// levels on top.
+//
+// Records may be emitted from IRQ context, keep sinks short.
+
+mod buffer;
+
+use crate::common::error::ErrorKind;
+pub use crate::common::level::Level;
+use crate::common::stream;
+use crate::timer;
+use crate::tty::ansi;
+use arrayvec::{ArrayString, ArrayVec};
+use buffer::LogBuffer;
+pub use buffer::LOG_BUFFER_SIZE;
+
+pub const MAX_SINKS: usize = 4;
+pub const MAX_FILTERS: usize = 16;
+pub const MODULE_CAP: usize = 48;
+
+// Level lives in `common` for `stream`, its colours here with the sinks.
+impl Level {
+  // Colour used by terminal sinks.
+  pub fn color(&self) -> ansi::Color {
+    match self {
+      Level::Error => ansi::Color::Red,
+      Level::Warn => ansi::Color::Yellow,
+      Level::Info => ansi::Color::Green,
+      Level::Debug => ansi::Color::Cyan,
+      Level::Trace => ansi::Color::White,
+    }
+  }
+}
+
+pub struct Record<'a> {
+  pub level: Level,
+  // Module path without the crate name, e.g. "io::uart::bcm2837_pl011".
+  pub module: &'a str,
+  pub timestamp_us: u64,
+  pub args: core::fmt::Arguments<'a>,
+}
+
+// Formats as "[    1.000123] info  io::uart: message", no newline.
+impl core::fmt::Display for Record<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(
+      f,
+      "[{:>5}.{:06}] {:<5} {}: {}",
+      self.timestamp_us / 1_000_000,
+      self.timestamp_us % 1_000_000,
+      self.level.as_str(),
+      self.module,
+      self.args
+    )
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct Sink {
+  pub name: &'static str,
+  // Most verbose level this sink accepts.
+  pub level: Level,
+  pub write: fn(&Record),
+}
+
+struct Filter {
+  module: ArrayString<MODULE_CAP>,
+  level: Level,
+}
+
+static mut DEFAULT_LEVEL: Level = Level::Info;
+static mut FILTERS: ArrayVec<Filter, MAX_FILTERS> = ArrayVec::new_const();
+static mut SINKS: ArrayVec<Sink, MAX_SINKS> = ArrayVec::new_const();
+static mut BUFFER: LogBuffer<LOG_BUFFER_SIZE> = LogBuffer::new();
+
+// Level for modules without a filter of their own.
+pub fn set_default_level(level: Level) {
+  unsafe { DEFAULT_LEVEL = level };
+}
+
+pub fn default_level() -> Level {
+  unsafe { DEFAULT_LEVEL }
+}
+
+// Sets the level of a module and its submodules, e.g. "interrupt" also
+// covers "interrupt::bcm2837_interrupt". The most specific filter wins.
+pub fn set_level(module: &str, level: Level) -> Result<(), ErrorKind> {
+  let module = ArrayString::<MODULE_CAP>::from(module)
+    .map_err(|_| ErrorKind::InvalidInput)?;
+  unsafe {
+    if let Some(filter) = FILTERS.iter_mut().find(|f| f.module == module) {
+      filter.level = level;
+      return Ok(());
+    }
+    FILTERS
+      .try_push(Filter { module, level })
+      .map_err(|_| ErrorKind::StorageFull)
+  }
+}
+
+pub fn clear_levels() {
+  unsafe { FILTERS.clear() };
+}
+
+pub fn level_for(module: &str) -> Level {
+  let mut best: Option<&Filter> = None;
+  unsafe {
+    for filter in FILTERS.iter() {
+      let matches = module == filter.module.as_str()
+        || (module.starts_with(filter.module.as_str())
+          && module[filter.module.len()..].starts_with("::"));
+      if matches && best.map_or(true, |b| filter.module.len() > b.module.len())
+      {
+        best = Some(filter);
+      }
+    }
+  }
+  best.map_or(default_level(), |filter| filter.level)
+}
+
+pub fn enabled(level: Level, module: &str) -> bool {
+  level <= level_for(module)
+}
+
+pub fn add_sink(sink: Sink) -> Result<(), ErrorKind> {
+  unsafe {
+    if SINKS.iter().any(|s| s.name == sink.name) {
+      return Err(ErrorKind::AlreadyExists);
+    }
+    SINKS.try_push(sink).map_err(|_| ErrorKind::StorageFull)
+  }
+}
+
+pub fn remove_sink(name: &str) -> Result<(), ErrorKind> {
+  unsafe {
+    let idx = SINKS
+      .iter()
+      .position(|s| s.name == name)
+      .ok_or(ErrorKind::NotFound)?;
+    SINKS.remove(idx);
+  }
+  Ok(())
+}
+
+pub fn set_sink_level(name: &str, level: Level) -> Result<(), ErrorKind> {
+  unsafe {
+    let sink = SINKS
+      .iter_mut()
+      .find(|s| s.name == name)
+      .ok_or(ErrorKind::NotFound)?;
+    sink.level = level;
+  }
+  Ok(())
+}
+
+// Strips the crate name from module_path!().
+fn module_tag(module_path: &str) -> &str {
+  module_path
+    .split_once("::")
+    .map_or(module_path, |(_, rest)| rest)
+}
+
+// Entry point of the logging macros.
+pub fn log(level: Level, module_path: &str, args: core::fmt::Arguments) {
+  let module = module_tag(module_path);
+  if !enabled(level, module) {
+    return;
+  }
+  let record = Record {
+    level,
+    module,
+    timestamp_us: timer::uptime_us().unwrap_or(0),
+    args,
+  };
+  unsafe {
+    for sink in SINKS.iter() {
+      if level <= sink.level {
+        (sink.write)(&record);
+      }
+    }
+  }
+}
+
+fn write_memory(record: &Record) {
+  use core::fmt::Write;
+  unsafe {
+    let _ = core::writeln!(BUFFER, "{}", record);
+  }
+}
+
+// Stores records in the in-memory ring buffer, see dmesg().
+pub fn memory_sink() -> Sink {
+  Sink {
+    name: "memory",
+    level: Level::Debug,
+    write: write_memory,
+  }
+}
+
+fn write_stream(record: &Record) {
+  let _ = core::writeln!(
+    stream::out_at(record.level),
+    "{}{}{}",
+    ansi::Command::Foreground(record.level.color()),
+    record,
+    ansi::Command::ResetStyle
+  );
+}
+
+// Forwards records to the output streams at the record's level.
+pub fn stream_sink() -> Sink {
+  Sink {
+    name: "stream",
+    level: Level::Trace,
+    write: write_stream,
+  }
+}
+
+// Calls `f` with every line in the ring buffer, oldest first.
+pub fn dmesg(f: impl FnMut(&str)) {
+  unsafe { BUFFER.for_each_line(f) };
+}
+
+pub fn clear_buffer() {
+  unsafe { BUFFER.clear() };
+}
+
+#[macro_export]
+macro_rules! error {
+  ( $( $arg:expr ),* $(,)? ) => {
+    $crate::log::log(
+      $crate::log::Level::Error,
+      core::module_path!(),
+      core::format_args!($($arg),*),
+    )
+  };
+}
+
+#[macro_export]
+macro_rules! warn {
+  ( $( $arg:expr ),* $(,)? ) => {
+    $crate::log::log(
+      $crate::log::Level::Warn,
+      core::module_path!(),
+      core::format_args!($($arg),*),
+    )
+  };
+}
+
+#[macro_export]
+macro_rules! info {
+  ( $( $arg:expr ),* $(,)? ) => {
+    $crate::log::log(
+      $crate::log::Level::Info,
+      core::module_path!(),
+      core::format_args!($($arg),*),
+    )
+  };
+}
+
+#[macro_export]
+macro_rules! debug {
+  ( $( $arg:expr ),* $(,)? ) => {
+    $crate::log::log(
+      $crate::log::Level::Debug,
+      core::module_path!(),
+      core::format_args!($($arg),*),
+    )
+  };
+}
+
+#[macro_export]
+macro_rules! trace {
+  ( $( $arg:expr ),* $(,)? ) => {
+    $crate::log::log(
+      $crate::log::Level::Trace,
+      core::module_path!(),
+      core::format_args!($($arg),*),
+    )
+  };
+}
+
+// `warn` alone would be ambiguous with the built-in attribute.
+pub use crate::{debug, error, info, trace, warn};
+
+#[cfg(test)]
+mod log_test;
@@ -2,22 +2,59 @@
 #![cfg_attr(feature = "device", no_main)]
 
 mod arch;
+mod bootloader;
 mod common;
 mod container;
 mod diagnostic;
+mod exec;
+mod fs;
+mod gdb;
 mod interrupt;
 mod io;
+mod kshell;
+mod ktest;
+mod log;
 mod metadata;
+mod mm;
 mod panic;
+mod process;
 mod syscall;
 mod timer;
 mod tty;
+mod video;
 
-#[cfg(feature = "device")]
+#[cfg(all(
+  feature = "device",
+  not(feature = "ktest"),
+  not(feature = "bootloader")
+))]
 #[no_mangle]
 extern "C" fn kernel_main() -> ! {
-  // diagnostic::test_interrupt();
-  // diagnostic::test_uart();
-  // diagnostic::test_panic();
-  diagnostic::test_uart_interrupt();
+  if let Err(e) = fs::initialize() {
+    log::warn!("Failed to mount filesystems: {}", e);
+  }
+  // User space if the initramfs has an init program, see fs::initramfs.
+  if let Ok(init) = exec::open_program("/sbin/init") {
+    if let Err(e) = process::run_init(init, &["/sbin/init"]) {
+      log::warn!("Failed to start /sbin/init: {}", e);
+    }
+  }
+  // Diagnostics are available through the `diag` command.
+  kshell::init();
+  let mut tty = tty::Tty::new(io::uart::as_tty_adapter());
+  kshell::run(&mut tty);
+}
+
+// Runs the #[ktest] tests instead of the shell.
+#[cfg(feature = "ktest")]
+#[no_mangle]
+extern "C" fn kernel_main() -> ! {
+  ktest::run_and_exit();
+}
+
+// Receives a kernel over the UART and boots it.
+#[cfg(feature = "bootloader")]
+#[no_mangle]
+extern "C" fn kernel_main() -> ! {
+  bootloader::run();
 }
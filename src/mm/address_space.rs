@@ -0,0 +1,539 @@
+// User address space: TTBR0 translation tables and an ASID.
+//
+// Pages are either mapped explicitly or populated on the first access to a
+// reserved region (demand paging). fork() shares all frames copy-on-write.
+// Tables and frames come from a Frames allocator, which must be passed to
+// every call; release() returns them.
+
+use super::page_table::{self, ENTRIES, LEVELS, LEVEL_SIZE};
+use super::{asid, Access, Flags, Frames, PAGE_SIZE, USER_BASE, USER_TOP};
+use crate::common::error::ErrorKind;
+use crate::exec::elf;
+use arrayvec::ArrayVec;
+
+pub const MAX_REGIONS: usize = 16;
+
+// Pages populated with zeroed frames on first access
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Region {
+  pub start: u64,
+  pub end: u64,
+  pub flags: Flags,
+}
+
+pub struct AddressSpace {
+  // Physical address of the level 0 table
+  root: u64,
+  asid: u16,
+  regions: ArrayVec<Region, MAX_REGIONS>,
+}
+
+fn page_down(va: u64) -> u64 {
+  va & !(PAGE_SIZE - 1)
+}
+
+// Page aligned and within user space.
+fn check_range(va: u64, size: u64) -> Result<u64, ErrorKind> {
+  let aligned = va.is_multiple_of(PAGE_SIZE) && size.is_multiple_of(PAGE_SIZE);
+  match va.checked_add(size) {
+    Some(end) if aligned && va >= USER_BASE && end <= USER_TOP => Ok(end),
+    _ => Err(ErrorKind::InvalidInput),
+  }
+}
+
+fn allows(flags: Flags, access: Access) -> bool {
+  match access {
+    Access::Read => flags.read,
+    Access::Write => flags.write,
+    Access::Execute => flags.execute,
+  }
+}
+
+impl AddressSpace {
+  // An empty address space. The kernel is not mapped, it lives in TTBR1.
+  pub fn new(frames: &mut Frames) -> Result<AddressSpace, ErrorKind> {
+    let asid = asid::allocate()?;
+    let root = match frames.alloc() {
+      Ok(root) => root,
+      Err(e) => {
+        asid::free(asid);
+        return Err(e);
+      }
+    };
+    Ok(AddressSpace {
+      root,
+      asid,
+      regions: ArrayVec::new(),
+    })
+  }
+
+  // Physical address of the level 0 table, for TTBR0_EL1.
+  pub fn root(&self) -> u64 {
+    self.root
+  }
+
+  pub fn asid(&self) -> u16 {
+    self.asid
+  }
+
+  pub fn regions(&self) -> &[Region] {
+    &self.regions
+  }
+
+  // Level 3 table and index for `va`, missing tables are allocated if
+  // `create`.
+  fn walk(
+    &self,
+    frames: &mut Frames,
+    va: u64,
+    create: bool,
+  ) -> Result<Option<(u64, usize)>, ErrorKind> {
+    let mut table = self.root;
+    for level in 0..LEVELS - 1 {
+      let index = page_table::index(va, level);
+      let desc = frames.table(table)[index];
+      table = if page_table::is_valid(desc) {
+        page_table::address(desc)
+      } else if create {
+        let next = frames.alloc()?;
+        frames.table(table)[index] = page_table::table(next);
+        next
+      } else {
+        return Ok(None);
+      };
+    }
+    Ok(Some((table, page_table::index(va, LEVELS - 1))))
+  }
+
+  fn descriptor(&self, frames: &mut Frames, va: u64) -> Option<u64> {
+    let (table, index) = self.walk(frames, va, false).ok().flatten()?;
+    Some(frames.table(table)[index]).filter(|&d| page_table::is_valid(d))
+  }
+
+  fn set_descriptor(
+    &self,
+    frames: &mut Frames,
+    va: u64,
+    desc: u64,
+  ) -> Result<(), ErrorKind> {
+    let (table, index) = self.walk(frames, va, true)?.unwrap();
+    frames.table(table)[index] = desc;
+    Ok(())
+  }
+
+  // Maps the page at `va` to the frame at `pa`. Frames of the allocator
+  // are retained until unmapped.
+  pub fn map(
+    &mut self,
+    frames: &mut Frames,
+    va: u64,
+    pa: u64,
+    flags: Flags,
+  ) -> Result<(), ErrorKind> {
+    check_range(va, PAGE_SIZE)?;
+    if !pa.is_multiple_of(PAGE_SIZE) {
+      return Err(ErrorKind::InvalidInput);
+    }
+    if self.descriptor(frames, va).is_some() {
+      return Err(ErrorKind::AlreadyExists);
+    }
+    self.set_descriptor(frames, va, page_table::user_page(pa, flags))?;
+    if frames.contains(pa) {
+      frames.retain(pa);
+    }
+    Ok(())
+  }
+
+  // Unmaps pages and regions in [va, va + size), holes are skipped.
+  pub fn unmap(
+    &mut self,
+    frames: &mut Frames,
+    va: u64,
+    size: u64,
+  ) -> Result<(), ErrorKind> {
+    let end = check_range(va, size)?;
+    self.update_regions(va, end, None)?;
+    for page in (va..end).step_by(PAGE_SIZE as usize) {
+      let Some((table, index)) = self.walk(frames, page, false)? else {
+        continue;
+      };
+      let desc = core::mem::take(&mut frames.table(table)[index]);
+      if !page_table::is_valid(desc) {
+        continue;
+      }
+      super::invalidate_page(self.asid, page);
+      let pa = page_table::address(desc);
+      if frames.contains(pa) {
+        frames.release(pa);
+      }
+    }
+    Ok(())
+  }
+
+  // Changes the flags of pages and regions in [va, va + size).
+  pub fn protect(
+    &mut self,
+    frames: &mut Frames,
+    va: u64,
+    size: u64,
+    flags: Flags,
+  ) -> Result<(), ErrorKind> {
+    let end = check_range(va, size)?;
+    self.update_regions(va, end, Some(flags))?;
+    for page in (va..end).step_by(PAGE_SIZE as usize) {
+      let Some(desc) = self.descriptor(frames, page) else {
+        continue;
+      };
+      let pa = page_table::address(desc);
+      let mut new = page_table::user_page(pa, flags);
+      // Shared since fork(), stays read-only until written.
+      if flags.write && frames.contains(pa) && frames.count(pa) > 1 {
+        new = page_table::share(new);
+      }
+      self.set_descriptor(frames, page, new)?;
+      super::invalidate_page(self.asid, page);
+    }
+    Ok(())
+  }
+
+  // Reserves [va, va + size) to be populated on first access.
+  pub fn reserve(
+    &mut self,
+    va: u64,
+    size: u64,
+    flags: Flags,
+  ) -> Result<(), ErrorKind> {
+    let end = check_range(va, size)?;
+    if size == 0 {
+      return Ok(());
+    }
+    if self.regions.iter().any(|r| va < r.end && r.start < end) {
+      return Err(ErrorKind::AlreadyExists);
+    }
+    self
+      .regions
+      .try_push(Region {
+        start: va,
+        end,
+        flags,
+      })
+      .map_err(|_| ErrorKind::OutOfMemory)
+  }
+
+  // Removes [start, end) from the regions, or gives it new flags, splitting
+  // regions that overlap partially.
+  fn update_regions(
+    &mut self,
+    start: u64,
+    end: u64,
+    flags: Option<Flags>,
+  ) -> Result<(), ErrorKind> {
+    let mut updated = ArrayVec::<Region, MAX_REGIONS>::new();
+    for region in &self.regions {
+      let parts = [
+        (region.start, region.end.min(start), Some(region.flags)),
+        (region.start.max(start), region.end.min(end), flags),
+        (region.start.max(end), region.end, Some(region.flags)),
+      ];
+      for (part_start, part_end, part_flags) in parts {
+        let Some(flags) = part_flags else {
+          continue;
+        };
+        if part_start < part_end {
+          updated
+            .try_push(Region {
+              start: part_start,
+              end: part_end,
+              flags,
+            })
+            .map_err(|_| ErrorKind::OutOfMemory)?;
+        }
+      }
+    }
+    self.regions = updated;
+    Ok(())
+  }
+
+  // Physical address and flags of the page mapped at `va`.
+  pub fn translate(
+    &self,
+    frames: &mut Frames,
+    va: u64,
+  ) -> Option<(u64, Flags)> {
+    let desc = self.descriptor(frames, page_down(va))?;
+    Some((
+      page_table::address(desc) + va % PAGE_SIZE,
+      page_table::flags(desc),
+    ))
+  }
+
+  fn region(&self, va: u64) -> Option<Region> {
+    self
+      .regions
+      .iter()
+      .find(|r| r.start <= va && va < r.end)
+      .copied()
+  }
+
+  // Descriptor of `page` after making it ready for `access`: populates
+  // reserved pages and copies shared ones before writes. With `check`, the
+  // access must be allowed by the page's flags.
+  fn populate(
+    &mut self,
+    frames: &mut Frames,
+    page: u64,
+    access: Access,
+    check: bool,
+  ) -> Result<u64, ErrorKind> {
+    let desc = match self.descriptor(frames, page) {
+      Some(desc) => desc,
+      None => {
+        let region = self.region(page).ok_or(ErrorKind::PermissionDenied)?;
+        if check && !allows(region.flags, access) {
+          return Err(ErrorKind::PermissionDenied);
+        }
+        let pa = frames.alloc()?;
+        let result = self.map(frames, page, pa, region.flags);
+        frames.release(pa);
+        result?;
+        self.descriptor(frames, page).unwrap()
+      }
+    };
+    if access == Access::Write && page_table::is_copy_on_write(desc) {
+      return self.copy_on_write(frames, page, desc);
+    }
+    if check && !allows(page_table::flags(desc), access) {
+      return Err(ErrorKind::PermissionDenied);
+    }
+    Ok(desc)
+  }
+
+  // Makes the shared page writable, copying its frame if it is still shared.
+  fn copy_on_write(
+    &mut self,
+    frames: &mut Frames,
+    page: u64,
+    desc: u64,
+  ) -> Result<u64, ErrorKind> {
+    let pa = page_table::address(desc);
+    let flags = page_table::flags(desc);
+    let new = match frames.count(pa) {
+      1 => page_table::user_page(pa, flags),
+      _ => {
+        let copy = frames.alloc()?;
+        let data = unsafe {
+          core::slice::from_raw_parts(frames.ptr(pa), PAGE_SIZE as usize)
+        };
+        frames.page(copy).copy_from_slice(data);
+        frames.release(pa);
+        page_table::user_page(copy, flags)
+      }
+    };
+    self.set_descriptor(frames, page, new)?;
+    super::invalidate_page(self.asid, page);
+    Ok(new)
+  }
+
+  // Resolves a translation or permission fault of a user access to `va`.
+  // PermissionDenied if the access is not allowed.
+  pub fn handle_fault(
+    &mut self,
+    frames: &mut Frames,
+    va: u64,
+    access: Access,
+  ) -> Result<(), ErrorKind> {
+    if !(USER_BASE..USER_TOP).contains(&va) {
+      return Err(ErrorKind::PermissionDenied);
+    }
+    self.populate(frames, page_down(va), access, true)?;
+    // A stale translation may be cached, e.g. after protect().
+    super::invalidate_page(self.asid, page_down(va));
+    Ok(())
+  }
+
+  // Calls `f` for each part of [va, va + len) with the kernel's view of it
+  // and its offset from `va`.
+  fn access(
+    &mut self,
+    frames: &mut Frames,
+    va: u64,
+    len: usize,
+    access: Access,
+    check: bool,
+    f: &mut dyn FnMut(&mut [u8], usize),
+  ) -> Result<(), ErrorKind> {
+    let mut done = 0;
+    while done < len {
+      let addr = va
+        .checked_add(done as u64)
+        .filter(|addr| (USER_BASE..USER_TOP).contains(addr))
+        .ok_or(ErrorKind::PermissionDenied)?;
+      let desc = self.populate(frames, page_down(addr), access, check)?;
+      let offset = (addr % PAGE_SIZE) as usize;
+      let chunk = (PAGE_SIZE as usize - offset).min(len - done);
+      let page = frames.page(page_table::address(desc));
+      f(&mut page[offset..offset + chunk], done);
+      done += chunk;
+    }
+    Ok(())
+  }
+
+  // Writes `data` to `va` regardless of page flags, e.g. to load a program.
+  pub fn write(
+    &mut self,
+    frames: &mut Frames,
+    va: u64,
+    data: &[u8],
+  ) -> Result<(), ErrorKind> {
+    self.access(
+      frames,
+      va,
+      data.len(),
+      Access::Write,
+      false,
+      &mut |dst, at| dst.copy_from_slice(&data[at..at + dst.len()]),
+    )
+  }
+
+  // Copies `data` to user memory at `va` as a user store would.
+  pub fn copy_out(
+    &mut self,
+    frames: &mut Frames,
+    va: u64,
+    data: &[u8],
+  ) -> Result<(), ErrorKind> {
+    self.access(
+      frames,
+      va,
+      data.len(),
+      Access::Write,
+      true,
+      &mut |dst, at| dst.copy_from_slice(&data[at..at + dst.len()]),
+    )
+  }
+
+  // Copies user memory at `va` to `buffer` as a user load would.
+  pub fn copy_in(
+    &mut self,
+    frames: &mut Frames,
+    va: u64,
+    buffer: &mut [u8],
+  ) -> Result<(), ErrorKind> {
+    let len = buffer.len();
+    self.access(frames, va, len, Access::Read, true, &mut |src, at| {
+      buffer[at..at + src.len()].copy_from_slice(src)
+    })
+  }
+
+  // Calls `f` with each mapped user page and stores the descriptor it
+  // returns.
+  fn update_pages(
+    &self,
+    frames: &mut Frames,
+    table: u64,
+    level: usize,
+    base: u64,
+    f: &mut dyn FnMut(&mut Frames, u64, u64) -> Result<u64, ErrorKind>,
+  ) -> Result<(), ErrorKind> {
+    for index in 0..ENTRIES {
+      let va = base + index as u64 * LEVEL_SIZE[level];
+      let desc = frames.table(table)[index];
+      if !page_table::is_valid(desc) {
+        continue;
+      }
+      if level == LEVELS - 1 {
+        frames.table(table)[index] = f(frames, va, desc)?;
+      } else {
+        let next = page_table::address(desc);
+        self.update_pages(frames, next, level + 1, va, f)?;
+      }
+    }
+    Ok(())
+  }
+
+  // A copy of this address space. Frames are shared until either side
+  // writes to them.
+  pub fn fork(
+    &mut self,
+    frames: &mut Frames,
+  ) -> Result<AddressSpace, ErrorKind> {
+    let mut child = AddressSpace::new(frames)?;
+    child.regions = self.regions.clone();
+    let result =
+      self.update_pages(frames, self.root, 0, 0, &mut |frames, va, desc| {
+        let pa = page_table::address(desc);
+        let shared = match frames.contains(pa) {
+          true => page_table::share(desc),
+          false => desc,
+        };
+        child.set_descriptor(frames, va, shared)?;
+        if frames.contains(pa) {
+          frames.retain(pa);
+        }
+        Ok(shared)
+      });
+    // Writable pages became read-only.
+    super::invalidate_asid(self.asid);
+    match result {
+      Ok(()) => Ok(child),
+      Err(e) => {
+        child.release(frames);
+        Err(e)
+      }
+    }
+  }
+
+  // Releases the frames mapped below `table`, the tables and `table`.
+  fn free_table(
+    &self,
+    frames: &mut Frames,
+    table: u64,
+    level: usize,
+    base: u64,
+  ) {
+    for index in 0..ENTRIES {
+      let va = base + index as u64 * LEVEL_SIZE[level];
+      let desc = frames.table(table)[index];
+      if !page_table::is_valid(desc) {
+        continue;
+      }
+      let pa = page_table::address(desc);
+      if level < LEVELS - 1 {
+        self.free_table(frames, pa, level + 1, va);
+      } else if frames.contains(pa) {
+        frames.release(pa);
+      }
+    }
+    frames.release(table);
+  }
+
+  // Frees all tables and mapped frames, and the ASID.
+  pub fn release(self, frames: &mut Frames) {
+    super::forget(&self);
+    self.free_table(frames, self.root, 0, 0);
+    super::invalidate_asid(self.asid);
+    asid::free(self.asid);
+  }
+}
+
+// Loads programs into an address space. Segments and the stack are
+// reserved and populated as they are written.
+pub struct Loader<'a> {
+  pub space: &'a mut AddressSpace,
+  pub frames: &'a mut Frames,
+}
+
+impl elf::Target for Loader<'_> {
+  fn map(
+    &mut self,
+    vaddr: u64,
+    size: u64,
+    flags: Flags,
+  ) -> Result<(), ErrorKind> {
+    self.space.reserve(vaddr, size, flags)
+  }
+
+  fn write(&mut self, vaddr: u64, data: &[u8]) -> Result<(), ErrorKind> {
+    self.space.write(self.frames, vaddr, data)
+  }
+}
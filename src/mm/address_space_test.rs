@@ -0,0 +1,303 @@
+use super::address_space::{AddressSpace, Loader, Region};
+use super::frame_test::host_frames;
+use super::{asid, Access, Flags, Frames, PAGE_SIZE, USER_BASE};
+use crate::common::error::ErrorKind;
+use crate::exec::elf::Target;
+
+const RW: Flags = Flags {
+  read: true,
+  write: true,
+  execute: false,
+};
+const RO: Flags = Flags {
+  read: true,
+  write: false,
+  execute: false,
+};
+const RX: Flags = Flags {
+  read: true,
+  write: false,
+  execute: true,
+};
+
+const VA: u64 = USER_BASE + 0x10_0000;
+
+fn read_byte(space: &AddressSpace, frames: &mut Frames, va: u64) -> u8 {
+  let (pa, _) = space.translate(frames, va).unwrap();
+  unsafe { *frames.ptr(pa) }
+}
+
+#[test]
+fn test_map_translate() {
+  let mut frames = host_frames(32);
+  let mut space = AddressSpace::new(&mut frames).unwrap();
+  let pa = frames.alloc().unwrap();
+  space.map(&mut frames, VA, pa, RX).unwrap();
+  assert_eq!(frames.count(pa), 2);
+  assert_eq!(
+    space.translate(&mut frames, VA + 0x123),
+    Some((pa + 0x123, RX))
+  );
+  assert_eq!(space.translate(&mut frames, VA + PAGE_SIZE), None);
+
+  assert!(matches!(
+    space.map(&mut frames, VA, pa, RW),
+    Err(ErrorKind::AlreadyExists)
+  ));
+  // Kernel range, unaligned, beyond user space
+  for va in [0x8_0000, VA + 1, 1 << 48] {
+    assert!(matches!(
+      space.map(&mut frames, va, pa, RW),
+      Err(ErrorKind::InvalidInput)
+    ));
+  }
+  // Peripherals are not counted.
+  space
+    .map(&mut frames, VA + PAGE_SIZE, 0x3F20_0000, RW)
+    .unwrap();
+  assert_eq!(
+    space.translate(&mut frames, VA + PAGE_SIZE),
+    Some((0x3F20_0000, RW))
+  );
+  space.release(&mut frames);
+  assert_eq!(frames.count(pa), 1);
+}
+
+#[test]
+fn test_unmap() {
+  let mut frames = host_frames(32);
+  let free = frames.free_count();
+  let mut space = AddressSpace::new(&mut frames).unwrap();
+  let pa = frames.alloc().unwrap();
+  space.map(&mut frames, VA, pa, RW).unwrap();
+  frames.release(pa);
+  // Holes are fine.
+  space
+    .unmap(&mut frames, VA - PAGE_SIZE, 4 * PAGE_SIZE)
+    .unwrap();
+  assert_eq!(space.translate(&mut frames, VA), None);
+  assert_eq!(frames.count(pa), 0);
+  space.release(&mut frames);
+  assert_eq!(frames.free_count(), free);
+}
+
+#[test]
+fn test_demand_paging() {
+  let mut frames = host_frames(32);
+  let mut space = AddressSpace::new(&mut frames).unwrap();
+  space.reserve(VA, 4 * PAGE_SIZE, RW).unwrap();
+  assert_eq!(space.translate(&mut frames, VA), None);
+
+  space
+    .handle_fault(&mut frames, VA + PAGE_SIZE + 8, Access::Write)
+    .unwrap();
+  let (pa, flags) = space.translate(&mut frames, VA + PAGE_SIZE).unwrap();
+  assert_eq!(flags, RW);
+  assert_eq!(frames.count(pa), 1);
+  assert!(frames.page(pa).iter().all(|&b| b == 0));
+  assert_eq!(space.translate(&mut frames, VA), None);
+
+  // Outside of regions, not allowed by the region
+  assert!(matches!(
+    space.handle_fault(&mut frames, VA + 4 * PAGE_SIZE, Access::Read),
+    Err(ErrorKind::PermissionDenied)
+  ));
+  assert!(matches!(
+    space.handle_fault(&mut frames, VA, Access::Execute),
+    Err(ErrorKind::PermissionDenied)
+  ));
+  assert!(matches!(
+    space.handle_fault(&mut frames, 0x8_0000, Access::Read),
+    Err(ErrorKind::PermissionDenied)
+  ));
+  assert!(matches!(
+    space.reserve(VA + 3 * PAGE_SIZE, 2 * PAGE_SIZE, RW),
+    Err(ErrorKind::AlreadyExists)
+  ));
+  space.release(&mut frames);
+}
+
+#[test]
+fn test_copy_in_out() {
+  let mut frames = host_frames(32);
+  let mut space = AddressSpace::new(&mut frames).unwrap();
+  space.reserve(VA, 2 * PAGE_SIZE, RW).unwrap();
+  space.reserve(VA + 2 * PAGE_SIZE, PAGE_SIZE, RO).unwrap();
+
+  // Across a page boundary
+  let data: Vec<u8> = (0..100).collect();
+  space
+    .copy_out(&mut frames, VA + PAGE_SIZE - 50, &data)
+    .unwrap();
+  let mut buffer = [0u8; 100];
+  space
+    .copy_in(&mut frames, VA + PAGE_SIZE - 50, &mut buffer)
+    .unwrap();
+  assert_eq!(&buffer[..], &data[..]);
+
+  assert!(matches!(
+    space.copy_out(&mut frames, VA + 2 * PAGE_SIZE, &data),
+    Err(ErrorKind::PermissionDenied)
+  ));
+  // The kernel may write anywhere, e.g. to load text.
+  space.write(&mut frames, VA + 2 * PAGE_SIZE, &data).unwrap();
+  assert_eq!(read_byte(&space, &mut frames, VA + 2 * PAGE_SIZE + 7), 7);
+  assert!(matches!(
+    space.copy_in(&mut frames, VA + 3 * PAGE_SIZE - 10, &mut buffer),
+    Err(ErrorKind::PermissionDenied)
+  ));
+  space.release(&mut frames);
+}
+
+#[test]
+fn test_protect() {
+  let mut frames = host_frames(32);
+  let mut space = AddressSpace::new(&mut frames).unwrap();
+  space.reserve(VA, 4 * PAGE_SIZE, RW).unwrap();
+  space.copy_out(&mut frames, VA + PAGE_SIZE, &[1]).unwrap();
+
+  space
+    .protect(&mut frames, VA + PAGE_SIZE, 2 * PAGE_SIZE, RO)
+    .unwrap();
+  assert_eq!(
+    space.regions(),
+    &[
+      Region {
+        start: VA,
+        end: VA + PAGE_SIZE,
+        flags: RW
+      },
+      Region {
+        start: VA + PAGE_SIZE,
+        end: VA + 3 * PAGE_SIZE,
+        flags: RO
+      },
+      Region {
+        start: VA + 3 * PAGE_SIZE,
+        end: VA + 4 * PAGE_SIZE,
+        flags: RW
+      },
+    ]
+  );
+  assert_eq!(space.translate(&mut frames, VA + PAGE_SIZE).unwrap().1, RO);
+  assert!(space.copy_out(&mut frames, VA + PAGE_SIZE, &[2]).is_err());
+  // Populated later with the new flags
+  assert!(space
+    .copy_out(&mut frames, VA + 2 * PAGE_SIZE, &[2])
+    .is_err());
+  space
+    .copy_out(&mut frames, VA + 3 * PAGE_SIZE, &[2])
+    .unwrap();
+
+  space.unmap(&mut frames, VA, 2 * PAGE_SIZE).unwrap();
+  assert_eq!(space.regions().len(), 2);
+  assert_eq!(space.regions()[0].start, VA + 2 * PAGE_SIZE);
+  space.release(&mut frames);
+}
+
+#[test]
+fn test_fork_copy_on_write() {
+  let mut frames = host_frames(64);
+  let free = frames.free_count();
+  let mut parent = AddressSpace::new(&mut frames).unwrap();
+  parent.reserve(VA, 2 * PAGE_SIZE, RW).unwrap();
+  parent.reserve(VA + 2 * PAGE_SIZE, PAGE_SIZE, RO).unwrap();
+  parent.copy_out(&mut frames, VA, &[1]).unwrap();
+  parent.copy_out(&mut frames, VA + PAGE_SIZE, &[1]).unwrap();
+  parent.write(&mut frames, VA + 2 * PAGE_SIZE, &[1]).unwrap();
+
+  let mut child = parent.fork(&mut frames).unwrap();
+  assert_ne!(child.asid(), parent.asid());
+  let (pa, flags) = parent.translate(&mut frames, VA).unwrap();
+  assert_eq!(child.translate(&mut frames, VA), Some((pa, flags)));
+  assert_eq!(flags, RW);
+  assert_eq!(frames.count(pa), 2);
+
+  // The child gets a copy, the parent keeps the frame.
+  child.copy_out(&mut frames, VA, &[2]).unwrap();
+  let (child_pa, _) = child.translate(&mut frames, VA).unwrap();
+  assert_ne!(child_pa, pa);
+  assert_eq!(read_byte(&child, &mut frames, VA), 2);
+  assert_eq!(read_byte(&parent, &mut frames, VA), 1);
+  assert_eq!(frames.count(pa), 1);
+  // The last owner writes in place.
+  parent.copy_out(&mut frames, VA, &[3]).unwrap();
+  assert_eq!(parent.translate(&mut frames, VA).unwrap().0, pa);
+
+  // Faults resolve copy-on-write as well.
+  parent
+    .handle_fault(&mut frames, VA + PAGE_SIZE, Access::Write)
+    .unwrap();
+  assert_eq!(read_byte(&child, &mut frames, VA + PAGE_SIZE), 1);
+  // Read-only pages stay shared, even when made writable.
+  assert!(child
+    .handle_fault(&mut frames, VA + 2 * PAGE_SIZE, Access::Write)
+    .is_err());
+  child
+    .protect(&mut frames, VA + 2 * PAGE_SIZE, PAGE_SIZE, RW)
+    .unwrap();
+  child
+    .copy_out(&mut frames, VA + 2 * PAGE_SIZE, &[4])
+    .unwrap();
+  assert_eq!(read_byte(&parent, &mut frames, VA + 2 * PAGE_SIZE), 1);
+
+  child.release(&mut frames);
+  parent.release(&mut frames);
+  assert_eq!(frames.free_count(), free);
+}
+
+#[test]
+fn test_out_of_frames() {
+  let mut frames = host_frames(8);
+  let free = frames.free_count();
+  let mut space = AddressSpace::new(&mut frames).unwrap();
+  space.reserve(VA, 64 * PAGE_SIZE, RW).unwrap();
+  assert!(matches!(
+    space.copy_out(&mut frames, VA, &[1; 64 * PAGE_SIZE as usize]),
+    Err(ErrorKind::OutOfMemory)
+  ));
+  assert!(matches!(
+    space.fork(&mut frames),
+    Err(ErrorKind::OutOfMemory)
+  ));
+  space.release(&mut frames);
+  assert_eq!(frames.free_count(), free);
+}
+
+#[test]
+fn test_loader() {
+  let mut frames = host_frames(32);
+  let mut space = AddressSpace::new(&mut frames).unwrap();
+  let mut loader = Loader {
+    space: &mut space,
+    frames: &mut frames,
+  };
+  loader.map(VA, 2 * PAGE_SIZE, RX).unwrap();
+  loader.write(VA + PAGE_SIZE, &[0xC0, 0x03]).unwrap();
+  assert!(loader.map(VA + PAGE_SIZE, PAGE_SIZE, RW).is_err());
+  assert_eq!(read_byte(&space, &mut frames, VA + PAGE_SIZE + 1), 0x03);
+  assert_eq!(space.translate(&mut frames, VA + PAGE_SIZE).unwrap().1, RX);
+  assert_eq!(space.translate(&mut frames, VA), None);
+  space.release(&mut frames);
+}
+
+#[test]
+fn test_kernel_not_mapped() {
+  let mut frames = host_frames(8);
+  let free = frames.free_count();
+  let space = AddressSpace::new(&mut frames).unwrap();
+  // Only the root table, the kernel lives in TTBR1.
+  assert_eq!(frames.free_count(), free - 1);
+  assert_eq!(space.translate(&mut frames, 0x8_0000), None);
+  space.release(&mut frames);
+  assert_eq!(frames.free_count(), free);
+}
+
+#[test]
+fn test_asids() {
+  let a = asid::allocate().unwrap();
+  let b = asid::allocate().unwrap();
+  assert!(a != 0 && b != 0 && a != b);
+  asid::free(a);
+  asid::free(b);
+}
@@ -0,0 +1,47 @@
+// Address space identifiers, which tag TLB entries so that switching TTBR0
+// needs no TLB flush. 8 bit ASIDs (TCR_EL1.AS = 0), 0 is the kernel's.
+//
+// There is no rollover: at most COUNT - 1 address spaces exist at a time.
+
+use crate::common::error::ErrorKind;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub const COUNT: usize = 256;
+
+// Bit set for ASIDs in use, ASID 0 is never handed out.
+static USED: [AtomicU64; COUNT / 64] = [
+  AtomicU64::new(1),
+  AtomicU64::new(0),
+  AtomicU64::new(0),
+  AtomicU64::new(0),
+];
+
+pub fn allocate() -> Result<u16, ErrorKind> {
+  for (word_index, word) in USED.iter().enumerate() {
+    let mut used = word.load(Ordering::Relaxed);
+    while used != u64::MAX {
+      let bit = (!used).trailing_zeros();
+      match word.compare_exchange_weak(
+        used,
+        used | 1 << bit,
+        Ordering::AcqRel,
+        Ordering::Relaxed,
+      ) {
+        Ok(_) => return Ok((word_index * 64) as u16 + bit as u16),
+        Err(current) => used = current,
+      }
+    }
+  }
+  Err(ErrorKind::ResourceBusy)
+}
+
+pub fn free(asid: u16) {
+  assert!(
+    asid != 0 && (asid as usize) < COUNT,
+    "Invalid ASID {}",
+    asid
+  );
+  let bit = 1u64 << (asid % 64);
+  let previous = USED[asid as usize / 64].fetch_and(!bit, Ordering::AcqRel);
+  assert!(previous & bit != 0, "ASID {} is free", asid);
+}
@@ -0,0 +1,127 @@
+// Physical page frame allocator with reference counts.
+//
+// Frames are shared by copy-on-write address spaces, a frame is free when
+// its count drops to 0. The counts live at the start of the managed memory.
+
+use super::page_table::Table;
+use super::PAGE_SIZE;
+use crate::common::error::ErrorKind;
+
+pub struct Frames {
+  // Physical address of the first frame
+  base: u64,
+  // Added to a physical address for the kernel's view of it
+  offset: u64,
+  counts: &'static mut [u16],
+  // Where the search for a free frame starts
+  next: usize,
+  free: usize,
+}
+
+impl Frames {
+  // Manages the physical memory [start, end), both page aligned, which the
+  // kernel reaches at physical address + `offset`.
+  //
+  // Safety: the memory must be unused and mapped for the kernel.
+  pub unsafe fn new(start: u64, end: u64, offset: u64) -> Frames {
+    assert!(
+      start.is_multiple_of(PAGE_SIZE)
+        && end.is_multiple_of(PAGE_SIZE)
+        && start <= end
+    );
+    // Each frame costs a page and its count.
+    let mut total = ((end - start) / (PAGE_SIZE + 2)) as usize;
+    let counts_size =
+      |total: usize| (total as u64 * 2).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+    while start + counts_size(total) + total as u64 * PAGE_SIZE > end {
+      total -= 1;
+    }
+    let counts =
+      core::slice::from_raw_parts_mut((start + offset) as *mut u16, total);
+    counts.fill(0);
+    Frames {
+      base: start + counts_size(total),
+      offset,
+      counts,
+      next: 0,
+      free: total,
+    }
+  }
+
+  fn index(&self, pa: u64) -> usize {
+    assert!(self.contains(pa), "Frame {:#x} not managed", pa);
+    ((pa - self.base) / PAGE_SIZE) as usize
+  }
+
+  // True if `pa` is in a frame of this allocator, not e.g. a peripheral.
+  pub fn contains(&self, pa: u64) -> bool {
+    pa >= self.base && pa - self.base < self.counts.len() as u64 * PAGE_SIZE
+  }
+
+  // A zeroed frame with a count of 1.
+  pub fn alloc(&mut self) -> Result<u64, ErrorKind> {
+    let total = self.counts.len();
+    let index = (0..total)
+      .map(|i| (self.next + i) % total)
+      .find(|&i| self.counts[i] == 0)
+      .ok_or(ErrorKind::OutOfMemory)?;
+    self.counts[index] = 1;
+    self.next = (index + 1) % total;
+    self.free -= 1;
+    let pa = self.base + index as u64 * PAGE_SIZE;
+    unsafe { core::ptr::write_bytes(self.ptr(pa), 0, PAGE_SIZE as usize) };
+    Ok(pa)
+  }
+
+  // Takes the free frame at `pa` out of allocation, e.g. for data the
+  // firmware loaded there. Released like an allocated frame.
+  pub fn reserve(&mut self, pa: u64) -> Result<(), ErrorKind> {
+    if !self.contains(pa) {
+      return Err(ErrorKind::InvalidInput);
+    }
+    let index = self.index(pa);
+    if self.counts[index] != 0 {
+      return Err(ErrorKind::ResourceBusy);
+    }
+    self.counts[index] = 1;
+    self.free -= 1;
+    Ok(())
+  }
+
+  pub fn retain(&mut self, pa: u64) {
+    let index = self.index(pa);
+    assert!(self.counts[index] > 0, "Frame {:#x} is free", pa);
+    self.counts[index] += 1;
+  }
+
+  // Frees the frame with the last reference.
+  pub fn release(&mut self, pa: u64) {
+    let index = self.index(pa);
+    assert!(self.counts[index] > 0, "Frame {:#x} is free", pa);
+    self.counts[index] -= 1;
+    if self.counts[index] == 0 {
+      self.free += 1;
+    }
+  }
+
+  pub fn count(&self, pa: u64) -> u16 {
+    self.counts[self.index(pa)]
+  }
+
+  pub fn free_count(&self) -> usize {
+    self.free
+  }
+
+  // Kernel pointer to the physical address `pa`.
+  pub fn ptr(&self, pa: u64) -> *mut u8 {
+    (pa + self.offset) as *mut u8
+  }
+
+  pub fn table(&mut self, pa: u64) -> &mut Table {
+    unsafe { &mut *(self.ptr(pa) as *mut Table) }
+  }
+
+  pub fn page(&mut self, pa: u64) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(self.ptr(pa), PAGE_SIZE as usize) }
+  }
+}
@@ -0,0 +1,91 @@
+use super::frame::Frames;
+use super::PAGE_SIZE;
+use crate::common::error::ErrorKind;
+
+// Frames over leaked host memory, physical addresses are host addresses.
+pub fn host_frames(pages: usize) -> Frames {
+  let size = pages * PAGE_SIZE as usize;
+  let layout =
+    std::alloc::Layout::from_size_align(size, PAGE_SIZE as usize).unwrap();
+  let start = unsafe { std::alloc::alloc_zeroed(layout) } as u64;
+  unsafe { Frames::new(start, start + size as u64, 0) }
+}
+
+#[test]
+fn test_counts_carved_from_memory() {
+  let frames = host_frames(64);
+  // One page of counts
+  assert_eq!(frames.free_count(), 63);
+}
+
+#[test]
+fn test_alloc_zeroed() {
+  let mut frames = host_frames(4);
+  let a = frames.alloc().unwrap();
+  frames.page(a).fill(0xAA);
+  frames.release(a);
+  let b = frames.alloc().unwrap();
+  let c = frames.alloc().unwrap();
+  let d = frames.alloc().unwrap();
+  assert_eq!(a % PAGE_SIZE, 0);
+  assert!(frames.contains(b) && frames.contains(c) && frames.contains(d));
+  assert!([b, c, d].contains(&a));
+  assert!(frames.page(a).iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn test_reference_counts() {
+  let mut frames = host_frames(4);
+  let a = frames.alloc().unwrap();
+  assert_eq!(frames.count(a), 1);
+  frames.retain(a);
+  assert_eq!(frames.count(a), 2);
+  frames.release(a);
+  assert_eq!(frames.free_count(), 2);
+  frames.release(a);
+  assert_eq!(frames.count(a), 0);
+  assert_eq!(frames.free_count(), 3);
+}
+
+#[test]
+fn test_out_of_memory() {
+  let mut frames = host_frames(3);
+  let a = frames.alloc().unwrap();
+  let b = frames.alloc().unwrap();
+  assert_ne!(a, b);
+  assert!(frames.alloc().is_err());
+  frames.release(b);
+  assert_eq!(frames.alloc().unwrap(), b);
+}
+
+#[test]
+fn test_contains() {
+  let mut frames = host_frames(3);
+  let a = frames.alloc().unwrap();
+  assert!(frames.contains(a + PAGE_SIZE - 1));
+  assert!(!frames.contains(a - PAGE_SIZE));
+  assert!(!frames.contains(0x3F00_0000));
+}
+
+#[test]
+fn test_reserve() {
+  let mut frames = host_frames(3);
+  let a = frames.alloc().unwrap();
+  frames.release(a);
+  frames.reserve(a).unwrap();
+  assert_eq!(frames.free_count(), 1);
+  assert_ne!(frames.alloc().unwrap(), a);
+  assert_eq!(frames.reserve(a), Err(ErrorKind::ResourceBusy));
+  assert_eq!(frames.reserve(0x3F00_0000), Err(ErrorKind::InvalidInput));
+  frames.release(a);
+  assert_eq!(frames.alloc().unwrap(), a);
+}
+
+#[test]
+#[should_panic]
+fn test_release_free_frame() {
+  let mut frames = host_frames(3);
+  let a = frames.alloc().unwrap();
+  frames.release(a);
+  frames.release(a);
+}
@@ -0,0 +1,164 @@
+// Memory management: physical frames and per-process address spaces.
+//
+// User address spaces are TTBR0 translation tables tagged with an ASID, see
+// AddressSpace. They hold user mappings only.
+//
+// The kernel lives in the high half: a linear map of physical memory in
+// TTBR1 at KERNEL_BASE, EL1 only and global. The image is linked and runs
+// at KERNEL_BASE plus its load address, and reaches all memory, including
+// frames of user pages and tables, peripherals and DMA buffers, through
+// the linear map, see phys_to_virt().
+
+pub mod address_space;
+pub mod asid;
+pub mod frame;
+pub mod page_table;
+
+pub use address_space::AddressSpace;
+pub use frame::Frames;
+
+pub const PAGE_SIZE: u64 = 4096;
+// Physical address 0 in the kernel's linear map
+pub const KERNEL_BASE: u64 = 0xFFFF_0000_0000_0000;
+// Kernel addresses minus physical ones. The bootloader runs with the MMU
+// off.
+#[cfg(not(feature = "bootloader"))]
+pub const KERNEL_OFFSET: u64 = KERNEL_BASE;
+#[cfg(feature = "bootloader")]
+pub const KERNEL_OFFSET: u64 = 0;
+pub const USER_BASE: u64 = 0x8000_0000;
+pub const USER_TOP: u64 = 0x8000_0000_0000;
+
+// Page permissions. Mapped pages are always readable.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Flags {
+  pub read: bool,
+  pub write: bool,
+  pub execute: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+  Read,
+  Write,
+  Execute,
+}
+
+pub struct Ops {
+  // Loads TTBR0_EL1 with the table at `root` and `asid`.
+  pub activate: fn(root: u64, asid: u16),
+  // Loads an empty TTBR0, leaving only the kernel's mappings.
+  pub deactivate: fn(),
+  pub invalidate_page: fn(asid: u16, va: u64),
+  pub invalidate_asid: fn(asid: u16),
+}
+
+static mut OPS: core::mem::MaybeUninit<Ops> =
+  core::mem::MaybeUninit::<Ops>::uninit();
+// This will be set to 0 during bss zero-ing.
+static mut SET: bool = false;
+static mut FRAMES: Option<Frames> = None;
+// Address space in TTBR0, see activate()
+static mut CURRENT: *mut AddressSpace = core::ptr::null_mut();
+// Its ASID, 0 if there is none
+static mut CURRENT_ASID: u16 = 0;
+
+pub fn register(ops: Ops) {
+  unsafe {
+    OPS = core::mem::MaybeUninit::<Ops>::new(ops);
+    SET = true;
+  };
+}
+
+// Kernel address of physical memory, e.g. of peripherals or buffers the
+// firmware returns.
+pub fn phys_to_virt(pa: u64) -> u64 {
+  pa + KERNEL_OFFSET
+}
+
+// Physical address of kernel memory, e.g. for DMA.
+pub fn virt_to_phys(va: u64) -> u64 {
+  va - KERNEL_OFFSET
+}
+
+// Without ops, e.g. on the host, there is no TLB to maintain.
+fn invalidate_page(asid: u16, va: u64) {
+  unsafe {
+    if SET {
+      (OPS.assume_init_ref().invalidate_page)(asid, va);
+    }
+  }
+}
+
+fn invalidate_asid(asid: u16) {
+  unsafe {
+    if SET {
+      (OPS.assume_init_ref().invalidate_asid)(asid);
+    }
+  }
+}
+
+// Hands [start, end) of physical memory to the frame allocator.
+//
+// Safety: see Frames::new().
+pub unsafe fn initialize(start: u64, end: u64, offset: u64) {
+  FRAMES = Some(Frames::new(start, end, offset));
+}
+
+pub fn frames() -> &'static mut Frames {
+  match try_frames() {
+    Some(frames) => frames,
+    None => panic!("Frame allocator not initialized"),
+  }
+}
+
+// None until initialize(), for callers that can fail instead.
+pub fn try_frames() -> Option<&'static mut Frames> {
+  unsafe { (*core::ptr::addr_of_mut!(FRAMES)).as_mut() }
+}
+
+// Switches TTBR0 to `space`, which must not move or be released while
+// active.
+pub fn activate(space: &mut AddressSpace) {
+  unsafe {
+    assert!(SET, "No mm ops registered");
+    CURRENT = space;
+    CURRENT_ASID = space.asid();
+    (OPS.assume_init_ref().activate)(space.root(), space.asid());
+  }
+}
+
+pub fn deactivate() {
+  unsafe {
+    assert!(SET, "No mm ops registered");
+    CURRENT = core::ptr::null_mut();
+    CURRENT_ASID = 0;
+    (OPS.assume_init_ref().deactivate)();
+  }
+}
+
+// Called when `space` is released. It may have moved since activate(), so
+// it is recognized by its ASID.
+fn forget(space: &AddressSpace) {
+  if unsafe { CURRENT_ASID } == space.asid() {
+    deactivate();
+  }
+}
+
+pub fn current() -> Option<&'static mut AddressSpace> {
+  unsafe { CURRENT.as_mut() }
+}
+
+// Page fault at `va`, from the exception handler. False if it was not
+// resolved: no active address space or the access is not allowed.
+pub fn handle_fault(va: u64, access: Access) -> bool {
+  let (Some(space), Some(frames)) = (current(), try_frames()) else {
+    return false;
+  };
+  space.handle_fault(frames, va, access).is_ok()
+}
+
+#[cfg(test)]
+mod address_space_test;
+#[cfg(test)]
+pub(crate) mod frame_test;
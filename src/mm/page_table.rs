@@ -0,0 +1,91 @@
+// VMSAv8-64 translation table descriptors, 4 KiB granule, 48 bit virtual
+// addresses: 4 levels of 512 entries, level 3 maps pages.
+// https://developer.arm.com/documentation/101811/0104/Descriptors
+
+use super::Flags;
+
+pub const ENTRIES: usize = 512;
+pub const LEVELS: usize = 4;
+pub type Table = [u64; ENTRIES];
+
+// Bytes covered by an entry at each level
+pub const LEVEL_SIZE: [u64; LEVELS] = [1 << 39, 1 << 30, 1 << 21, 1 << 12];
+
+const VALID: u64 = 1 << 0;
+// Next level table, or a page at level 3. Blocks have this bit clear.
+const TABLE: u64 = 1 << 1;
+// MAIR_EL1 attribute index
+pub const ATTR_DEVICE: u64 = 0 << 2;
+pub const ATTR_NORMAL: u64 = 1 << 2;
+const AP_USER: u64 = 1 << 6;
+const AP_READ_ONLY: u64 = 1 << 7;
+const SH_INNER: u64 = 3 << 8;
+const AF: u64 = 1 << 10;
+const NOT_GLOBAL: u64 = 1 << 11;
+const PXN: u64 = 1 << 53;
+const UXN: u64 = 1 << 54;
+// Software bit: read-only while the frame is shared, writable for the owner.
+pub const COPY_ON_WRITE: u64 = 1 << 55;
+const ADDRESS_MASK: u64 = 0x0000_FFFF_FFFF_F000;
+
+pub fn index(va: u64, level: usize) -> usize {
+  ((va / LEVEL_SIZE[level]) % ENTRIES as u64) as usize
+}
+
+pub fn is_valid(desc: u64) -> bool {
+  desc & VALID != 0
+}
+
+pub fn address(desc: u64) -> u64 {
+  desc & ADDRESS_MASK
+}
+
+pub fn table(pa: u64) -> u64 {
+  pa | TABLE | VALID
+}
+
+// Non-global user page. Readable whenever mapped, never executable by the
+// kernel.
+pub fn user_page(pa: u64, flags: Flags) -> u64 {
+  let mut desc = pa | ATTR_NORMAL | AP_USER | SH_INNER | AF | NOT_GLOBAL;
+  desc |= PXN | TABLE | VALID;
+  if !flags.write {
+    desc |= AP_READ_ONLY;
+  }
+  if !flags.execute {
+    desc |= UXN;
+  }
+  desc
+}
+
+// Global block of LEVEL_SIZE[2] bytes, for the kernel only.
+pub fn kernel_block(pa: u64, attr: u64) -> u64 {
+  let mut desc = pa | attr | AF | UXN | VALID;
+  if attr == ATTR_DEVICE {
+    desc |= PXN;
+  } else {
+    desc |= SH_INNER;
+  }
+  desc
+}
+
+// Flags of a user page, a copy-on-write page is writable.
+pub fn flags(desc: u64) -> Flags {
+  Flags {
+    read: true,
+    write: desc & AP_READ_ONLY == 0 || desc & COPY_ON_WRITE != 0,
+    execute: desc & UXN == 0,
+  }
+}
+
+// Read-only until written, see AddressSpace::handle_fault().
+pub fn share(desc: u64) -> u64 {
+  match desc & AP_READ_ONLY {
+    0 => desc | AP_READ_ONLY | COPY_ON_WRITE,
+    _ => desc,
+  }
+}
+
+pub fn is_copy_on_write(desc: u64) -> bool {
+  desc & COPY_ON_WRITE != 0
+}
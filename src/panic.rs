@@ -30,6 +30,14 @@ fn on_panic(info: &core::panic::PanicInfo) -> ! {
 
   if let Some(location) = info.location() {
     stream::println!("{}:{}", location.file(), location.line());
+    // Exits if a test was running.
+    #[cfg(feature = "ktest")]
+    crate::ktest::on_panic(
+      &mut stream::out(),
+      info.message().as_str().unwrap_or("No message"),
+      location.file(),
+      location.line(),
+    );
   }
 
   unsafe { (OPS.assume_init_ref().post_handler)() };
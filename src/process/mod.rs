@@ -0,0 +1,476 @@
+// Processes: PIDs, parent/child relationships and the fork/exec/wait/exit
+// lifecycle.
+//
+// A process is its saved user registers and an address space. User code
+// enters the kernel through exceptions on the one kernel stack: the entry
+// code passes the user registers as a frame, see save(), and returns to
+// whichever process is current afterwards, see resume(). A process runs
+// until it blocks in wait4 or exits, there is no preemption.
+
+use crate::common::error::ErrorKind;
+use crate::exec::{self, elf};
+use crate::fs::{self, vfs};
+use crate::gdb::Registers;
+use crate::log;
+use crate::mm::address_space::Loader;
+use crate::mm::{self, AddressSpace, Frames};
+
+pub type Pid = u32;
+
+pub const MAX_PROCESSES: usize = 32;
+// Parent of orphans
+pub const INIT_PID: Pid = 1;
+const MAX_PID: Pid = 32768;
+
+// Load address of position independent programs
+pub const LOAD_BASE: u64 = 0x1_0000_0000;
+pub const STACK_SIZE: u64 = 1 << 20;
+// EL0t with all interrupts unmasked
+const PSTATE_EL0: u64 = 0;
+// Size of the svc instruction, see block()
+const INSTRUCTION_SIZE: u64 = 4;
+
+// wait4() options
+pub const WNOHANG: u64 = 1;
+
+// Wait status of a process killed by SIGSEGV
+pub const STATUS_FAULT: i32 = 11;
+
+// Wait status of a process that exited with `code`.
+pub fn exit_status(code: u64) -> i32 {
+  ((code & 0xFF) as i32) << 8
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum State {
+  Runnable,
+  // In wait4() for the child with this PID, any child if not positive
+  Waiting(i64),
+  // Exited with a wait status, until the parent reaps it
+  Zombie(i32),
+}
+
+pub struct Process {
+  pid: Pid,
+  // 0 for processes started by the kernel
+  parent: Pid,
+  state: State,
+  pub registers: Registers,
+  // None once exited
+  space: Option<AddressSpace>,
+  pub files: vfs::FdTable,
+}
+
+impl Process {
+  pub fn pid(&self) -> Pid {
+    self.pid
+  }
+
+  pub fn parent(&self) -> Pid {
+    self.parent
+  }
+
+  pub fn state(&self) -> State {
+    self.state
+  }
+
+  pub fn space(&mut self) -> Option<&mut AddressSpace> {
+    self.space.as_mut()
+  }
+}
+
+// A new address space with `image` loaded and the registers to start it.
+fn load(
+  frames: &mut Frames,
+  image: &mut impl elf::Source,
+  args: &[&str],
+  env: &[&str],
+) -> Result<(AddressSpace, Registers), ErrorKind> {
+  let mut space = AddressSpace::new(frames)?;
+  let params = elf::LoadParams {
+    base: LOAD_BASE,
+    stack_top: mm::USER_TOP,
+    stack_size: STACK_SIZE,
+    args,
+    env,
+  };
+  let mut loader = Loader {
+    space: &mut space,
+    frames,
+  };
+  match elf::load(image, &mut loader, &params) {
+    Ok(loaded) => {
+      let registers = Registers {
+        sp: loaded.stack_pointer,
+        pc: loaded.entry,
+        pstate: PSTATE_EL0,
+        ..Default::default()
+      };
+      Ok((space, registers))
+    }
+    Err(e) => {
+      space.release(frames);
+      Err(e)
+    }
+  }
+}
+
+pub struct ProcessTable {
+  // Processes never move, mm::activate() keeps a pointer to their space.
+  slots: [Option<Process>; MAX_PROCESSES],
+  next_pid: Pid,
+  current: Option<Pid>,
+}
+
+impl ProcessTable {
+  pub const fn new() -> ProcessTable {
+    ProcessTable {
+      slots: [const { None }; MAX_PROCESSES],
+      next_pid: INIT_PID,
+      current: None,
+    }
+  }
+
+  pub fn get(&self, pid: Pid) -> Option<&Process> {
+    self
+      .slots
+      .iter()
+      .flatten()
+      .find(|process| process.pid == pid)
+  }
+
+  pub fn get_mut(&mut self, pid: Pid) -> Option<&mut Process> {
+    self
+      .slots
+      .iter_mut()
+      .flatten()
+      .find(|process| process.pid == pid)
+  }
+
+  // The process running when the kernel was entered from user space.
+  pub fn current(&self) -> Option<Pid> {
+    self.current
+  }
+
+  pub fn count(&self) -> usize {
+    self.slots.iter().flatten().count()
+  }
+
+  fn slot(&self, pid: Pid) -> Option<usize> {
+    self
+      .slots
+      .iter()
+      .position(|slot| slot.as_ref().is_some_and(|process| process.pid == pid))
+  }
+
+  fn allocate_pid(&mut self) -> Pid {
+    // PIDs wrap around to the first one after init.
+    let next = |pid| if pid < MAX_PID { pid + 1 } else { INIT_PID + 1 };
+    let mut pid = self.next_pid;
+    while self.get(pid).is_some() {
+      pid = next(pid);
+    }
+    self.next_pid = next(pid);
+    pid
+  }
+
+  // Releases `space` if there is no free slot.
+  fn insert(
+    &mut self,
+    frames: &mut Frames,
+    parent: Pid,
+    registers: Registers,
+    space: AddressSpace,
+  ) -> Result<Pid, ErrorKind> {
+    let Some(index) = self.slots.iter().position(Option::is_none) else {
+      space.release(frames);
+      return Err(ErrorKind::ResourceBusy);
+    };
+    let pid = self.allocate_pid();
+    self.slots[index] = Some(Process {
+      pid,
+      parent,
+      state: State::Runnable,
+      registers,
+      space: Some(space),
+      files: vfs::FdTable::new(),
+    });
+    Ok(pid)
+  }
+
+  // Starts `image` in a process without a parent. It becomes the current
+  // process if there is none.
+  pub fn spawn(
+    &mut self,
+    frames: &mut Frames,
+    image: &mut impl elf::Source,
+    args: &[&str],
+    env: &[&str],
+  ) -> Result<Pid, ErrorKind> {
+    let (space, registers) = load(frames, image, args, env)?;
+    let pid = self.insert(frames, 0, registers, space)?;
+    if self.current.is_none() {
+      self.current = Some(pid);
+    }
+    Ok(pid)
+  }
+
+  // A copy of `pid` sharing its memory until written and its open files.
+  // The child returns 0 from the syscall.
+  pub fn fork(
+    &mut self,
+    frames: &mut Frames,
+    pid: Pid,
+  ) -> Result<Pid, ErrorKind> {
+    let parent = self.get_mut(pid).ok_or(ErrorKind::NotFound)?;
+    let space = parent.space.as_mut().ok_or(ErrorKind::NotFound)?;
+    let space = space.fork(frames)?;
+    let mut registers = parent.registers;
+    registers.x[0] = 0;
+    let files = parent.files;
+    let child = self.insert(frames, pid, registers, space)?;
+    self.get_mut(child).unwrap().files = files.duplicate(fs::vfs());
+    Ok(child)
+  }
+
+  // Replaces the program of `pid`, which keeps its old program if `image`
+  // can't be loaded.
+  pub fn exec(
+    &mut self,
+    frames: &mut Frames,
+    pid: Pid,
+    image: &mut impl elf::Source,
+    args: &[&str],
+    env: &[&str],
+  ) -> Result<(), ErrorKind> {
+    self.get(pid).ok_or(ErrorKind::NotFound)?;
+    let (space, registers) = load(frames, image, args, env)?;
+    let process = self.get_mut(pid).unwrap();
+    if let Some(old) = process.space.take() {
+      old.release(frames);
+    }
+    process.space = Some(space);
+    process.registers = registers;
+    Ok(())
+  }
+
+  // Ends `pid` with the wait `status`. Its children are handed to init.
+  pub fn exit(
+    &mut self,
+    frames: &mut Frames,
+    pid: Pid,
+    status: i32,
+  ) -> Result<(), ErrorKind> {
+    let process = self.get_mut(pid).ok_or(ErrorKind::NotFound)?;
+    if let Some(space) = process.space.take() {
+      space.release(frames);
+    }
+    process.files.close_all(fs::vfs());
+    process.state = State::Zombie(status);
+    let parent = process.parent;
+    for child in self.slots.iter_mut().flatten() {
+      if child.parent == pid {
+        child.parent = INIT_PID;
+      }
+    }
+    self.wake(parent);
+    self.wake(INIT_PID);
+    Ok(())
+  }
+
+  // An exited child of `parent` matching `target`, see State::Waiting.
+  // NotFound if no child matches at all.
+  fn find_zombie(
+    &self,
+    parent: Pid,
+    target: i64,
+  ) -> Result<Option<Pid>, ErrorKind> {
+    let mut found = false;
+    for child in self.slots.iter().flatten() {
+      if child.parent != parent || (target > 0 && child.pid as i64 != target) {
+        continue;
+      }
+      if let State::Zombie(_) = child.state {
+        return Ok(Some(child.pid));
+      }
+      found = true;
+    }
+    match found {
+      true => Ok(None),
+      false => Err(ErrorKind::NotFound),
+    }
+  }
+
+  // Reaps an exited child of `pid` matching `target` and stores its wait
+  // status at `status` in user memory, unless 0. Without one it returns
+  // None with WNOHANG, otherwise `pid` blocks until a child exits.
+  pub fn wait(
+    &mut self,
+    frames: &mut Frames,
+    pid: Pid,
+    target: i64,
+    status: u64,
+    options: u64,
+  ) -> Result<Option<Pid>, ErrorKind> {
+    let Some(child) = self.find_zombie(pid, target)? else {
+      if options & WNOHANG == 0 {
+        self.block(pid, target);
+      }
+      return Ok(None);
+    };
+    let Some(State::Zombie(code)) = self.get(child).map(Process::state) else {
+      unreachable!();
+    };
+    if status != 0 {
+      let process = self.get_mut(pid).ok_or(ErrorKind::NotFound)?;
+      let space = process.space.as_mut().ok_or(ErrorKind::NotFound)?;
+      space.copy_out(frames, status, &code.to_le_bytes())?;
+    }
+    let index = self.slot(child).unwrap();
+    self.slots[index] = None;
+    Ok(Some(child))
+  }
+
+  // The wait4 syscall is executed again once `pid` is woken up, and then
+  // finds the zombie.
+  fn block(&mut self, pid: Pid, target: i64) {
+    if let Some(process) = self.get_mut(pid) {
+      process.state = State::Waiting(target);
+      process.registers.pc -= INSTRUCTION_SIZE;
+    }
+  }
+
+  // Makes `pid` runnable if it waits for a child that has exited.
+  fn wake(&mut self, pid: Pid) {
+    let Some(State::Waiting(target)) = self.get(pid).map(Process::state) else {
+      return;
+    };
+    if let Ok(Some(_)) = self.find_zombie(pid, target) {
+      self.get_mut(pid).unwrap().state = State::Runnable;
+    }
+  }
+
+  // Stores a syscall result in x0 of the current process, unless it has
+  // blocked or exited.
+  pub fn set_result(&mut self, value: u64) {
+    let current = self.current.and_then(|pid| self.get_mut(pid));
+    if let Some(process) = current {
+      if process.state == State::Runnable {
+        process.registers.x[0] = value;
+      }
+    }
+  }
+
+  // Picks the process to run next: the current one while it is runnable,
+  // then the others in turn.
+  pub fn switch(&mut self) -> Option<Pid> {
+    let current = self.current.and_then(|pid| self.get(pid));
+    if current.is_some_and(|process| process.state == State::Runnable) {
+      return self.current;
+    }
+    let start = self
+      .current
+      .and_then(|pid| self.slot(pid))
+      .map_or(0, |index| index + 1);
+    self.current = (0..MAX_PROCESSES)
+      .filter_map(|i| self.slots[(start + i) % MAX_PROCESSES].as_ref())
+      .find(|process| process.state == State::Runnable)
+      .map(Process::pid);
+    self.current
+  }
+}
+
+pub struct Ops {
+  // Returns to user space with `registers`.
+  pub enter: fn(registers: &Registers) -> !,
+}
+
+static mut OPS: core::mem::MaybeUninit<Ops> =
+  core::mem::MaybeUninit::<Ops>::uninit();
+// This will be set to 0 during bss zero-ing.
+static mut SET: bool = false;
+static mut TABLE: ProcessTable = ProcessTable::new();
+
+pub fn register(ops: Ops) {
+  unsafe {
+    OPS = core::mem::MaybeUninit::<Ops>::new(ops);
+    SET = true;
+  };
+}
+
+pub fn table() -> &'static mut ProcessTable {
+  unsafe { &mut *core::ptr::addr_of_mut!(TABLE) }
+}
+
+// Starts `image` as init with the console as its standard input, output
+// and error. Only returns if it could not be started.
+pub fn run_init(image: exec::Program, args: &[&str]) -> Result<(), ErrorKind> {
+  assert!(unsafe { SET }, "No process ops registered");
+  let vfs = fs::vfs();
+  let console = vfs.open("/dev/console", vfs::O_RDWR)?;
+  let env = ["PATH=/sbin:/bin"];
+  // Closes `image` before enter(), which does not return.
+  let spawned = {
+    let mut image = image;
+    table().spawn(mm::frames(), &mut image, args, &env)
+  };
+  let pid = match spawned {
+    Ok(pid) => pid,
+    Err(e) => {
+      vfs.release(console);
+      return Err(e);
+    }
+  };
+  let files = &mut table().get_mut(pid).unwrap().files;
+  for _ in 0..3 {
+    vfs.retain(console);
+    files.insert(console);
+  }
+  vfs.release(console);
+  let mut registers = Registers::default();
+  resume(&mut registers);
+  unsafe { (OPS.assume_init_ref().enter)(&registers) }
+}
+
+// Keeps the user registers of the current process on exception entry.
+pub fn save(frame: &Registers) {
+  let table = table();
+  if let Some(process) = table.current.and_then(|pid| table.get_mut(pid)) {
+    process.registers = *frame;
+  }
+}
+
+// Loads the registers of the process to run next into `frame`, which is
+// restored on exception return.
+pub fn resume(frame: &mut Registers) {
+  let table = table();
+  if let Some(State::Zombie(status)) = table.get(INIT_PID).map(Process::state) {
+    panic!("init exited with status {:#x}", status);
+  }
+  let Some(pid) = table.switch() else {
+    panic!("No runnable process");
+  };
+  let process = table.get_mut(pid).unwrap();
+  if let Some(space) = process.space.as_mut() {
+    mm::activate(space);
+  }
+  *frame = process.registers;
+}
+
+// Ends the current process after an exception it caused.
+pub fn kill_current(frame: &Registers, esr: u64) {
+  let table = table();
+  let Some(pid) = table.current else {
+    return;
+  };
+  log::warn!(
+    "Process {} killed: esr_el1={:08X}, pc={:#x}",
+    pid,
+    esr,
+    frame.pc
+  );
+  let _ = table.exit(mm::frames(), pid, STATUS_FAULT);
+}
+
+#[cfg(test)]
+mod process_test;
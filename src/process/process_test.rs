@@ -0,0 +1,279 @@
+use super::*;
+use crate::exec::elf::elf_test::{build_executable, SegmentSpec, RW, RX};
+use crate::mm::frame_test::host_frames;
+
+const ENTRY: u64 = LOAD_BASE + 0x10;
+const DATA: u64 = LOAD_BASE + 0x1_0000;
+
+fn program(fill: u8) -> Vec<u8> {
+  build_executable(
+    ENTRY,
+    &[
+      SegmentSpec {
+        flags: RX,
+        vaddr: LOAD_BASE,
+        data: &[fill; 0x40],
+        mem_size: 0x40,
+      },
+      SegmentSpec {
+        flags: RW,
+        vaddr: DATA,
+        data: &[fill; 0x10],
+        mem_size: 0x2000,
+      },
+    ],
+  )
+}
+
+fn read(
+  table: &mut ProcessTable,
+  frames: &mut Frames,
+  pid: Pid,
+  va: u64,
+) -> u8 {
+  let mut byte = [0];
+  let space = table.get_mut(pid).unwrap().space().unwrap();
+  space.copy_in(frames, va, &mut byte).unwrap();
+  byte[0]
+}
+
+fn write(
+  table: &mut ProcessTable,
+  frames: &mut Frames,
+  pid: Pid,
+  va: u64,
+  byte: u8,
+) {
+  let space = table.get_mut(pid).unwrap().space().unwrap();
+  space.copy_out(frames, va, &[byte]).unwrap();
+}
+
+#[test]
+fn test_spawn() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let pid = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &["init"], &[])
+    .unwrap();
+  assert_eq!(pid, INIT_PID);
+  assert_eq!(table.current(), Some(INIT_PID));
+  let process = table.get(pid).unwrap();
+  assert_eq!(process.parent(), 0);
+  assert_eq!(process.state(), State::Runnable);
+  assert_eq!(process.registers.pc, ENTRY);
+  assert_eq!(process.registers.pstate, PSTATE_EL0);
+  assert!(process.registers.sp < mm::USER_TOP);
+  assert_eq!(process.registers.sp % 16, 0);
+  assert_eq!(read(&mut table, &mut frames, pid, DATA), 1);
+
+  let free = frames.free_count();
+  assert_eq!(
+    table.spawn(&mut frames, &mut &b"not an elf"[..], &[], &[]),
+    Err(ErrorKind::InvalidData)
+  );
+  assert_eq!(frames.free_count(), free);
+}
+
+#[test]
+fn test_fork_copies_memory() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let parent = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  table.get_mut(parent).unwrap().registers.x[0] = 0x55;
+  table.get_mut(parent).unwrap().registers.x[1] = 0x66;
+  let child = table.fork(&mut frames, parent).unwrap();
+  assert_ne!(child, parent);
+
+  let process = table.get(child).unwrap();
+  assert_eq!(process.parent(), parent);
+  assert_eq!(process.registers.x[0], 0);
+  assert_eq!(process.registers.x[1], 0x66);
+  assert_eq!(table.current(), Some(parent));
+
+  write(&mut table, &mut frames, child, DATA, 2);
+  assert_eq!(read(&mut table, &mut frames, parent, DATA), 1);
+  assert_eq!(read(&mut table, &mut frames, child, DATA), 2);
+}
+
+#[test]
+fn test_exec() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let pid = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  table.get_mut(pid).unwrap().registers.pc = ENTRY + 0x20;
+
+  assert_eq!(
+    table.exec(&mut frames, pid, &mut &b"\x7fELF"[..], &[], &[]),
+    Err(ErrorKind::InvalidData)
+  );
+  assert_eq!(table.get(pid).unwrap().registers.pc, ENTRY + 0x20);
+  assert_eq!(read(&mut table, &mut frames, pid, DATA), 1);
+
+  let free = frames.free_count();
+  table
+    .exec(
+      &mut frames,
+      pid,
+      &mut program(3).as_slice(),
+      &["sh", "-c"],
+      &["A=B"],
+    )
+    .unwrap();
+  assert_eq!(table.get(pid).unwrap().registers.pc, ENTRY);
+  assert_eq!(read(&mut table, &mut frames, pid, DATA), 3);
+  // The old pages were released.
+  assert_eq!(frames.free_count(), free);
+}
+
+#[test]
+fn test_wait_without_children() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let pid = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  assert_eq!(
+    table.wait(&mut frames, pid, -1, 0, 0),
+    Err(ErrorKind::NotFound)
+  );
+  let child = table.fork(&mut frames, pid).unwrap();
+  assert_eq!(
+    table.wait(&mut frames, pid, child as i64 + 1, 0, 0),
+    Err(ErrorKind::NotFound)
+  );
+  assert_eq!(table.wait(&mut frames, pid, -1, 0, WNOHANG), Ok(None));
+  assert_eq!(table.get(pid).unwrap().state(), State::Runnable);
+}
+
+#[test]
+fn test_wait_blocks_until_exit() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let parent = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  let free = frames.free_count();
+  let child = table.fork(&mut frames, parent).unwrap();
+
+  let pc = table.get(parent).unwrap().registers.pc;
+  assert_eq!(
+    table.wait(&mut frames, parent, child as i64, DATA, 0),
+    Ok(None)
+  );
+  let process = table.get(parent).unwrap();
+  assert_eq!(process.state(), State::Waiting(child as i64));
+  // wait4 is executed again when the parent resumes.
+  assert_eq!(process.registers.pc, pc - INSTRUCTION_SIZE);
+  assert_eq!(table.switch(), Some(child));
+
+  table.exit(&mut frames, child, exit_status(0x1234)).unwrap();
+  assert_eq!(table.get(child).unwrap().state(), State::Zombie(0x3400));
+  assert_eq!(table.get(parent).unwrap().state(), State::Runnable);
+  assert_eq!(table.switch(), Some(parent));
+
+  assert_eq!(
+    table.wait(&mut frames, parent, -1, DATA, 0),
+    Ok(Some(child))
+  );
+  let mut status = [0; 4];
+  let space = table.get_mut(parent).unwrap().space().unwrap();
+  space.copy_in(&mut frames, DATA, &mut status).unwrap();
+  assert_eq!(i32::from_le_bytes(status), 0x3400);
+  assert!(table.get(child).is_none());
+  assert_eq!(table.count(), 1);
+  // The child's frames were released, the parent got its pages back.
+  assert_eq!(frames.free_count(), free);
+}
+
+#[test]
+fn test_set_result() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let parent = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  table.fork(&mut frames, parent).unwrap();
+  table.set_result(7);
+  assert_eq!(table.get(parent).unwrap().registers.x[0], 7);
+
+  table.wait(&mut frames, parent, -1, 0, 0).unwrap();
+  table.set_result(8);
+  assert_eq!(table.get(parent).unwrap().registers.x[0], 7);
+}
+
+#[test]
+fn test_orphans_go_to_init() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let init = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  let parent = table.fork(&mut frames, init).unwrap();
+  let orphan = table.fork(&mut frames, parent).unwrap();
+  table.wait(&mut frames, init, -1, 0, 0).unwrap();
+
+  table.exit(&mut frames, orphan, exit_status(1)).unwrap();
+  table.exit(&mut frames, parent, exit_status(2)).unwrap();
+  assert_eq!(table.get(orphan).unwrap().parent(), INIT_PID);
+  assert_eq!(table.get(init).unwrap().state(), State::Runnable);
+  let mut reaped = [
+    table.wait(&mut frames, init, -1, 0, 0).unwrap().unwrap(),
+    table.wait(&mut frames, init, -1, 0, 0).unwrap().unwrap(),
+  ];
+  reaped.sort();
+  assert_eq!(reaped, [parent, orphan]);
+}
+
+#[test]
+fn test_switch_round_robin() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let a = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  let b = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  let c = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  assert_eq!(table.switch(), Some(a));
+  table.exit(&mut frames, a, 0).unwrap();
+  assert_eq!(table.switch(), Some(b));
+  table.exit(&mut frames, b, 0).unwrap();
+  assert_eq!(table.switch(), Some(c));
+  table.exit(&mut frames, c, 0).unwrap();
+  assert_eq!(table.switch(), None);
+}
+
+#[test]
+fn test_table_full() {
+  let mut frames = host_frames(1024);
+  let mut table = ProcessTable::new();
+  let init = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  for _ in 1..MAX_PROCESSES {
+    table.fork(&mut frames, init).unwrap();
+  }
+  let free = frames.free_count();
+  assert_eq!(table.fork(&mut frames, init), Err(ErrorKind::ResourceBusy));
+  assert_eq!(frames.free_count(), free);
+}
+
+#[test]
+fn test_pids_are_not_reused_at_once() {
+  let mut frames = host_frames(128);
+  let mut table = ProcessTable::new();
+  let init = table
+    .spawn(&mut frames, &mut program(1).as_slice(), &[], &[])
+    .unwrap();
+  let child = table.fork(&mut frames, init).unwrap();
+  table.exit(&mut frames, child, 0).unwrap();
+  table.wait(&mut frames, init, -1, 0, 0).unwrap();
+  assert!(table.fork(&mut frames, init).unwrap() > child);
+}
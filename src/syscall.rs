@@ -1,10 +1,35 @@
+// System calls from user space: `svc #0` with the number in x8, arguments
+// in x0 to x3 and the result, or a negated errno, returned in x0 as on
+// Linux.
+
+use crate::common::error::ErrorKind;
+use crate::exec::{self, elf};
+use crate::fs::{self, vfs};
+use crate::gdb::Registers;
 use crate::io::uart;
+use crate::mm::{self, AddressSpace, Frames, PAGE_SIZE};
+use crate::process;
+use arrayvec::ArrayVec;
 use core::result::Result;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum SyscallID {
   UartRead,
   UartWrite,
+  Fork,
+  Execve,
+  Wait4,
+  Exit,
+  GetPid,
+  GetPpid,
+  Open,
+  Read,
+  Write,
+  Close,
+  Lseek,
+  Stat,
+  Fstat,
+  Readdir,
   Invalid,
 }
 
@@ -15,6 +40,20 @@ impl TryFrom<u32> for SyscallID {
     match value {
       0 => Ok(SyscallID::UartRead),
       1 => Ok(SyscallID::UartWrite),
+      2 => Ok(SyscallID::Fork),
+      3 => Ok(SyscallID::Execve),
+      4 => Ok(SyscallID::Wait4),
+      5 => Ok(SyscallID::Exit),
+      6 => Ok(SyscallID::GetPid),
+      7 => Ok(SyscallID::GetPpid),
+      8 => Ok(SyscallID::Open),
+      9 => Ok(SyscallID::Read),
+      10 => Ok(SyscallID::Write),
+      11 => Ok(SyscallID::Close),
+      12 => Ok(SyscallID::Lseek),
+      13 => Ok(SyscallID::Stat),
+      14 => Ok(SyscallID::Fstat),
+      15 => Ok(SyscallID::Readdir),
       _ => Ok(SyscallID::Invalid),
     }
   }
@@ -25,48 +64,448 @@ pub enum SyscallError {
   InvalidSyscall,
   WriteError,
   ReadError,
+  // A pointer argument is not accessible user memory
+  BadAddress,
+  NoChild,
+  NotExecutable,
+  BadFileDescriptor,
+  TooManyFiles,
+  Error(ErrorKind),
+}
+
+impl From<ErrorKind> for SyscallError {
+  fn from(kind: ErrorKind) -> Self {
+    SyscallError::Error(kind)
+  }
+}
+
+impl SyscallError {
+  // Linux errno, see asm-generic/errno-base.h
+  pub fn errno(&self) -> u64 {
+    match self {
+      SyscallError::InvalidSyscall => 38,
+      SyscallError::WriteError | SyscallError::ReadError => 5,
+      SyscallError::BadAddress => 14,
+      SyscallError::NoChild => 10,
+      SyscallError::NotExecutable => 8,
+      SyscallError::BadFileDescriptor => 9,
+      SyscallError::TooManyFiles => 24,
+      SyscallError::Error(kind) => match kind {
+        ErrorKind::NotFound => 2,
+        ErrorKind::ArgumentListTooLong => 7,
+        ErrorKind::ResourceBusy | ErrorKind::WouldBlock => 11,
+        ErrorKind::OutOfMemory => 12,
+        ErrorKind::PermissionDenied => 13,
+        ErrorKind::AlreadyExists => 17,
+        ErrorKind::NotADirectory => 20,
+        ErrorKind::IsADirectory => 21,
+        ErrorKind::InvalidInput | ErrorKind::InvalidData => 22,
+        ErrorKind::FileTooLarge => 27,
+        ErrorKind::StorageFull => 28,
+        ErrorKind::NotSeekable => 29,
+        ErrorKind::ReadOnlyFilesystem => 30,
+        ErrorKind::InvalidFilename => 36,
+        ErrorKind::Unsupported => 38,
+        ErrorKind::DirectoryNotEmpty => 39,
+        _ => 5,
+      },
+    }
+  }
 }
 
+pub const MAX_ARGS: usize = 4;
+const SYSCALL_COUNT: usize = SyscallID::Invalid as usize;
+// Longest execve() path, including the NUL
+const MAX_PATH: usize = 256;
+// Bytes of the execve() arguments and of the environment strings each
+const MAX_STRINGS: usize = 2048;
+// Bytes copied between user memory and a file at a time
+const CHUNK_SIZE: usize = 512;
+// Sizes of struct stat and of our struct dirent
+const STAT_SIZE: usize = 128;
+const DIRENT_SIZE: usize = 16 + vfs::MAX_NAME + 1;
+
 pub type SyscallResult = Result<u64, SyscallError>;
-pub type SyscallFn = fn(u64, u64) -> SyscallResult;
+pub type SyscallFn = fn([u64; MAX_ARGS]) -> SyscallResult;
 
 pub struct SyscallTable {
-  table: [Option<SyscallFn>; 2],
+  table: [Option<SyscallFn>; SYSCALL_COUNT],
 }
 
 impl SyscallTable {
   pub fn new() -> Self {
-    let mut table: [Option<SyscallFn>; 2] = [None; 2];
+    let mut table: [Option<SyscallFn>; SYSCALL_COUNT] = [None; SYSCALL_COUNT];
     table[SyscallID::UartRead as usize] = Some(sys_uart_read);
     table[SyscallID::UartWrite as usize] = Some(sys_uart_write);
+    table[SyscallID::Fork as usize] = Some(sys_fork);
+    table[SyscallID::Execve as usize] = Some(sys_execve);
+    table[SyscallID::Wait4 as usize] = Some(sys_wait4);
+    table[SyscallID::Exit as usize] = Some(sys_exit);
+    table[SyscallID::GetPid as usize] = Some(sys_getpid);
+    table[SyscallID::GetPpid as usize] = Some(sys_getppid);
+    table[SyscallID::Open as usize] = Some(sys_open);
+    table[SyscallID::Read as usize] = Some(sys_read);
+    table[SyscallID::Write as usize] = Some(sys_write);
+    table[SyscallID::Close as usize] = Some(sys_close);
+    table[SyscallID::Lseek as usize] = Some(sys_lseek);
+    table[SyscallID::Stat as usize] = Some(sys_stat);
+    table[SyscallID::Fstat as usize] = Some(sys_fstat);
+    table[SyscallID::Readdir as usize] = Some(sys_readdir);
     SyscallTable { table }
   }
 
-  pub fn dispatch(&self, id: SyscallID, arg1: u64, arg2: u64) -> SyscallResult {
+  pub fn dispatch(
+    &self,
+    id: SyscallID,
+    args: [u64; MAX_ARGS],
+  ) -> SyscallResult {
     // Check if the ID is invalid or out of bounds and return InvalidSyscall error
     if id == SyscallID::Invalid || id as usize >= self.table.len() {
       return Err(SyscallError::InvalidSyscall);
     }
 
     match self.table[id as usize] {
-      Some(syscall_fn) => syscall_fn(arg1, arg2),
+      Some(syscall_fn) => syscall_fn(args),
       None => Err(SyscallError::InvalidSyscall),
     }
   }
 }
 
+// svc from the current process, whose registers are `frame`. The result
+// goes to the saved registers, see process::resume().
+pub fn handle(frame: &Registers) {
+  let id = u32::try_from(frame.x[8])
+    .ok()
+    .and_then(|number| SyscallID::try_from(number).ok())
+    .unwrap_or(SyscallID::Invalid);
+  let args = [frame.x[0], frame.x[1], frame.x[2], frame.x[3]];
+  let value = match SyscallTable::new().dispatch(id, args) {
+    Ok(value) => value,
+    Err(e) => (e.errno() as i64).wrapping_neg() as u64,
+  };
+  process::table().set_result(value);
+}
+
+fn current_pid() -> Result<process::Pid, SyscallError> {
+  process::table()
+    .current()
+    .ok_or(SyscallError::InvalidSyscall)
+}
+
+// File descriptors of the calling process.
+fn current_files() -> Result<&'static mut vfs::FdTable, SyscallError> {
+  let pid = current_pid()?;
+  let process = process::table().get_mut(pid);
+  Ok(&mut process.ok_or(SyscallError::InvalidSyscall)?.files)
+}
+
+// The open file behind `fd` of the calling process.
+fn current_file(fd: u64) -> Result<vfs::FileId, SyscallError> {
+  current_files()?
+    .get(fd)
+    .ok_or(SyscallError::BadFileDescriptor)
+}
+
+// Address space of the calling process.
+fn user_space() -> Result<&'static mut AddressSpace, SyscallError> {
+  mm::current().ok_or(SyscallError::BadAddress)
+}
+
+// Reads the NUL terminated string at `va` into `buffer`, returns its length.
+fn read_string(
+  space: &mut AddressSpace,
+  frames: &mut Frames,
+  va: u64,
+  buffer: &mut [u8],
+) -> Result<usize, SyscallError> {
+  let mut len = 0;
+  while len < buffer.len() {
+    let addr = va.checked_add(len as u64).ok_or(SyscallError::BadAddress)?;
+    // The string may end right before an unmapped page.
+    let chunk =
+      ((PAGE_SIZE - addr % PAGE_SIZE) as usize).min(buffer.len() - len);
+    let part = &mut buffer[len..len + chunk];
+    space
+      .copy_in(frames, addr, part)
+      .map_err(|_| SyscallError::BadAddress)?;
+    if let Some(end) = part.iter().position(|&byte| byte == 0) {
+      return Ok(len + end);
+    }
+    len += chunk;
+  }
+  Err(ErrorKind::ArgumentListTooLong.into())
+}
+
+fn read_str<'a>(
+  space: &mut AddressSpace,
+  frames: &mut Frames,
+  va: u64,
+  buffer: &'a mut [u8],
+) -> Result<(&'a str, &'a mut [u8]), SyscallError> {
+  let len = read_string(space, frames, va, buffer)?;
+  let (string, rest) = buffer.split_at_mut(len + 1);
+  let string: &'a [u8] = string;
+  match core::str::from_utf8(&string[..len]) {
+    Ok(string) => Ok((string, rest)),
+    Err(_) => Err(ErrorKind::InvalidInput.into()),
+  }
+}
+
+// Reads the NULL terminated array of strings at `va`, which may be 0 for
+// none, into `buffer`.
+fn read_strings<'a, const N: usize>(
+  space: &mut AddressSpace,
+  frames: &mut Frames,
+  va: u64,
+  mut buffer: &'a mut [u8],
+) -> Result<ArrayVec<&'a str, N>, SyscallError> {
+  let mut strings = ArrayVec::new();
+  if va == 0 {
+    return Ok(strings);
+  }
+  loop {
+    let at = (strings.len() as u64)
+      .checked_mul(8)
+      .and_then(|offset| va.checked_add(offset))
+      .ok_or(SyscallError::BadAddress)?;
+    let mut pointer = [0; 8];
+    space
+      .copy_in(frames, at, &mut pointer)
+      .map_err(|_| SyscallError::BadAddress)?;
+    let pointer = u64::from_le_bytes(pointer);
+    if pointer == 0 {
+      return Ok(strings);
+    }
+    let (string, rest) = read_str(space, frames, pointer, buffer)?;
+    buffer = rest;
+    strings
+      .try_push(string)
+      .map_err(|_| ErrorKind::ArgumentListTooLong)?;
+  }
+}
+
 // Syscall handler for UART read
-fn sys_uart_read(_: u64, _: u64) -> SyscallResult {
+fn sys_uart_read(_: [u64; MAX_ARGS]) -> SyscallResult {
   let byte = uart::getc();
   Ok(byte as u64)
 }
 
 // Syscall handler for UART write
-fn sys_uart_write(byte: u64, _: u64) -> SyscallResult {
+fn sys_uart_write([byte, ..]: [u64; MAX_ARGS]) -> SyscallResult {
   uart::putc(byte as u8);
   Ok(0)
 }
 
+// Returns the child's PID, the child returns 0.
+fn sys_fork(_: [u64; MAX_ARGS]) -> SyscallResult {
+  let child = process::table().fork(mm::frames(), current_pid()?)?;
+  Ok(child as u64)
+}
+
+// execve(path, argv, envp). Only returns on errors.
+fn sys_execve([path, argv, envp, _]: [u64; MAX_ARGS]) -> SyscallResult {
+  let pid = current_pid()?;
+  let space = user_space()?;
+  let frames = mm::frames();
+  let mut path_buffer = [0; MAX_PATH];
+  let (path, _) = read_str(space, frames, path, &mut path_buffer)?;
+  let mut arg_buffer = [0; MAX_STRINGS];
+  let args =
+    read_strings::<{ elf::MAX_ARGS }>(space, frames, argv, &mut arg_buffer)?;
+  let mut env_buffer = [0; MAX_STRINGS];
+  let env =
+    read_strings::<{ elf::MAX_ENV }>(space, frames, envp, &mut env_buffer)?;
+  let mut image = exec::open_program(path)?;
+  match process::table().exec(frames, pid, &mut image, &args, &env) {
+    Ok(()) => Ok(0),
+    Err(ErrorKind::InvalidData | ErrorKind::Unsupported) => {
+      Err(SyscallError::NotExecutable)
+    }
+    Err(e) => Err(e.into()),
+  }
+}
+
+// wait4(pid, status, options, rusage), without resource usage.
+fn sys_wait4([pid, status, options, _]: [u64; MAX_ARGS]) -> SyscallResult {
+  let table = process::table();
+  match table.wait(mm::frames(), current_pid()?, pid as i64, status, options) {
+    Ok(Some(child)) => Ok(child as u64),
+    // WNOHANG, or blocked
+    Ok(None) => Ok(0),
+    Err(ErrorKind::NotFound) => Err(SyscallError::NoChild),
+    Err(ErrorKind::PermissionDenied) => Err(SyscallError::BadAddress),
+    Err(e) => Err(e.into()),
+  }
+}
+
+fn sys_exit([code, ..]: [u64; MAX_ARGS]) -> SyscallResult {
+  let status = process::exit_status(code);
+  process::table().exit(mm::frames(), current_pid()?, status)?;
+  Ok(0)
+}
+
+fn sys_getpid(_: [u64; MAX_ARGS]) -> SyscallResult {
+  Ok(current_pid()? as u64)
+}
+
+fn sys_getppid(_: [u64; MAX_ARGS]) -> SyscallResult {
+  let table = process::table();
+  let pid = current_pid()?;
+  Ok(table.get(pid).map_or(0, |process| process.parent()) as u64)
+}
+
+// open(path, flags), returns the new file descriptor.
+fn sys_open([path, flags, ..]: [u64; MAX_ARGS]) -> SyscallResult {
+  let files = current_files()?;
+  let mut buffer = [0; MAX_PATH];
+  let (path, _) = read_str(user_space()?, mm::frames(), path, &mut buffer)?;
+  let vfs = fs::vfs();
+  let file = vfs.open(path, flags as u32)?;
+  match files.insert(file) {
+    Some(fd) => Ok(fd as u64),
+    None => {
+      vfs.release(file);
+      Err(SyscallError::TooManyFiles)
+    }
+  }
+}
+
+// read(fd, buffer, count). Stops at the first short read, so a terminal
+// returns what was typed so far.
+fn sys_read([fd, buffer, count, _]: [u64; MAX_ARGS]) -> SyscallResult {
+  let file = current_file(fd)?;
+  let space = user_space()?;
+  let mut chunk = [0; CHUNK_SIZE];
+  let mut done = 0;
+  while done < count {
+    let len = (count - done).min(CHUNK_SIZE as u64) as usize;
+    let read = match fs::vfs().read(file, &mut chunk[..len]) {
+      Ok(read) => read,
+      Err(ErrorKind::PermissionDenied) => {
+        return Err(SyscallError::BadFileDescriptor)
+      }
+      Err(e) => return Err(e.into()),
+    };
+    let at = buffer.checked_add(done).ok_or(SyscallError::BadAddress)?;
+    space
+      .copy_out(mm::frames(), at, &chunk[..read])
+      .map_err(|_| SyscallError::BadAddress)?;
+    done += read as u64;
+    if read < len {
+      break;
+    }
+  }
+  Ok(done)
+}
+
+// write(fd, buffer, count)
+fn sys_write([fd, buffer, count, _]: [u64; MAX_ARGS]) -> SyscallResult {
+  let file = current_file(fd)?;
+  let space = user_space()?;
+  let mut chunk = [0; CHUNK_SIZE];
+  let mut done = 0;
+  while done < count {
+    let len = (count - done).min(CHUNK_SIZE as u64) as usize;
+    let at = buffer.checked_add(done).ok_or(SyscallError::BadAddress)?;
+    space
+      .copy_in(mm::frames(), at, &mut chunk[..len])
+      .map_err(|_| SyscallError::BadAddress)?;
+    let written = match fs::vfs().write(file, &chunk[..len]) {
+      Ok(written) => written,
+      Err(ErrorKind::PermissionDenied) => {
+        return Err(SyscallError::BadFileDescriptor)
+      }
+      Err(e) => return Err(e.into()),
+    };
+    done += written as u64;
+    if written < len {
+      break;
+    }
+  }
+  Ok(done)
+}
+
+fn sys_close([fd, ..]: [u64; MAX_ARGS]) -> SyscallResult {
+  let file = current_files()?
+    .remove(fd)
+    .ok_or(SyscallError::BadFileDescriptor)?;
+  fs::vfs().release(file);
+  Ok(0)
+}
+
+// lseek(fd, offset, whence), returns the new offset.
+fn sys_lseek([fd, offset, whence, _]: [u64; MAX_ARGS]) -> SyscallResult {
+  let file = current_file(fd)?;
+  Ok(fs::vfs().seek(file, offset as i64, whence as u32)?)
+}
+
+// Linux struct stat on AArch64, see asm-generic/stat.h.
+fn encode_stat(stat: &vfs::Stat) -> [u8; STAT_SIZE] {
+  let mode: u32 = match stat.kind {
+    vfs::FileType::Regular => 0o100644,
+    vfs::FileType::Directory => 0o040755,
+    vfs::FileType::CharDevice => 0o020620,
+  };
+  let mut buffer = [0; STAT_SIZE];
+  buffer[0..8].copy_from_slice(&stat.device.to_le_bytes());
+  buffer[8..16].copy_from_slice(&stat.inode.to_le_bytes());
+  buffer[16..20].copy_from_slice(&mode.to_le_bytes());
+  buffer[20..24].copy_from_slice(&stat.links.to_le_bytes());
+  buffer[48..56].copy_from_slice(&stat.size.to_le_bytes());
+  buffer[56..60].copy_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+  buffer
+}
+
+fn copy_stat(stat: vfs::Stat, va: u64) -> SyscallResult {
+  user_space()?
+    .copy_out(mm::frames(), va, &encode_stat(&stat))
+    .map_err(|_| SyscallError::BadAddress)?;
+  Ok(0)
+}
+
+// stat(path, statbuf)
+fn sys_stat([path, va, ..]: [u64; MAX_ARGS]) -> SyscallResult {
+  let mut buffer = [0; MAX_PATH];
+  let (path, _) = read_str(user_space()?, mm::frames(), path, &mut buffer)?;
+  let vfs = fs::vfs();
+  let inode = vfs.resolve(path)?;
+  copy_stat(vfs.stat(inode)?, va)
+}
+
+// fstat(fd, statbuf)
+fn sys_fstat([fd, va, ..]: [u64; MAX_ARGS]) -> SyscallResult {
+  let file = current_file(fd)?;
+  copy_stat(fs::vfs().fstat(file)?, va)
+}
+
+// The inode, a DT_* type and the NUL terminated name.
+fn encode_dirent(entry: &vfs::DirEntry) -> [u8; DIRENT_SIZE] {
+  let kind: u8 = match entry.kind {
+    vfs::FileType::Regular => 8,
+    vfs::FileType::Directory => 4,
+    vfs::FileType::CharDevice => 2,
+  };
+  let mut buffer = [0; DIRENT_SIZE];
+  buffer[0..8].copy_from_slice(&entry.inode.to_le_bytes());
+  buffer[8] = kind;
+  let name = entry.name.as_bytes();
+  buffer[16..16 + name.len()].copy_from_slice(name);
+  buffer
+}
+
+// readdir(fd, dirent) stores the next entry, returns 0 after the last.
+fn sys_readdir([fd, va, ..]: [u64; MAX_ARGS]) -> SyscallResult {
+  let file = current_file(fd)?;
+  let entry = match fs::vfs().readdir(file)? {
+    Some(entry) => entry,
+    None => return Ok(0),
+  };
+  user_space()?
+    .copy_out(mm::frames(), va, &encode_dirent(&entry))
+    .map_err(|_| SyscallError::BadAddress)?;
+  Ok(1)
+}
+
 #[cfg(test)]
 #[path = "syscall_test.rs"]
 mod syscall_test;
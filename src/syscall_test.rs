@@ -3,26 +3,29 @@
 mod tests {
   use super::*;
   use crate::io::uart;
-  use crate::syscall::{SyscallError, SyscallID, SyscallTable};
+  use crate::mm::frame_test::host_frames;
+  use crate::mm::{Flags, USER_BASE};
+  use crate::syscall::*;
   use std::convert::TryFrom;
 
   #[test]
   fn test_uart_read_syscall() {
-    uart::mock::initialize();
+    let _guard = uart::mock::initialize();
     uart::mock::set_input("H");
 
     let syscall_table = SyscallTable::new();
-    let result = syscall_table.dispatch(SyscallID::UartRead, 0, 0);
+    let result = syscall_table.dispatch(SyscallID::UartRead, [0; MAX_ARGS]);
 
     assert_eq!(result, Ok('H' as u64));
   }
 
   #[test]
   fn test_uart_write_syscall() {
-    uart::mock::initialize();
+    let _guard = uart::mock::initialize();
 
     let syscall_table = SyscallTable::new();
-    let result = syscall_table.dispatch(SyscallID::UartWrite, 'A' as u64, 0);
+    let result =
+      syscall_table.dispatch(SyscallID::UartWrite, ['A' as u64, 0, 0, 0]);
 
     assert_eq!(result, Ok(0));
     assert_eq!(uart::mock::get_output(), vec!['A' as u8]);
@@ -33,8 +36,105 @@ mod tests {
     let syscall_table = SyscallTable::new();
     let invalid_syscall_id =
       SyscallID::try_from(99).unwrap_or(SyscallID::Invalid);
-    let result = syscall_table.dispatch(invalid_syscall_id, 0, 0);
+    let result = syscall_table.dispatch(invalid_syscall_id, [0; MAX_ARGS]);
 
     assert_eq!(result, Err(SyscallError::InvalidSyscall));
   }
+
+  #[test]
+  fn test_errno() {
+    assert_eq!(SyscallError::InvalidSyscall.errno(), 38);
+    assert_eq!(SyscallError::NoChild.errno(), 10);
+    assert_eq!(SyscallError::Error(ErrorKind::NotFound).errno(), 2);
+    assert_eq!(SyscallError::from(ErrorKind::OutOfMemory).errno(), 12);
+    assert_eq!(SyscallError::BadFileDescriptor.errno(), 9);
+    assert_eq!(SyscallError::TooManyFiles.errno(), 24);
+  }
+
+  #[test]
+  fn test_encode_stat() {
+    let stat = vfs::Stat {
+      inode: 3,
+      device: 1,
+      kind: vfs::FileType::Directory,
+      size: 0x1234,
+      links: 2,
+    };
+    let buffer = encode_stat(&stat);
+    assert_eq!(buffer[0..8], 1u64.to_le_bytes());
+    assert_eq!(buffer[8..16], 3u64.to_le_bytes());
+    assert_eq!(buffer[16..20], 0o040755u32.to_le_bytes());
+    assert_eq!(buffer[20..24], 2u32.to_le_bytes());
+    assert_eq!(buffer[48..56], 0x1234u64.to_le_bytes());
+  }
+
+  #[test]
+  fn test_encode_dirent() {
+    let entry = vfs::DirEntry {
+      inode: 7,
+      kind: vfs::FileType::CharDevice,
+      name: arrayvec::ArrayString::from("console").unwrap(),
+    };
+    let buffer = encode_dirent(&entry);
+    assert_eq!(buffer[0..8], 7u64.to_le_bytes());
+    assert_eq!(buffer[8], 2);
+    assert_eq!(&buffer[16..24], b"console\0");
+  }
+
+  fn user_strings() -> (Frames, AddressSpace) {
+    let mut frames = host_frames(32);
+    let mut space = AddressSpace::new(&mut frames).unwrap();
+    let rw = Flags {
+      read: true,
+      write: true,
+      execute: false,
+    };
+    space.reserve(USER_BASE, 2 * PAGE_SIZE, rw).unwrap();
+    // "sh" ends right before the unmapped page.
+    let end = USER_BASE + 2 * PAGE_SIZE;
+    space.write(&mut frames, end - 3, b"sh\0").unwrap();
+    space.write(&mut frames, USER_BASE, b"-c\0").unwrap();
+    let argv = [end - 3, USER_BASE, 0].map(u64::to_le_bytes).concat();
+    space.write(&mut frames, USER_BASE + 0x100, &argv).unwrap();
+    (frames, space)
+  }
+
+  #[test]
+  fn test_read_strings() {
+    let (mut frames, mut space) = user_strings();
+    let mut buffer = [0; 16];
+    let strings = read_strings::<4>(
+      &mut space,
+      &mut frames,
+      USER_BASE + 0x100,
+      &mut buffer,
+    );
+    assert_eq!(strings.unwrap().as_slice(), ["sh", "-c"]);
+
+    let mut buffer = [0; 16];
+    let none = read_strings::<4>(&mut space, &mut frames, 0, &mut buffer);
+    assert!(none.unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_read_strings_errors() {
+    let (mut frames, mut space) = user_strings();
+    let argv = USER_BASE + 0x100;
+    let mut buffer = [0; 16];
+    let result = read_strings::<1>(&mut space, &mut frames, argv, &mut buffer);
+    assert_eq!(
+      result.unwrap_err(),
+      SyscallError::Error(ErrorKind::ArgumentListTooLong)
+    );
+    let mut buffer = [0; 4];
+    let result = read_strings::<4>(&mut space, &mut frames, argv, &mut buffer);
+    assert_eq!(
+      result.unwrap_err(),
+      SyscallError::Error(ErrorKind::ArgumentListTooLong)
+    );
+    let mut buffer = [0; 16];
+    let result =
+      read_strings::<4>(&mut space, &mut frames, USER_BASE - 8, &mut buffer);
+    assert_eq!(result.unwrap_err(), SyscallError::BadAddress);
+  }
 }
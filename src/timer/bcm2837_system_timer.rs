@@ -50,6 +50,18 @@ fn set_timer(jiffies: u32) {
   unsafe { interrupt::unmask_interrupt(IRQ_CHANNEL.assume_init()) };
 }
 
+// The counter runs at 1MHz.
+fn now_us() -> u64 {
+  // CHI may tick over between the two reads.
+  loop {
+    let hi = mmio::read(Reg::ST_CHI);
+    let lo = mmio::read(Reg::ST_CLO);
+    if mmio::read(Reg::ST_CHI) == hi {
+      return ((hi as u64) << 32) | lo as u64;
+    }
+  }
+}
+
 fn handle_irq() {
   unsafe { interrupt::mask_interrupt(IRQ_CHANNEL.assume_init()) };
   mmio::write(Reg::ST_CS, Bit::ST_CS_M1);
@@ -59,5 +71,9 @@ fn handle_irq() {
 pub fn initialize(params: InitParams) {
   unsafe { IRQ_CHANNEL = core::mem::MaybeUninit::new(params.irq_channel) };
   interrupt::set_handler(params.irq_channel, handle_irq);
-  timer::register_device(timer::Ops { set_timer });
+  timer::register_device(timer::Ops { set_timer, now_us });
 }
+
+#[cfg(test)]
+#[path = "bcm2837_system_timer_test.rs"]
+mod bcm2837_system_timer_test;
@@ -0,0 +1,21 @@
+use super::*;
+use crate::io::mmio::mock;
+
+#[test]
+fn test_now_us() {
+  let _guard = mock::initialize();
+  mock::set(Reg::ST_CHI, 0x1);
+  mock::set(Reg::ST_CLO, 0x2345_6789);
+  assert_eq!(now_us(), 0x1_2345_6789);
+}
+
+#[test]
+fn test_now_us_high_word_rollover() {
+  let _guard = mock::initialize();
+  // CLO wraps after the first CHI read, the read is retried.
+  let mut chi = vec![1, 1, 1, 0];
+  mock::on_read(Reg::ST_CHI, move |_| chi.pop().unwrap());
+  let mut clo = vec![0x0000_0002, 0x0000_0002];
+  mock::on_read(Reg::ST_CLO, move |_| clo.pop().unwrap());
+  assert_eq!(now_us(), 0x1_0000_0002);
+}
@@ -15,6 +15,8 @@ static mut IMPL_SET: bool = false;
 
 struct Ops {
   set_timer: fn(jiffies: u32),
+  // Free running counter in microseconds.
+  now_us: fn() -> u64,
 }
 
 pub fn set_timer(jiffies: u32, callback: fn()) -> Result<(), error::ErrorKind> {
@@ -31,6 +33,17 @@ pub fn set_timer(jiffies: u32, callback: fn()) -> Result<(), error::ErrorKind> {
   Ok(())
 }
 
+// Microseconds since the counter started, or None if no timer is registered
+// yet.
+pub fn uptime_us() -> Option<u64> {
+  unsafe {
+    if !IMPL_SET {
+      return None;
+    }
+    Some((OPS.assume_init_ref().now_us)())
+  }
+}
+
 fn do_callback() {
   unsafe {
     assert!(
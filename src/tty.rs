@@ -1,3 +1,12 @@
+pub mod ansi;
+pub mod completion;
+pub mod history;
+
+use ansi::Key;
+use arrayvec::ArrayString;
+use completion::Completer;
+use history::History;
+
 pub type Result<T> = core::result::Result<T, TtyError>;
 
 static mut TTY: core::mem::MaybeUninit<Tty> =
@@ -8,23 +17,52 @@ static mut TTY_SET: bool = false;
 pub enum TtyError {
   WriteError,
   ReadError,
+  // Ctrl-D was received on an empty line.
+  EndOfFile,
 }
 
-const INPUT_BUFFER_SIZE: usize = 256;
+pub const INPUT_BUFFER_SIZE: usize = 256;
 const OUTPUT_BUFFER_SIZE: usize = 256;
 const BACKSPACE: u8 = b'\x08';
 const CARRIAGE_RETURN: u8 = b'\r';
 const LINE_FEED: u8 = b'\n';
 const CTRL_C: u8 = b'\x03';
+const CTRL_D: u8 = b'\x04';
+const CTRL_G: u8 = b'\x07';
+const CTRL_R: u8 = b'\x12';
+const CTRL_U: u8 = b'\x15';
+const TAB: u8 = b'\t';
+const SEARCH_QUERY_CAP: usize = 64;
+
+type Line = ArrayString<INPUT_BUFFER_SIZE>;
 const DELETE: u8 = b'\x7f';
 
 pub struct Tty {
   stream_impl: TtyStreamAdapter,
   input_buffer: [u8; INPUT_BUFFER_SIZE],
+  // Length of the line in input_buffer.
   input_pos: usize,
+  // Cursor position within the line, 0..=input_pos.
+  cursor_pos: usize,
+  key_parser: ansi::KeyParser,
   output_buffer: [u8; OUTPUT_BUFFER_SIZE],
   output_pos: usize,
   echo_enabled: bool,
+  // In raw mode, read_line() does no line editing and no echo.
+  raw_enabled: bool,
+  // Set after a CR was taken as end of line, so that the LF of a CRLF pair
+  // does not submit another empty line.
+  skip_line_feed: bool,
+  // Called when Ctrl-C is received in canonical mode.
+  on_interrupt: Option<fn()>,
+  // Written at the start of read_line() and when the line is redrawn.
+  prompt: &'static str,
+  history: History,
+  // Age of the history entry shown while browsing with Up/Down.
+  history_age: Option<usize>,
+  // The line being edited before history browsing started.
+  draft: Line,
+  completer: Option<&'static dyn Completer>,
 }
 
 // Adapter struct for implementations
@@ -39,9 +77,19 @@ impl Tty {
       stream_impl,
       input_buffer: [b'\0'; INPUT_BUFFER_SIZE],
       input_pos: 0,
+      cursor_pos: 0,
+      key_parser: ansi::KeyParser::new(),
       output_buffer: [b'\0'; OUTPUT_BUFFER_SIZE],
       output_pos: 0,
       echo_enabled: true,
+      raw_enabled: false,
+      skip_line_feed: false,
+      on_interrupt: None,
+      prompt: "",
+      history: History::new(),
+      history_age: None,
+      draft: Line::new_const(),
+      completer: None,
     }
   }
 
@@ -49,11 +97,439 @@ impl Tty {
     self.echo_enabled = enabled;
   }
 
-  pub fn write(&mut self, output: &str) {
+  pub fn set_raw(&mut self, enabled: bool) {
+    self.raw_enabled = enabled;
+  }
+
+  pub fn set_interrupt_callback(&mut self, callback: fn()) {
+    self.on_interrupt = Some(callback);
+  }
+
+  pub fn set_prompt(&mut self, prompt: &'static str) {
+    self.prompt = prompt;
+  }
+
+  // Tab invokes the completer instead of inserting a tab character.
+  pub fn set_completer(&mut self, completer: &'static dyn Completer) {
+    self.completer = Some(completer);
+  }
+
+  pub fn history(&self) -> &History {
+    &self.history
+  }
+
+  pub fn write(&mut self, output: &str) -> Result<()> {
     for ch in output.as_bytes() {
       self.write_char(*ch);
     }
     self.flush();
+    Ok(())
+  }
+
+  // Writes an ANSI command such as cursor movement or colour changes.
+  pub fn execute(&mut self, command: ansi::Command) -> Result<()> {
+    use core::fmt::Write;
+    core::write!(self, "{}", command).map_err(|_| TtyError::WriteError)
+  }
+
+  // Reads a single key, decoding escape sequences.
+  pub fn read_key(&mut self) -> Result<ansi::Key> {
+    if let Some(key) = self.key_parser.pending() {
+      return Ok(key);
+    }
+    loop {
+      let ch = self.read_char()?;
+      if let Some(key) = self.key_parser.feed(ch) {
+        return Ok(key);
+      }
+    }
+  }
+
+  // Reads a line into the input buffer and returns it without the line
+  // terminator.
+  //
+  // Canonical mode handles:
+  // - Backspace/DEL: erase the character before the cursor
+  // - Left/Right/Home/End: move the cursor within the line
+  // - Delete: erase the character under the cursor
+  // - Up/Down: browse the history
+  // - Tab: complete through the completer, if set
+  // - CR: translated to LF
+  // - Ctrl-C: calls the interrupt callback and discards the line
+  // - Ctrl-R: reverse incremental history search
+  // - Ctrl-U: erase the whole line
+  // - Ctrl-D: end of file on an empty line, otherwise submits the line
+  pub fn read_line(&mut self) -> Result<&str> {
+    self.input_pos = 0;
+    self.cursor_pos = 0;
+    if !self.prompt.is_empty() {
+      self.write(self.prompt)?;
+    }
+    if self.raw_enabled {
+      self.read_line_raw()?;
+    } else {
+      self.read_line_canonical()?;
+    }
+    core::str::from_utf8(&self.input_buffer[..self.input_pos])
+      .map_err(|_| TtyError::ReadError)
+  }
+
+  fn read_line_raw(&mut self) -> Result<()> {
+    loop {
+      match self.read_char()? {
+        LINE_FEED => return Ok(()),
+        ch => {
+          if self.input_pos < INPUT_BUFFER_SIZE {
+            self.input_buffer[self.input_pos] = ch;
+            self.input_pos += 1;
+          }
+        }
+      }
+    }
+  }
+
+  // Reads a key with CR and CRLF translated to LF.
+  fn read_line_key(&mut self) -> Result<Key> {
+    loop {
+      match self.read_key()? {
+        Key::Char(LINE_FEED) if core::mem::take(&mut self.skip_line_feed) => {
+          continue
+        }
+        Key::Char(CARRIAGE_RETURN) => {
+          self.skip_line_feed = true;
+          return Ok(Key::Char(LINE_FEED));
+        }
+        key => {
+          self.skip_line_feed = false;
+          return Ok(key);
+        }
+      }
+    }
+  }
+
+  fn read_line_canonical(&mut self) -> Result<()> {
+    self.history_age = None;
+    // Key left over from the reverse search.
+    let mut pending: Option<Key> = None;
+    loop {
+      let key = match pending.take() {
+        Some(key) => key,
+        None => self.read_line_key()?,
+      };
+
+      match key {
+        Key::Char(LINE_FEED) => {
+          self.move_cursor_to(self.input_pos);
+          self.echo(LINE_FEED);
+          self.push_history();
+          return Ok(());
+        }
+        Key::Char(BACKSPACE | DELETE) => self.erase_before_cursor(),
+        Key::Char(CTRL_U) => {
+          self.move_cursor_to(self.input_pos);
+          while self.cursor_pos > 0 {
+            self.erase_before_cursor();
+          }
+        }
+        Key::Char(CTRL_C) => {
+          self.input_pos = 0;
+          self.cursor_pos = 0;
+          if let Some(callback) = self.on_interrupt {
+            callback();
+          }
+          return Err(TtyError::ReadError);
+        }
+        Key::Char(CTRL_D) if self.input_pos == 0 => {
+          return Err(TtyError::EndOfFile)
+        }
+        Key::Char(CTRL_D) => {
+          self.move_cursor_to(self.input_pos);
+          // The terminal shows nothing for Ctrl-D, end the line ourselves.
+          self.echo(CARRIAGE_RETURN);
+          self.echo(LINE_FEED);
+          self.push_history();
+          return Ok(());
+        }
+        Key::Char(CTRL_R) => pending = self.reverse_search()?,
+        Key::Char(TAB) if self.completer.is_some() => self.complete(),
+        Key::Char(ch @ (b' '..=b'~' | TAB)) => self.insert_at_cursor(ch),
+        Key::Left if self.cursor_pos > 0 => {
+          self.move_cursor_to(self.cursor_pos - 1)
+        }
+        Key::Right => self.move_cursor_to(self.cursor_pos + 1),
+        Key::Home => self.move_cursor_to(0),
+        Key::End => self.move_cursor_to(self.input_pos),
+        Key::Delete => self.erase_at_cursor(),
+        Key::Up => self.history_prev(),
+        Key::Down => self.history_next(),
+        // Other control characters and keys are ignored.
+        _ => {}
+      }
+    }
+  }
+
+  fn line(&self) -> Line {
+    let mut line = Line::new();
+    for &ch in &self.input_buffer[..self.input_pos] {
+      line.push(ch as char);
+    }
+    line
+  }
+
+  fn push_history(&mut self) {
+    let line = self.line();
+    self.history.push(&line);
+  }
+
+  fn history_prev(&mut self) {
+    let age = self.history_age.map_or(0, |age| age + 1);
+    let Some(entry) = self
+      .history
+      .get(age)
+      .and_then(|entry| Line::from(entry).ok())
+    else {
+      return;
+    };
+    if self.history_age.is_none() {
+      self.draft = self.line();
+    }
+    self.history_age = Some(age);
+    self.replace_line(&entry);
+  }
+
+  fn history_next(&mut self) {
+    let line = match self.history_age {
+      None => return,
+      Some(0) => {
+        self.history_age = None;
+        self.draft
+      }
+      Some(age) => {
+        self.history_age = Some(age - 1);
+        self
+          .history
+          .get(age - 1)
+          .and_then(|entry| Line::from(entry).ok())
+          .unwrap_or_default()
+      }
+    };
+    self.replace_line(&line);
+  }
+
+  // Ctrl-R. Typing refines the query, Ctrl-R again finds an older match.
+  // Enter submits the match, Ctrl-C/Ctrl-G restores the original line and any
+  // other key keeps the match for editing. Returns the key that ended the
+  // search, if it still has to be handled.
+  fn reverse_search(&mut self) -> Result<Option<Key>> {
+    let original = self.line();
+    let mut query = ArrayString::<SEARCH_QUERY_CAP>::new();
+    let mut found: Option<usize> = None;
+    loop {
+      self.draw_search(&query, found);
+      match self.read_line_key()? {
+        Key::Char(CTRL_R) => {
+          let from_age = found.map_or(0, |age| age + 1);
+          if let Some(age) = self.history.search(&query, from_age) {
+            found = Some(age);
+          }
+        }
+        Key::Char(BACKSPACE | DELETE) => {
+          query.pop();
+          found = self.history.search(&query, 0);
+        }
+        Key::Char(ch @ b' '..=b'~') => {
+          if query.try_push(ch as char).is_ok() {
+            found = self.history.search(&query, found.unwrap_or(0));
+          }
+        }
+        Key::Char(CTRL_C | CTRL_G) => {
+          self.replace_line(&original);
+          return Ok(None);
+        }
+        key => {
+          let line = found
+            .and_then(|age| self.history.get(age))
+            .and_then(|entry| Line::from(entry).ok())
+            .unwrap_or(original);
+          self.replace_line(&line);
+          return Ok(Some(key));
+        }
+      }
+    }
+  }
+
+  fn draw_search(&mut self, query: &str, found: Option<usize>) {
+    if !self.echo_enabled {
+      return;
+    }
+    let entry = found
+      .and_then(|age| self.history.get(age))
+      .and_then(|entry| Line::from(entry).ok())
+      .unwrap_or_default();
+    use core::fmt::Write;
+    let _ = core::write!(
+      self,
+      "\r{}(reverse-i-search)`{}': {}",
+      ansi::Command::ClearToEndOfLine,
+      query,
+      entry
+    );
+  }
+
+  // Tab. A single candidate replaces the word, several candidates extend it
+  // to their common prefix, or get listed if there is nothing to extend.
+  fn complete(&mut self) {
+    let Some(completer) = self.completer else {
+      return;
+    };
+    let before_cursor = Line::from(
+      core::str::from_utf8(&self.input_buffer[..self.cursor_pos]).unwrap_or(""),
+    )
+    .unwrap_or_default();
+    let mut candidates = completion::Candidates::new();
+    let start = completer.complete(&before_cursor, &mut candidates);
+    let word_len = before_cursor.len().saturating_sub(start);
+
+    match candidates.len() {
+      0 => self.echo(CTRL_G),
+      1 => {
+        for ch in candidates[0].bytes().skip(word_len) {
+          self.insert_at_cursor(ch);
+        }
+        self.insert_at_cursor(b' ');
+      }
+      _ => {
+        let common = completion::common_prefix(&candidates);
+        if common.len() > word_len {
+          for ch in common.bytes().skip(word_len) {
+            self.insert_at_cursor(ch);
+          }
+          return;
+        }
+        if !self.echo_enabled {
+          return;
+        }
+        self.write_char(LINE_FEED);
+        for (idx, candidate) in candidates.iter().enumerate() {
+          if idx > 0 {
+            self.write_char(b' ');
+            self.write_char(b' ');
+          }
+          for ch in candidate.bytes() {
+            self.write_char(ch);
+          }
+        }
+        self.write_char(LINE_FEED);
+        self.redraw_line();
+      }
+    }
+  }
+
+  // Replaces the whole line and puts the cursor at the end.
+  fn replace_line(&mut self, line: &str) {
+    let len = core::cmp::min(line.len(), INPUT_BUFFER_SIZE);
+    self.input_buffer[..len].copy_from_slice(&line.as_bytes()[..len]);
+    self.input_pos = len;
+    self.cursor_pos = len;
+    self.redraw_line();
+  }
+
+  // Redraws the prompt and the line, then moves back to the cursor.
+  fn redraw_line(&mut self) {
+    if !self.echo_enabled {
+      return;
+    }
+    let prompt = self.prompt;
+    use core::fmt::Write;
+    let _ =
+      core::write!(self, "\r{}{}", ansi::Command::ClearToEndOfLine, prompt);
+    for idx in 0..self.input_pos {
+      self.write_char(self.input_buffer[idx]);
+    }
+    for _ in self.cursor_pos..self.input_pos {
+      self.write_char(BACKSPACE);
+    }
+    self.flush();
+  }
+
+  // Inserts a character at the cursor and redraws the rest of the line.
+  fn insert_at_cursor(&mut self, ch: u8) {
+    if self.input_pos >= INPUT_BUFFER_SIZE {
+      return;
+    }
+    self
+      .input_buffer
+      .copy_within(self.cursor_pos..self.input_pos, self.cursor_pos + 1);
+    self.input_buffer[self.cursor_pos] = ch;
+    self.input_pos += 1;
+    self.cursor_pos += 1;
+    if !self.echo_enabled {
+      return;
+    }
+    self.write_char(ch);
+    self.redraw_tail(0);
+  }
+
+  fn erase_before_cursor(&mut self) {
+    if self.cursor_pos == 0 {
+      return;
+    }
+    self.cursor_pos -= 1;
+    if self.echo_enabled {
+      self.write_char(BACKSPACE);
+    }
+    self.erase_at_cursor();
+  }
+
+  fn erase_at_cursor(&mut self) {
+    if self.cursor_pos >= self.input_pos {
+      return;
+    }
+    self
+      .input_buffer
+      .copy_within(self.cursor_pos + 1..self.input_pos, self.cursor_pos);
+    self.input_pos -= 1;
+    if self.echo_enabled {
+      self.redraw_tail(1);
+    }
+  }
+
+  // Writes everything after the cursor, blanks out `erased` trailing cells
+  // and moves the terminal cursor back to where it was. Only plain
+  // backspaces are used so this also works on dumb terminals.
+  fn redraw_tail(&mut self, erased: usize) {
+    for idx in self.cursor_pos..self.input_pos {
+      self.write_char(self.input_buffer[idx]);
+    }
+    for _ in 0..erased {
+      self.write_char(b' ');
+    }
+    for _ in 0..(self.input_pos - self.cursor_pos + erased) {
+      self.write_char(BACKSPACE);
+    }
+    self.flush();
+  }
+
+  fn move_cursor_to(&mut self, pos: usize) {
+    let pos = core::cmp::min(pos, self.input_pos);
+    if self.echo_enabled {
+      // Moving right re-echoes the characters we pass over.
+      for idx in self.cursor_pos..pos {
+        self.write_char(self.input_buffer[idx]);
+      }
+      for _ in pos..self.cursor_pos {
+        self.write_char(BACKSPACE);
+      }
+      self.flush();
+    }
+    self.cursor_pos = pos;
+  }
+
+  fn echo(&mut self, ch: u8) {
+    if self.echo_enabled {
+      self.write_char(ch);
+      self.flush();
+    }
   }
 
   pub fn read_char(&mut self) -> Result<u8> {
@@ -89,8 +565,7 @@ pub fn init(stream_impl: TtyStreamAdapter) {
 
 impl core::fmt::Write for Tty {
   fn write_str(&mut self, s: &str) -> core::fmt::Result {
-    self.write(s);
-    Ok(())
+    self.write(s).map_err(|_| core::fmt::Error)
   }
 }
 
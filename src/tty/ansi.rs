@@ -0,0 +1,215 @@
+// ANSI/VT100 escape sequences.
+// https://vt100.net/docs/vt100-ug/chapter3.html
+// https://en.wikipedia.org/wiki/ANSI_escape_code
+
+const ESCAPE: u8 = b'\x1b';
+
+// Key events decoded from the input stream.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Key {
+  // Any byte that is not part of an escape sequence, including control
+  // characters.
+  Char(u8),
+  Up,
+  Down,
+  Left,
+  Right,
+  Home,
+  End,
+  Insert,
+  Delete,
+  PageUp,
+  PageDown,
+  // F1..F12
+  Function(u8),
+  // ESC that does not start a sequence
+  Escape,
+  // Well-formed sequence that we don't recognize.
+  Unknown,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+  Ground,
+  // Received ESC
+  Escape,
+  // Received ESC [
+  Csi,
+  // Received ESC O
+  Ss3,
+}
+
+// Incremental decoder, fed one byte at a time.
+//
+// Recognized input:
+//   ESC [ A/B/C/D      arrow keys
+//   ESC [ H/F          Home/End (xterm)
+//   ESC [ <n> ~        Home/Insert/Delete/End/PageUp/PageDown/F1..F12 (vt220)
+//   ESC O P/Q/R/S      F1..F4 (vt100)
+//   ESC O H/F          Home/End (application mode)
+// Modifier parameters (e.g. ESC [ 1 ; 5 C) are ignored. ESC followed by
+// any other byte is Escape, then that byte is parsed as a key of its own.
+pub struct KeyParser {
+  state: State,
+  // First numeric parameter of a CSI sequence.
+  param: u16,
+  // Set once ';' is seen, further parameters are ignored.
+  param_done: bool,
+  // Byte that followed a lone ESC, see pending().
+  pending: Option<u8>,
+}
+
+impl KeyParser {
+  pub const fn new() -> Self {
+    Self {
+      state: State::Ground,
+      param: 0,
+      param_done: false,
+      pending: None,
+    }
+  }
+
+  // Parses the byte held back after an Escape. Call before feeding the
+  // next byte.
+  pub fn pending(&mut self) -> Option<Key> {
+    let byte = self.pending.take()?;
+    self.feed(byte)
+  }
+
+  // Returns a key once a full event has been decoded.
+  pub fn feed(&mut self, byte: u8) -> Option<Key> {
+    match self.state {
+      State::Ground => {
+        if byte == ESCAPE {
+          self.state = State::Escape;
+          return None;
+        }
+        Some(Key::Char(byte))
+      }
+      State::Escape => match byte {
+        b'[' => {
+          self.state = State::Csi;
+          self.param = 0;
+          self.param_done = false;
+          None
+        }
+        b'O' => {
+          self.state = State::Ss3;
+          None
+        }
+        _ => {
+          self.pending = Some(byte);
+          self.finish(Key::Escape)
+        }
+      },
+      State::Csi => match byte {
+        b'0'..=b'9' => {
+          if !self.param_done {
+            self.param = self
+              .param
+              .saturating_mul(10)
+              .saturating_add((byte - b'0') as u16);
+          }
+          None
+        }
+        b';' => {
+          self.param_done = true;
+          None
+        }
+        b'A' => self.finish(Key::Up),
+        b'B' => self.finish(Key::Down),
+        b'C' => self.finish(Key::Right),
+        b'D' => self.finish(Key::Left),
+        b'H' => self.finish(Key::Home),
+        b'F' => self.finish(Key::End),
+        b'~' => self.finish(vt220_key(self.param)),
+        // Final byte of an unsupported sequence
+        0x40..=0x7e => self.finish(Key::Unknown),
+        // Intermediate bytes, keep consuming
+        _ => None,
+      },
+      State::Ss3 => match byte {
+        b'P'..=b'S' => self.finish(Key::Function(byte - b'P' + 1)),
+        b'H' => self.finish(Key::Home),
+        b'F' => self.finish(Key::End),
+        _ => self.finish(Key::Unknown),
+      },
+    }
+  }
+
+  fn finish(&mut self, key: Key) -> Option<Key> {
+    self.state = State::Ground;
+    Some(key)
+  }
+}
+
+fn vt220_key(param: u16) -> Key {
+  match param {
+    1 | 7 => Key::Home,
+    2 => Key::Insert,
+    3 => Key::Delete,
+    4 | 8 => Key::End,
+    5 => Key::PageUp,
+    6 => Key::PageDown,
+    11..=15 => Key::Function((param - 10) as u8),
+    17..=21 => Key::Function((param - 11) as u8),
+    23 | 24 => Key::Function((param - 12) as u8),
+    _ => Key::Unknown,
+  }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Color {
+  Black = 0,
+  Red = 1,
+  Green = 2,
+  Yellow = 3,
+  Blue = 4,
+  Magenta = 5,
+  Cyan = 6,
+  White = 7,
+}
+
+// Output commands. Rows and columns are 1-based as in the VT100 spec.
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Command {
+  CursorUp(u16),
+  CursorDown(u16),
+  CursorForward(u16),
+  CursorBack(u16),
+  CursorPosition { row: u16, col: u16 },
+  SaveCursor,
+  RestoreCursor,
+  ClearScreen,
+  ClearLine,
+  ClearToEndOfLine,
+  Foreground(Color),
+  Background(Color),
+  Bold,
+  ResetStyle,
+}
+
+impl core::fmt::Display for Command {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      Command::CursorUp(n) => write!(f, "\x1b[{}A", n),
+      Command::CursorDown(n) => write!(f, "\x1b[{}B", n),
+      Command::CursorForward(n) => write!(f, "\x1b[{}C", n),
+      Command::CursorBack(n) => write!(f, "\x1b[{}D", n),
+      Command::CursorPosition { row, col } => {
+        write!(f, "\x1b[{};{}H", row, col)
+      }
+      Command::SaveCursor => write!(f, "\x1b7"),
+      Command::RestoreCursor => write!(f, "\x1b8"),
+      Command::ClearScreen => write!(f, "\x1b[2J"),
+      Command::ClearLine => write!(f, "\x1b[2K"),
+      Command::ClearToEndOfLine => write!(f, "\x1b[K"),
+      Command::Foreground(color) => write!(f, "\x1b[{}m", 30 + *color as u8),
+      Command::Background(color) => write!(f, "\x1b[{}m", 40 + *color as u8),
+      Command::Bold => write!(f, "\x1b[1m"),
+      Command::ResetStyle => write!(f, "\x1b[0m"),
+    }
+  }
+}
@@ -0,0 +1,32 @@
+use arrayvec::{ArrayString, ArrayVec};
+
+pub const MAX_CANDIDATES: usize = 32;
+pub const CANDIDATE_CAP: usize = 64;
+
+pub type Candidate = ArrayString<CANDIDATE_CAP>;
+pub type Candidates = ArrayVec<Candidate, MAX_CANDIDATES>;
+
+// Hook for Tab completion in Tty::read_line().
+pub trait Completer {
+  // `line` is the input up to the cursor. Pushes every full word that could
+  // replace the word being completed into `candidates`, and returns the byte
+  // offset in `line` where that word starts.
+  fn complete(&self, line: &str, candidates: &mut Candidates) -> usize;
+}
+
+// Longest prefix shared by all candidates.
+pub fn common_prefix(candidates: &[Candidate]) -> &str {
+  let Some(first) = candidates.first() else {
+    return "";
+  };
+  let mut len = first.len();
+  for candidate in &candidates[1..] {
+    len = first
+      .bytes()
+      .zip(candidate.bytes())
+      .take(len)
+      .take_while(|(a, b)| a == b)
+      .count();
+  }
+  &first[..len]
+}
@@ -0,0 +1,59 @@
+use arrayvec::ArrayString;
+
+pub const HISTORY_SIZE: usize = 16;
+pub const LINE_CAP: usize = super::INPUT_BUFFER_SIZE;
+
+// Fixed-size ring of previously entered lines. Once full, the oldest entry is
+// overwritten. Entries are addressed by age, 0 being the most recent.
+pub struct History {
+  entries: [ArrayString<LINE_CAP>; HISTORY_SIZE],
+  // Slot the next entry goes to.
+  next: usize,
+  len: usize,
+}
+
+impl History {
+  pub const fn new() -> Self {
+    Self {
+      entries: [const { ArrayString::new_const() }; HISTORY_SIZE],
+      next: 0,
+      len: 0,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  // Empty lines and repeats of the most recent entry are not recorded.
+  pub fn push(&mut self, line: &str) {
+    if line.is_empty() || self.get(0) == Some(line) {
+      return;
+    }
+    let entry = &mut self.entries[self.next];
+    entry.clear();
+    // Lines come from the Tty input buffer, so they always fit.
+    let _ = entry.try_push_str(line);
+    self.next = (self.next + 1) % HISTORY_SIZE;
+    self.len = core::cmp::min(self.len + 1, HISTORY_SIZE);
+  }
+
+  pub fn get(&self, age: usize) -> Option<&str> {
+    if age >= self.len {
+      return None;
+    }
+    let idx = (self.next + HISTORY_SIZE - 1 - age) % HISTORY_SIZE;
+    Some(self.entries[idx].as_str())
+  }
+
+  // Finds the most recent entry at or older than `from_age` containing
+  // `needle`.
+  pub fn search(&self, needle: &str, from_age: usize) -> Option<usize> {
+    (from_age..self.len)
+      .find(|&age| self.get(age).is_some_and(|entry| entry.contains(needle)))
+  }
+}
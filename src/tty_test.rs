@@ -1,42 +1,28 @@
+use super::ansi::{Color, Command, Key};
 use super::{Tty, TtyError, TtyStreamAdapter};
+use crate::io::uart;
 use core::fmt::Write;
-use std::sync::Mutex;
-
-// Mock UART buffer for testing
-static MOCK_UART_BUFFER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
-static MOCK_INPUT: Mutex<Vec<char>> = Mutex::new(Vec::new());
-
-// Test helper functions
-fn make_mock() -> TtyStreamAdapter {
-  *MOCK_UART_BUFFER.lock().unwrap() = Vec::new();
-  *MOCK_INPUT.lock().unwrap() = Vec::new();
-  TtyStreamAdapter {
-    read_char: mock_getc,
-    write_char: mock_putc,
-  }
-}
-
-fn mock_putc(c: u8) {
-  MOCK_UART_BUFFER.lock().unwrap().push(c);
-}
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::MutexGuard;
 
-fn mock_getc() -> u8 {
-  MOCK_INPUT.lock().unwrap().pop().unwrap_or('\0' as char) as u8
+// Test helper functions. The mock UART is locked while the guard lives.
+fn make_mock() -> (MutexGuard<'static, ()>, TtyStreamAdapter) {
+  let guard = uart::mock::initialize();
+  (guard, uart::as_tty_adapter())
 }
 
 fn set_mock_input(input: &str) {
-  let mut mock_input = MOCK_INPUT.lock().unwrap();
-  mock_input.clear();
-  mock_input.extend(input.chars().rev());
+  uart::mock::set_input(input);
 }
 
 fn get_output() -> Vec<u8> {
-  MOCK_UART_BUFFER.lock().unwrap().clone()
+  uart::mock::get_output()
 }
 
 #[test]
 fn test_tty_write() {
-  let mut tty = Tty::new(make_mock());
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
   tty.write("Hello, Tty!").expect("Write should succeed");
 
   assert_eq!(get_output(), b"Hello, Tty!");
@@ -44,7 +30,8 @@ fn test_tty_write() {
 
 #[test]
 fn test_tty_write_fmt() {
-  let mut tty = Tty::new(make_mock());
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
   write!(tty, "Value: {}", 42).expect("Write formatting should succeed");
 
   assert_eq!(get_output(), b"Value: 42");
@@ -52,7 +39,8 @@ fn test_tty_write_fmt() {
 
 #[test]
 fn test_tty_read_line() {
-  let mut tty = Tty::new(make_mock());
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
 
   set_mock_input("Hello\n");
 
@@ -64,7 +52,8 @@ fn test_tty_read_line() {
 
 #[test]
 fn test_tty_ctrl_c() {
-  let mut tty = Tty::new(make_mock());
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
 
   set_mock_input("\x03");
 
@@ -74,7 +63,8 @@ fn test_tty_ctrl_c() {
 
 #[test]
 fn test_tty_empty_line() {
-  let mut tty = Tty::new(make_mock());
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
 
   set_mock_input("\n");
 
@@ -86,7 +76,8 @@ fn test_tty_empty_line() {
 
 #[test]
 fn test_tty_multiple_backspaces() {
-  let mut tty = Tty::new(make_mock());
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
 
   set_mock_input("\x08Hello\x08\x08\n");
 
@@ -99,7 +90,8 @@ fn test_tty_multiple_backspaces() {
 
 #[test]
 fn test_tty_echo_disable() {
-  let mut tty = Tty::new(make_mock());
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
   tty.set_echo(false);
 
   set_mock_input("Hello\n");
@@ -109,3 +101,322 @@ fn test_tty_echo_disable() {
 
   assert_eq!(get_output(), b"");
 }
+
+#[test]
+fn test_tty_delete_key() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("abc\x7f\n");
+
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "ab");
+
+  assert_eq!(get_output(), b"abc\x08 \x08\n");
+}
+
+#[test]
+fn test_tty_carriage_return() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("Hello\r\nWorld\r");
+
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "Hello");
+  // The LF of CRLF does not submit an extra empty line.
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "World");
+
+  assert_eq!(get_output(), b"Hello\nWorld\n");
+}
+
+static INTERRUPT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn on_interrupt() {
+  INTERRUPT_COUNT.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn test_tty_ctrl_c_callback() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+  tty.set_interrupt_callback(on_interrupt);
+  INTERRUPT_COUNT.store(0, Ordering::SeqCst);
+
+  set_mock_input("abc\x03def\n");
+
+  assert!(matches!(tty.read_line(), Err(TtyError::ReadError)));
+  assert_eq!(INTERRUPT_COUNT.load(Ordering::SeqCst), 1);
+  // The interrupted line is discarded.
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "def");
+}
+
+#[test]
+fn test_tty_ctrl_u() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("ab\x15cd\n");
+
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "cd");
+
+  assert_eq!(get_output(), b"ab\x08 \x08\x08 \x08cd\n");
+}
+
+#[test]
+fn test_tty_ctrl_d() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("\x04abc\x04");
+
+  assert_eq!(tty.read_line(), Err(TtyError::EndOfFile));
+  // Ctrl-D on a non-empty line submits it.
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "abc");
+  // The next output starts on a new line.
+  assert_eq!(get_output(), b"abc\r\n");
+}
+
+#[test]
+fn test_tty_raw_mode() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+  tty.set_raw(true);
+
+  set_mock_input("a\x08\x03b\n");
+
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "a\x08\x03b");
+
+  assert_eq!(get_output(), b"");
+}
+
+#[test]
+fn test_tty_read_key() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("\x1b[A\x1b[B\x1b[C\x1b[Da\x1b[3~\x1b[H\x1b[4~");
+  assert_eq!(tty.read_key(), Ok(Key::Up));
+  assert_eq!(tty.read_key(), Ok(Key::Down));
+  assert_eq!(tty.read_key(), Ok(Key::Right));
+  assert_eq!(tty.read_key(), Ok(Key::Left));
+  assert_eq!(tty.read_key(), Ok(Key::Char(b'a')));
+  assert_eq!(tty.read_key(), Ok(Key::Delete));
+  assert_eq!(tty.read_key(), Ok(Key::Home));
+  assert_eq!(tty.read_key(), Ok(Key::End));
+
+  set_mock_input("\x1bOP\x1b[15~\x1b[24~\x1b[1;5C\x1b[99~");
+  assert_eq!(tty.read_key(), Ok(Key::Function(1)));
+  assert_eq!(tty.read_key(), Ok(Key::Function(5)));
+  assert_eq!(tty.read_key(), Ok(Key::Function(12)));
+  assert_eq!(tty.read_key(), Ok(Key::Right));
+  assert_eq!(tty.read_key(), Ok(Key::Unknown));
+
+  // A lone ESC does not swallow the next key.
+  set_mock_input("\x1bx\x1b\x1b[A");
+  assert_eq!(tty.read_key(), Ok(Key::Escape));
+  assert_eq!(tty.read_key(), Ok(Key::Char(b'x')));
+  assert_eq!(tty.read_key(), Ok(Key::Escape));
+  assert_eq!(tty.read_key(), Ok(Key::Up));
+}
+
+#[test]
+fn test_tty_escape_then_char() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("ab\x1bc\n");
+
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "abc");
+}
+
+#[test]
+fn test_tty_cursor_insert() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("abc\x1b[D\x1b[DX\n");
+
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "aXbc");
+
+  assert_eq!(get_output(), b"abc\x08\x08Xbc\x08\x08bc\n");
+}
+
+#[test]
+fn test_tty_cursor_erase() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  // Backspace and Delete in the middle of the line
+  set_mock_input("abcd\x1b[D\x1b[D\x08\x1b[3~\n");
+
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "ad");
+}
+
+#[test]
+fn test_tty_home_end() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("bc\x1b[Ha\x1b[Fd\n");
+
+  let line = tty.read_line().expect("Read line should succeed");
+  assert_eq!(line, "abcd");
+}
+
+#[test]
+fn test_tty_execute() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  tty
+    .execute(Command::CursorPosition { row: 2, col: 10 })
+    .expect("Write should succeed");
+  tty
+    .execute(Command::Foreground(Color::Red))
+    .expect("Write should succeed");
+  tty
+    .execute(Command::ResetStyle)
+    .expect("Write should succeed");
+
+  assert_eq!(get_output(), b"\x1b[2;10H\x1b[31m\x1b[0m");
+}
+
+#[test]
+fn test_tty_history_browse() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("first\nsecond\n\nthird\x1b[A\x1b[A\x1b[A\x1b[B\n");
+
+  assert_eq!(tty.read_line(), Ok("first"));
+  assert_eq!(tty.read_line(), Ok("second"));
+  // Empty lines are not recorded
+  assert_eq!(tty.read_line(), Ok(""));
+  assert_eq!(tty.history().len(), 2);
+  // Up, Up, Up (stays on oldest), Down
+  assert_eq!(tty.read_line(), Ok("second"));
+  assert_eq!(tty.history().len(), 2);
+
+  // Down past the newest entry restores the draft
+  set_mock_input("draft\x1b[A\x1b[B\n");
+  assert_eq!(tty.read_line(), Ok("draft"));
+  assert_eq!(tty.history().get(0), Some("draft"));
+}
+
+#[test]
+fn test_tty_history_ring() {
+  use super::history::{History, HISTORY_SIZE};
+
+  let mut history = History::new();
+  for idx in 0..(HISTORY_SIZE + 3) {
+    history.push(&idx.to_string());
+  }
+  assert_eq!(history.len(), HISTORY_SIZE);
+  assert_eq!(
+    history.get(0),
+    Some((HISTORY_SIZE + 2).to_string().as_str())
+  );
+  assert_eq!(history.get(HISTORY_SIZE - 1), Some("3"));
+  assert_eq!(history.get(HISTORY_SIZE), None);
+
+  history.push("same");
+  history.push("same");
+  assert_eq!(
+    history.get(1),
+    Some((HISTORY_SIZE + 2).to_string().as_str())
+  );
+}
+
+#[test]
+fn test_tty_reverse_search() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("clocks\nboardinfo\nclear\n");
+  for _ in 0..3 {
+    tty.read_line().unwrap();
+  }
+
+  // "cl" matches "clear", Ctrl-R again goes to "clocks"
+  set_mock_input("\x12cl\x12\n");
+  assert_eq!(tty.read_line(), Ok("clocks"));
+
+  // Any other key keeps the match for editing
+  set_mock_input("\x12boa\x1b[Dx\n");
+  assert_eq!(tty.read_line(), Ok("boardinfxo"));
+
+  // Ctrl-G cancels and restores the line
+  set_mock_input("abc\x12cl\x07d\n");
+  assert_eq!(tty.read_line(), Ok("abcd"));
+}
+
+struct TestCompleter;
+
+impl super::completion::Completer for TestCompleter {
+  fn complete(
+    &self,
+    line: &str,
+    candidates: &mut super::completion::Candidates,
+  ) -> usize {
+    let start = line.rfind(' ').map_or(0, |idx| idx + 1);
+    for word in ["boardinfo", "clocks", "clear", "help"] {
+      if word.starts_with(&line[start..]) {
+        candidates.push(arrayvec::ArrayString::from(word).unwrap());
+      }
+    }
+    start
+  }
+}
+
+static TEST_COMPLETER: TestCompleter = TestCompleter;
+
+#[test]
+fn test_tty_tab_completion() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+  tty.set_completer(&TEST_COMPLETER);
+
+  // Single candidate
+  set_mock_input("bo\t\n");
+  assert_eq!(tty.read_line(), Ok("boardinfo "));
+
+  // Extends to the common prefix
+  set_mock_input("c\tear\n");
+  assert_eq!(tty.read_line(), Ok("clear"));
+
+  // Completes the word before the cursor
+  set_mock_input("x he\t\n");
+  assert_eq!(tty.read_line(), Ok("x help "));
+}
+
+#[test]
+fn test_tty_tab_completion_list() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+  tty.set_prompt("> ");
+  tty.set_completer(&TEST_COMPLETER);
+
+  set_mock_input("cl\t\n");
+  assert_eq!(tty.read_line(), Ok("cl"));
+
+  let output = String::from_utf8(get_output()).unwrap();
+  assert_eq!(output, "> cl\nclocks  clear\n\r\x1b[K> cl\n");
+}
+
+#[test]
+fn test_tty_tab_without_completer() {
+  let (_guard, uart) = make_mock();
+  let mut tty = Tty::new(uart);
+
+  set_mock_input("a\tb\n");
+  assert_eq!(tty.read_line(), Ok("a\tb"));
+}
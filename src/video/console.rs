@@ -0,0 +1,364 @@
+// Text console on top of the framebuffer.
+//
+// Understands newline, carriage return, backspace, tab and the subset of
+// ANSI escape sequences emitted by `tty::ansi::Command`:
+//   ESC [ <n> A/B/C/D  cursor movement
+//   ESC [ <r> ; <c> H  cursor position
+//   ESC [ <n> J        clear screen (0: to end, 2: all)
+//   ESC [ <n> K        clear line (0: to end, 2: all)
+//   ESC [ ... m        SGR: reset, bold, 30-37/39, 40-47/49, 90-97
+//   ESC 7 / ESC 8      save/restore cursor
+
+use super::font;
+use super::font::Font;
+use super::framebuffer;
+use super::framebuffer::Color;
+use super::framebuffer::Framebuffer;
+use crate::common::error::ErrorKind;
+use crate::common::stream;
+use crate::log::Level;
+
+static mut CONSOLE: Option<Console<'static>> = None;
+
+const ESCAPE: u8 = b'\x1b';
+const BACKSPACE: u8 = b'\x08';
+const TAB_WIDTH: u32 = 8;
+const MAX_PARAMS: usize = 4;
+
+const PALETTE: [Color; 8] = [
+  Color::BLACK,
+  Color::RED,
+  Color::GREEN,
+  Color::YELLOW,
+  Color::BLUE,
+  Color::MAGENTA,
+  Color::CYAN,
+  Color::WHITE,
+];
+const BRIGHT_PALETTE: [Color; 8] = [
+  Color(0x55_55_55),
+  Color(0xFF_55_55),
+  Color(0x55_FF_55),
+  Color(0xFF_FF_55),
+  Color(0x55_55_FF),
+  Color(0xFF_55_FF),
+  Color(0x55_FF_FF),
+  Color(0xFF_FF_FF),
+];
+const DEFAULT_FG: usize = 7;
+const DEFAULT_BG: usize = 0;
+
+#[derive(Clone, Copy)]
+enum State {
+  Ground,
+  // Received ESC
+  Escape,
+  // Received ESC [
+  Csi,
+}
+
+pub struct Console<'a> {
+  fb: &'a mut Framebuffer,
+  font: Font,
+  cols: u32,
+  rows: u32,
+  // May be equal to `cols` after writing the last column, the wrap happens
+  // on the next printable character.
+  col: u32,
+  row: u32,
+  saved: (u32, u32),
+  // Palette indices
+  fg: usize,
+  bg: usize,
+  bold: bool,
+  state: State,
+  params: [u16; MAX_PARAMS],
+  param_count: usize,
+}
+
+impl<'a> Console<'a> {
+  pub fn new(fb: &'a mut Framebuffer, font: Font) -> Console<'a> {
+    let cols = fb.width() / font.width;
+    let rows = fb.height() / font.height;
+    let mut console = Console {
+      fb,
+      font,
+      cols,
+      rows,
+      col: 0,
+      row: 0,
+      saved: (0, 0),
+      fg: DEFAULT_FG,
+      bg: DEFAULT_BG,
+      bold: false,
+      state: State::Ground,
+      params: [0; MAX_PARAMS],
+      param_count: 0,
+    };
+    console.clear();
+    console
+  }
+
+  pub fn cols(&self) -> u32 {
+    self.cols
+  }
+
+  pub fn rows(&self) -> u32 {
+    self.rows
+  }
+
+  // (column, row), 0-based.
+  pub fn cursor(&self) -> (u32, u32) {
+    (self.col, self.row)
+  }
+
+  pub fn clear(&mut self) {
+    let bg = self.bg_color();
+    self.fb.clear(bg);
+    self.col = 0;
+    self.row = 0;
+  }
+
+  pub fn write_str(&mut self, s: &str) {
+    for byte in s.bytes() {
+      self.write_byte(byte);
+    }
+  }
+
+  pub fn write_byte(&mut self, byte: u8) {
+    match self.state {
+      State::Ground => self.write_ground(byte),
+      State::Escape => {
+        self.state = State::Ground;
+        match byte {
+          b'[' => {
+            self.state = State::Csi;
+            self.params = [0; MAX_PARAMS];
+            self.param_count = 0;
+          }
+          b'7' => self.saved = (self.col, self.row),
+          b'8' => (self.col, self.row) = self.saved,
+          _ => {}
+        }
+      }
+      State::Csi => match byte {
+        b'0'..=b'9' => {
+          if self.param_count == 0 {
+            self.param_count = 1;
+          }
+          if let Some(param) = self.params.get_mut(self.param_count - 1) {
+            *param = param
+              .saturating_mul(10)
+              .saturating_add((byte - b'0') as u16);
+          }
+        }
+        b';' => {
+          // An empty parameter counts as 0. Parameters past MAX_PARAMS are
+          // dropped.
+          let count = self.param_count.max(1) + 1;
+          self.param_count = count.min(MAX_PARAMS + 1);
+        }
+        0x40..=0x7e => {
+          self.state = State::Ground;
+          self.execute_csi(byte);
+        }
+        // Intermediate bytes, keep consuming
+        _ => {}
+      },
+    }
+  }
+
+  fn write_ground(&mut self, byte: u8) {
+    match byte {
+      ESCAPE => self.state = State::Escape,
+      b'\n' => self.newline(),
+      b'\r' => self.col = 0,
+      BACKSPACE => self.col = self.col.min(self.cols).saturating_sub(1),
+      b'\t' => {
+        let next = (self.col / TAB_WIDTH + 1) * TAB_WIDTH;
+        self.col = next.min(self.cols.saturating_sub(1));
+      }
+      // Other control characters are ignored.
+      0x00..=0x1f | 0x7f => {}
+      _ => {
+        if self.col >= self.cols {
+          self.newline();
+        }
+        self.draw_glyph(byte);
+        self.col += 1;
+      }
+    }
+  }
+
+  fn newline(&mut self) {
+    self.col = 0;
+    if self.row + 1 < self.rows {
+      self.row += 1;
+    } else {
+      self.scroll();
+    }
+  }
+
+  fn scroll(&mut self) {
+    let height = self.font.height;
+    self.fb.copy_rows(height, 0, (self.rows - 1) * height);
+    self.clear_cells(0, self.rows - 1, self.cols);
+  }
+
+  fn param(&self, index: usize, default: u16) -> u16 {
+    match self.params.get(index) {
+      Some(&value) if index < self.param_count && value != 0 => value,
+      _ => default,
+    }
+  }
+
+  fn execute_csi(&mut self, command: u8) {
+    let n = self.param(0, 1) as u32;
+    match command {
+      b'A' => self.row = self.row.saturating_sub(n),
+      b'B' => self.row = (self.row + n).min(self.rows - 1),
+      b'C' => self.col = (self.col + n).min(self.cols - 1),
+      b'D' => self.col = self.col.min(self.cols).saturating_sub(n),
+      b'H' | b'f' => {
+        self.row = (self.param(0, 1) as u32 - 1).min(self.rows - 1);
+        self.col = (self.param(1, 1) as u32 - 1).min(self.cols - 1);
+      }
+      b'J' => match self.param(0, 0) {
+        0 => {
+          self.clear_cells(self.col, self.row, self.cols);
+          for row in self.row + 1..self.rows {
+            self.clear_cells(0, row, self.cols);
+          }
+        }
+        2 => {
+          let (col, row) = (self.col, self.row);
+          self.clear();
+          (self.col, self.row) = (col, row);
+        }
+        _ => {}
+      },
+      b'K' => match self.param(0, 0) {
+        0 => self.clear_cells(self.col, self.row, self.cols),
+        2 => self.clear_cells(0, self.row, self.cols),
+        _ => {}
+      },
+      b'm' => self.select_graphic_rendition(),
+      _ => {}
+    }
+  }
+
+  fn select_graphic_rendition(&mut self) {
+    // ESC [ m is a reset.
+    let count = self.param_count.clamp(1, MAX_PARAMS);
+    for i in 0..count {
+      match self.params[i] {
+        0 => {
+          self.fg = DEFAULT_FG;
+          self.bg = DEFAULT_BG;
+          self.bold = false;
+        }
+        1 => self.bold = true,
+        22 => self.bold = false,
+        code @ 30..=37 => self.fg = (code - 30) as usize,
+        39 => self.fg = DEFAULT_FG,
+        code @ 40..=47 => self.bg = (code - 40) as usize,
+        49 => self.bg = DEFAULT_BG,
+        code @ 90..=97 => {
+          self.fg = (code - 90) as usize;
+          self.bold = true;
+        }
+        _ => {}
+      }
+    }
+  }
+
+  fn fg_color(&self) -> Color {
+    if self.bold {
+      BRIGHT_PALETTE[self.fg]
+    } else {
+      PALETTE[self.fg]
+    }
+  }
+
+  fn bg_color(&self) -> Color {
+    PALETTE[self.bg]
+  }
+
+  // Clears cells [col, end_col) of the row.
+  fn clear_cells(&mut self, col: u32, row: u32, end_col: u32) {
+    if col >= end_col {
+      return;
+    }
+    let bg = self.bg_color();
+    self.fb.fill_rect(
+      col * self.font.width,
+      row * self.font.height,
+      (end_col - col) * self.font.width,
+      self.font.height,
+      bg,
+    );
+  }
+
+  fn draw_glyph(&mut self, ch: u8) {
+    let fg = self.fg_color();
+    let bg = self.bg_color();
+    let x = self.col * self.font.width;
+    let y = self.row * self.font.height;
+    let font = self.font;
+    for (dy, bits) in font.glyph(ch).iter().enumerate() {
+      for dx in 0..font.width {
+        let color = if bits & (0x80 >> dx) != 0 { fg } else { bg };
+        self.fb.put_pixel(x + dx, y + dy as u32, color);
+      }
+    }
+  }
+}
+
+impl core::fmt::Write for Console<'_> {
+  fn write_str(&mut self, s: &str) -> core::fmt::Result {
+    Console::write_str(self, s);
+    Ok(())
+  }
+}
+
+// Requires `framebuffer::initialize`.
+pub fn initialize() -> Result<(), ErrorKind> {
+  let fb = framebuffer::get().ok_or(ErrorKind::NotFound)?;
+  let font = font::default();
+  // Console::new needs room for at least one glyph.
+  if fb.width() / font.width == 0 || fb.height() / font.height == 0 {
+    return Err(ErrorKind::InvalidInput);
+  }
+  unsafe {
+    CONSOLE = Some(Console::new(fb, font));
+  }
+  Ok(())
+}
+
+pub fn puts(s: &str) -> Result<(), ErrorKind> {
+  unsafe {
+    CONSOLE
+      .as_mut()
+      .ok_or(ErrorKind::NotConnected)?
+      .write_str(s);
+  }
+  Ok(())
+}
+
+pub fn stream_sink() -> stream::Sink {
+  unsafe { assert!(CONSOLE.is_some(), "Console not initialized") };
+  stream::Sink {
+    name: "console",
+    level: Level::Info,
+    write: puts,
+  }
+}
+
+// Adds the console as an output stream next to the already attached ones.
+pub fn set_as_stream() -> Result<(), ErrorKind> {
+  stream::attach(stream_sink())
+}
+
+#[cfg(test)]
+#[path = "console_test.rs"]
+mod console_test;
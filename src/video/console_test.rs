@@ -0,0 +1,141 @@
+use super::*;
+use crate::video::framebuffer::Info;
+use crate::video::framebuffer::PixelOrder;
+
+const COLS: u32 = 4;
+const ROWS: u32 = 3;
+
+fn make_framebuffer() -> Framebuffer {
+  let info = Info {
+    width: COLS * 8,
+    height: ROWS * 16,
+    pitch: COLS * 8 * 4,
+    depth: 32,
+    order: PixelOrder::Rgb,
+  };
+  let buf = Box::leak(vec![0u8; info.size_bytes()].into_boxed_slice());
+  unsafe { Framebuffer::from_raw(buf.as_mut_ptr(), info) }
+}
+
+// Returns the cell contents as a bitmap, one byte per glyph row.
+fn cell(fb: &Framebuffer, col: u32, row: u32, fg: Color) -> Vec<u8> {
+  (0..16)
+    .map(|dy| {
+      (0..8).fold(0u8, |bits, dx| {
+        let pixel = fb.get_pixel(col * 8 + dx, row * 16 + dy).unwrap();
+        bits | if pixel == fg { 0x80 >> dx } else { 0 }
+      })
+    })
+    .collect()
+}
+
+fn glyph(ch: u8) -> Vec<u8> {
+  font::default().glyph(ch).to_vec()
+}
+
+#[test]
+fn test_geometry() {
+  let mut fb = make_framebuffer();
+  let console = Console::new(&mut fb, font::default());
+  assert_eq!((console.cols(), console.rows()), (COLS, ROWS));
+}
+
+#[test]
+fn test_draws_glyphs() {
+  let mut fb = make_framebuffer();
+  {
+    let mut console = Console::new(&mut fb, font::default());
+    console.write_str("Hi");
+    assert_eq!(console.cursor(), (2, 0));
+  }
+  assert_eq!(cell(&fb, 0, 0, Color::WHITE), glyph(b'H'));
+  assert_eq!(cell(&fb, 1, 0, Color::WHITE), glyph(b'i'));
+  assert_eq!(cell(&fb, 2, 0, Color::WHITE), glyph(b' '));
+}
+
+#[test]
+fn test_control_characters() {
+  let mut fb = make_framebuffer();
+  {
+    let mut console = Console::new(&mut fb, font::default());
+    console.write_str("ab\n");
+    assert_eq!(console.cursor(), (0, 1));
+    console.write_str("x\ty");
+    // Tab stops clamp to the last column on narrow screens.
+    assert_eq!(console.cursor(), (4, 1));
+    console.write_str("\x08\x08z\r");
+    assert_eq!(console.cursor(), (0, 1));
+  }
+  assert_eq!(cell(&fb, 2, 1, Color::WHITE), glyph(b'z'));
+}
+
+#[test]
+fn test_wraps_and_scrolls() {
+  let mut fb = make_framebuffer();
+  {
+    let mut console = Console::new(&mut fb, font::default());
+    // Writing the last column does not wrap by itself.
+    console.write_str("abcd");
+    assert_eq!(console.cursor(), (4, 0));
+    console.write_str("efgh\n12\nXY");
+    assert_eq!(console.cursor(), (2, 2));
+  }
+  assert_eq!(cell(&fb, 0, 0, Color::WHITE), glyph(b'e'));
+  assert_eq!(cell(&fb, 0, 1, Color::WHITE), glyph(b'1'));
+  assert_eq!(cell(&fb, 1, 2, Color::WHITE), glyph(b'Y'));
+  assert_eq!(cell(&fb, 2, 2, Color::WHITE), glyph(b' '));
+}
+
+#[test]
+fn test_colours() {
+  let mut fb = make_framebuffer();
+  {
+    let mut console = Console::new(&mut fb, font::default());
+    console.write_str("\x1b[31;44mA\x1b[1mB\x1b[0mC");
+  }
+  assert_eq!(fb.get_pixel(0, 0), Some(Color::BLUE));
+  assert_eq!(cell(&fb, 0, 0, Color::RED), glyph(b'A'));
+  assert_eq!(cell(&fb, 1, 0, BRIGHT_PALETTE[1]), glyph(b'B'));
+  assert_eq!(cell(&fb, 2, 0, Color::WHITE), glyph(b'C'));
+  assert_eq!(fb.get_pixel(8 * 2, 0), Some(Color::BLACK));
+}
+
+#[test]
+fn test_cursor_and_clear_commands() {
+  let mut fb = make_framebuffer();
+  {
+    let mut console = Console::new(&mut fb, font::default());
+    console.write_str("abcd");
+    console.write_str(&format!(
+      "{}{}",
+      crate::tty::ansi::Command::CursorBack(3),
+      crate::tty::ansi::Command::ClearToEndOfLine
+    ));
+    assert_eq!(console.cursor(), (1, 0));
+    console.write_str(&format!(
+      "{}",
+      crate::tty::ansi::Command::CursorPosition { row: 3, col: 2 }
+    ));
+    assert_eq!(console.cursor(), (1, 2));
+    console.write_str("\x1b7\x1b[H\x1b8");
+    assert_eq!(console.cursor(), (1, 2));
+  }
+  assert_eq!(cell(&fb, 0, 0, Color::WHITE), glyph(b'a'));
+  assert_eq!(cell(&fb, 1, 0, Color::WHITE), glyph(b' '));
+  assert_eq!(cell(&fb, 3, 0, Color::WHITE), glyph(b' '));
+}
+
+#[test]
+fn test_clear_screen() {
+  let mut fb = make_framebuffer();
+  {
+    let mut console = Console::new(&mut fb, font::default());
+    console.write_str("ab\ncd\x1b[2J");
+    assert_eq!(console.cursor(), (2, 1));
+  }
+  for row in 0..ROWS {
+    for col in 0..COLS {
+      assert_eq!(cell(&fb, col, row, Color::WHITE), glyph(b' '));
+    }
+  }
+}
@@ -0,0 +1,320 @@
+// PC Screen Font (PSF) parsing and the built-in 8x16 console font.
+// https://www.win.tue.nl/~aeb/linux/kbd/font-formats-1.html
+
+use crate::common::error::ErrorKind;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_HEADER_SIZE: usize = 4;
+const PSF1_MODE_512: u8 = 0x01;
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HEADER_SIZE: usize = 32;
+
+// Glyph bitmaps, one byte per row, most significant bit is the leftmost
+// pixel. Only fonts up to 8 pixels wide are supported.
+#[derive(Clone, Copy)]
+pub struct Font {
+  pub width: u32,
+  pub height: u32,
+  glyph_count: usize,
+  glyphs: &'static [u8],
+}
+
+impl Font {
+  // Accepts PSF1 and PSF2. Unicode tables are ignored, glyphs are indexed by
+  // byte value.
+  pub fn from_psf(data: &'static [u8]) -> Result<Font, ErrorKind> {
+    if data.len() >= PSF1_HEADER_SIZE && data[0..2] == PSF1_MAGIC {
+      let glyph_count = if data[2] & PSF1_MODE_512 != 0 {
+        512
+      } else {
+        256
+      };
+      let height = data[3] as usize;
+      return Font::new(8, height, glyph_count, &data[PSF1_HEADER_SIZE..]);
+    }
+    if data.len() >= PSF2_HEADER_SIZE && data[0..4] == PSF2_MAGIC {
+      let field = |i: usize| {
+        u32::from_le_bytes([
+          data[4 * i],
+          data[4 * i + 1],
+          data[4 * i + 2],
+          data[4 * i + 3],
+        ]) as usize
+      };
+      let header_size = field(2);
+      let glyph_count = field(4);
+      let bytes_per_glyph = field(5);
+      let height = field(6);
+      let width = field(7);
+      if width > 8 || bytes_per_glyph != height || header_size > data.len() {
+        return Err(ErrorKind::Unsupported);
+      }
+      return Font::new(width, height, glyph_count, &data[header_size..]);
+    }
+    Err(ErrorKind::InvalidData)
+  }
+
+  fn new(
+    width: usize,
+    height: usize,
+    glyph_count: usize,
+    glyphs: &'static [u8],
+  ) -> Result<Font, ErrorKind> {
+    if width == 0 || height == 0 || glyphs.len() < glyph_count * height {
+      return Err(ErrorKind::InvalidData);
+    }
+    Ok(Font {
+      width: width as u32,
+      height: height as u32,
+      glyph_count,
+      glyphs: &glyphs[..glyph_count * height],
+    })
+  }
+
+  // Rows of the glyph for `ch`, falls back to `?` (or glyph 0 in small
+  // fonts) for missing glyphs.
+  pub fn glyph(&self, ch: u8) -> &[u8] {
+    let index = if (ch as usize) < self.glyph_count {
+      ch as usize
+    } else if (b'?' as usize) < self.glyph_count {
+      b'?' as usize
+    } else {
+      0
+    };
+    let height = self.height as usize;
+    &self.glyphs[index * height..(index + 1) * height]
+  }
+}
+
+// Built-in font as a PSF1 blob.
+pub fn default() -> Font {
+  Font::from_psf(&DEFAULT_PSF).expect("Invalid built-in font")
+}
+
+const DEFAULT_HEIGHT: usize = 16;
+static DEFAULT_PSF: [u8; PSF1_HEADER_SIZE + 256 * DEFAULT_HEIGHT] =
+  build_default_psf();
+
+// Doubles each row of the 8x8 table to get 8x16 glyphs and converts to PSF
+// bit order. Bytes without a glyph are left blank.
+const fn build_default_psf() -> [u8; PSF1_HEADER_SIZE + 256 * DEFAULT_HEIGHT] {
+  let mut psf = [0u8; PSF1_HEADER_SIZE + 256 * DEFAULT_HEIGHT];
+  psf[0] = PSF1_MAGIC[0];
+  psf[1] = PSF1_MAGIC[1];
+  psf[2] = 0;
+  psf[3] = DEFAULT_HEIGHT as u8;
+  let mut i = 0;
+  while i < BASIC_8X8.len() {
+    let base = PSF1_HEADER_SIZE + (BASIC_FIRST as usize + i) * DEFAULT_HEIGHT;
+    let mut row = 0;
+    while row < 8 {
+      let bits = BASIC_8X8[i][row].reverse_bits();
+      psf[base + 2 * row] = bits;
+      psf[base + 2 * row + 1] = bits;
+      row += 1;
+    }
+    i += 1;
+  }
+  psf
+}
+
+// Printable ASCII from font8x8_basic by Daniel Hepper (public domain), based
+// on the IBM PC BIOS font. Least significant bit is the leftmost pixel.
+// https://github.com/dhepper/font8x8
+const BASIC_FIRST: u8 = 0x20;
+const BASIC_8X8: [[u8; 8]; 95] = [
+  // 0x20 ' '
+  [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+  // 0x21 '!'
+  [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00],
+  // 0x22 '"'
+  [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+  // 0x23 '#'
+  [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00],
+  // 0x24 '$'
+  [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00],
+  // 0x25 '%'
+  [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00],
+  // 0x26 '&'
+  [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00],
+  // 0x27 '\''
+  [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00],
+  // 0x28 '('
+  [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00],
+  // 0x29 ')'
+  [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00],
+  // 0x2A '*'
+  [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00],
+  // 0x2B '+'
+  [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00],
+  // 0x2C ','
+  [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06],
+  // 0x2D '-'
+  [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00],
+  // 0x2E '.'
+  [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00],
+  // 0x2F '/'
+  [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00],
+  // 0x30 '0'
+  [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00],
+  // 0x31 '1'
+  [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00],
+  // 0x32 '2'
+  [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00],
+  // 0x33 '3'
+  [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00],
+  // 0x34 '4'
+  [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00],
+  // 0x35 '5'
+  [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00],
+  // 0x36 '6'
+  [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00],
+  // 0x37 '7'
+  [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00],
+  // 0x38 '8'
+  [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00],
+  // 0x39 '9'
+  [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00],
+  // 0x3A ':'
+  [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00],
+  // 0x3B ';'
+  [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06],
+  // 0x3C '<'
+  [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00],
+  // 0x3D '='
+  [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00],
+  // 0x3E '>'
+  [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00],
+  // 0x3F '?'
+  [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00],
+  // 0x40 '@'
+  [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00],
+  // 0x41 'A'
+  [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00],
+  // 0x42 'B'
+  [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00],
+  // 0x43 'C'
+  [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00],
+  // 0x44 'D'
+  [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00],
+  // 0x45 'E'
+  [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00],
+  // 0x46 'F'
+  [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00],
+  // 0x47 'G'
+  [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00],
+  // 0x48 'H'
+  [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00],
+  // 0x49 'I'
+  [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],
+  // 0x4A 'J'
+  [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00],
+  // 0x4B 'K'
+  [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00],
+  // 0x4C 'L'
+  [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00],
+  // 0x4D 'M'
+  [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00],
+  // 0x4E 'N'
+  [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00],
+  // 0x4F 'O'
+  [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00],
+  // 0x50 'P'
+  [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00],
+  // 0x51 'Q'
+  [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00],
+  // 0x52 'R'
+  [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00],
+  // 0x53 'S'
+  [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00],
+  // 0x54 'T'
+  [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],
+  // 0x55 'U'
+  [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00],
+  // 0x56 'V'
+  [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00],
+  // 0x57 'W'
+  [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00],
+  // 0x58 'X'
+  [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00],
+  // 0x59 'Y'
+  [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00],
+  // 0x5A 'Z'
+  [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00],
+  // 0x5B '['
+  [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00],
+  // 0x5C '\\'
+  [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00],
+  // 0x5D ']'
+  [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00],
+  // 0x5E '^'
+  [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00],
+  // 0x5F '_'
+  [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF],
+  // 0x60 '`'
+  [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00],
+  // 0x61 'a'
+  [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00],
+  // 0x62 'b'
+  [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00],
+  // 0x63 'c'
+  [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00],
+  // 0x64 'd'
+  [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00],
+  // 0x65 'e'
+  [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00],
+  // 0x66 'f'
+  [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00],
+  // 0x67 'g'
+  [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F],
+  // 0x68 'h'
+  [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00],
+  // 0x69 'i'
+  [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],
+  // 0x6A 'j'
+  [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E],
+  // 0x6B 'k'
+  [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00],
+  // 0x6C 'l'
+  [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],
+  // 0x6D 'm'
+  [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00],
+  // 0x6E 'n'
+  [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00],
+  // 0x6F 'o'
+  [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00],
+  // 0x70 'p'
+  [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F],
+  // 0x71 'q'
+  [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78],
+  // 0x72 'r'
+  [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00],
+  // 0x73 's'
+  [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00],
+  // 0x74 't'
+  [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00],
+  // 0x75 'u'
+  [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00],
+  // 0x76 'v'
+  [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00],
+  // 0x77 'w'
+  [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00],
+  // 0x78 'x'
+  [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00],
+  // 0x79 'y'
+  [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F],
+  // 0x7A 'z'
+  [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00],
+  // 0x7B '{'
+  [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00],
+  // 0x7C '|'
+  [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00],
+  // 0x7D '}'
+  [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00],
+  // 0x7E '~'
+  [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
+#[cfg(test)]
+#[path = "font_test.rs"]
+mod font_test;
@@ -0,0 +1,80 @@
+use super::*;
+
+#[test]
+fn test_default_font_geometry() {
+  let font = default();
+  assert_eq!(font.width, 8);
+  assert_eq!(font.height, 16);
+  assert_eq!(font.glyph(b' '), &[0u8; 16]);
+}
+
+#[test]
+fn test_default_font_bit_order() {
+  let font = default();
+  // '_' is a full bottom row, '|' is a centred vertical bar.
+  assert_eq!(font.glyph(b'_')[15], 0xFF);
+  assert_eq!(font.glyph(b'|')[0], 0x18);
+  // Third row of '(' is 0x0C in font8x8 order, mirrored to 0x30.
+  assert_eq!(font.glyph(b'(')[2], 0x30);
+  assert_eq!(font.glyph(b'(')[3], 0x30);
+}
+
+#[test]
+fn test_psf1_parse() {
+  static DATA: [u8; 4 + 256 * 2] = {
+    let mut data = [0u8; 4 + 256 * 2];
+    data[0] = 0x36;
+    data[1] = 0x04;
+    data[3] = 2;
+    data[4 + 2 * 0x41] = 0xAA;
+    data
+  };
+  let font = Font::from_psf(&DATA).unwrap();
+  assert_eq!(font.height, 2);
+  assert_eq!(font.glyph(b'A'), &[0xAA, 0x00]);
+}
+
+// Magic, version, header size, flags, glyph count, bytes per glyph, height,
+// width
+const fn psf2_header(data: &mut [u8], header: [u32; 8]) {
+  let mut i = 0;
+  while i < 8 {
+    let bytes = header[i].to_le_bytes();
+    data[4 * i] = bytes[0];
+    data[4 * i + 1] = bytes[1];
+    data[4 * i + 2] = bytes[2];
+    data[4 * i + 3] = bytes[3];
+    i += 1;
+  }
+}
+
+#[test]
+fn test_psf2_parse() {
+  static DATA: [u8; 32 + 3 * 4] = {
+    let mut data = [0u8; 32 + 3 * 4];
+    psf2_header(&mut data, [0x864ab572, 0, 32, 0, 3, 4, 4, 6]);
+    data[32 + 4] = 0xFC;
+    data
+  };
+  let font = Font::from_psf(&DATA).unwrap();
+  assert_eq!((font.width, font.height), (6, 4));
+  assert_eq!(font.glyph(1), &[0xFC, 0, 0, 0]);
+  // Neither 'A' nor '?' exist, falls back to glyph 0.
+  assert_eq!(font.glyph(b'A'), font.glyph(0));
+}
+
+#[test]
+fn test_psf2_zero_width() {
+  static DATA: [u8; 32 + 4] = {
+    let mut data = [0u8; 32 + 4];
+    psf2_header(&mut data, [0x864ab572, 0, 32, 0, 1, 4, 4, 0]);
+    data
+  };
+  assert!(matches!(Font::from_psf(&DATA), Err(ErrorKind::InvalidData)));
+}
+
+#[test]
+fn test_invalid_magic() {
+  static DATA: [u8; 8] = [0; 8];
+  assert!(Font::from_psf(&DATA).is_err());
+}
@@ -0,0 +1,312 @@
+// https://github.com/raspberrypi/firmware/wiki/Mailbox-framebuffer-interface
+// https://github.com/raspberrypi/firmware/wiki/Mailbox-property-interface#frame-buffer
+
+use crate::common::error::ErrorKind;
+use crate::io::dma;
+use crate::io::mailbox;
+use crate::io::mailbox::tag::FramebufferAllocate;
+use crate::io::mailbox::tag::FramebufferGetPitch;
+use crate::io::mailbox::tag::FramebufferSetDepth;
+use crate::io::mailbox::tag::FramebufferSetPhysicalSize;
+use crate::io::mailbox::tag::FramebufferSetPixelOrder;
+use crate::io::mailbox::tag::FramebufferSetVirtualOffset;
+use crate::io::mailbox::tag::FramebufferSetVirtualSize;
+use crate::mm;
+
+static mut FRAMEBUFFER: Option<Framebuffer> = None;
+
+const ALLOCATE_ALIGNMENT_BYTES: u32 = 16;
+
+// Colour in 0x00RRGGBB form. Converted to the framebuffer pixel format on
+// write.
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Color(pub u32);
+
+#[allow(dead_code)]
+impl Color {
+  pub const BLACK: Color = Color(0x00_00_00);
+  pub const RED: Color = Color(0xAA_00_00);
+  pub const GREEN: Color = Color(0x00_AA_00);
+  pub const YELLOW: Color = Color(0xAA_55_00);
+  pub const BLUE: Color = Color(0x00_00_AA);
+  pub const MAGENTA: Color = Color(0xAA_00_AA);
+  pub const CYAN: Color = Color(0x00_AA_AA);
+  pub const WHITE: Color = Color(0xAA_AA_AA);
+
+  pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color(((r as u32) << 16) | ((g as u32) << 8) | b as u32)
+  }
+
+  pub const fn r(&self) -> u8 {
+    (self.0 >> 16) as u8
+  }
+
+  pub const fn g(&self) -> u8 {
+    (self.0 >> 8) as u8
+  }
+
+  pub const fn b(&self) -> u8 {
+    self.0 as u8
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PixelOrder {
+  Bgr = 0,
+  Rgb = 1,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Info {
+  pub width: u32,
+  pub height: u32,
+  // Bytes per line. May be larger than width * bytes per pixel.
+  pub pitch: u32,
+  // Bits per pixel. 16, 24 and 32 are supported.
+  pub depth: u32,
+  pub order: PixelOrder,
+}
+
+impl Info {
+  pub fn bytes_per_pixel(&self) -> usize {
+    (self.depth / 8) as usize
+  }
+
+  pub fn size_bytes(&self) -> usize {
+    self.pitch as usize * self.height as usize
+  }
+}
+
+pub struct Framebuffer {
+  base: *mut u8,
+  info: Info,
+}
+
+impl Framebuffer {
+  // Safety: `base` must point to at least `info.size_bytes()` writable bytes
+  // that stay valid for the lifetime of the framebuffer.
+  pub unsafe fn from_raw(base: *mut u8, info: Info) -> Framebuffer {
+    assert!(
+      matches!(info.depth, 16 | 24 | 32),
+      "Unsupported framebuffer depth"
+    );
+    Framebuffer { base, info }
+  }
+
+  pub fn info(&self) -> Info {
+    self.info
+  }
+
+  pub fn width(&self) -> u32 {
+    self.info.width
+  }
+
+  pub fn height(&self) -> u32 {
+    self.info.height
+  }
+
+  // Drawing outside of the screen is silently ignored.
+  pub fn put_pixel(&mut self, x: u32, y: u32, color: Color) {
+    if x >= self.info.width || y >= self.info.height {
+      return;
+    }
+    let raw = self.pack(color);
+    unsafe { self.write_raw(self.offset(x, y), raw) };
+  }
+
+  pub fn get_pixel(&self, x: u32, y: u32) -> Option<Color> {
+    if x >= self.info.width || y >= self.info.height {
+      return None;
+    }
+    Some(self.unpack(unsafe { self.read_raw(self.offset(x, y)) }))
+  }
+
+  // Fills the rectangle clipped to the screen.
+  pub fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Color) {
+    let x_end = x.saturating_add(w).min(self.info.width);
+    let y_end = y.saturating_add(h).min(self.info.height);
+    let raw = self.pack(color);
+    for row in y..y_end {
+      for col in x..x_end {
+        unsafe { self.write_raw(self.offset(col, row), raw) };
+      }
+    }
+  }
+
+  pub fn clear(&mut self, color: Color) {
+    self.fill_rect(0, 0, self.info.width, self.info.height, color);
+  }
+
+  // Copies a `w` x `h` block of row-major pixels to (x, y), clipped to the
+  // screen.
+  pub fn blit(
+    &mut self,
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    pixels: &[Color],
+  ) -> Result<(), ErrorKind> {
+    if pixels.len() < w as usize * h as usize {
+      return Err(ErrorKind::InvalidInput);
+    }
+    let cols = w.min(self.info.width.saturating_sub(x));
+    let rows = h.min(self.info.height.saturating_sub(y));
+    for row in 0..rows {
+      for col in 0..cols {
+        let raw = self.pack(pixels[(row * w + col) as usize]);
+        unsafe { self.write_raw(self.offset(x + col, y + row), raw) };
+      }
+    }
+    Ok(())
+  }
+
+  // Moves `rows` lines starting at `src_y` to `dst_y`. Overlap is allowed.
+  pub fn copy_rows(&mut self, src_y: u32, dst_y: u32, rows: u32) {
+    let rows = rows
+      .min(self.info.height.saturating_sub(src_y))
+      .min(self.info.height.saturating_sub(dst_y));
+    let pitch = self.info.pitch as usize;
+    unsafe {
+      core::ptr::copy(
+        self.base.add(src_y as usize * pitch),
+        self.base.add(dst_y as usize * pitch),
+        rows as usize * pitch,
+      );
+    }
+  }
+
+  fn offset(&self, x: u32, y: u32) -> usize {
+    y as usize * self.info.pitch as usize
+      + x as usize * self.info.bytes_per_pixel()
+  }
+
+  fn pack(&self, color: Color) -> u32 {
+    let (r, g, b) = match self.info.order {
+      PixelOrder::Rgb => (color.r(), color.g(), color.b()),
+      PixelOrder::Bgr => (color.b(), color.g(), color.r()),
+    };
+    match self.info.depth {
+      // RGB565
+      16 => ((r as u32 >> 3) << 11) | ((g as u32 >> 2) << 5) | (b as u32 >> 3),
+      // Bytes are stored in the given order, first component at the lowest
+      // address.
+      _ => r as u32 | ((g as u32) << 8) | ((b as u32) << 16),
+    }
+  }
+
+  fn unpack(&self, raw: u32) -> Color {
+    let (r, g, b) = match self.info.depth {
+      16 => (
+        ((raw >> 11) << 3) as u8,
+        (((raw >> 5) & 0x3F) << 2) as u8,
+        ((raw & 0x1F) << 3) as u8,
+      ),
+      _ => (raw as u8, (raw >> 8) as u8, (raw >> 16) as u8),
+    };
+    match self.info.order {
+      PixelOrder::Rgb => Color::rgb(r, g, b),
+      PixelOrder::Bgr => Color::rgb(b, g, r),
+    }
+  }
+
+  unsafe fn write_raw(&mut self, offset: usize, raw: u32) {
+    let ptr = self.base.add(offset);
+    for i in 0..self.info.bytes_per_pixel() {
+      core::ptr::write_volatile(ptr.add(i), (raw >> (8 * i)) as u8);
+    }
+  }
+
+  unsafe fn read_raw(&self, offset: usize) -> u32 {
+    let ptr = self.base.add(offset);
+    let mut raw = 0;
+    for i in 0..self.info.bytes_per_pixel() {
+      raw |= (core::ptr::read_volatile(ptr.add(i)) as u32) << (8 * i);
+    }
+    raw
+  }
+}
+
+// Asks the firmware for a framebuffer. The firmware may pick a different
+// geometry than requested, see `get()` for the result.
+pub fn initialize(
+  width: u32,
+  height: u32,
+  depth: u32,
+) -> Result<(), ErrorKind> {
+  if !matches!(depth, 16 | 24 | 32) {
+    return Err(ErrorKind::InvalidInput);
+  }
+  let physical_size_tag =
+    FramebufferSetPhysicalSize::Request { width, height }.to_tag();
+  let virtual_size_tag =
+    FramebufferSetVirtualSize::Request { width, height }.to_tag();
+  let virtual_offset_tag =
+    FramebufferSetVirtualOffset::Request { x: 0, y: 0 }.to_tag();
+  let depth_tag = FramebufferSetDepth::Request {
+    bits_per_pixel: depth,
+  }
+  .to_tag();
+  let pixel_order_tag = FramebufferSetPixelOrder::Request {
+    order: PixelOrder::Rgb as u32,
+  }
+  .to_tag();
+  let allocate_tag = FramebufferAllocate::Request {
+    alignment_bytes: ALLOCATE_ALIGNMENT_BYTES,
+  }
+  .to_tag();
+  let pitch_tag = FramebufferGetPitch::Request {}.to_tag();
+
+  // All tags must be in a single message for the allocation to take the
+  // geometry into account.
+  let mut message = mailbox::Message::new();
+  message
+    .add_tag(&physical_size_tag)?
+    .add_tag(&virtual_size_tag)?
+    .add_tag(&virtual_offset_tag)?
+    .add_tag(&depth_tag)?
+    .add_tag(&pixel_order_tag)?
+    .add_tag(&allocate_tag)?
+    .add_tag(&pitch_tag)?;
+  mailbox::send(&mut message)?;
+
+  let size = FramebufferSetPhysicalSize::read_response(&message)?;
+  let depth = FramebufferSetDepth::read_response(&message)?.bits_per_pixel();
+  let order = FramebufferSetPixelOrder::read_response(&message)?.order();
+  let allocation = FramebufferAllocate::read_response(&message)?;
+  let pitch = FramebufferGetPitch::read_response(&message)?.bytes_per_line();
+
+  if allocation.base_address() == 0 || !matches!(depth, 16 | 24 | 32) {
+    return Err(ErrorKind::Unsupported);
+  }
+  let info = Info {
+    width: size.width(),
+    height: size.height(),
+    pitch,
+    depth,
+    order: if order == PixelOrder::Rgb as u32 {
+      PixelOrder::Rgb
+    } else {
+      PixelOrder::Bgr
+    },
+  };
+  if info.size_bytes() > allocation.size_bytes() as usize {
+    return Err(ErrorKind::InvalidData);
+  }
+
+  // The firmware returns a VideoCore bus address.
+  let base = mm::phys_to_virt(dma::bus_to_phys(allocation.base_address()));
+  unsafe {
+    FRAMEBUFFER = Some(Framebuffer::from_raw(base as *mut u8, info));
+  }
+  Ok(())
+}
+
+pub fn get() -> Option<&'static mut Framebuffer> {
+  unsafe { FRAMEBUFFER.as_mut() }
+}
+
+#[cfg(test)]
+#[path = "framebuffer_test.rs"]
+mod framebuffer_test;
@@ -0,0 +1,105 @@
+use super::*;
+
+fn make(
+  width: u32,
+  height: u32,
+  depth: u32,
+  order: PixelOrder,
+) -> (Vec<u8>, Info) {
+  let bytes_per_pixel = depth / 8;
+  // Pad lines like the firmware may do.
+  let pitch = width * bytes_per_pixel + 8;
+  let info = Info {
+    width,
+    height,
+    pitch,
+    depth,
+    order,
+  };
+  (vec![0u8; info.size_bytes()], info)
+}
+
+#[test]
+fn test_put_pixel_rgb32() {
+  let (mut buf, info) = make(4, 3, 32, PixelOrder::Rgb);
+  let mut fb = unsafe { Framebuffer::from_raw(buf.as_mut_ptr(), info) };
+  fb.put_pixel(1, 2, Color::rgb(0x11, 0x22, 0x33));
+  assert_eq!(fb.get_pixel(1, 2), Some(Color::rgb(0x11, 0x22, 0x33)));
+  let offset = (2 * info.pitch + 4) as usize;
+  assert_eq!(&buf[offset..offset + 3], &[0x11, 0x22, 0x33]);
+}
+
+#[test]
+fn test_put_pixel_bgr32() {
+  let (mut buf, info) = make(4, 3, 32, PixelOrder::Bgr);
+  let mut fb = unsafe { Framebuffer::from_raw(buf.as_mut_ptr(), info) };
+  fb.put_pixel(0, 0, Color::rgb(0x11, 0x22, 0x33));
+  assert_eq!(fb.get_pixel(0, 0), Some(Color::rgb(0x11, 0x22, 0x33)));
+  assert_eq!(&buf[0..3], &[0x33, 0x22, 0x11]);
+}
+
+#[test]
+fn test_put_pixel_rgb565() {
+  let (mut buf, info) = make(4, 3, 16, PixelOrder::Rgb);
+  let mut fb = unsafe { Framebuffer::from_raw(buf.as_mut_ptr(), info) };
+  fb.put_pixel(0, 0, Color::rgb(0xFF, 0x00, 0xFF));
+  assert_eq!(&buf[0..2], &[0x1F, 0xF8]);
+  assert_eq!(fb.get_pixel(0, 0), Some(Color::rgb(0xF8, 0x00, 0xF8)));
+}
+
+#[test]
+fn test_put_pixel_out_of_bounds() {
+  let (mut buf, info) = make(4, 3, 32, PixelOrder::Rgb);
+  let mut fb = unsafe { Framebuffer::from_raw(buf.as_mut_ptr(), info) };
+  fb.put_pixel(4, 0, Color::WHITE);
+  fb.put_pixel(0, 3, Color::WHITE);
+  assert_eq!(fb.get_pixel(4, 0), None);
+  assert!(buf.iter().all(|b| *b == 0));
+}
+
+#[test]
+fn test_fill_rect_clipped() {
+  let (mut buf, info) = make(4, 3, 32, PixelOrder::Rgb);
+  let mut fb = unsafe { Framebuffer::from_raw(buf.as_mut_ptr(), info) };
+  fb.fill_rect(2, 1, 10, 10, Color::RED);
+  for y in 0..3 {
+    for x in 0..4 {
+      let expected = if x >= 2 && y >= 1 {
+        Color::RED
+      } else {
+        Color::BLACK
+      };
+      assert_eq!(fb.get_pixel(x, y), Some(expected), "({}, {})", x, y);
+    }
+  }
+}
+
+#[test]
+fn test_blit_clipped() {
+  let (mut buf, info) = make(4, 3, 24, PixelOrder::Rgb);
+  let mut fb = unsafe { Framebuffer::from_raw(buf.as_mut_ptr(), info) };
+  let pixels = [
+    Color::RED,
+    Color::GREEN,
+    Color::BLUE,
+    Color::CYAN,
+    Color::MAGENTA,
+    Color::YELLOW,
+  ];
+  fb.blit(2, 2, 3, 2, &pixels).unwrap();
+  assert_eq!(fb.get_pixel(2, 2), Some(Color::RED));
+  assert_eq!(fb.get_pixel(3, 2), Some(Color::GREEN));
+  assert_eq!(fb.get_pixel(1, 2), Some(Color::BLACK));
+  assert!(fb.blit(0, 0, 3, 3, &pixels).is_err());
+}
+
+#[test]
+fn test_copy_rows_scrolls() {
+  let (mut buf, info) = make(2, 3, 32, PixelOrder::Rgb);
+  let mut fb = unsafe { Framebuffer::from_raw(buf.as_mut_ptr(), info) };
+  fb.fill_rect(0, 1, 2, 1, Color::GREEN);
+  fb.fill_rect(0, 2, 2, 1, Color::BLUE);
+  fb.copy_rows(1, 0, 2);
+  assert_eq!(fb.get_pixel(0, 0), Some(Color::GREEN));
+  assert_eq!(fb.get_pixel(1, 1), Some(Color::BLUE));
+}
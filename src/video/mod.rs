@@ -0,0 +1,3 @@
+pub mod console;
+pub mod font;
+pub mod framebuffer;
@@ -0,0 +1,101 @@
+#!/usr/bin/env python3
+"""Sends a kernel image to the serial bootloader, see src/bootloader.
+
+Usage: chainload.py <port> <image> [--baud 115200]
+
+<port> is anything pyserial opens, e.g. /dev/ttyUSB0, a QEMU pty such as
+/dev/pts/3, or socket://localhost:5555. After the transfer the console
+output of the booted kernel is printed until Ctrl-C.
+"""
+
+import argparse
+import binascii
+import struct
+import sys
+
+import serial
+
+ACK = 0x06
+NAK = 0x15
+SYNC = b"BL"
+KIND_HEADER = ord("H")
+KIND_DATA = ord("D")
+MAX_CHUNK = 1024
+MAX_IMAGE_SIZE = 16 << 20
+
+RETRIES = 10
+# Seconds to wait for an answer before resending
+TIMEOUT = 1.0
+
+
+def frame(kind, seq, payload):
+    body = struct.pack("<BHH", kind, seq, len(payload)) + payload
+    return SYNC + body + struct.pack("<I", binascii.crc32(body))
+
+
+def wait_answer(port):
+    """ACK, NAK or None on timeout. Console output is passed through."""
+    while True:
+        byte = port.read(1)
+        if not byte:
+            return None
+        if byte[0] in (ACK, NAK):
+            return byte[0]
+        sys.stdout.buffer.write(byte)
+        sys.stdout.flush()
+
+
+def send_frame(port, data):
+    for _ in range(RETRIES):
+        port.reset_input_buffer()
+        port.write(data)
+        if wait_answer(port) == ACK:
+            return True
+    return False
+
+
+def send_image(port, image):
+    header = struct.pack("<II", len(image), binascii.crc32(image))
+    if not send_frame(port, frame(KIND_HEADER, 0, header)):
+        raise RuntimeError("bootloader does not answer")
+
+    chunks = [image[i:i + MAX_CHUNK] for i in range(0, len(image), MAX_CHUNK)]
+    for seq, chunk in enumerate(chunks):
+        if not send_frame(port, frame(KIND_DATA, seq & 0xFFFF, chunk)):
+            # Includes an image CRC mismatch on the last frame
+            raise RuntimeError("chunk {} not accepted".format(seq))
+        print("\r{}/{} bytes".format(
+            min((seq + 1) * MAX_CHUNK, len(image)), len(image)),
+            end="", file=sys.stderr)
+    print(file=sys.stderr)
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("port")
+    parser.add_argument("image")
+    parser.add_argument("--baud", type=int, default=115200)
+    args = parser.parse_args()
+
+    with open(args.image, "rb") as f:
+        image = f.read()
+    if not 0 < len(image) <= MAX_IMAGE_SIZE:
+        sys.exit("image size must be 1 to {} bytes".format(MAX_IMAGE_SIZE))
+
+    port = serial.serial_for_url(args.port, baudrate=args.baud,
+                                 timeout=TIMEOUT)
+    try:
+        send_image(port, image)
+    except RuntimeError as e:
+        sys.exit("chainload: {}".format(e))
+    print("Booting", file=sys.stderr)
+    try:
+        while True:
+            sys.stdout.buffer.write(port.read(port.in_waiting or 1))
+            sys.stdout.flush()
+    except KeyboardInterrupt:
+        pass
+
+
+if __name__ == "__main__":
+    main()
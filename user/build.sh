@@ -0,0 +1,8 @@
+#!/bin/bash
+# Builds the user programs, see "User space" in README.md.
+# RUSTFLAGS replaces the kernel's linker script from ../.cargo/config.toml.
+
+set -e
+cd "$(dirname "$0")"
+RUSTFLAGS="-C link-arg=-T$(pwd)/user.ld" \
+  cargo build --release --target aarch64-unknown-none-softfloat
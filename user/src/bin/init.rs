@@ -0,0 +1,25 @@
+// First process: starts the shell and starts it again when it exits.
+// Orphans are handed to init and reaped by the same wait4.
+#![no_std]
+#![no_main]
+
+use osdev_user::{execve, exit, fork, println, wait4, Args};
+
+#[no_mangle]
+pub fn main(_: Args) -> i32 {
+  loop {
+    let pid = fork();
+    if pid == 0 {
+      let error = execve(c"/bin/sh", &[c"sh"]);
+      println!("init: cannot run /bin/sh: {}", error);
+      exit(127);
+    }
+    if pid < 0 {
+      println!("init: fork failed: {}", pid);
+      return 1;
+    }
+    let mut status = 0;
+    while wait4(-1, &mut status, 0) != pid {}
+    println!("init: sh exited with {}", status >> 8);
+  }
+}
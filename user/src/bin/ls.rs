@@ -0,0 +1,46 @@
+// Lists the directories given as arguments, or /.
+#![no_std]
+#![no_main]
+
+use core::ffi::CStr;
+use osdev_user::{
+  close, open, println, readdir, Args, DirEntry, DT_CHR, DT_DIR, O_DIRECTORY,
+  O_RDONLY,
+};
+
+fn list(path: &CStr) -> bool {
+  let name = path.to_str().unwrap_or("?");
+  let fd = open(path, O_RDONLY | O_DIRECTORY);
+  if fd < 0 {
+    println!("ls: {}: cannot open: {}", name, fd);
+    return false;
+  }
+  let mut entry = DirEntry::new();
+  while readdir(fd as u64, &mut entry) > 0 {
+    let suffix = match entry.kind {
+      DT_DIR => "/",
+      DT_CHR => "@",
+      _ => "",
+    };
+    println!("{}{}", entry.name(), suffix);
+  }
+  close(fd as u64);
+  true
+}
+
+#[no_mangle]
+pub fn main(args: Args) -> i32 {
+  if args.len() < 2 {
+    return if list(c"/") { 0 } else { 1 };
+  }
+  let mut status = 0;
+  for index in 1..args.len() {
+    if args.len() > 2 {
+      println!("{}:", args.get(index).unwrap().to_str().unwrap_or("?"));
+    }
+    if !list(args.get(index).unwrap()) {
+      status = 1;
+    }
+  }
+  status
+}
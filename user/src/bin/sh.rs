@@ -0,0 +1,107 @@
+// Minimal shell: runs each line as a program with its arguments, looking
+// up names without a slash in /bin.
+#![no_std]
+#![no_main]
+
+use core::ffi::CStr;
+use osdev_user::{
+  execve, exit, fork, getc, getpid, print, println, putc, wait4, Args, MAX_ARGS,
+};
+
+const LINE_SIZE: usize = 256;
+const PROMPT: &str = "$ ";
+
+// Reads a line with echo and backspace, without the newline.
+fn read_line(line: &mut [u8; LINE_SIZE]) -> usize {
+  let mut len = 0;
+  loop {
+    match getc() {
+      b'\r' | b'\n' => {
+        println!();
+        return len;
+      }
+      0x7F | 0x08 if len > 0 => {
+        len -= 1;
+        print!("\x08 \x08");
+      }
+      byte @ b' '..=b'~' if len < LINE_SIZE - 1 => {
+        line[len] = byte;
+        len += 1;
+        putc(byte);
+      }
+      _ => {}
+    }
+  }
+}
+
+// Splits `line`, which ends with a 0, into words in place.
+fn split<'a>(line: &'a mut [u8], words: &mut [&'a CStr; MAX_ARGS]) -> usize {
+  for byte in line.iter_mut().filter(|byte| **byte == b' ') {
+    *byte = 0;
+  }
+  let line: &'a [u8] = line;
+  let starts =
+    (0..line.len()).filter(|&i| line[i] != 0 && (i == 0 || line[i - 1] == 0));
+  let mut count = 0;
+  for (word, start) in words.iter_mut().zip(starts) {
+    *word = CStr::from_bytes_until_nul(&line[start..]).unwrap();
+    count += 1;
+  }
+  count
+}
+
+fn run(words: &[&CStr]) {
+  let name = words[0].to_bytes();
+  let mut path = [0u8; LINE_SIZE + 5];
+  let len = match name.contains(&b'/') {
+    true => {
+      path[..name.len()].copy_from_slice(name);
+      name.len()
+    }
+    false => {
+      path[..5].copy_from_slice(b"/bin/");
+      path[5..5 + name.len()].copy_from_slice(name);
+      5 + name.len()
+    }
+  };
+  let path = CStr::from_bytes_with_nul(&path[..len + 1]).unwrap();
+
+  let pid = fork();
+  if pid == 0 {
+    let error = execve(path, words);
+    println!(
+      "sh: {}: cannot run: {}",
+      path.to_str().unwrap_or("?"),
+      error
+    );
+    exit(127);
+  }
+  if pid < 0 {
+    println!("sh: fork failed: {}", pid);
+    return;
+  }
+  let mut status = 0;
+  wait4(pid, &mut status, 0);
+  if status & 0x7F != 0 {
+    println!("sh: killed by signal {}", status & 0x7F);
+  }
+}
+
+#[no_mangle]
+pub fn main(_: Args) -> i32 {
+  println!("sh: pid {}", getpid());
+  let mut line = [0u8; LINE_SIZE];
+  loop {
+    print!("{}", PROMPT);
+    let len = read_line(&mut line);
+    // The NUL after the last word
+    line[len] = 0;
+    let mut words = [c""; MAX_ARGS];
+    let count = split(&mut line[..len + 1], &mut words);
+    match words[..count] {
+      [] => {}
+      [exit_command, ..] if exit_command.to_bytes() == b"exit" => return 0,
+      _ => run(&words[..count]),
+    }
+  }
+}
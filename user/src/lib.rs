@@ -0,0 +1,302 @@
+// Runtime for user programs: the entry point, syscall wrappers and output
+// to the standard output. Programs define `main`, see src/bin.
+#![no_std]
+
+use core::ffi::{c_char, CStr};
+use core::fmt::{self, Write};
+
+// SyscallID in the kernel's src/syscall.rs
+const FORK: u64 = 2;
+const EXECVE: u64 = 3;
+const WAIT4: u64 = 4;
+const EXIT: u64 = 5;
+const GETPID: u64 = 6;
+const GETPPID: u64 = 7;
+const OPEN: u64 = 8;
+const READ: u64 = 9;
+const WRITE: u64 = 10;
+const CLOSE: u64 = 11;
+const LSEEK: u64 = 12;
+const STAT: u64 = 13;
+const FSTAT: u64 = 14;
+const READDIR: u64 = 15;
+
+pub const WNOHANG: u64 = 1;
+pub const STDIN: u64 = 0;
+pub const STDOUT: u64 = 1;
+pub const STDERR: u64 = 2;
+pub const O_RDONLY: u64 = 0;
+pub const O_WRONLY: u64 = 1;
+pub const O_RDWR: u64 = 2;
+pub const O_CREAT: u64 = 0o100;
+pub const O_TRUNC: u64 = 0o1000;
+pub const O_APPEND: u64 = 0o2000;
+pub const O_DIRECTORY: u64 = 0o200000;
+pub const SEEK_SET: u64 = 0;
+pub const SEEK_CUR: u64 = 1;
+pub const SEEK_END: u64 = 2;
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+// Arguments passed to execve()
+pub const MAX_ARGS: usize = 16;
+
+// Result in x0, negated errno on failure.
+fn syscall(number: u64, args: [u64; 4]) -> i64 {
+  let result: i64;
+  unsafe {
+    core::arch::asm!(
+      "svc #0",
+      inlateout("x0") args[0] => result,
+      in("x1") args[1],
+      in("x2") args[2],
+      in("x3") args[3],
+      in("x8") number,
+      options(nostack)
+    );
+  }
+  result
+}
+
+// Reads a byte from the standard input, 0 at its end.
+pub fn getc() -> u8 {
+  let mut byte = [0];
+  read(STDIN, &mut byte);
+  byte[0]
+}
+
+pub fn putc(byte: u8) {
+  write(STDOUT, &[byte]);
+}
+
+pub fn fork() -> i64 {
+  syscall(FORK, [0; 4])
+}
+
+// Runs `path` with `args` and an empty environment. Only returns on errors.
+pub fn execve(path: &CStr, args: &[&CStr]) -> i64 {
+  let mut argv = [core::ptr::null(); MAX_ARGS + 1];
+  if args.len() > MAX_ARGS {
+    return -7;
+  }
+  for (pointer, arg) in argv.iter_mut().zip(args) {
+    *pointer = arg.as_ptr();
+  }
+  let envp: [*const c_char; 1] = [core::ptr::null()];
+  let args = [
+    path.as_ptr() as u64,
+    argv.as_ptr() as u64,
+    envp.as_ptr() as u64,
+    0,
+  ];
+  syscall(EXECVE, args)
+}
+
+// Waits for the child `pid`, or any child if -1. The exit code is in bits
+// 8 to 15 of `status`.
+pub fn wait4(pid: i64, status: &mut i32, options: u64) -> i64 {
+  let status = status as *mut i32 as u64;
+  syscall(WAIT4, [pid as u64, status, options, 0])
+}
+
+pub fn exit(code: i32) -> ! {
+  syscall(EXIT, [code as u64, 0, 0, 0]);
+  unreachable!()
+}
+
+pub fn getpid() -> i64 {
+  syscall(GETPID, [0; 4])
+}
+
+pub fn getppid() -> i64 {
+  syscall(GETPPID, [0; 4])
+}
+
+// Returns the file descriptor.
+pub fn open(path: &CStr, flags: u64) -> i64 {
+  syscall(OPEN, [path.as_ptr() as u64, flags, 0, 0])
+}
+
+pub fn read(fd: u64, buffer: &mut [u8]) -> i64 {
+  let args = [fd, buffer.as_mut_ptr() as u64, buffer.len() as u64, 0];
+  syscall(READ, args)
+}
+
+pub fn write(fd: u64, data: &[u8]) -> i64 {
+  syscall(WRITE, [fd, data.as_ptr() as u64, data.len() as u64, 0])
+}
+
+pub fn close(fd: u64) -> i64 {
+  syscall(CLOSE, [fd, 0, 0, 0])
+}
+
+pub fn lseek(fd: u64, offset: i64, whence: u64) -> i64 {
+  syscall(LSEEK, [fd, offset as u64, whence, 0])
+}
+
+// The fields of the kernel's struct stat that it fills in.
+#[repr(C)]
+pub struct Stat {
+  pub dev: u64,
+  pub ino: u64,
+  pub mode: u32,
+  pub nlink: u32,
+  reserved: [u64; 3],
+  pub size: i64,
+  pub blksize: i32,
+  padding: [u32; 17],
+}
+
+impl Stat {
+  pub const fn new() -> Stat {
+    Stat {
+      dev: 0,
+      ino: 0,
+      mode: 0,
+      nlink: 0,
+      reserved: [0; 3],
+      size: 0,
+      blksize: 0,
+      padding: [0; 17],
+    }
+  }
+
+  pub fn is_dir(&self) -> bool {
+    self.mode & S_IFMT == S_IFDIR
+  }
+}
+
+impl Default for Stat {
+  fn default() -> Self {
+    Stat::new()
+  }
+}
+
+pub fn stat(path: &CStr, stat: &mut Stat) -> i64 {
+  let stat = stat as *mut Stat as u64;
+  syscall(STAT, [path.as_ptr() as u64, stat, 0, 0])
+}
+
+pub fn fstat(fd: u64, stat: &mut Stat) -> i64 {
+  syscall(FSTAT, [fd, stat as *mut Stat as u64, 0, 0])
+}
+
+// A directory entry as stored by readdir().
+#[repr(C)]
+pub struct DirEntry {
+  pub ino: u64,
+  pub kind: u8,
+  padding: [u8; 7],
+  name: [u8; 256],
+}
+
+impl DirEntry {
+  pub const fn new() -> DirEntry {
+    DirEntry {
+      ino: 0,
+      kind: 0,
+      padding: [0; 7],
+      name: [0; 256],
+    }
+  }
+
+  pub fn name(&self) -> &str {
+    let name = CStr::from_bytes_until_nul(&self.name).unwrap_or_default();
+    name.to_str().unwrap_or("?")
+  }
+}
+
+impl Default for DirEntry {
+  fn default() -> Self {
+    DirEntry::new()
+  }
+}
+
+// Stores the next entry of the directory `fd`. Returns 0 after the last.
+pub fn readdir(fd: u64, entry: &mut DirEntry) -> i64 {
+  syscall(READDIR, [fd, entry as *mut DirEntry as u64, 0, 0])
+}
+
+// Formatted output to the standard output; the terminal adds CR to LF.
+pub struct Console;
+
+impl Write for Console {
+  fn write_str(&mut self, s: &str) -> fmt::Result {
+    match write(STDOUT, s.as_bytes()) {
+      count if count < 0 => Err(fmt::Error),
+      _ => Ok(()),
+    }
+  }
+}
+
+pub fn print_fmt(args: fmt::Arguments) {
+  let _ = Console.write_fmt(args);
+}
+
+#[macro_export]
+macro_rules! print {
+  ($($arg:tt)*) => ($crate::print_fmt(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+  () => ($crate::print!("\n"));
+  ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+// Command line arguments, as laid out on the stack by the kernel's ELF
+// loader.
+#[derive(Clone, Copy)]
+pub struct Args {
+  argc: usize,
+  argv: *const *const c_char,
+}
+
+impl Args {
+  pub fn len(&self) -> usize {
+    self.argc
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.argc == 0
+  }
+
+  pub fn get(&self, index: usize) -> Option<&'static CStr> {
+    if index >= self.argc {
+      return None;
+    }
+    Some(unsafe { CStr::from_ptr(*self.argv.add(index)) })
+  }
+}
+
+extern "Rust" {
+  fn main(args: Args) -> i32;
+}
+
+core::arch::global_asm!(
+  ".section .text._start",
+  ".globl _start",
+  "_start:",
+  // sp points to argc
+  "mov x0, sp",
+  "bl start",
+);
+
+#[no_mangle]
+extern "C" fn start(stack: *const usize) -> ! {
+  let args = unsafe {
+    Args {
+      argc: *stack,
+      argv: stack.add(1) as *const *const c_char,
+    }
+  };
+  exit(unsafe { main(args) })
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+  println!("{}", info);
+  exit(101)
+}